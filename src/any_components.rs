@@ -3,7 +3,7 @@ use std::any::{Any, TypeId};
 use rustc_hash::FxHashMap;
 
 use crate::{
-    entity::{Entity, WrongGeneration},
+    entity::{Entity, EntityError},
     world::World,
     world_common::Component,
 };
@@ -11,8 +11,6 @@ use crate::{
 /// A dynamic set of components that can be inserted into a world.
 #[derive(Default)]
 pub struct AnyComponentSet {
-    // TODO: This is slower than anymap, at least switch to using anymap's TypeIdHasher when that is
-    // public (anymap 1.0 release).
     components: FxHashMap<TypeId, Box<dyn AnyComponent>>,
 }
 
@@ -73,6 +71,10 @@ impl AnyComponentSet {
         self.components.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
     /// Merges the given component set on top of this one.
     ///
     /// Returns true if any component in this set was overwritten by the merge.
@@ -92,11 +94,7 @@ impl AnyComponentSet {
     /// # Panics
     /// Panics if any of the component types in this set are not previously registered into the
     /// given world.
-    pub fn insert_into_world(
-        self,
-        world: &mut World,
-        entity: Entity,
-    ) -> Result<bool, WrongGeneration> {
+    pub fn insert_into_world(self, world: &mut World, entity: Entity) -> Result<bool, EntityError> {
         let mut overwritten = false;
         for (_, component) in self.components {
             overwritten |= component.insert_into_world(world, entity)?;
@@ -176,9 +174,9 @@ impl AnyCloneComponentSet {
         &self,
         world: &mut World,
         entity: Entity,
-    ) -> Result<bool, WrongGeneration> {
+    ) -> Result<bool, EntityError> {
         let mut overwritten = false;
-        for (_, component) in &self.components {
+        for component in self.components.values() {
             overwritten |= component.clone_into_world(world, entity)?;
         }
         Ok(overwritten)
@@ -206,7 +204,7 @@ trait AnyComponent: Send + Sync {
         self: Box<Self>,
         world: &mut World,
         entity: Entity,
-    ) -> Result<bool, WrongGeneration>;
+    ) -> Result<bool, EntityError>;
 
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
@@ -222,7 +220,7 @@ where
         self: Box<Self>,
         world: &mut World,
         entity: Entity,
-    ) -> Result<bool, WrongGeneration> {
+    ) -> Result<bool, EntityError> {
         Ok(world
             .get_component_mut::<C>()
             .insert(entity, *self)?
@@ -244,7 +242,7 @@ where
 
 trait AnyCloneComponent: AnyComponent {
     fn boxed_clone(&self) -> Box<dyn AnyComponent>;
-    fn clone_into_world(&self, world: &mut World, entity: Entity) -> Result<bool, WrongGeneration>;
+    fn clone_into_world(&self, world: &mut World, entity: Entity) -> Result<bool, EntityError>;
 }
 
 impl<C> AnyCloneComponent for C
@@ -256,7 +254,7 @@ where
         Box::new(self.clone())
     }
 
-    fn clone_into_world(&self, world: &mut World, entity: Entity) -> Result<bool, WrongGeneration> {
+    fn clone_into_world(&self, world: &mut World, entity: Entity) -> Result<bool, EntityError> {
         Ok(world
             .get_component_mut::<C>()
             .insert(entity, self.clone())?
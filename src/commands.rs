@@ -0,0 +1,77 @@
+use crate::{
+    entity::{Entity, EntityError},
+    fetch_resources::FetchResources,
+    resources::ResourceConflict,
+    world::World,
+    world_common::{Component, WorldResources},
+};
+
+/// Queues structural changes to the `World`, which are applied in order at the end of the next
+/// call to `World::merge`, see `World::defer`.
+///
+/// This does not conflict with any other resource, so any system can fetch it, even systems that
+/// run in parallel with systems reading or writing the affected components.
+///
+/// Spawned entities are allocated atomically and are alive immediately, so further commands can
+/// refer to them, but they have no components until the merge.  Commands which refer to an entity
+/// that is dead by the time they are applied do nothing.
+#[derive(Copy, Clone)]
+pub struct Commands<'a>(&'a World);
+
+impl<'a> Commands<'a> {
+    /// # Panics
+    /// Panics if there are no entity indexes left to allocate.
+    pub fn spawn(&self) -> Entity {
+        self.0.entities().create()
+    }
+
+    /// Request that the entity be deleted, see `Entities::delete`.
+    pub fn despawn(&self, e: Entity) -> Result<(), EntityError> {
+        self.0.entities().delete(e)
+    }
+
+    /// Queue inserting a component, replacing any existing one.
+    ///
+    /// # Panics
+    /// The merge which applies this panics if the component has not been inserted into the world.
+    pub fn insert<C>(&self, e: Entity, c: C)
+    where
+        C: Component + Send + 'static,
+        C::Storage: Send,
+    {
+        self.0.defer(move |world| {
+            let _ = world.write_component::<C>().insert(e, c);
+        });
+    }
+
+    /// Queue removing a component.
+    ///
+    /// # Panics
+    /// The merge which applies this panics if the component has not been inserted into the world.
+    pub fn remove<C>(&self, e: Entity)
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.0.defer(move |world| {
+            let _ = world.write_component::<C>().remove(e);
+        });
+    }
+
+    /// Queue an arbitrary operation on the world.
+    pub fn add(&self, op: impl FnOnce(&mut World) + Send + 'static) {
+        self.0.defer(op);
+    }
+}
+
+impl<'a> FetchResources<'a, World> for Commands<'a> {
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new())
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Commands(world)
+    }
+}
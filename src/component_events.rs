@@ -0,0 +1,107 @@
+use std::{mem, sync::Mutex};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    entity::Entity, make_sync::MakeSync, stats::ComponentStats, world_common::ComponentId,
+};
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ComponentEventKind {
+    Added,
+    Removed,
+}
+
+/// A structural change to a component storage of a `World`.
+///
+/// Only changes to the *presence* of a component are recorded, replacing an existing component or
+/// mutating it in place does not produce an event.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ComponentEvent {
+    pub entity: Entity,
+    pub component: ComponentId,
+    pub kind: ComponentEventKind,
+}
+
+/// Returned from `World::subscribe_component_events`, can be used to later unsubscribe.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SubscriptionId(u64);
+
+type Subscriber = MakeSync<Box<dyn FnMut(&[ComponentEvent]) + Send>>;
+
+// Collects component events from any thread while there are subscribers, and hands them to the
+// subscribers when drained.  Also counts events per component while counting is turned on.
+#[derive(Default)]
+pub(crate) struct ComponentEventLog {
+    events: Mutex<Vec<ComponentEvent>>,
+    subscribers: Vec<(SubscriptionId, Subscriber)>,
+    next_id: u64,
+    counts: Option<Mutex<FxHashMap<ComponentId, ComponentStats>>>,
+}
+
+impl ComponentEventLog {
+    pub(crate) fn subscribe(
+        &mut self,
+        f: impl FnMut(&[ComponentEvent]) + Send + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, MakeSync::new(Box::new(f))));
+        id
+    }
+
+    pub(crate) fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|(i, _)| *i != id);
+        if self.subscribers.is_empty() {
+            self.events.get_mut().unwrap().clear();
+        }
+        self.subscribers.len() != len
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    pub(crate) fn set_counting(&mut self, counting: bool) {
+        if counting != self.counts.is_some() {
+            self.counts = counting.then(Mutex::default);
+        }
+    }
+
+    // Returns the counts since the last call, or `None` if counting is turned off.
+    pub(crate) fn take_counts(&mut self) -> Option<FxHashMap<ComponentId, ComponentStats>> {
+        self.counts
+            .as_mut()
+            .map(|counts| mem::take(counts.get_mut().unwrap()))
+    }
+
+    pub(crate) fn record(&self, entity: Entity, component: ComponentId, kind: ComponentEventKind) {
+        if let Some(counts) = &self.counts {
+            let mut counts = counts.lock().unwrap();
+            let stats = counts.entry(component).or_default();
+            match kind {
+                ComponentEventKind::Added => stats.inserted += 1,
+                ComponentEventKind::Removed => stats.removed += 1,
+            }
+        }
+
+        if self.enabled() {
+            self.events.lock().unwrap().push(ComponentEvent {
+                entity,
+                component,
+                kind,
+            });
+        }
+    }
+
+    // Send all events recorded since the last drain to every subscriber.
+    pub(crate) fn drain(&mut self) {
+        let events = mem::take(self.events.get_mut().unwrap());
+        if !events.is_empty() {
+            for (_, subscriber) in &mut self.subscribers {
+                (subscriber.get_mut())(&events);
+            }
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::{marker::PhantomData, ops::Range};
+
+use crate::{
+    join::Index,
+    masked::{MaskVersion, MaskedStorage},
+    resource_set::ResourceSet,
+    storage::{ArrangeStorage, DenseStorage},
+    world_common::{Component, ComponentId, ComponentStorage},
+};
+
+/// A set of components which can be inserted as a group with `World::insert_component_group`.
+///
+/// Implemented for tuples of 2 to 8 components whose storages implement `ArrangeStorage`, such as
+/// `DenseVecStorage`.
+pub trait GroupMembers: 'static {
+    /// The id of every member, in order.
+    fn ids() -> Vec<ComponentId>;
+
+    /// Call `f` with the position and storage of every member, in order.
+    ///
+    /// # Panics
+    /// Panics if any of the members has not been inserted.
+    fn for_each_storage(
+        components: &mut ResourceSet,
+        f: &mut dyn FnMut(usize, &mut dyn MemberStorage),
+    );
+}
+
+/// The type erased operations a `ComponentGroup` needs from the storage of each member.
+pub trait MemberStorage {
+    fn mask_version(&self) -> MaskVersion;
+
+    /// The index of every value in the dense slice, in order.
+    fn indexes(&self) -> &[Index];
+
+    /// Sort the dense slice by the rank of each index, which must be in bounds of `rank`.
+    fn arrange(&mut self, rank: &[u32]);
+}
+
+impl<S: ArrangeStorage> MemberStorage for MaskedStorage<S> {
+    fn mask_version(&self) -> MaskVersion {
+        MaskedStorage::mask_version(self)
+    }
+
+    fn indexes(&self) -> &[Index] {
+        self.slice_indexes()
+    }
+
+    fn arrange(&mut self, rank: &[u32]) {
+        self.raw_storage_mut()
+            .arrange_by_key(|index| rank[index as usize]);
+    }
+}
+
+/// Entities of a `ComponentGroup` which have exactly the same members of the group.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Archetype {
+    members: u32,
+    len: usize,
+    // The start of this archetype in the dense slice of every member, only meaningful for the
+    // members this archetype has.
+    starts: Box<[usize]>,
+}
+
+impl Archetype {
+    /// A mask of the members of the group that every entity of this archetype has, where bit `i`
+    /// is set for the `i`th member.
+    pub fn members(&self) -> u32 {
+        self.members
+    }
+
+    pub fn has_member(&self, member: usize) -> bool {
+        member < 32 && self.members & (1 << member) != 0
+    }
+
+    /// The number of entities in this archetype.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Archetypes with no entities are never stored, so this is always false.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The range of the dense slice of the given member which holds the components of this
+    /// archetype, or `None` if this archetype does not have the member.
+    pub fn range(&self, member: usize) -> Option<Range<usize>> {
+        if self.has_member(member) {
+            let start = self.starts[member];
+            Some(start..start + self.len)
+        } else {
+            None
+        }
+    }
+}
+
+/// The layout of a group of components whose dense storages are kept arranged so that entities
+/// with the same set of members are stored together, in the same order, in every member storage.
+///
+/// This is a table per archetype built on top of the existing storages: every member is still an
+/// ordinary component, fetched, borrowed and changed on its own.  Inserted with
+/// `World::insert_component_group` and rearranged by every `World::merge`.
+///
+/// Inserting or removing a member component moves values in its dense slice, so a member storage
+/// only lines up with the layout until its next structural change, which `is_arranged` checks with
+/// `MaskedStorage::mask_version`.  Changing component values never invalidates the layout.
+/// Reordering a dense slice by other means, such as calling `MaskedStorage::shrink` directly, is
+/// not detected, `World::compact_storages` rearranges every group afterwards.
+pub struct ComponentGroup<G> {
+    members: Vec<ComponentId>,
+    versions: Vec<Option<MaskVersion>>,
+    archetypes: Vec<Archetype>,
+    marker: PhantomData<fn() -> G>,
+}
+
+impl<G: GroupMembers> ComponentGroup<G> {
+    pub(crate) fn new() -> Self {
+        let members = G::ids();
+        ComponentGroup {
+            versions: vec![None; members.len()],
+            members,
+            archetypes: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Rearrange every member storage and recompute the layout, which is skipped if no member
+    /// storage has changed structurally since the last time, unless `force` is true.
+    pub(crate) fn arrange(&mut self, components: &mut ResourceSet, force: bool) {
+        let mut unchanged = !force;
+        G::for_each_storage(components, &mut |member, storage| {
+            unchanged &= self.versions[member] == Some(storage.mask_version());
+        });
+        if unchanged {
+            return;
+        }
+
+        // The members of every index, and every index with at least one member.
+        let mut keys: Vec<u32> = Vec::new();
+        let mut present: Vec<Index> = Vec::new();
+        G::for_each_storage(components, &mut |member, storage| {
+            for &index in storage.indexes() {
+                let index = index as usize;
+                if keys.len() <= index {
+                    keys.resize(index + 1, 0);
+                }
+                if keys[index] == 0 {
+                    present.push(index as Index);
+                }
+                keys[index] |= 1 << member;
+            }
+        });
+        present.sort_unstable_by_key(|&index| (keys[index as usize], index));
+
+        let mut rank = vec![0; keys.len()];
+        self.archetypes.clear();
+        for (r, &index) in present.iter().enumerate() {
+            rank[index as usize] = r as u32;
+            let members = keys[index as usize];
+            match self.archetypes.last_mut() {
+                Some(archetype) if archetype.members == members => archetype.len += 1,
+                _ => self.archetypes.push(Archetype {
+                    members,
+                    len: 1,
+                    starts: Box::default(),
+                }),
+            }
+        }
+
+        let mut next = vec![0; self.members.len()];
+        for archetype in &mut self.archetypes {
+            archetype.starts = next.clone().into_boxed_slice();
+            for (member, next) in next.iter_mut().enumerate() {
+                if archetype.has_member(member) {
+                    *next += archetype.len;
+                }
+            }
+        }
+
+        let versions = &mut self.versions;
+        G::for_each_storage(components, &mut |member, storage| {
+            storage.arrange(&rank);
+            versions[member] = Some(storage.mask_version());
+        });
+    }
+
+    /// The id of every member, in order.
+    pub fn members(&self) -> &[ComponentId] {
+        &self.members
+    }
+
+    /// The position of the given component in the group, if it is a member.
+    pub fn member<C: Component + 'static>(&self) -> Option<usize> {
+        self.members
+            .iter()
+            .position(|&id| id == ComponentId::of::<C>())
+    }
+
+    /// Every archetype, ordered by `Archetype::members`.
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    /// Every archetype which has all of the given components.
+    ///
+    /// If any of the components is not a member of the group, there are no such archetypes.
+    pub fn archetypes_with<'a>(
+        &'a self,
+        components: &[ComponentId],
+    ) -> impl Iterator<Item = &'a Archetype> + 'a {
+        let mut members = Some(0);
+        for id in components {
+            members = members.and_then(|m| {
+                let member = self.members.iter().position(|m| m == id)?;
+                Some(m | 1 << member)
+            });
+        }
+        self.archetypes
+            .iter()
+            .filter(move |a| members.is_some_and(|m| a.members & m == m))
+    }
+
+    /// Whether the given storage of a member still lines up with the layout of this group.
+    pub fn is_arranged<C>(&self, storage: &ComponentStorage<C>) -> bool
+    where
+        C: Component + 'static,
+    {
+        self.member::<C>()
+            .is_some_and(|m| self.versions[m] == Some(storage.mask_version()))
+    }
+
+    /// The components of the given archetype in the given member storage, in the same entity
+    /// order as every other member.
+    ///
+    /// Returns an empty slice if the archetype does not have the component.
+    ///
+    /// # Panics
+    /// Panics if the component is not a member of the group, or the storage is not arranged (see
+    /// `ComponentGroup::is_arranged`).
+    pub fn column<'s, C>(&self, archetype: &Archetype, storage: &'s ComponentStorage<C>) -> &'s [C]
+    where
+        C: Component + 'static,
+        C::Storage: DenseStorage,
+    {
+        match archetype.range(self.arranged_member::<C>(storage)) {
+            Some(range) => &storage.as_slice()[range],
+            None => &[],
+        }
+    }
+
+    /// Mutable version of `ComponentGroup::column`.
+    ///
+    /// This goes through `MaskedStorage::as_mut_slice`, so a tracked storage marks all of its
+    /// components as modified.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `ComponentGroup::column`.
+    pub fn column_mut<'s, C>(
+        &self,
+        archetype: &Archetype,
+        storage: &'s mut ComponentStorage<C>,
+    ) -> &'s mut [C]
+    where
+        C: Component + 'static,
+        C::Storage: DenseStorage,
+    {
+        match archetype.range(self.arranged_member::<C>(storage)) {
+            Some(range) => &mut storage.as_mut_slice()[range],
+            None => &mut [],
+        }
+    }
+
+    fn arranged_member<C>(&self, storage: &ComponentStorage<C>) -> usize
+    where
+        C: Component + 'static,
+    {
+        let member = self
+            .member::<C>()
+            .expect("component is not a member of the group");
+        assert!(
+            self.versions[member] == Some(storage.mask_version()),
+            "component storage has changed since the group was arranged"
+        );
+        member
+    }
+}
+
+macro_rules! impl_group_members {
+    ($($ty:ident),*) => {
+        impl<$($ty),*> GroupMembers for ($($ty,)*)
+        where
+            $($ty: Component + 'static, $ty::Storage: ArrangeStorage + Send,)*
+        {
+            fn ids() -> Vec<ComponentId> {
+                vec![$(ComponentId::of::<$ty>()),*]
+            }
+
+            fn for_each_storage(
+                components: &mut ResourceSet,
+                f: &mut dyn FnMut(usize, &mut dyn MemberStorage),
+            ) {
+                let mut member = 0..;
+                $(f(member.next().unwrap(), components.get_mut::<ComponentStorage<$ty>>());)*
+            }
+        }
+    };
+}
+
+impl_group_members!(A, B);
+impl_group_members!(A, B, C);
+impl_group_members!(A, B, C, D);
+impl_group_members!(A, B, C, D, E);
+impl_group_members!(A, B, C, D, E, F);
+impl_group_members!(A, B, C, D, E, F, G);
+impl_group_members!(A, B, C, D, E, F, G, H);
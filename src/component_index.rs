@@ -0,0 +1,164 @@
+use std::hash::Hash;
+
+use hibitset::{BitSet, BitSetLike};
+use rustc_hash::FxHashMap;
+
+use crate::{join::Index, world_common::Component};
+
+/// A structure derived from the values of a tracked component, which a `World` keeps up to date.
+///
+/// Once registered with `World::insert_component_index`, every change to the component is given
+/// to `ComponentIndex::update` during `World::merge`, and the index itself can be accessed as a
+/// resource.
+pub trait ComponentIndex: Send + 'static {
+    type Component: Component;
+
+    /// Called with every index whose component was inserted, modified, or removed since the last
+    /// update.
+    ///
+    /// `value` is the current component at the index, or `None` if it was removed.
+    fn update(&mut self, index: Index, value: Option<&Self::Component>);
+}
+
+type KeyFn<C, K> = dyn Fn(&C) -> K + Send + Sync;
+
+/// A `ComponentIndex` which maps a key extracted from each component to the set of indexes whose
+/// component has that key.
+///
+/// This allows finding every entity with a particular component value without scanning the whole
+/// storage.
+pub struct ValueIndex<C, K> {
+    key: Box<KeyFn<C, K>>,
+    indexes: FxHashMap<K, BitSet>,
+    keys: FxHashMap<Index, K>,
+}
+
+impl<C, K> ValueIndex<C, K>
+where
+    K: Eq + Hash + Clone,
+{
+    pub fn new(key: impl Fn(&C) -> K + Send + Sync + 'static) -> Self {
+        ValueIndex {
+            key: Box::new(key),
+            indexes: FxHashMap::default(),
+            keys: FxHashMap::default(),
+        }
+    }
+
+    /// Returns the indexes whose component currently has the given key.
+    ///
+    /// The returned `BitSet` can be joined with component storages to find the matching entities.
+    pub fn get(&self, key: &K) -> Option<&BitSet> {
+        self.indexes.get(key)
+    }
+
+    /// Returns the key of the component at the given index, as of the last update.
+    pub fn key_of(&self, index: Index) -> Option<&K> {
+        self.keys.get(&index)
+    }
+
+    /// Iterate over every key which is present in at least one component.
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.indexes.keys()
+    }
+}
+
+impl<C, K> ComponentIndex for ValueIndex<C, K>
+where
+    C: Component + 'static,
+    K: Eq + Hash + Clone + Send + 'static,
+{
+    type Component = C;
+
+    fn update(&mut self, index: Index, value: Option<&C>) {
+        if let Some(old) = self.keys.remove(&index) {
+            let set = self.indexes.get_mut(&old).unwrap();
+            set.remove(index);
+            if set.is_empty() {
+                self.indexes.remove(&old);
+            }
+        }
+
+        if let Some(value) = value {
+            let key = (self.key)(value);
+            self.indexes.entry(key.clone()).or_default().add(index);
+            self.keys.insert(index, key);
+        }
+    }
+}
+
+/// A user provided spatial acceleration structure, such as a grid or a BVH, which can be kept up
+/// to date by a `SpatialIndex`.
+pub trait SpatialStructure: Send + 'static {
+    type Position;
+    type Region;
+
+    /// Called when a component is added at an index which was not previously in the structure.
+    fn insert(&mut self, index: Index, position: Self::Position);
+
+    /// Called when the component at an index already in the structure is modified.
+    fn update(&mut self, index: Index, position: Self::Position);
+
+    fn remove(&mut self, index: Index);
+
+    /// Add every index within the given region to `out`.
+    fn query(&self, region: &Self::Region, out: &mut BitSet);
+}
+
+type PositionFn<C, P> = dyn Fn(&C) -> P + Send + Sync;
+
+/// A `ComponentIndex` which keeps a `SpatialStructure` up to date with positions extracted from a
+/// tracked component.
+pub struct SpatialIndex<C, S: SpatialStructure> {
+    position: Box<PositionFn<C, S::Position>>,
+    structure: S,
+    present: BitSet,
+}
+
+impl<C, S: SpatialStructure> SpatialIndex<C, S> {
+    pub fn new(structure: S, position: impl Fn(&C) -> S::Position + Send + Sync + 'static) -> Self {
+        SpatialIndex {
+            position: Box::new(position),
+            structure,
+            present: BitSet::new(),
+        }
+    }
+
+    pub fn structure(&self) -> &S {
+        &self.structure
+    }
+
+    /// Returns every index within the given region, as a `BitSet` which can be joined with
+    /// component storages.
+    pub fn query(&self, region: &S::Region) -> BitSet {
+        let mut out = BitSet::new();
+        self.structure.query(region, &mut out);
+        out
+    }
+}
+
+impl<C, S> ComponentIndex for SpatialIndex<C, S>
+where
+    C: Component + 'static,
+    S: SpatialStructure,
+{
+    type Component = C;
+
+    fn update(&mut self, index: Index, value: Option<&C>) {
+        match value {
+            Some(value) => {
+                let position = (self.position)(value);
+                if self.present.add(index) {
+                    self.structure.update(index, position);
+                } else {
+                    self.structure.insert(index, position);
+                }
+            }
+            None => {
+                if self.present.remove(index) {
+                    self.structure.remove(index);
+                }
+            }
+        }
+    }
+}
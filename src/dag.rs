@@ -0,0 +1,265 @@
+use std::sync::{Condvar, Mutex};
+
+use hibitset::BitSet;
+
+use crate::{
+    resources::{ResourceConflict, Resources},
+    system::{Error, Pool, System},
+};
+
+/// Identifies a system added to a `Dag`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct DagNode(usize);
+
+/// A `System` made of a list of systems of the same type along with explicit ordering
+/// dependencies between them.
+///
+/// When run, every system starts as soon as all of the systems it depends on have finished, so
+/// unlike the stages produced by `parallelize`, a long chain of dependent systems does not hold up
+/// unrelated systems.
+///
+/// Two systems which are not ordered relative to each other (directly or indirectly) may run in
+/// parallel, so `Dag::check_resources` returns an error if any such pair has conflicting
+/// resources.
+///
+/// `Pinned` systems are only ever run on the thread which called `run`, alongside the other
+/// systems running on the pool.
+pub struct Dag<S> {
+    systems: Vec<S>,
+    dependents: Vec<Vec<usize>>,
+    dependencies: Vec<usize>,
+}
+
+impl<S> Default for Dag<S> {
+    fn default() -> Self {
+        Dag {
+            systems: Vec::new(),
+            dependents: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+impl<S> Dag<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, system: S) -> DagNode {
+        self.systems.push(system);
+        self.dependents.push(Vec::new());
+        self.dependencies.push(0);
+        DagNode(self.systems.len() - 1)
+    }
+
+    /// Add a system which depends on every one of the given systems.
+    pub fn add_after(&mut self, system: S, after: impl IntoIterator<Item = DagNode>) -> DagNode {
+        let node = self.add(system);
+        for before in after {
+            self.add_dependency(before, node);
+        }
+        node
+    }
+
+    /// Require that `before` finishes before `after` starts.
+    ///
+    /// # Panics
+    /// Panics if the dependency would create a cycle.
+    pub fn add_dependency(&mut self, before: DagNode, after: DagNode) {
+        assert!(
+            !self.reachable(after.0).contains(before.0 as u32),
+            "dependency cycle between systems {} and {}",
+            before.0,
+            after.0
+        );
+        if !self.dependents[before.0].contains(&after.0) {
+            self.dependents[before.0].push(after.0);
+            self.dependencies[after.0] += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    // Every system reachable from the given system, including itself.
+    fn reachable(&self, from: usize) -> BitSet {
+        let mut reached = BitSet::new();
+        let mut stack = vec![from];
+        while let Some(i) = stack.pop() {
+            if !reached.add(i as u32) {
+                stack.extend(&self.dependents[i]);
+            }
+        }
+        reached
+    }
+}
+
+impl<A, S> System<A> for Dag<S>
+where
+    A: Copy + Send,
+    S: System<A> + Send,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    type Resources = S::Resources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<Self::Resources, ResourceConflict> {
+        let resources = self
+            .systems
+            .iter()
+            .map(|s| s.check_resources())
+            .collect::<Result<Vec<_>, _>>()?;
+        let reachable = (0..self.systems.len())
+            .map(|i| self.reachable(i))
+            .collect::<Vec<_>>();
+
+        let mut all = S::Resources::default();
+        for (i, r) in resources.iter().enumerate() {
+            for (j, other) in resources.iter().enumerate().skip(i + 1) {
+                let ordered = reachable[i].contains(j as u32) || reachable[j].contains(i as u32);
+                if !ordered && r.conflicts_with(other) {
+                    return Err(ResourceConflict::conflict_in::<Self>());
+                }
+            }
+            all.union(r);
+        }
+        Ok(all)
+    }
+
+    fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
+        let pinned = self
+            .systems
+            .iter()
+            .map(|s| s.is_pinned())
+            .collect::<Vec<_>>();
+        let mut state = RunState::<S::Error> {
+            dependencies: self.dependencies.clone(),
+            ready: Vec::new(),
+            ready_pinned: Vec::new(),
+            running: 0,
+            error: None,
+        };
+        for i in (0..self.systems.len()).rev() {
+            if self.dependencies[i] == 0 {
+                state.push_ready(i, pinned[i]);
+            }
+        }
+        let state = Mutex::new(state);
+        let wake = Condvar::new();
+        let dependents = &self.dependents;
+        let systems = self.systems.iter_mut().map(Mutex::new).collect::<Vec<_>>();
+
+        // Every worker repeatedly takes a system whose dependencies have all finished and runs it,
+        // until there are no systems left to run.  Only the local worker, which runs on the
+        // calling thread, takes pinned systems.
+        let worker = |args: A, local: bool| loop {
+            let next = {
+                let mut state = state.lock().unwrap();
+                loop {
+                    let next = if local {
+                        state.ready_pinned.pop().or_else(|| state.ready.pop())
+                    } else {
+                        state.ready.pop()
+                    };
+                    if let Some(next) = next {
+                        state.running += 1;
+                        break next;
+                    } else if state.running == 0 && state.ready_pinned.is_empty() {
+                        return;
+                    }
+                    state = wake.wait(state).unwrap();
+                }
+            };
+
+            let res = systems[next].lock().unwrap().run(pool, args);
+
+            let mut state = state.lock().unwrap();
+            state.running -= 1;
+            match res {
+                Ok(()) if state.error.is_none() => {
+                    for &d in &dependents[next] {
+                        state.dependencies[d] -= 1;
+                        if state.dependencies[d] == 0 {
+                            state.push_ready(d, pinned[d]);
+                        }
+                    }
+                }
+                Ok(()) => {}
+                Err(err) => {
+                    state.ready.clear();
+                    state.ready_pinned.clear();
+                    state.error = Some(match state.error.take() {
+                        Some(prev) => prev.combine(err),
+                        None => err,
+                    });
+                }
+            }
+            wake.notify_all();
+        };
+
+        if pinned.contains(&true) {
+            let (worker, count) = (&worker, systems.len() - 1);
+            pool.join_local(
+                || worker(args, true),
+                move || spawn_workers(pool, count, args, worker),
+            );
+        } else {
+            spawn_workers(pool, systems.len(), args, &worker);
+        }
+
+        match state.into_inner().unwrap().error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.systems.iter().any(|s| s.is_pinned())
+    }
+}
+
+struct RunState<E> {
+    // The number of unfinished dependencies of every system.
+    dependencies: Vec<usize>,
+    ready: Vec<usize>,
+    // Ready systems which must run on the calling thread.
+    ready_pinned: Vec<usize>,
+    running: usize,
+    error: Option<E>,
+}
+
+impl<E> RunState<E> {
+    fn push_ready(&mut self, system: usize, pinned: bool) {
+        if pinned {
+            self.ready_pinned.push(system);
+        } else {
+            self.ready.push(system);
+        }
+    }
+}
+
+// Run `count` copies of `worker` which are not local, as parallel as the pool allows.
+fn spawn_workers<P, A>(pool: &P, count: usize, args: A, worker: &(dyn Fn(A, bool) + Sync))
+where
+    P: Pool + Sync,
+    A: Copy + Send,
+{
+    match count {
+        0 => {}
+        1 => worker(args, false),
+        _ => {
+            let half = count / 2;
+            pool.join(
+                move || spawn_workers(pool, half, args, worker),
+                move || spawn_workers(pool, count - half, args, worker),
+            );
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    collections::BTreeMap,
+    ops::{Deref, DerefMut},
+};
+
+use atomic_refcell::{AtomicRef, AtomicRefMut};
+use hibitset::BitSet;
+
+use crate::{
+    entity::{Entity, EntityError},
+    join::IntoJoin,
+    masked::MaskedStorage,
+    storage::DenseVecStorage,
+    tracked::Flagged,
+    world::Entities,
+};
+
+/// A dynamically typed value, for data attached to entities at runtime, such as from a scripting
+/// language, without a compiled Rust component type.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum DynValue {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<DynValue>),
+    Map(BTreeMap<String, DynValue>),
+}
+
+impl DynValue {
+    pub fn is_null(&self) -> bool {
+        matches!(self, DynValue::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            DynValue::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            DynValue::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a float, converting an integer value if necessary.
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            DynValue::Int(i) => Some(i as f64),
+            DynValue::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DynValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[DynValue]> {
+        match self {
+            DynValue::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, DynValue>> {
+        match self {
+            DynValue::Map(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    /// Look up a key if this value is a map.
+    pub fn get(&self, key: &str) -> Option<&DynValue> {
+        self.as_map()?.get(key)
+    }
+
+    /// Look up a key if this value is a map.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut DynValue> {
+        match self {
+            DynValue::Map(m) => m.get_mut(key),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for DynValue {
+    fn from(b: bool) -> Self {
+        DynValue::Bool(b)
+    }
+}
+
+impl From<i64> for DynValue {
+    fn from(i: i64) -> Self {
+        DynValue::Int(i)
+    }
+}
+
+impl From<f64> for DynValue {
+    fn from(f: f64) -> Self {
+        DynValue::Float(f)
+    }
+}
+
+impl From<&str> for DynValue {
+    fn from(s: &str) -> Self {
+        DynValue::String(s.to_owned())
+    }
+}
+
+impl From<String> for DynValue {
+    fn from(s: String) -> Self {
+        DynValue::String(s)
+    }
+}
+
+impl From<Vec<DynValue>> for DynValue {
+    fn from(l: Vec<DynValue>) -> Self {
+        DynValue::List(l)
+    }
+}
+
+impl From<BTreeMap<String, DynValue>> for DynValue {
+    fn from(m: BTreeMap<String, DynValue>) -> Self {
+        DynValue::Map(m)
+    }
+}
+
+/// The storage of a single named dynamic component.
+///
+/// Modification tracking is always turned on, and modified bits are cleared on `World::merge`.
+pub type DynStorage = MaskedStorage<Flagged<DenseVecStorage<DynValue>>>;
+
+pub type ReadDyn<'a> = DynAccess<'a, AtomicRef<'a, DynStorage>>;
+pub type WriteDyn<'a> = DynAccess<'a, AtomicRefMut<'a, DynStorage>>;
+
+/// Returned from `World::read_dyn_component` and `World::write_dyn_component`.
+///
+/// Like `ComponentAccess`, this wraps a `DynStorage` paired with the entity allocator, and
+/// prevents inserting or accessing values that do not have a live `Entity`.
+pub struct DynAccess<'a, R> {
+    pub(crate) entities: Entities<'a>,
+    pub(crate) storage: R,
+}
+
+impl<'a, R> DynAccess<'a, R>
+where
+    R: Deref<Target = DynStorage>,
+{
+    pub fn storage(&self) -> &DynStorage {
+        &self.storage
+    }
+
+    pub fn mask(&self) -> &BitSet {
+        self.storage.mask()
+    }
+
+    pub fn contains(&self, e: Entity) -> bool {
+        self.entities.is_alive(e) && self.storage.contains(e.index())
+    }
+
+    pub fn get(&self, e: Entity) -> Option<&DynValue> {
+        if self.entities.is_alive(e) {
+            self.storage.get(e.index())
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, R> DynAccess<'a, R>
+where
+    R: DerefMut<Target = DynStorage>,
+{
+    /// Access the inner `DynStorage`, see `ComponentAccess::storage_mut`.
+    pub fn storage_mut(&mut self) -> &mut DynStorage {
+        &mut self.storage
+    }
+
+    pub fn get_mut(&mut self, e: Entity) -> Option<&mut DynValue> {
+        if self.entities.is_alive(e) {
+            self.storage.get_mut(e.index())
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        e: Entity,
+        v: impl Into<DynValue>,
+    ) -> Result<Option<DynValue>, EntityError> {
+        self.entities.check(e)?;
+        Ok(self.storage.insert(e.index(), v.into()))
+    }
+
+    pub fn remove(&mut self, e: Entity) -> Result<Option<DynValue>, EntityError> {
+        self.entities.check(e)?;
+        Ok(self.storage.remove(e.index()))
+    }
+}
+
+impl<'a, 'b, R> IntoJoin for &'a DynAccess<'b, R>
+where
+    R: Deref<Target = DynStorage> + 'a,
+{
+    type Item = &'a DynValue;
+    type IntoJoin = &'a DynStorage;
+
+    fn into_join(self) -> Self::IntoJoin {
+        &*self.storage
+    }
+}
+
+impl<'a, 'b, R> IntoJoin for &'a mut DynAccess<'b, R>
+where
+    R: DerefMut<Target = DynStorage> + 'a,
+{
+    type Item = &'a mut DynValue;
+    type IntoJoin = &'a mut DynStorage;
+
+    fn into_join(self) -> Self::IntoJoin {
+        &mut *self.storage
+    }
+}
@@ -1,19 +1,130 @@
 use std::{
-    iter,
+    iter, mem,
     num::NonZeroI32,
-    sync::atomic::{AtomicU32, Ordering},
-    u32,
+    sync::{
+        atomic::{AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
 };
 
+#[cfg(feature = "world-id")]
+use std::{num::NonZeroU16, sync::atomic::AtomicU16};
+
 use hibitset::{AtomicBitSet, BitSet, BitSetLike, BitSetOr};
 use thiserror::Error;
 
-use crate::join::{Index, Join};
+use crate::{
+    join::{Index, Join},
+    memory::{bitset_usage, reserve_bitset, MemoryUsage},
+};
 
 #[derive(Debug, Error)]
 #[error("Entity is no longer alive or has a mismatched generation")]
 pub struct WrongGeneration;
 
+#[cfg(feature = "world-id")]
+#[derive(Debug, Error)]
+#[error("Entity was allocated by a different allocator")]
+pub struct WrongWorld;
+
+/// Why an `Entity` was rejected by the `Allocator` it was used with.
+///
+/// Without the `world-id` feature the only possible reason is a mismatched generation, so this is
+/// just `WrongGeneration`.
+#[cfg(not(feature = "world-id"))]
+pub type EntityError = WrongGeneration;
+
+/// Why an `Entity` was rejected by the `Allocator` it was used with.
+#[cfg(feature = "world-id")]
+#[derive(Debug, Error)]
+pub enum EntityError {
+    #[error(transparent)]
+    WrongGeneration(#[from] WrongGeneration),
+    #[error(transparent)]
+    WrongWorld(#[from] WrongWorld),
+}
+
+#[derive(Debug, Error)]
+#[error("no entity index left to allocate")]
+pub struct IndexExhausted;
+
+/// Returned by allocation under `GenerationOverflow::Error` once the generation of an index is
+/// exhausted, holding that index.
+#[derive(Debug, Error)]
+#[error("generation of entity index {0} is exhausted")]
+pub struct GenerationExhausted(pub Index);
+
+#[derive(Debug, Error)]
+pub enum AllocateError {
+    #[error(transparent)]
+    IndexExhausted(#[from] IndexExhausted),
+    #[error(transparent)]
+    GenerationExhausted(#[from] GenerationExhausted),
+}
+
+/// A small id unique to each `Allocator`, which every `Entity` it allocates is branded with.
+///
+/// Ids are assigned from a global counter which wraps after `u16::MAX` allocators, so this is a
+/// best-effort check against mixing entities between worlds.
+#[cfg(feature = "world-id")]
+#[derive(Clone, Copy, Debug, Hash, Eq, Ord, PartialEq, PartialOrd)]
+pub struct WorldId(NonZeroU16);
+
+#[cfg(feature = "world-id")]
+impl WorldId {
+    pub fn get(self) -> u16 {
+        self.0.get()
+    }
+}
+
+// Every `Allocator` gets a fresh id on construction.
+#[cfg(feature = "world-id")]
+impl Default for WorldId {
+    fn default() -> Self {
+        static NEXT: AtomicU16 = AtomicU16::new(0);
+        loop {
+            let id = NEXT.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+            if let Some(id) = NonZeroU16::new(id) {
+                return WorldId(id);
+            }
+        }
+    }
+}
+
+/// What an `Allocator` should do with an index whose generation can no longer be incremented.
+///
+/// A single index can be re-used roughly 2 billion times before its generation is exhausted.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum GenerationOverflow {
+    /// Never re-use the index again.  This is always correct, but permanently uses up one index
+    /// out of the available index space, which may eventually cause allocation to fail with
+    /// `IndexExhausted`.
+    #[default]
+    Retire,
+    /// Start the generation of the index over again at 1.
+    ///
+    /// This keeps the index usable, but is not sound for stale handles: the next entity allocated
+    /// at the index has generation 1, so an `Entity` from the first use of the index that is still
+    /// held somewhere is equal to the new entity.  `Allocator::is_alive`, `Allocator::check` and
+    /// every component access will accept it, and a `WeakEntity` of it will report it as alive.
+    /// Every later generation of the index is revived the same way as it is re-used again.
+    Wrap,
+    /// Hold the index back and fail every allocation with `GenerationExhausted` until the policy is
+    /// changed.
+    ///
+    /// Only the fallible `try_` allocation methods surface the error, the others panic.  Switching
+    /// to `GenerationOverflow::Wrap` afterwards makes the held indexes available again, switching
+    /// to `GenerationOverflow::Retire` retires them.
+    Error,
+}
+
+impl GenerationOverflow {
+    // Whether an index with the given dead generation may be placed back in the cache for re-use.
+    fn allows_reuse(self, generation: Generation) -> bool {
+        self == GenerationOverflow::Wrap || !generation.is_exhausted()
+    }
+}
+
 /// Entities are unqiue "generational indexes" with low-valued `index` values that are appropriate
 /// as indexes into contiguous arrays.
 ///
@@ -25,6 +136,8 @@ pub struct WrongGeneration;
 pub struct Entity {
     index: Index,
     generation: AliveGeneration,
+    #[cfg(feature = "world-id")]
+    world: WorldId,
 }
 
 impl Entity {
@@ -36,14 +149,19 @@ impl Entity {
 
     /// The entity's generation.
     ///
-    /// This will never be zero.
+    /// This will never be zero.  Every time an index is re-used its generation is incremented, so
+    /// the number of times a single index can be re-used is limited by the range of the generation
+    /// rather than the range of `Index`.
     #[inline]
     pub fn generation(self) -> u32 {
         self.generation.id() as u32
     }
 
-    fn new(index: Index, generation: AliveGeneration) -> Entity {
-        Entity { index, generation }
+    /// The id of the `Allocator` which allocated this entity.
+    #[cfg(feature = "world-id")]
+    #[inline]
+    pub fn world_id(self) -> WorldId {
+        self.world
     }
 }
 
@@ -59,6 +177,18 @@ pub struct Allocator {
     // The maximum ever allocated index + 1.  If there are no outstanding atomic operations, the
     // `generations` vector should be equal to this length.
     index_len: AtomicIndex,
+    // The number of live entities, including atomically allocated ones.
+    alive_count: AtomicIndex,
+    generation_overflow: GenerationOverflow,
+    // Exhausted indexes held back under `GenerationOverflow::Error`.
+    exhausted: Vec<Index>,
+    killed_generations: Arc<KilledGenerations>,
+    // Incremented whenever the set of live entities changes.
+    live_version: AtomicU64,
+    allocated_total: AtomicU64,
+    killed_total: AtomicU64,
+    #[cfg(feature = "world-id")]
+    world: WorldId,
 }
 
 impl Allocator {
@@ -66,18 +196,43 @@ impl Allocator {
         Allocator::default()
     }
 
+    /// The id every `Entity` allocated by this allocator is branded with.
+    #[cfg(feature = "world-id")]
+    pub fn world_id(&self) -> WorldId {
+        self.world
+    }
+
+    pub fn generation_overflow(&self) -> GenerationOverflow {
+        self.generation_overflow
+    }
+
+    /// Set the policy for indexes whose generation is exhausted, the default is
+    /// `GenerationOverflow::Retire`.
+    ///
+    /// The policy is applied when an entity is killed, so it affects any index killed after this
+    /// call, and to the indexes held back by `GenerationOverflow::Error`.
+    pub fn set_generation_overflow(&mut self, policy: GenerationOverflow) {
+        self.generation_overflow = policy;
+        let exhausted = mem::take(&mut self.exhausted);
+        match policy {
+            GenerationOverflow::Retire => {}
+            GenerationOverflow::Wrap => self.cache.extend(exhausted),
+            GenerationOverflow::Error => self.exhausted = exhausted,
+        }
+    }
+
     /// Kill the given entity.
     ///
-    /// Will return `Err(WrongGeneration)` if the given entity is not the current generation in this
-    /// allcoator.
+    /// Will return an error if the given entity is not the current generation in this allcoator.
     #[inline]
-    pub fn kill(&mut self, entity: Entity) -> Result<(), WrongGeneration> {
-        if !self.is_alive(entity) {
-            return Err(WrongGeneration);
-        }
+    pub fn kill(&mut self, entity: Entity) -> Result<(), EntityError> {
+        self.check(entity)?;
 
         self.alive.remove(entity.index);
         self.killed_atomic.remove(entity.index);
+        *self.alive_count.get_mut() -= 1;
+        *self.live_version.get_mut() += 1;
+        *self.killed_total.get_mut() += 1;
 
         if self.raised_atomic.remove(entity.index) {
             // If this entity is alive atomically and we're killing it non-atomically, we must commit
@@ -92,7 +247,8 @@ impl Allocator {
             *generation = generation.killed();
         }
 
-        self.cache.push(entity.index);
+        self.killed_generations.set(entity.index, entity.generation);
+        self.release(entity.index);
 
         Ok(())
     }
@@ -103,13 +259,11 @@ impl Allocator {
     /// it is still considered live and may even have `Allocator::kill_atomic` called on it multiple
     /// times.
     ///
-    /// If the entity is not current at the time of this call, however, then this will return
-    /// `Err(WrongGeneration)`.
+    /// If the entity is not current at the time of this call, however, then this will return an
+    /// error.
     #[inline]
-    pub fn kill_atomic(&self, e: Entity) -> Result<(), WrongGeneration> {
-        if !self.is_alive(e) {
-            return Err(WrongGeneration);
-        }
+    pub fn kill_atomic(&self, e: Entity) -> Result<(), EntityError> {
+        self.check(e)?;
 
         self.killed_atomic.add_atomic(e.index());
         Ok(())
@@ -122,42 +276,91 @@ impl Allocator {
     /// alive generation for that index.  This generally means that the generation is too old
     /// because the `Allocator` allocated it and then later killed it, but it may also happen if
     /// `Entity`s are improperly mixed between `Allocator` instances and this entity has a newer
-    /// generation than the current live one for that index.
+    /// generation than the current live one for that index.  With the `world-id` feature, entities
+    /// of other allocators are never alive.
     #[inline]
     pub fn is_alive(&self, e: Entity) -> bool {
         self.entity(e.index()) == Some(e)
     }
 
+    /// Returns `Ok(())` if the given entity is alive, otherwise the reason it is not.
+    #[inline]
+    #[cfg_attr(not(feature = "world-id"), allow(clippy::useless_conversion))]
+    pub fn check(&self, e: Entity) -> Result<(), EntityError> {
+        #[cfg(feature = "world-id")]
+        if e.world != self.world {
+            return Err(WrongWorld.into());
+        }
+
+        if self.is_alive(e) {
+            Ok(())
+        } else {
+            Err(WrongGeneration.into())
+        }
+    }
+
+    /// Create a `WeakEntity` for the given entity, which can check whether the entity is alive
+    /// without access to this allocator.
+    pub fn weak(&self, e: Entity) -> WeakEntity {
+        WeakEntity {
+            entity: e,
+            killed_generations: Arc::clone(&self.killed_generations),
+        }
+    }
+
     /// *If* the given index has a live entity associated with it, returns that live `Entity`.
     #[inline]
     pub fn entity(&self, index: Index) -> Option<Entity> {
         let generation = self.generation(index);
         if let Some(alive) = generation.to_alive() {
-            Some(Entity::new(index, alive))
+            Some(self.make_entity(index, alive))
         } else if self.raised_atomic.contains(index) {
-            Some(Entity::new(index, generation.raised()))
+            Some(self.make_entity(index, generation.raised()))
         } else {
             None
         }
     }
 
     /// Allocate a new unique Entity.
+    ///
+    /// # Panics
+    /// Panics if allocation fails, see `Allocator::try_allocate`.
     #[inline]
     pub fn allocate(&mut self) -> Entity {
-        let index = self.cache.pop().unwrap_or_else(|| {
-            let index = *self.index_len.get_mut();
-            let index_len = index.checked_add(1).expect("no entity left to allocate");
-            *self.index_len.get_mut() = index_len;
-            self.update_generation_length();
-            index
-        });
+        self.try_allocate().unwrap()
+    }
+
+    /// Allocate a new unique Entity, or return an error if allocation fails.
+    ///
+    /// Returns `AllocateError::IndexExhausted` once every index a `BitSet` can hold (see `Index`)
+    /// is either allocated or has been retired due to `GenerationOverflow::Retire`, and
+    /// `AllocateError::GenerationExhausted` while an index is held back due to
+    /// `GenerationOverflow::Error`.
+    #[inline]
+    pub fn try_allocate(&mut self) -> Result<Entity, AllocateError> {
+        self.check_exhausted()?;
+        let index = match self.cache.pop() {
+            Some(index) => index,
+            None => {
+                let index = *self.index_len.get_mut();
+                if index == MAX_INDEX {
+                    return Err(IndexExhausted.into());
+                }
+                *self.index_len.get_mut() = index + 1;
+                self.update_generation_length();
+                index
+            }
+        };
 
         self.alive.add(index);
+        *self.alive_count.get_mut() += 1;
+        *self.live_version.get_mut() += 1;
+        *self.allocated_total.get_mut() += 1;
 
         let generation = &mut self.generations[index as usize];
         let raised = generation.raised();
         *generation = raised.generation();
-        Entity::new(index, raised)
+        Ok(self.make_entity(index, raised))
     }
 
     /// Allocate an entity atomically.
@@ -168,33 +371,153 @@ impl Allocator {
     /// The only observable difference is that the query performance of atomically allocated
     /// entities may be slightly worse until `merge_atomic` is called, at which point they will be
     /// merged into the same data structure that keeps track of regular live entities.
+    ///
+    /// # Panics
+    /// Panics if allocation fails, see `Allocator::try_allocate`.
     #[inline]
     pub fn allocate_atomic(&self) -> Entity {
-        let index = self.cache.pop_atomic().unwrap_or_else(|| {
-            atomic_increment(&self.index_len).expect("no entity left to allocate")
-        });
+        self.try_allocate_atomic().unwrap()
+    }
+
+    /// Allocate an entity atomically, or return an error if allocation fails, see
+    /// `Allocator::try_allocate`.
+    #[inline]
+    pub fn try_allocate_atomic(&self) -> Result<Entity, AllocateError> {
+        self.check_exhausted()?;
+        let index = match self.cache.pop_atomic() {
+            Some(index) => index,
+            None => atomic_increment(&self.index_len).ok_or(IndexExhausted)?,
+        };
 
         self.raised_atomic.add_atomic(index);
-        Entity::new(index, self.generation(index).raised())
+        self.alive_count.fetch_add(1, Ordering::Relaxed);
+        self.live_version.fetch_add(1, Ordering::Relaxed);
+        self.allocated_total.fetch_add(1, Ordering::Relaxed);
+        Ok(self.make_entity(index, self.generation(index).raised()))
+    }
+
+    /// Allocate `count` entities atomically, pushing them onto the given `Vec`.
+    ///
+    /// This is equivalent to calling `Allocator::allocate_atomic` `count` times, but only performs
+    /// a few contended atomic operations rather than a few per entity, so it is much faster when
+    /// many threads are allocating entities at the same time.
+    ///
+    /// Dead indexes are re-used first, and the rest are allocated as a single range of new
+    /// indexes.  If there are not enough new indexes left for the whole range, this returns
+    /// `AllocateError::IndexExhausted` without allocating any of them, but the entities which
+    /// re-used dead indexes are still allocated and pushed.  Nothing is allocated while an index is
+    /// held back due to `GenerationOverflow::Error`.
+    pub fn allocate_atomic_batch(
+        &self,
+        count: Index,
+        entities: &mut Vec<Entity>,
+    ) -> Result<(), AllocateError> {
+        self.check_exhausted()?;
+        entities.reserve(count as usize);
+        self.live_version.fetch_add(1, Ordering::Relaxed);
+
+        let cached = self.cache.pop_atomic_n(count, |index| {
+            self.raised_atomic.add_atomic(index);
+            entities.push(self.make_entity(index, self.generation(index).raised()));
+        });
+
+        self.alive_count.fetch_add(cached, Ordering::Relaxed);
+        self.allocated_total
+            .fetch_add(cached as u64, Ordering::Relaxed);
+
+        let remaining = count - cached;
+        if remaining > 0 {
+            let start = atomic_add_n(&self.index_len, remaining).ok_or(IndexExhausted)?;
+            for index in start..start + remaining {
+                self.raised_atomic.add_atomic(index);
+                entities.push(self.make_entity(index, self.generation(index).raised()));
+            }
+            self.alive_count.fetch_add(remaining, Ordering::Relaxed);
+            self.allocated_total
+                .fetch_add(remaining as u64, Ordering::Relaxed);
+        }
+
+        Ok(())
     }
 
     /// Returns a `BitSetLike` for all live entities.
     ///
     /// This is a `BitSetOr` of the non-atomically live entities and the atomically live entities.
     #[inline]
-    pub fn live_bitset(&self) -> LiveBitSet {
+    pub fn live_bitset(&self) -> LiveBitSet<'_> {
         BitSetOr(&self.alive, &self.raised_atomic)
     }
 
+    /// Iterate over every live entity in index order, including atomically allocated entities.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.live_bitset()
+            .iter()
+            .map(move |index| self.make_entity(index, self.generation(index).raised()))
+    }
+
+    /// Returns the number of currently live entities, including atomically allocated entities.
+    ///
+    /// Entities marked for deletion with `Allocator::kill_atomic` are still counted until the next
+    /// call to `Allocator::merge_atomic`.
+    #[inline]
+    pub fn alive_count(&self) -> Index {
+        self.alive_count.load(Ordering::Relaxed)
+    }
+
+    /// A counter which changes whenever an entity is allocated or killed, so it can be compared
+    /// with a previous value to find out whether the set of live entities may have changed.
+    #[inline]
+    pub fn live_version(&self) -> u64 {
+        self.live_version.load(Ordering::Relaxed)
+    }
+
+    /// The total number of entities ever allocated by this allocator, including atomically
+    /// allocated ones.
+    #[inline]
+    pub fn allocated_total(&self) -> u64 {
+        self.allocated_total.load(Ordering::Relaxed)
+    }
+
+    /// The total number of entities ever killed by this allocator.
+    ///
+    /// Entities marked with `Allocator::kill_atomic` are only counted once they are merged.
+    #[inline]
+    pub fn killed_total(&self) -> u64 {
+        self.killed_total.load(Ordering::Relaxed)
+    }
+
     /// Returns the maximum ever allocated entity index + 1.
     ///
-    /// Since finding the actual live entity count is costly, this is a very cheap way of finding
-    /// out the approximate maximum number of entities ever allocated.
+    /// This is a cheap way of finding out the approximate maximum number of entities ever
+    /// allocated, for the current number of live entities use `Allocator::alive_count`.
     #[inline]
     pub fn max_entity_count(&self) -> Index {
         self.index_len.load(Ordering::Relaxed)
     }
 
+    /// Reserve space for `additional` more entity indexes beyond `Allocator::max_entity_count`, so
+    /// that allocating them does not need to grow any internal structures.
+    ///
+    /// # Panics
+    /// Panics if the reserved indexes would be beyond the maximum index a `BitSet` can hold.
+    pub fn reserve(&mut self, additional: Index) {
+        self.update_generation_length();
+        let len = self.max_entity_count().saturating_add(additional);
+        self.generations
+            .reserve(len as usize - self.generations.len());
+        reserve_bitset(&mut self.alive, len);
+    }
+
+    /// Report the memory used by this allocator.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::of_vec(&self.generations)
+            + bitset_usage(&self.alive)
+            + bitset_usage(&self.raised_atomic)
+            + bitset_usage(&self.killed_atomic)
+            + MemoryUsage::of_vec(&self.cache.cache)
+            + MemoryUsage::of_vec(&self.killed_generations.0.read().unwrap())
+    }
+
     /// Merge all atomic operations done since the last call to `Allocator::merge_atomic`.
     ///
     /// Atomically allocated entities become merged into the faster non-atomic BitSet, and entities
@@ -216,13 +539,115 @@ impl Allocator {
 
         for index in (&self.killed_atomic).iter() {
             self.alive.remove(index);
-            let generation = &mut self.generations[index as usize];
-            killed.push(Entity::new(index, generation.to_alive().unwrap()));
-            *generation = generation.killed();
+            let generation = self.generations[index as usize];
+            killed.push(self.make_entity(index, generation.to_alive().unwrap()));
+            self.killed_generations
+                .set(index, generation.to_alive().unwrap());
+            self.generations[index as usize] = generation.killed();
         }
         self.killed_atomic.clear();
+        *self.alive_count.get_mut() -= killed.len() as Index;
+        *self.killed_total.get_mut() += killed.len() as u64;
+        if !killed.is_empty() {
+            *self.live_version.get_mut() += 1;
+        }
+
+        let generations = &self.generations;
+        let policy = self.generation_overflow;
+        self.cache.extend(
+            killed
+                .iter()
+                .map(|e| e.index)
+                .filter(|&index| policy.allows_reuse(generations[index as usize])),
+        );
+        if policy == GenerationOverflow::Error {
+            self.exhausted.extend(
+                killed
+                    .iter()
+                    .map(|e| e.index)
+                    .filter(|&index| generations[index as usize].is_exhausted()),
+            );
+        }
+    }
+
+    /// Renumber every live entity so that the live entities occupy the lowest indexes that can
+    /// still be used, keeping their relative order.
+    ///
+    /// Returns every entity which moved paired with its new value, in index order.  Entities which
+    /// keep their index are not included.  Vacated indexes keep their dead generations, so a stale
+    /// `Entity` is never mistaken for a live one, and are re-used lowest index first.
+    ///
+    /// Indexes retired under `GenerationOverflow::Retire` are never used as the new index of an
+    /// entity, so if there are retired indexes below `Allocator::alive_count`, the live entities
+    /// are spread past it by that many.
+    ///
+    /// # Panics
+    /// Panics if there are atomic operations which have not been merged with
+    /// `Allocator::merge_atomic`.
+    pub fn compact(&mut self) -> Vec<(Entity, Entity)> {
+        assert!(
+            self.raised_atomic.is_empty() && self.killed_atomic.is_empty(),
+            "cannot compact an allocator with unmerged atomic operations"
+        );
+        self.update_generation_length();
+
+        let policy = self.generation_overflow;
+        let usable =
+            |generation: Generation| !generation.is_alive() && policy.allows_reuse(generation);
+        let live: Vec<Index> = (&self.alive).iter().collect();
+        let mut moved = Vec::new();
+        // The lowest index which may be the new index of the next live entity.  Every index below
+        // it is either live or cannot be re-used.
+        let mut next = 0;
+        for old_index in live {
+            while next < old_index && !usable(self.generations[next as usize]) {
+                next += 1;
+            }
+            if next == old_index {
+                next += 1;
+                continue;
+            }
+
+            let new_index = next;
+            next += 1;
+            let old_generation = self.generations[old_index as usize];
+            // Whatever was at `new_index` is either dead or has already moved to a lower index,
+            // and its dead generation is allowed to be re-used, so raising it gives a value no
+            // previous entity had.
+            let new_generation = self.generations[new_index as usize].raised();
+            self.generations[new_index as usize] = new_generation.generation();
+            self.generations[old_index as usize] = old_generation.killed();
+            self.killed_generations
+                .set(old_index, old_generation.to_alive().unwrap());
+            self.alive.remove(old_index);
+            self.alive.add(new_index);
+
+            moved.push((
+                self.make_entity(old_index, old_generation.to_alive().unwrap()),
+                self.make_entity(new_index, new_generation),
+            ));
+        }
+
+        *self.live_version.get_mut() += 1;
+        let generations = &self.generations;
+        let alive = &self.alive;
+        self.cache = EntityCache::default();
+        self.cache
+            .extend((0..generations.len() as Index).rev().filter(|&index| {
+                !alive.contains(index) && policy.allows_reuse(generations[index as usize])
+            }));
+
+        moved
+    }
 
-        self.cache.extend(killed.iter().map(|e| e.index));
+    #[inline]
+    fn make_entity(&self, index: Index, generation: AliveGeneration) -> Entity {
+        Entity {
+            index,
+            generation,
+            #[cfg(feature = "world-id")]
+            world: self.world,
+        }
     }
 
     fn generation(&self, index: Index) -> Generation {
@@ -234,6 +659,24 @@ impl Allocator {
 
     // Commit the changes to the length of the generation vector from the atomically adjusted index
     // length.
+    // Make a newly killed index available for re-use, if the `GenerationOverflow` policy allows
+    // it.
+    fn release(&mut self, index: Index) {
+        let generation = self.generations[index as usize];
+        if self.generation_overflow.allows_reuse(generation) {
+            self.cache.push(index);
+        } else if self.generation_overflow == GenerationOverflow::Error {
+            self.exhausted.push(index);
+        }
+    }
+
+    fn check_exhausted(&self) -> Result<(), GenerationExhausted> {
+        match self.exhausted.first() {
+            Some(&index) => Err(GenerationExhausted(index)),
+            None => Ok(()),
+        }
+    }
+
     fn update_generation_length(&mut self) {
         let index_len = *self.index_len.get_mut() as usize;
         if self.generations.len() < index_len {
@@ -252,16 +695,99 @@ impl<'a> Join for &'a Allocator {
     }
 
     unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
-        Entity::new(index, access.generation(index).raised())
+        access.make_entity(index, access.generation(index).raised())
     }
 }
 
-#[derive(Default, Debug)]
+/// An owned handle to an `Entity` which can check whether the entity is still alive without any
+/// access to the `Allocator` or `World`, for example from another thread or an async task.
+///
+/// Entities are alive until they are killed by their allocator, entities marked with
+/// `Allocator::kill_atomic` are alive until the next `Allocator::merge_atomic`.  Liveness is not
+/// reliable for entities whose index has had its generation wrap with `GenerationOverflow::Wrap`.
+#[derive(Debug, Clone)]
+pub struct WeakEntity {
+    entity: Entity,
+    killed_generations: Arc<KilledGenerations>,
+}
+
+impl WeakEntity {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.killed_generations.is_killed(self.entity)
+    }
+}
+
+// The most recently killed generation of every index, shared between an `Allocator` and every
+// `WeakEntity` created from it.
+//
+// Generations only ever increase, so an entity is dead exactly when its index has had a generation
+// at least as new as its own killed.  Indexes past the end of the table have never been killed.
+#[derive(Debug, Default)]
+struct KilledGenerations(RwLock<Vec<AtomicI32>>);
+
+impl KilledGenerations {
+    fn set(&self, index: Index, generation: AliveGeneration) {
+        let table = self.0.read().unwrap();
+        if let Some(killed) = table.get(index as usize) {
+            killed.store(generation.id(), Ordering::Release);
+        } else {
+            drop(table);
+            let mut table = self.0.write().unwrap();
+            table.resize_with(index as usize + 1, Default::default);
+            *table[index as usize].get_mut() = generation.id();
+        }
+    }
+
+    fn is_killed(&self, e: Entity) -> bool {
+        self.0
+            .read()
+            .unwrap()
+            .get(e.index as usize)
+            .is_some_and(|killed| killed.load(Ordering::Acquire) >= e.generation.id())
+    }
+}
+
+// A stack of dead indexes available for re-use, split into segments so that threads allocating
+// atomically at the same time mostly pop from different atomics.
+//
+// The `cache` vector is only ever mutated through `&mut self`, so it cannot change while any
+// atomic pop is in progress.  It is divided into contiguous segments, each of which is a stack
+// with its own atomic length, and an atomic pop decrements the length of a single segment, which
+// hands out a unique slot of the (immutable) vector to each caller.  Each thread starts at its
+// own segment and only moves on to the others once that one is empty.
+//
+// `maintain` commits atomic pops to the vector once we have exclusive access again, keeping the
+// remaining indexes in stack order, and re-divides the vector into segments of equal size.
+#[derive(Debug)]
 struct EntityCache {
     cache: Vec<Index>,
+    segments: Box<[Segment]>,
+}
+
+// Aligned to a cache line so that segments popped by different threads don't contend.
+#[derive(Default, Debug)]
+#[repr(align(64))]
+struct Segment {
+    start: Index,
+    // The number of indexes in `cache[start..]` which belong to this segment, before any atomic
+    // pops.
+    capacity: Index,
     len: AtomicIndex,
 }
 
+impl Default for EntityCache {
+    fn default() -> Self {
+        EntityCache {
+            cache: Vec::new(),
+            segments: (0..CACHE_SEGMENTS).map(|_| Segment::default()).collect(),
+        }
+    }
+}
+
 impl EntityCache {
     fn push(&mut self, index: Index) {
         self.extend(iter::once(index));
@@ -269,17 +795,85 @@ impl EntityCache {
 
     fn pop(&mut self) -> Option<Index> {
         self.maintain();
-        let x = self.cache.pop();
-        *self.len.get_mut() = self.cache.len() as Index;
-        x
+        let x = self.cache.pop()?;
+        // After `maintain` every segment is full, so the top of the stack belongs to the last
+        // non-empty segment, and the empty segments after it start at the top of the stack.
+        let top = self.cache.len() as Index;
+        for segment in self.segments.iter_mut().rev() {
+            if segment.capacity == 0 {
+                segment.start = top;
+            } else {
+                segment.capacity -= 1;
+                *segment.len.get_mut() -= 1;
+                break;
+            }
+        }
+        Some(x)
     }
 
     fn pop_atomic(&self) -> Option<Index> {
-        atomic_decrement(&self.len).map(|x| self.cache[(x - 1) as usize])
+        self.search_segments(|segment| {
+            atomic_decrement(&segment.len).map(|x| self.cache[(segment.start + x - 1) as usize])
+        })
+    }
+
+    // Atomically pop up to `count` indexes, with one atomic operation for each segment visited.
+    //
+    // Returns the number of indexes popped.
+    fn pop_atomic_n(&self, count: Index, mut f: impl FnMut(Index)) -> Index {
+        let mut popped = 0;
+        self.search_segments(|segment| {
+            let (start, end) = atomic_decrement_n(&segment.len, count - popped);
+            for &index in
+                &self.cache[(segment.start + start) as usize..(segment.start + end) as usize]
+            {
+                f(index);
+            }
+            popped += end - start;
+            (popped == count).then_some(())
+        });
+        popped
+    }
+
+    // Calls `f` with every segment, starting at the calling thread's own segment, until it
+    // returns `Some`.
+    fn search_segments<R>(&self, mut f: impl FnMut(&Segment) -> Option<R>) -> Option<R> {
+        let home = HOME_SEGMENT.with(|&home| home);
+        (0..CACHE_SEGMENTS).find_map(|i| f(&self.segments[(home + i) % CACHE_SEGMENTS]))
     }
 
     fn maintain(&mut self) {
-        self.cache.truncate(*self.len.get_mut() as usize);
+        if self
+            .segments
+            .iter_mut()
+            .all(|segment| *segment.len.get_mut() == segment.capacity)
+        {
+            return;
+        }
+
+        let mut len = 0;
+        for segment in self.segments.iter_mut() {
+            let start = segment.start as usize;
+            let remaining = *segment.len.get_mut() as usize;
+            self.cache.copy_within(start..start + remaining, len);
+            len += remaining;
+        }
+        self.cache.truncate(len);
+        self.divide();
+    }
+
+    // Divide the whole vector into full segments of equal size.
+    fn divide(&mut self) {
+        let len = self.cache.len() as Index;
+        let per_segment = len.div_ceil(CACHE_SEGMENTS as Index);
+        let mut start = 0;
+        for segment in self.segments.iter_mut() {
+            let capacity = per_segment.min(len - start);
+            segment.start = start;
+            segment.capacity = capacity;
+            *segment.len.get_mut() = capacity;
+            start += capacity;
+        }
     }
 }
 
@@ -287,11 +881,22 @@ impl Extend<Index> for EntityCache {
     fn extend<T: IntoIterator<Item = Index>>(&mut self, iter: T) {
         self.maintain();
         self.cache.extend(iter);
-        *self.len.get_mut() = self.cache.len() as Index;
+        self.divide();
     }
 }
 
-const MAX_INDEX: Index = u32::MAX;
+const CACHE_SEGMENTS: usize = 8;
+
+thread_local! {
+    // The segment of every `EntityCache` that this thread pops from first, assigned round-robin.
+    static HOME_SEGMENT: usize = {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        NEXT.fetch_add(1, Ordering::Relaxed) % CACHE_SEGMENTS
+    };
+}
+
+// One past the highest index a `BitSet` can hold, the maximum value of `index_len`.
+const MAX_INDEX: Index = (BitSet::BITS_PER_USIZE as Index).pow(4);
 type AtomicIndex = AtomicU32;
 
 type GenId = i32;
@@ -335,18 +940,23 @@ impl Generation {
         }
     }
 
+    // Returns true if this is a dead generation which cannot be raised without wrapping.
+    fn is_exhausted(self) -> bool {
+        self.0 == -GenId::MAX
+    }
+
     // If this generation is dead, returns the 'raised' version of this generation, otherwise just
     // returns the current live generation.
     //
     // The 'raised' version of a generation has an ID which is the negation of its current dead ID
-    // (so the positive verison of its dead ID) + 1.
+    // (so the positive verison of its dead ID) + 1.  An exhausted generation wraps back around to
+    // 1, the `Allocator` is responsible for never raising an exhausted generation unless its
+    // `GenerationOverflow` policy allows it.
     fn raised(self) -> AliveGeneration {
         if self.0 > 0 {
             AliveGeneration(unsafe { NZGenId::new_unchecked(self.0) })
         } else {
-            let id = (1 as GenId)
-                .checked_sub(self.id())
-                .expect("generation overflow");
+            let id = (1 as GenId).checked_sub(self.id()).unwrap_or(1);
             AliveGeneration(unsafe { NZGenId::new_unchecked(id) })
         }
     }
@@ -368,7 +978,7 @@ impl AliveGeneration {
     }
 }
 
-// Increments `i` atomically without wrapping on overflow.
+// Increments `i` atomically without going past `MAX_INDEX`.
 //
 // Resembles a `fetch_add(1, Ordering::Relaxed)` with checked overflow, returning `None` instead.
 fn atomic_increment(i: &AtomicIndex) -> Option<Index> {
@@ -382,6 +992,38 @@ fn atomic_increment(i: &AtomicIndex) -> Option<Index> {
     None
 }
 
+// Adds `n` to `i` atomically, returning `None` without changing `i` if the result would be past
+// `MAX_INDEX`.
+//
+// On success returns the previous value of `i`.
+fn atomic_add_n(i: &AtomicIndex, n: Index) -> Option<Index> {
+    let mut prev = i.load(Ordering::Relaxed);
+    while let Some(next) = prev.checked_add(n).filter(|&next| next <= MAX_INDEX) {
+        match i.compare_exchange_weak(prev, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(x) => return Some(x),
+            Err(next_prev) => prev = next_prev,
+        }
+    }
+    None
+}
+
+// Subtracts up to `n` from `i` atomically, stopping at zero.
+//
+// Returns the range `(new, prev)` of values that were subtracted.
+fn atomic_decrement_n(i: &AtomicIndex, n: Index) -> (Index, Index) {
+    let mut prev = i.load(Ordering::Relaxed);
+    loop {
+        let next = prev.saturating_sub(n);
+        if next == prev {
+            return (prev, prev);
+        }
+        match i.compare_exchange_weak(prev, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(x) => return (next, x),
+            Err(next_prev) => prev = next_prev,
+        }
+    }
+}
+
 // Decrements `i` atomically without wrapping on underflow.
 //
 // Resembles a `fetch_sub(1, Ordering::Relaxed)` with checked underflow, returning `None` instead.
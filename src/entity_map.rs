@@ -0,0 +1,122 @@
+use rustc_hash::FxHashMap;
+
+use crate::{
+    entity::Entity,
+    join::IntoJoinExt,
+    resource_set::ResourceSet,
+    world_common::{Component, ComponentStorage},
+};
+
+/// Implemented by components and resources which hold `Entity` values, so that those values can be
+/// rewritten when entities are renumbered by `World::compact`.
+pub trait MapEntities {
+    fn map_entities(&mut self, map: &EntityMap);
+}
+
+/// A mapping from old to new `Entity` values, returned from `World::compact`.
+///
+/// Only entities which actually changed are present in the map.
+#[derive(Debug, Clone, Default)]
+pub struct EntityMap(FxHashMap<Entity, Entity>);
+
+impl EntityMap {
+    /// Returns the new value of the given entity, or `None` if the entity did not change.
+    pub fn get(&self, e: Entity) -> Option<Entity> {
+        self.0.get(&e).copied()
+    }
+
+    /// Returns the new value of the given entity, or the entity itself if it did not change.
+    pub fn map(&self, e: Entity) -> Entity {
+        self.get(e).unwrap_or(e)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over every (old, new) pair, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.0.iter().map(|(&old, &new)| (old, new))
+    }
+}
+
+impl FromIterator<(Entity, Entity)> for EntityMap {
+    fn from_iter<I: IntoIterator<Item = (Entity, Entity)>>(iter: I) -> Self {
+        EntityMap(iter.into_iter().collect())
+    }
+}
+
+/// The set of component and resource types which hold `Entity` values that `World::compact` must
+/// rewrite.
+#[derive(Default)]
+pub struct EntityMapRegistry {
+    components: Vec<fn(&mut ResourceSet, &EntityMap)>,
+    resources: Vec<fn(&mut ResourceSet, &EntityMap)>,
+}
+
+impl EntityMapRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a component type whose values refer to other entities.
+    ///
+    /// If the component is not inserted into the world being compacted, it is skipped.
+    pub fn register_component<C>(&mut self)
+    where
+        C: Component + MapEntities + 'static,
+        C::Storage: Send,
+    {
+        fn map<C>(components: &mut ResourceSet, map: &EntityMap)
+        where
+            C: Component + MapEntities + 'static,
+            C::Storage: Send,
+        {
+            if components.contains_local::<ComponentStorage<C>>() {
+                for c in components.get_mut::<ComponentStorage<C>>().join() {
+                    c.map_entities(map);
+                }
+            }
+        }
+
+        self.components.push(map::<C>);
+    }
+
+    /// Register a resource type whose value refers to entities.
+    ///
+    /// If the resource is not inserted into the world being compacted, it is skipped.  Resources
+    /// of a parent `ResourceSet` are never rewritten.
+    pub fn register_resource<R>(&mut self)
+    where
+        R: MapEntities + Send + 'static,
+    {
+        fn map<R>(resources: &mut ResourceSet, map: &EntityMap)
+        where
+            R: MapEntities + Send + 'static,
+        {
+            if resources.contains_local::<R>() {
+                resources.get_mut::<R>().map_entities(map);
+            }
+        }
+
+        self.resources.push(map::<R>);
+    }
+
+    pub(crate) fn map_entities(
+        &self,
+        resources: &mut ResourceSet,
+        components: &mut ResourceSet,
+        map: &EntityMap,
+    ) {
+        for mapper in &self.components {
+            mapper(components, map);
+        }
+        for mapper in &self.resources {
+            mapper(resources, map);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+use crate::{
+    fetch_resources::{FetchError, FetchResources},
+    local::{Local, Locals},
+    resources::ResourceConflict,
+    world::{ReadResource, World, WriteResource},
+    world_common::WorldResources,
+};
+
+/// A channel of events of type `T`, stored as a resource and inserted with `World::insert_events`.
+///
+/// Events are kept for two calls to `World::merge`, so every system which runs once per frame
+/// sees every event exactly once, no matter whether it runs before or after the system which sent
+/// it.  Events are read with `EventReader` and sent with `EventWriter`.
+pub struct Events<T> {
+    events: VecDeque<T>,
+    // The sequence number of the first event in `events`.
+    first: u64,
+    // The number of events which were sent before the last update, and are dropped by the next.
+    previous: usize,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Events {
+            events: VecDeque::new(),
+            first: 0,
+            previous: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.push_back(event);
+    }
+
+    /// Drop every event sent before the previous update, called by `World::merge` for every
+    /// channel inserted with `World::insert_events`.
+    pub fn update(&mut self) {
+        self.events.drain(..self.previous);
+        self.first += self.previous as u64;
+        self.previous = self.events.len();
+    }
+
+    /// The number of events currently held.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Iterate over every event currently held, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.events.iter()
+    }
+
+    // Returns every event at or after the given sequence number which is still held, and advances
+    // the cursor past them.
+    fn read_from(&self, cursor: &mut u64) -> impl Iterator<Item = &T> + '_ {
+        let start = cursor.saturating_sub(self.first) as usize;
+        *cursor = self.end();
+        self.events.range(start.min(self.events.len())..)
+    }
+
+    fn unread(&self, cursor: u64) -> usize {
+        (self.end() - cursor.max(self.first)) as usize
+    }
+
+    fn end(&self) -> u64 {
+        self.first + self.events.len() as u64
+    }
+}
+
+// How far a single system has read into `Events<T>`, kept in a `Local` so that every system has
+// its own.
+struct EventCursor<T>(u64, PhantomData<fn() -> T>);
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        EventCursor(0, PhantomData)
+    }
+}
+
+/// `FetchResources` type that reads the events in `Events<T>` which the fetching system has not yet
+/// seen.
+///
+/// The position of every reader is kept in a `Local`, so it must be fetched with
+/// `FetchResources::fetch_local`, such as by a system wrapped in `WithLocals`.  A reader's first
+/// fetch sees every event still held by the channel.
+///
+/// # Panics
+/// Panics if `Events<T>` does not exist or is already borrowed for writing, or if fetched without
+/// `Locals`.
+pub struct EventReader<'a, T: Send + Sync + 'static> {
+    events: ReadResource<'a, Events<T>>,
+    cursor: Local<EventCursor<T>>,
+}
+
+impl<'a, T: Send + Sync + 'static> EventReader<'a, T> {
+    /// Iterate over every unread event, marking them as read.
+    pub fn read(&mut self) -> impl Iterator<Item = &T> + '_ {
+        self.events.read_from(&mut self.cursor.0)
+    }
+
+    /// The number of unread events.
+    pub fn len(&self) -> usize {
+        self.events.unread(self.cursor.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mark every event as read without looking at them.
+    pub fn clear(&mut self) {
+        self.cursor.0 = self.events.end();
+    }
+}
+
+impl<'a, T> FetchResources<'a, World> for EventReader<'a, T>
+where
+    T: Send + Sync + 'static,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        <(ReadResource<'a, Events<T>>, Local<EventCursor<T>>)>::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        let (events, cursor) = FetchResources::fetch(world);
+        EventReader { events, cursor }
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        let (events, cursor) = FetchResources::try_fetch(world)?;
+        Ok(EventReader { events, cursor })
+    }
+
+    fn fetch_local(world: &'a World, locals: &Locals) -> Self {
+        let (events, cursor) = FetchResources::fetch_local(world, locals);
+        EventReader { events, cursor }
+    }
+
+    fn try_fetch_local(world: &'a World, locals: &Locals) -> Result<Self, FetchError> {
+        let (events, cursor) = FetchResources::try_fetch_local(world, locals)?;
+        Ok(EventReader { events, cursor })
+    }
+}
+
+/// `FetchResources` type that sends events into `Events<T>`.
+///
+/// # Panics
+/// Panics if `Events<T>` does not exist or is already borrowed.
+pub struct EventWriter<'a, T: Send + 'static>(WriteResource<'a, Events<T>>);
+
+impl<'a, T: Send + 'static> EventWriter<'a, T> {
+    pub fn send(&mut self, event: T) {
+        self.0.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.0.events.extend(events);
+    }
+}
+
+impl<'a, T> FetchResources<'a, World> for EventWriter<'a, T>
+where
+    T: Send + 'static,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        WriteResource::<'a, Events<T>>::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        EventWriter(FetchResources::fetch(world))
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(EventWriter(FetchResources::try_fetch(world)?))
+    }
+}
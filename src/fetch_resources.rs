@@ -1,17 +1,71 @@
-use std::marker::PhantomData;
+use std::{any::type_name, marker::PhantomData};
 
-use crate::resources::{ResourceConflict, Resources};
+use thiserror::Error;
+
+use crate::{
+    local::Locals,
+    resources::{ResourceConflict, Resources},
+};
+
+/// Returned from `FetchResources::try_fetch` when a resource cannot be fetched.
+#[derive(Debug, Clone, Error)]
+pub enum FetchError {
+    #[error("no such resource {0:?}")]
+    Missing(&'static str),
+    #[error("resource {0:?} is already borrowed")]
+    Borrowed(&'static str),
+}
 
 /// A trait for statically defining mutable and immutable resources fetched from a data source which
 /// may or may not conflict.
 ///
 /// Tuples of types that implement `FetchResources` automatically themselves implement
 /// `FetchResources` and correctly find the union of the resources they use.
+///
+/// Conflicts within a tuple, such as `(ReadComponent<A>, WriteComponent<A>)`, are only detected at
+/// runtime by `FetchResources::check_resources`.  Detecting them at compile time would require
+/// comparing `TypeId`s in a const context or negative trait reasoning, neither of which is
+/// available on stable Rust, so calling `check_resources` for every system in a test is the
+/// cheapest way to catch them early.
 pub trait FetchResources<'a, Source> {
     type Resources: Resources;
 
     fn check_resources() -> Result<Self::Resources, ResourceConflict>;
     fn fetch(source: &'a Source) -> Self;
+
+    /// Like `FetchResources::fetch`, but returns an error if a resource is missing or cannot be
+    /// borrowed, rather than panicking.
+    ///
+    /// The default implementation simply calls `FetchResources::fetch`, so it still panics.
+    fn try_fetch(source: &'a Source) -> Result<Self, FetchError>
+    where
+        Self: Sized,
+    {
+        Ok(Self::fetch(source))
+    }
+
+    /// Like `FetchResources::fetch`, but with the `Locals` of the fetching system, which is how
+    /// `Local` values are fetched.
+    ///
+    /// The default implementation ignores `locals` and calls `FetchResources::fetch`.
+    fn fetch_local(source: &'a Source, locals: &Locals) -> Self
+    where
+        Self: Sized,
+    {
+        let _ = locals;
+        Self::fetch(source)
+    }
+
+    /// Like `FetchResources::try_fetch`, but with the `Locals` of the fetching system.
+    ///
+    /// The default implementation ignores `locals` and calls `FetchResources::try_fetch`.
+    fn try_fetch_local(source: &'a Source, locals: &Locals) -> Result<Self, FetchError>
+    where
+        Self: Sized,
+    {
+        let _ = locals;
+        Self::try_fetch(source)
+    }
 }
 
 /// An empty type useful in generic contexts that implements `FetchResources` but does not actually
@@ -36,6 +90,86 @@ impl<'a, S, R: Resources> FetchResources<'a, S> for FetchNone<S, R> {
     }
 }
 
+/// Caches the checked resources of a `FetchResources` type, so that a system which is run many
+/// times only checks its fetch for internal conflicts once.
+///
+/// The cache never borrows the source.  Every call to `CachedFetch::fetch` fetches fresh from the
+/// given source, so borrows are released as soon as the fetched value is dropped and nothing is
+/// held between runs.  The cached resources are available from `CachedFetch::resources`, so that
+/// a schedule can check them against other systems without calling `check_resources` again.
+///
+/// The cache is keyed by the name of the fetched type, so fetching a different type through the
+/// same cache checks that type instead.
+pub struct CachedFetch<R> {
+    cached: Option<(&'static str, R)>,
+}
+
+impl<R> Default for CachedFetch<R> {
+    fn default() -> Self {
+        CachedFetch { cached: None }
+    }
+}
+
+impl<R: Resources> CachedFetch<R> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch `F` from the given source, checking it for internal conflicts only if it is not
+    /// already cached.
+    ///
+    /// # Panics
+    /// Panics if `F` has an internal resource conflict, or if any of its resources cannot be
+    /// fetched.
+    pub fn fetch<'a, S, F>(&mut self, source: &'a S) -> F
+    where
+        F: FetchResources<'a, S, Resources = R>,
+    {
+        self.check::<S, F>();
+        F::fetch(source)
+    }
+
+    /// Like `CachedFetch::fetch`, but returns an error if a resource is missing or cannot be
+    /// borrowed, see `FetchResources::try_fetch`.
+    ///
+    /// # Panics
+    /// Panics if `F` has an internal resource conflict.
+    pub fn try_fetch<'a, S, F>(&mut self, source: &'a S) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, S, Resources = R>,
+    {
+        self.check::<S, F>();
+        F::try_fetch(source)
+    }
+
+    /// The resources of the most recently fetched type, if any.
+    pub fn resources(&self) -> Option<&R> {
+        self.cached.as_ref().map(|(_, resources)| resources)
+    }
+
+    pub fn is_cached(&self) -> bool {
+        self.cached.is_some()
+    }
+
+    /// Drop the cached resources, so that the next fetch checks them again.
+    pub fn clear(&mut self) {
+        self.cached = None;
+    }
+
+    fn check<'a, S, F>(&mut self)
+    where
+        F: FetchResources<'a, S, Resources = R>,
+    {
+        let name = type_name::<F>();
+        if !matches!(&self.cached, Some((cached, _)) if *cached == name) {
+            match F::check_resources() {
+                Ok(resources) => self.cached = Some((name, resources)),
+                Err(err) => panic!("cannot fetch {:?}: {}", name, err),
+            }
+        }
+    }
+}
+
 macro_rules! impl_data {
     ($($ty:ident),*) => {
         impl<'a, ST, RT, $($ty),*> FetchResources<'a, ST> for ($($ty,)*)
@@ -60,6 +194,18 @@ macro_rules! impl_data {
             fn fetch(source: &'a ST) -> Self {
                 ($(<$ty as FetchResources<'a, ST>>::fetch(source),)*)
             }
+
+            fn try_fetch(source: &'a ST) -> Result<Self, FetchError> {
+                Ok(($(<$ty as FetchResources<'a, ST>>::try_fetch(source)?,)*))
+            }
+
+            fn fetch_local(source: &'a ST, locals: &Locals) -> Self {
+                ($(<$ty as FetchResources<'a, ST>>::fetch_local(source, locals),)*)
+            }
+
+            fn try_fetch_local(source: &'a ST, locals: &Locals) -> Result<Self, FetchError> {
+                Ok(($(<$ty as FetchResources<'a, ST>>::try_fetch_local(source, locals)?,)*))
+            }
         }
     };
 }
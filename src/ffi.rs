@@ -0,0 +1,269 @@
+//! A C interface over opaque `World` handles, for embedding goggles in an engine written in
+//! another language.
+//!
+//! Entities are passed as packed `u64` ids, with the index in the low 32 bits and the generation
+//! in the high 32 bits, so an id of 0 is never a valid entity.
+//!
+//! Components created from C are registered at runtime with `goggles_component_register`, which
+//! returns a component id for a fixed size byte blob.  These components are stored entirely
+//! separately from the statically typed components of the `World`, but are removed along with
+//! their entity just the same.
+//!
+//! Every function taking a `World` handle requires that it is a valid handle returned from
+//! `goggles_world_new` which has not yet been passed to `goggles_world_free`, and that it is not
+//! used from more than one thread at a time.
+
+use std::slice;
+
+use crate::{entity::Entity, storage::DenseVecStorage, world::World, world_common::Component};
+
+/// Create a new, empty `World`.
+///
+/// The returned handle must eventually be freed with `goggles_world_free`.
+#[no_mangle]
+pub extern "C" fn goggles_world_new() -> *mut World {
+    let mut world = World::new();
+    init(&mut world);
+    Box::into_raw(Box::new(world))
+}
+
+/// Free a `World` created with `goggles_world_new`.  Does nothing if `world` is null.
+///
+/// # Safety
+/// `world` must be null or a valid `World` handle, which must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_world_free(world: *mut World) {
+    if !world.is_null() {
+        drop(Box::from_raw(world));
+    }
+}
+
+/// Call `World::merge`, finishing the deletion of any entities deleted since the last merge.
+///
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_world_merge(world: *mut World) {
+    (*world).merge();
+}
+
+/// Create a new entity, returning its packed id.
+///
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_entity_create(world: *mut World) -> u64 {
+    pack((*world).create_entity())
+}
+
+/// Delete an entity along with all of its components, returns false if the entity was not alive.
+///
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_entity_delete(world: *mut World, entity: u64) -> bool {
+    let world = &mut *world;
+    match unpack(world, entity) {
+        Some(entity) => world.delete_entity(entity).is_ok(),
+        None => false,
+    }
+}
+
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_entity_is_alive(world: *const World, entity: u64) -> bool {
+    unpack(&*world, entity).is_some()
+}
+
+/// Register a new component type holding `size` bytes, returning its component id.
+///
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_component_register(world: *mut World, size: usize) -> u32 {
+    let world = &mut *world;
+    init(world);
+    let mut registry = world.write_resource::<BlobRegistry>();
+    registry.sizes.push(size);
+    (registry.sizes.len() - 1) as u32
+}
+
+/// Set the component with the given id on an entity, copying `size` bytes from `data`.
+///
+/// Returns false if the entity is not alive, the component id is unknown, or `size` does not
+/// match the registered size of the component.
+///
+/// # Safety
+/// `world` must be a valid `World` handle, and `data` must be valid for reads of `size` bytes.
+/// `data` may be null if `size` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_component_set(
+    world: *mut World,
+    entity: u64,
+    component: u32,
+    data: *const u8,
+    size: usize,
+) -> bool {
+    let world = &mut *world;
+    let entity = match unpack(world, entity) {
+        Some(entity) if registered_size(world, component) == Some(size) => entity,
+        _ => return false,
+    };
+    // `data` may be null for a zero sized component, which `slice::from_raw_parts` does not allow.
+    let value: Box<[u8]> = if size == 0 {
+        Box::default()
+    } else {
+        slice::from_raw_parts(data, size).into()
+    };
+
+    let mut blobs = world.write_component::<Blobs>();
+    if let Some(blobs) = blobs.get_mut(entity) {
+        match blobs.0.iter_mut().find(|(id, _)| *id == component) {
+            Some((_, existing)) => *existing = value,
+            None => blobs.0.push((component, value)),
+        }
+    } else {
+        blobs
+            .insert(entity, Blobs(vec![(component, value)]))
+            .unwrap();
+    }
+    true
+}
+
+/// Copy the component with the given id on an entity into `out`, which must be exactly the
+/// registered size of the component.
+///
+/// Returns false if the entity is not alive, does not have the component, or `size` does not
+/// match the registered size of the component.
+///
+/// # Safety
+/// `world` must be a valid `World` handle, and `out` must be valid for writes of `size` bytes.
+/// `out` may be null if `size` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_component_get(
+    world: *const World,
+    entity: u64,
+    component: u32,
+    out: *mut u8,
+    size: usize,
+) -> bool {
+    let world = &*world;
+    let entity = match unpack(world, entity) {
+        Some(entity) if registered_size(world, component) == Some(size) => entity,
+        _ => return false,
+    };
+
+    let blobs = world.read_component::<Blobs>();
+    match blobs.get(entity).and_then(|b| b.get(component)) {
+        Some(value) => {
+            if size != 0 {
+                slice::from_raw_parts_mut(out, size).copy_from_slice(value);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_component_has(
+    world: *const World,
+    entity: u64,
+    component: u32,
+) -> bool {
+    let world = &*world;
+    match unpack(world, entity) {
+        Some(entity) if world.contains_component::<Blobs>() => world
+            .read_component::<Blobs>()
+            .get(entity)
+            .and_then(|b| b.get(component))
+            .is_some(),
+        _ => false,
+    }
+}
+
+/// Remove the component with the given id from an entity, returns false if the entity did not
+/// have the component.
+///
+/// # Safety
+/// `world` must be a valid `World` handle.
+#[no_mangle]
+pub unsafe extern "C" fn goggles_component_remove(
+    world: *mut World,
+    entity: u64,
+    component: u32,
+) -> bool {
+    let world = &mut *world;
+    let entity = match unpack(world, entity) {
+        Some(entity) if world.contains_component::<Blobs>() => entity,
+        _ => return false,
+    };
+
+    let mut blobs = world.write_component::<Blobs>();
+    let (removed, now_empty) = match blobs.get_mut(entity) {
+        Some(blobs) => {
+            let len = blobs.0.len();
+            blobs.0.retain(|(id, _)| *id != component);
+            (blobs.0.len() != len, blobs.0.is_empty())
+        }
+        None => return false,
+    };
+    if now_empty {
+        blobs.remove(entity).unwrap();
+    }
+    removed
+}
+
+// The byte blob components of a single entity, along with their component ids.
+struct Blobs(Vec<(u32, Box<[u8]>)>);
+
+impl Blobs {
+    fn get(&self, component: u32) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|(id, _)| *id == component)
+            .map(|(_, value)| &**value)
+    }
+}
+
+impl Component for Blobs {
+    type Storage = DenseVecStorage<Blobs>;
+}
+
+// The size of every component registered with `goggles_component_register`, indexed by id.
+#[derive(Default)]
+struct BlobRegistry {
+    sizes: Vec<usize>,
+}
+
+fn init(world: &mut World) {
+    if !world.contains_component::<Blobs>() {
+        world.insert_component::<Blobs>().unwrap();
+        world.insert_resource(BlobRegistry::default());
+    }
+}
+
+fn registered_size(world: &World, component: u32) -> Option<usize> {
+    if !world.contains_resource::<BlobRegistry>() {
+        return None;
+    }
+    world
+        .read_resource::<BlobRegistry>()
+        .sizes
+        .get(component as usize)
+        .copied()
+}
+
+fn pack(entity: Entity) -> u64 {
+    (entity.generation() as u64) << 32 | entity.index() as u64
+}
+
+fn unpack(world: &World, id: u64) -> Option<Entity> {
+    world
+        .entities()
+        .entity(id as u32)
+        .filter(|e| e.generation() == (id >> 32) as u32)
+}
@@ -0,0 +1,148 @@
+use std::{
+    alloc::{self, Layout},
+    cmp, mem,
+    ptr::{self, NonNull},
+    sync::Mutex,
+};
+
+/// A bump allocator for transient per-frame data, inserted as a resource by `World::new` and reset
+/// by every `World::merge`.
+///
+/// Allocating only requires a shared reference, so any number of systems may allocate from the
+/// arena in parallel through a `ReadResource<FrameArena>`.  Allocations are never moved, and all of
+/// them are dropped at once when the arena is reset, which requires unique access and so cannot
+/// happen while any allocation is still borrowed.
+///
+/// Memory is kept between frames, so after the first few frames allocating usually never touches
+/// the global allocator at all.
+#[derive(Default)]
+pub struct FrameArena(Mutex<Chunks>);
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the given value into the arena.
+    // Every call returns a fresh allocation which is never handed out again until `reset`, which
+    // takes `&mut self`, so the returned reference is unique.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T: Send>(&self, value: T) -> &mut T {
+        let mut chunks = self.0.lock().unwrap();
+        unsafe {
+            let ptr = chunks.alloc_layout(Layout::new::<T>()).cast::<T>();
+            ptr.as_ptr().write(value);
+            if mem::needs_drop::<T>() {
+                chunks.drops.push(DropEntry {
+                    ptr: ptr.cast(),
+                    drop: drop_erased::<T>,
+                });
+            }
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copy the given slice into the arena.
+    // Unique for the same reason as `alloc`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy + Send>(&self, values: &[T]) -> &mut [T] {
+        let layout = Layout::array::<T>(values.len()).unwrap();
+        let mut chunks = self.0.lock().unwrap();
+        unsafe {
+            let ptr = chunks.alloc_layout(layout).cast::<T>();
+            ptr::copy_nonoverlapping(values.as_ptr(), ptr.as_ptr(), values.len());
+            std::slice::from_raw_parts_mut(ptr.as_ptr(), values.len())
+        }
+    }
+
+    /// The number of bytes allocated since the last reset, including alignment padding.
+    pub fn allocated_bytes(&self) -> usize {
+        self.0.lock().unwrap().allocated
+    }
+
+    /// Drop every allocation and make all of the arena's memory available again.
+    pub fn reset(&mut self) {
+        let chunks = self.0.get_mut().unwrap();
+        chunks.drop_all();
+        chunks.current = 0;
+        chunks.offset = 0;
+        chunks.allocated = 0;
+    }
+}
+
+const MIN_CHUNK_SIZE: usize = 4096;
+
+#[derive(Default)]
+struct Chunks {
+    // Chunks are only freed when the arena is dropped, so allocations stay in place.  They are
+    // accessed only through raw pointers, so handing out references into them never aliases.
+    list: Vec<(NonNull<u8>, Layout)>,
+    current: usize,
+    offset: usize,
+    allocated: usize,
+    drops: Vec<DropEntry>,
+}
+
+// Chunks are owned by the arena, and only `Send` values are ever stored in them.
+unsafe impl Send for Chunks {}
+
+impl Chunks {
+    unsafe fn alloc_layout(&mut self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            return NonNull::new_unchecked(layout.align() as *mut u8);
+        }
+
+        loop {
+            if let Some(&(base, chunk_layout)) = self.list.get(self.current) {
+                let base = base.as_ptr();
+                let start = self.offset + base.add(self.offset).align_offset(layout.align());
+                if start + layout.size() <= chunk_layout.size() {
+                    self.allocated += start + layout.size() - self.offset;
+                    self.offset = start + layout.size();
+                    return NonNull::new_unchecked(base.add(start));
+                }
+                // Chunks kept from previous frames are tried in order before allocating a new one.
+                self.current += 1;
+                self.offset = 0;
+            } else {
+                let last = self.list.last().map(|(_, l)| l.size()).unwrap_or(0);
+                let size = cmp::max(
+                    cmp::max(last * 2, MIN_CHUNK_SIZE),
+                    layout.size() + layout.align(),
+                );
+                let chunk_layout = Layout::from_size_align(size, 1).unwrap();
+                let base = match NonNull::new(alloc::alloc(chunk_layout)) {
+                    Some(base) => base,
+                    None => alloc::handle_alloc_error(chunk_layout),
+                };
+                self.list.push((base, chunk_layout));
+                self.current = self.list.len() - 1;
+                self.offset = 0;
+            }
+        }
+    }
+
+    fn drop_all(&mut self) {
+        for entry in mem::take(&mut self.drops) {
+            unsafe { (entry.drop)(entry.ptr) };
+        }
+    }
+}
+
+impl Drop for Chunks {
+    fn drop(&mut self) {
+        self.drop_all();
+        for &(base, layout) in &self.list {
+            unsafe { alloc::dealloc(base.as_ptr(), layout) };
+        }
+    }
+}
+
+struct DropEntry {
+    ptr: NonNull<u8>,
+    drop: unsafe fn(NonNull<u8>),
+}
+
+unsafe fn drop_erased<T>(ptr: NonNull<u8>) {
+    ptr::drop_in_place(ptr.cast::<T>().as_ptr());
+}
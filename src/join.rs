@@ -1,8 +1,27 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::BuildHasher,
+    marker::PhantomData,
+    vec,
+};
+
 use hibitset::{
     AtomicBitSet, BitIter, BitSet, BitSetAll, BitSetAnd, BitSetLike, BitSetNot, BitSetOr, BitSetXor,
 };
 use thiserror::Error;
 
+/// The index type used by every `Join`, storage, and entity.
+///
+/// This is fixed at `u32` because every mask is a `hibitset` bitset, which only supports `u32`
+/// indexes.  A `BitSet` cannot even hold every `u32`, only indexes below `BITS_PER_USIZE^4` (2^24
+/// on 64-bit targets and 2^20 on 32-bit targets), so an `Allocator` never hands out an index past
+/// that.
+///
+/// Indexes of killed entities are normally re-used with an incremented generation, see
+/// `Entity::generation`, so the limit applies to the number of indexes in use at once rather than
+/// to the number of entities created over the lifetime of a `World`.  The exception is an index
+/// whose generation is exhausted, which `GenerationOverflow::Retire` never re-uses, so under that
+/// policy every exhausted index permanently uses up part of the index space.
 pub type Index = u32;
 
 pub trait Join {
@@ -14,6 +33,7 @@ pub trait Join {
 
     /// Get a value out of the access type returned from `open`.
     ///
+    /// # Safety
     /// MUST be called only with indexes which are present in the mask returned along with the
     /// access value from `open`.
     ///
@@ -25,6 +45,14 @@ pub trait Join {
     /// currently follow is that `Join::get` may only be called once per index for a given `Access`
     /// object.
     unsafe fn get(access: &Self::Access, index: Index) -> Self::Item;
+
+    /// Forget every index fetched from the given access so far, so that the same indexes can be
+    /// fetched again in a new pass.
+    ///
+    /// Joins which record fetched indexes in a `JoinChecks` must clear it here, and joins which
+    /// wrap other joins must forward this to them.  Callers must ensure that no item fetched so
+    /// far is still alive.
+    fn clear_checks(_access: &mut Self::Access) {}
 }
 
 pub trait IntoJoin {
@@ -50,6 +78,8 @@ pub struct JoinIterUnconstrained;
 pub trait IntoJoinExt: IntoJoin {
     /// Safely iterate over this `Join`.
     ///
+    /// Items are always yielded in ascending index order.
+    ///
     /// # Panics
     /// Panics if the result of this join is unconstrained.
     fn join(self) -> JoinIter<Self::IntoJoin>
@@ -70,12 +100,150 @@ pub trait IntoJoinExt: IntoJoin {
         JoinIter::new_unconstrained(self.into_join())
     }
 
+    /// Iterate over this `Join` in the order given by a key extracted from each item, rather than
+    /// in index order.
+    ///
+    /// Every item is collected before sorting, so this allocates.  The sort is stable, so items
+    /// with equal keys are yielded in ascending index order.
+    ///
+    /// # Panics
+    /// Panics if the result of this join is unconstrained.
+    fn join_sorted_by_key<K, F>(self, f: F) -> vec::IntoIter<Self::Item>
+    where
+        Self: Sized,
+        <Self::IntoJoin as Join>::Mask: BitSetConstrained,
+        K: Ord,
+        F: FnMut(&Self::Item) -> K,
+    {
+        let mut items = self.join().collect::<Vec<_>>();
+        items.sort_by_key(f);
+        items.into_iter()
+    }
+
+    /// Open this join and collect its mask into an owned `BitSet`, without ever fetching any
+    /// items.
+    ///
+    /// This is useful for computing a set of entities to process later or to hand to other
+    /// systems, without touching component data.
+    ///
+    /// # Panics
+    /// Panics if the result of this join is unconstrained.
+    fn matched_mask(self) -> BitSet
+    where
+        Self: Sized,
+        <Self::IntoJoin as Join>::Mask: BitSetConstrained,
+    {
+        let (mask, _) = self.into_join().open();
+        if !mask.is_constrained() {
+            panic!("{}", JoinIterUnconstrained);
+        }
+
+        let mut matched = BitSet::new();
+        matched |= &mask;
+        matched
+    }
+
+    /// Pack every item of this join into a contiguous buffer, such as a per-frame instance buffer
+    /// for GPU upload, and the index of each item into `indexes` in the same order.
+    ///
+    /// Both buffers are cleared first, so they can be re-used every frame without re-allocating.
+    /// Items are packed in ascending index order.  When packing a single storage of `Copy`
+    /// components, `MaskedStorage::pack_copied_into` copies whole slices at once instead.
+    ///
+    /// # Panics
+    /// Panics if the result of this join is unconstrained.
+    fn pack_into<T, F>(self, values: &mut Vec<T>, indexes: &mut Vec<Index>, mut f: F)
+    where
+        Self: Sized,
+        <Self::IntoJoin as Join>::Mask: BitSetConstrained,
+        F: FnMut(Self::Item) -> T,
+    {
+        let (mask, access) = self.into_join().open();
+        if !mask.is_constrained() {
+            panic!("{}", JoinIterUnconstrained);
+        }
+
+        values.clear();
+        indexes.clear();
+        for index in (&mask).iter() {
+            // Every index is fetched only once, just like in `JoinIter`.
+            values.push(f(unsafe { <Self::IntoJoin as Join>::get(&access, index) }));
+            indexes.push(index);
+        }
+    }
+
     fn maybe(self) -> MaybeJoin<Self::IntoJoin>
     where
         Self: Sized,
     {
         MaybeJoin(self.into_join())
     }
+
+    /// Constrain this join by an arbitrary bitset, such as the result of a spatial query or a
+    /// selection set, yielding the same items but only for indexes also present in `mask`.
+    ///
+    /// References to bitsets are also `BitSetLike`, so `mask` may be borrowed.
+    fn constrain<B>(self, mask: B) -> ConstrainedJoin<Self::IntoJoin, B>
+    where
+        Self: Sized,
+        B: BitSetLike,
+    {
+        ConstrainedJoin {
+            join: self.into_join(),
+            mask,
+        }
+    }
+
+    /// Transform every item of this join with `f`, preserving the mask.
+    ///
+    /// Unlike calling `Iterator::map` on the result of `join`, the result is still a `Join`, so it
+    /// can be returned from a function as a reusable view over several storages and combined with
+    /// other joins.
+    fn map<F, U>(self, f: F) -> MappedJoin<Self::IntoJoin, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Item) -> U,
+    {
+        MappedJoin {
+            join: self.into_join(),
+            f,
+        }
+    }
+
+    /// Run `pred` over every item of this join to build a refined mask of only the indexes whose
+    /// item matches, and return a join over that refined mask.
+    ///
+    /// The refined mask is available from `FilteredJoin::mask`, so the result of an expensive
+    /// filter can be re-used to constrain other joins in the same system.
+    ///
+    /// # Panics
+    /// Panics if the result of this join is unconstrained.
+    fn filter<P>(self, mut pred: P) -> FilteredJoin<Self::IntoJoin>
+    where
+        Self: Sized,
+        <Self::IntoJoin as Join>::Mask: BitSetConstrained,
+        P: FnMut(&Self::Item) -> bool,
+    {
+        let (mask, mut access) = self.into_join().open();
+        if !mask.is_constrained() {
+            panic!("{}", JoinIterUnconstrained);
+        }
+
+        let mut refined = BitSet::new();
+        for index in (&mask).iter() {
+            // Each item is dropped before the next is fetched, and before the returned join can
+            // fetch any item again, so no two items for the same index are ever alive at once.
+            if pred(&unsafe { <Self::IntoJoin as Join>::get(&access, index) }) {
+                refined.add(index);
+            }
+        }
+        <Self::IntoJoin as Join>::clear_checks(&mut access);
+
+        FilteredJoin {
+            mask: refined,
+            access,
+        }
+    }
 }
 
 impl<J: IntoJoin> IntoJoinExt for J {}
@@ -101,8 +269,238 @@ impl<J: Join> Join for MaybeJoin<J> {
             None
         }
     }
+
+    fn clear_checks((_, access): &mut Self::Access) {
+        J::clear_checks(access);
+    }
+}
+
+pub struct ConstrainedJoin<J, B> {
+    join: J,
+    mask: B,
+}
+
+impl<J: Join, B: BitSetLike> Join for ConstrainedJoin<J, B> {
+    type Item = J::Item;
+    type Access = J::Access;
+    type Mask = BitSetAnd<J::Mask, B>;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        let (mask, access) = self.join.open();
+        (BitSetAnd(mask, self.mask), access)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        // The combined mask is a subset of the inner mask, so the inner `Join` is only given
+        // indexes from its own mask.
+        J::get(access, index)
+    }
+
+    fn clear_checks(access: &mut Self::Access) {
+        J::clear_checks(access);
+    }
+}
+
+pub struct MappedJoin<J, F> {
+    join: J,
+    f: F,
+}
+
+impl<J, F, U> Join for MappedJoin<J, F>
+where
+    J: Join,
+    F: Fn(J::Item) -> U,
+{
+    type Item = U;
+    type Access = (J::Access, F);
+    type Mask = J::Mask;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        let (mask, access) = self.join.open();
+        (mask, (access, self.f))
+    }
+
+    unsafe fn get((access, f): &Self::Access, index: Index) -> Self::Item {
+        f(J::get(access, index))
+    }
+
+    fn clear_checks((access, _): &mut Self::Access) {
+        J::clear_checks(access);
+    }
+}
+
+/// A join over the items of an inner join which matched a predicate, returned from
+/// `IntoJoinExt::filter`.
+pub struct FilteredJoin<J: Join> {
+    mask: BitSet,
+    access: J::Access,
+}
+
+impl<J: Join> FilteredJoin<J> {
+    /// The refined mask of every index whose item matched the predicate.
+    pub fn mask(&self) -> &BitSet {
+        &self.mask
+    }
+}
+
+impl<J: Join> Join for FilteredJoin<J> {
+    type Item = J::Item;
+    type Access = J::Access;
+    type Mask = BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (self.mask, self.access)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        J::get(access, index)
+    }
+
+    fn clear_checks(access: &mut Self::Access) {
+        J::clear_checks(access);
+    }
+}
+
+/// Joins over a map keyed by `Index`, such as an ad-hoc side table keyed by entity index.
+///
+/// The mask is built from the keys of the map every time the join is opened, so this allocates
+/// and is linear in the size of the map.  Only shared access to the map is supported, mutably
+/// joining would require mutable access to many map values through a shared `Join::Access`.
+pub struct MapJoin<M>(pub M);
+
+impl<'a, T, S> Join for MapJoin<&'a HashMap<Index, T, S>>
+where
+    S: BuildHasher,
+{
+    type Item = &'a T;
+    type Access = &'a HashMap<Index, T, S>;
+    type Mask = BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (self.0.keys().copied().collect(), self.0)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        access.get(&index).unwrap()
+    }
+}
+
+impl<'a, T> Join for MapJoin<&'a BTreeMap<Index, T>> {
+    type Item = &'a T;
+    type Access = &'a BTreeMap<Index, T>;
+    type Mask = BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (self.0.keys().copied().collect(), self.0)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        access.get(&index).unwrap()
+    }
+}
+
+/// A join built from a mask and a function from index to item, for creating custom joins over
+/// external data without implementing `Join` by hand.
+///
+/// The function is an ordinary safe `Fn`, so it can hand out shared references to borrowed data,
+/// owned values, or values with interior mutability, but never unique references.  For mutably
+/// joining over a slice, see `SliceJoinMut`.
+pub struct SimpleJoin<M, F> {
+    mask: M,
+    f: F,
+}
+
+impl<M, F> SimpleJoin<M, F> {
+    /// `f` is only ever called with indexes in `mask`.
+    pub fn new<I>(mask: M, f: F) -> Self
+    where
+        M: BitSetLike,
+        F: Fn(Index) -> I,
+    {
+        SimpleJoin { mask, f }
+    }
+}
+
+impl<M, F, I> Join for SimpleJoin<M, F>
+where
+    M: BitSetLike,
+    F: Fn(Index) -> I,
+{
+    type Item = I;
+    type Access = F;
+    type Mask = M;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (self.mask, self.f)
+    }
+
+    unsafe fn get(f: &Self::Access, index: Index) -> Self::Item {
+        f(index)
+    }
+}
+
+/// A join yielding unique references to the elements of a slice at the indexes in a mask, the
+/// element at index `i` being `slice[i]`.
+///
+/// # Panics
+/// Fetching an item panics if the mask contains an index past the end of the slice.
+pub struct SliceJoinMut<'a, M, T> {
+    mask: M,
+    slice: &'a mut [T],
+}
+
+impl<'a, M: BitSetLike, T> SliceJoinMut<'a, M, T> {
+    pub fn new(mask: M, slice: &'a mut [T]) -> Self {
+        SliceJoinMut { mask, slice }
+    }
+}
+
+/// The `Join::Access` of `SliceJoinMut`.
+pub struct SliceAccessMut<'a, T> {
+    ptr: *mut T,
+    len: usize,
+    checks: JoinChecks,
+    marker: PhantomData<&'a mut [T]>,
+}
+
+// Items are only handed out through `Join::get`, which is never called twice with the same index
+// while a previous item is alive, so this behaves like a `&mut [T]`.
+unsafe impl<'a, T: Send> Send for SliceAccessMut<'a, T> {}
+unsafe impl<'a, T: Send> Sync for SliceAccessMut<'a, T> {}
+
+impl<'a, M: BitSetLike, T> Join for SliceJoinMut<'a, M, T> {
+    type Item = &'a mut T;
+    type Access = SliceAccessMut<'a, T>;
+    type Mask = M;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        let access = SliceAccessMut {
+            ptr: self.slice.as_mut_ptr(),
+            len: self.slice.len(),
+            checks: JoinChecks::default(),
+            marker: PhantomData,
+        };
+        (self.mask, access)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        assert!(
+            (index as usize) < access.len,
+            "index {} is past the end of the slice of length {}",
+            index,
+            access.len
+        );
+        // The mask is handed out by `open`, so only repeated indexes can be checked here.
+        access.checks.check(|| true, index);
+        &mut *access.ptr.add(index as usize)
+    }
+
+    fn clear_checks(access: &mut Self::Access) {
+        access.checks.clear();
+    }
 }
 
+/// An iterator over the items of a `Join`, in ascending index order.
 pub struct JoinIter<J: Join>(BitIter<J::Mask>, J::Access);
 
 impl<J: Join> JoinIter<J> {
@@ -131,10 +529,59 @@ impl<J: Join> Iterator for JoinIter<J> {
         // `JoinIter` only implements `Iterator`, so we only call `J::get` *once* for each index
         // that is returned from `BitIter`.  Since `BitIter` iterates over the correct mask and ond
         // does not return repeat indexes, our requirements are upheld.
-        self.0.next().map(|index| unsafe { J::get(&self.1, index) })
+        let index = self.0.next()?;
+        Some(unsafe { J::get(&self.1, index) })
     }
 }
 
+/// Records the indexes fetched from a single `Join::Access`, for joins whose items are unique
+/// references.
+///
+/// With the `debug-join-checks` feature, `JoinChecks::check` panics if an index is fetched twice
+/// or is not in the join's mask, both of which violate the safety requirements of `Join::get`.
+/// Every join in this crate which hands out unique references keeps one of these in its access,
+/// so an unsound `Join` impl built on top of them, such as one which fetches the same index of an
+/// inner join twice, panics instead of aliasing.  Custom joins over their own data can do the
+/// same.  Without the feature, this is empty and does nothing.
+#[cfg(feature = "debug-join-checks")]
+#[derive(Default)]
+pub struct JoinChecks(AtomicBitSet);
+
+#[cfg(feature = "debug-join-checks")]
+impl JoinChecks {
+    /// Must be called in `Join::get` before handing out the item for `index`, `in_mask` returns
+    /// whether the index is in the join's mask.
+    pub fn check(&self, in_mask: impl FnOnce() -> bool, index: Index) {
+        if !in_mask() {
+            panic!(
+                "`Join::get` called with index {} outside of the mask",
+                index
+            );
+        }
+        if self.0.add_atomic(index) {
+            panic!("`Join::get` called twice with index {}", index);
+        }
+    }
+
+    /// Forget every recorded index, see `Join::clear_checks`.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(not(feature = "debug-join-checks"))]
+#[derive(Default)]
+pub struct JoinChecks(());
+
+#[cfg(not(feature = "debug-join-checks"))]
+impl JoinChecks {
+    #[inline]
+    pub fn check(&self, _in_mask: impl FnOnce() -> bool, _index: Index) {}
+
+    #[inline]
+    pub fn clear(&mut self) {}
+}
+
 pub struct JoinParIter<J: Join>(J::Mask, J::Access);
 
 impl<J: Join> JoinParIter<J> {
@@ -187,6 +634,13 @@ macro_rules! define_join {
                 let ($first, $($rest),*) = access;
                 ($first::get($first, index), $($rest::get($rest, index)),*)
             }
+
+            #[allow(non_snake_case)]
+            fn clear_checks(access: &mut Self::Access) {
+                let ($first, $($rest),*) = access;
+                $first::clear_checks($first);
+                $($rest::clear_checks($rest);)*
+            }
         }
     };
 }
@@ -374,7 +828,7 @@ pub trait BitSetConstrained: BitSetLike {
     fn is_constrained(&self) -> bool;
 }
 
-impl<'a, B: BitSetConstrained> BitSetConstrained for &'a B {
+impl<B: BitSetConstrained> BitSetConstrained for &B {
     fn is_constrained(&self) -> bool {
         (*self).is_constrained()
     }
@@ -1,42 +1,120 @@
 pub use hibitset;
 
 pub mod any_components;
+pub mod commands;
+pub mod component_events;
+pub mod component_group;
+pub mod component_index;
+pub mod dag;
+pub mod dyn_value;
 pub mod entity;
+pub mod entity_map;
+pub mod events;
 pub mod fetch_resources;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod frame_arena;
 pub mod join;
+pub mod local;
 pub mod make_sync;
+pub mod mask_query;
 pub mod masked;
+pub mod memory;
+pub mod net_sync;
+pub mod observer;
+pub mod par_join;
+pub mod plugin;
+mod query;
+pub mod reactive;
+pub mod record;
 pub mod resource_set;
 pub mod resources;
+pub mod scene;
+pub mod schedule;
+pub mod soa;
+pub mod state;
+pub mod stats;
 pub mod storage;
+pub mod storage_report;
 pub mod system;
 pub mod tracked;
+pub mod verify;
 pub mod world;
 pub mod world_common;
+pub mod world_handle;
+mod world_system;
+
+#[cfg(feature = "world-id")]
+pub use self::entity::{WorldId, WrongWorld};
 
 pub use {
-    self::entity::{Entity, WrongGeneration},
+    self::entity::{
+        AllocateError, Entity, EntityError, GenerationExhausted, GenerationOverflow,
+        IndexExhausted, WrongGeneration,
+    },
     any_components::{AnyCloneComponentSet, AnyComponentSet},
-    fetch_resources::{FetchNone, FetchResources},
-    join::{Index, IntoJoin, IntoJoinExt, Join, JoinIter, JoinIterUnconstrained, JoinParIter},
+    commands::Commands,
+    component_events::{ComponentEvent, ComponentEventKind},
+    component_group::{Archetype, ComponentGroup, GroupMembers},
+    component_index::{ComponentIndex, SpatialIndex, SpatialStructure, ValueIndex},
+    dag::{Dag, DagNode},
+    dyn_value::{DynAccess, DynStorage, DynValue, ReadDyn, WriteDyn},
+    entity_map::{EntityMap, EntityMapRegistry, MapEntities},
+    events::{EventReader, EventWriter, Events},
+    fetch_resources::{CachedFetch, FetchError, FetchNone, FetchResources},
+    frame_arena::FrameArena,
+    join::{
+        ConstrainedJoin, FilteredJoin, Index, IntoJoin, IntoJoinExt, Join, JoinIter,
+        JoinIterUnconstrained, JoinParIter, MapJoin, MappedJoin, SimpleJoin, SliceJoinMut,
+    },
+    local::{Local, LocalSystem, Locals, WithLocals},
     make_sync::MakeSync,
-    masked::MaskedStorage,
+    mask_query::{MaskQuery, Not, Or, With, Without},
+    masked::{MaskVersion, MaskedStorage, ModifiedItem, PartitionMut},
+    memory::{MemoryReport, MemoryUsage},
+    net_sync::{ComponentChange, DecodeError, DeltaPacket, NetSync, NetSyncError, SyncComponent},
+    observer::Observer,
+    par_join::{JoinIndexedParIter, ParJoinExt},
+    plugin::Plugin,
+    reactive::ReactiveQuery,
+    record::{RecordedTick, Recorder, Replayer, SyncResource},
     resource_set::{Read, ResourceSet, Write},
     resources::{ResourceConflict, Resources, RwResources},
-    storage::{DenseStorage, DenseVecStorage, HashMapStorage, RawStorage, VecStorage},
-    system::{parallelize, Error as SystemError, Par, Pool, Seq, SeqPool, System},
-    tracked::{Flagged, TrackedStorage},
-    world::{Entities, ReadComponent, ReadResource, World, WriteComponent, WriteResource},
+    scene::{Scene, SceneEntities, SceneEntity},
+    schedule::{BoxedWorldSystem, Schedule},
+    soa::{SoaColumnPtr, SoaIndex, SoaStorage},
+    state::{State, StateSet, StateSystems},
+    stats::{ComponentStats, FrameStats},
+    storage::{
+        ArrangeStorage, DenseStorage, DenseVecStorage, HashMapStorage, RawStorage, SplitStorage,
+        StableStorage, StorageKind, VecStorage,
+    },
+    storage_report::{ComponentStorageReport, StorageReport},
+    system::{
+        parallelize, parallelize_with_barriers, run_sequential, Enabled, EnabledFlag,
+        Error as SystemError, FailurePolicy, Par, Pinned, Pool, ScheduleItem, Seq, SeqPool, System,
+        ThreadLimit,
+    },
+    tracked::{Flagged, ReaderId, TrackedStorage},
+    verify::{verify_sequential, VerifyError},
+    world::{
+        AutoRegister, ComponentAlreadyInserted, ComponentConfig, ComponentInfo,
+        ComponentNotInserted, DefaultAccess, DefaultResource, DeferredResources, DynComponentRef,
+        Entities, FetchUnchecked, FrozenWorld, HasComponent, KeyedAccess, ReadComponent,
+        ReadComponentUnchecked, ReadDefault, ReadKeyed, ReadModified, ReadResource,
+        ReadResourceUnchecked, ReadSingleton, RegisterComponent, ResourceKey, SingletonAccess,
+        SplitComponents, Tick, World, WriteComponent, WriteComponentUnchecked, WriteDefault,
+        WriteKeyed, WriteResource, WriteResourceUnchecked, WriteSingleton,
+    },
     world_common::{Component, ComponentId, ResourceId, WorldResourceId, WorldResources},
+    world_handle::WorldHandle,
 };
 
 #[cfg(feature = "rayon")]
 pub use rayon;
 
-#[cfg(feature = "rayon")]
-pub mod par_join;
 #[cfg(feature = "rayon")]
 pub mod rayon_pool;
 
 #[cfg(feature = "rayon")]
-pub use self::{par_join::ParJoinExt, rayon_pool::RayonPool};
+pub use self::rayon_pool::RayonPool;
@@ -0,0 +1,174 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    fetch_resources::{FetchError, FetchResources},
+    resources::ResourceConflict,
+    system::System,
+    world::World,
+    world_common::{WorldResourceId, WorldResources},
+};
+
+// Every `Local` value of a single system, a slot is `None` while its value is fetched.
+type Slots = FxHashMap<TypeId, Option<Box<dyn Any + Send>>>;
+
+/// The private storage for every `Local` value of a single system.
+///
+/// A system wrapped in `WithLocals` is run with its `Locals`, see `LocalSystem`.
+#[derive(Clone, Default)]
+pub struct Locals(Arc<Mutex<Slots>>);
+
+/// A `System<&World>` which can also be run with the `Locals` of a `WithLocals` wrapper, which
+/// it passes to `FetchResources::fetch_local`.
+///
+/// Systems defined with `system!` implement this.
+pub trait LocalSystem<'a>: System<&'a World> {
+    fn run_local(
+        &mut self,
+        pool: &Self::Pool,
+        world: &'a World,
+        locals: &Locals,
+    ) -> Result<(), Self::Error>;
+}
+
+/// Wraps a system to give it private storage for `Local` values, which persists across runs.
+///
+/// The inner system is run with `LocalSystem::run_local`, given the `Locals` of the wrapper.  Each
+/// `Local<T>` is checked for conflicts under its own `WorldResourceId::Local` id, so fetching the
+/// same `Local<T>` twice is an error from `System::check_resources`, but these ids are removed
+/// from the resources of the wrapper, so they never conflict with other systems.
+pub struct WithLocals<S> {
+    system: S,
+    locals: Locals,
+}
+
+impl<S> WithLocals<S> {
+    pub fn new(system: S) -> Self {
+        WithLocals {
+            system,
+            locals: Locals::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.system
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.system
+    }
+}
+
+impl<'a, S> System<&'a World> for WithLocals<S>
+where
+    S: LocalSystem<'a, Resources = WorldResources>,
+{
+    type Resources = WorldResources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<WorldResources, ResourceConflict> {
+        let resources = self.system.check_resources()?;
+        let shared = |r: &&WorldResourceId| !matches!(r, WorldResourceId::Local(_));
+        Ok(WorldResources::from_iters(
+            resources.reads().filter(shared).cloned(),
+            resources.writes().filter(shared).cloned(),
+        ))
+    }
+
+    fn run(&mut self, pool: &Self::Pool, world: &'a World) -> Result<(), Self::Error> {
+        self.system.run_local(pool, world, &self.locals)
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.system.is_pinned()
+    }
+}
+
+/// A value private to the fetching system, which persists across runs of that system.
+///
+/// The value starts as `T::default()`. Unlike a shared resource, `Local` never conflicts with
+/// other systems, so it is useful for scratch buffers and other system state.
+///
+/// A `Local` can only be fetched with `FetchResources::fetch_local`, such as by a system wrapped
+/// in `WithLocals`.  A plain `FetchResources::try_fetch` returns `FetchError::Missing`.
+///
+/// # Panics
+/// Fetching a `Local` panics if it is fetched without `Locals`, or if the same `Local<T>` is
+/// already fetched from the same `Locals`.
+pub struct Local<T: Send + 'static> {
+    value: Option<T>,
+    locals: Locals,
+}
+
+impl<T: Send + 'static> Deref for Local<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T: Send + 'static> DerefMut for Local<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl<T: Send + 'static> Drop for Local<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.locals
+                .0
+                .lock()
+                .unwrap()
+                .insert(TypeId::of::<T>(), Some(Box::new(value)));
+        }
+    }
+}
+
+impl<'a, T> FetchResources<'a, World> for Local<T>
+where
+    T: Default + Send + 'static,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().write(WorldResourceId::local::<T>()))
+    }
+
+    fn fetch(_: &'a World) -> Self {
+        panic!("{} fetched without Locals", type_name::<Self>());
+    }
+
+    fn try_fetch(_: &'a World) -> Result<Self, FetchError> {
+        Err(FetchError::Missing(type_name::<Self>()))
+    }
+
+    fn fetch_local(world: &'a World, locals: &Locals) -> Self {
+        match Self::try_fetch_local(world, locals) {
+            Ok(local) => local,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn try_fetch_local(_: &'a World, locals: &Locals) -> Result<Self, FetchError> {
+        let value = locals
+            .0
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Some(Box::new(T::default())))
+            .take()
+            .ok_or(FetchError::Borrowed(type_name::<Self>()))?;
+        Ok(Local {
+            value: Some(*value.downcast().unwrap()),
+            locals: locals.clone(),
+        })
+    }
+}
@@ -19,6 +19,18 @@ impl<T> MakeSync<T> {
     }
 }
 
+impl<T> MakeSync<T> {
+    /// Shared access to the inner type without requiring `T: Sync`.
+    ///
+    /// # Safety
+    /// The returned reference must only be used to access parts of `T` that are themselves `Sync`
+    /// (and are not reachable through any interior mutability), since other threads may hold the
+    /// same reference.
+    pub(crate) unsafe fn get_unchecked(&self) -> &T {
+        &self.0
+    }
+}
+
 impl<T: Sync> MakeSync<T> {
     pub fn get(&self) -> &T {
         &self.0
@@ -0,0 +1,116 @@
+use std::marker::PhantomData;
+
+use hibitset::{BitSet, BitSetNot};
+
+use crate::{masked::MaskVersion, world::World, world_common::Component};
+
+/// A boolean expression over component masks, evaluated by `World::query_mask`.
+///
+/// Tuples of expressions match the entities which match every element of the tuple.
+pub trait MaskQuery {
+    /// Restrict the given mask to only the entities which match this expression.
+    fn restrict(world: &World, mask: &mut BitSet);
+
+    /// Push the current mask version of every component in this expression, which
+    /// `World::query_mask_cached` uses to find out when a cached result is stale.
+    fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>);
+}
+
+/// Matches entities which have the component `C`.
+pub struct With<C>(PhantomData<fn() -> C>);
+
+/// Matches entities which do not have the component `C`.
+pub struct Without<C>(PhantomData<fn() -> C>);
+
+/// Matches entities which match either `A` or `B`.
+pub struct Or<A, B>(PhantomData<fn() -> (A, B)>);
+
+/// Matches entities which do not match `A`.
+pub struct Not<A>(PhantomData<fn() -> A>);
+
+impl<C> MaskQuery for With<C>
+where
+    C: Component + 'static,
+    C::Storage: Send,
+{
+    fn restrict(world: &World, mask: &mut BitSet) {
+        *mask &= world.has_component::<C>().mask();
+    }
+
+    fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>) {
+        versions.push(world.component_mask_version::<C>());
+    }
+}
+
+impl<C> MaskQuery for Without<C>
+where
+    C: Component + 'static,
+    C::Storage: Send,
+{
+    fn restrict(world: &World, mask: &mut BitSet) {
+        *mask &= &BitSetNot(world.has_component::<C>().mask());
+    }
+
+    fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>) {
+        versions.push(world.component_mask_version::<C>());
+    }
+}
+
+impl<A, B> MaskQuery for Or<A, B>
+where
+    A: MaskQuery,
+    B: MaskQuery,
+{
+    fn restrict(world: &World, mask: &mut BitSet) {
+        let mut a = mask.clone();
+        A::restrict(world, &mut a);
+        B::restrict(world, mask);
+        *mask |= &a;
+    }
+
+    fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>) {
+        A::mask_versions(world, versions);
+        B::mask_versions(world, versions);
+    }
+}
+
+impl<A> MaskQuery for Not<A>
+where
+    A: MaskQuery,
+{
+    fn restrict(world: &World, mask: &mut BitSet) {
+        let mut a = mask.clone();
+        A::restrict(world, &mut a);
+        *mask &= &BitSetNot(&a);
+    }
+
+    fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>) {
+        A::mask_versions(world, versions);
+    }
+}
+
+macro_rules! impl_mask_query {
+    ($($ty:ident),*) => {
+        impl<$($ty),*> MaskQuery for ($($ty,)*)
+        where
+            $($ty: MaskQuery),*
+        {
+            fn restrict(world: &World, mask: &mut BitSet) {
+                $($ty::restrict(world, mask);)*
+            }
+
+            fn mask_versions(world: &World, versions: &mut Vec<MaskVersion>) {
+                $($ty::mask_versions(world, versions);)*
+            }
+        }
+    };
+}
+
+impl_mask_query!(A);
+impl_mask_query!(A, B);
+impl_mask_query!(A, B, C);
+impl_mask_query!(A, B, C, D);
+impl_mask_query!(A, B, C, D, E);
+impl_mask_query!(A, B, C, D, E, F);
+impl_mask_query!(A, B, C, D, E, F, G);
+impl_mask_query!(A, B, C, D, E, F, G, H);
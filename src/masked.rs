@@ -1,18 +1,53 @@
-use std::mem;
+use std::{
+    marker::PhantomData,
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
-use hibitset::{BitIter, BitSet, BitSetLike};
+use hibitset::{BitIter, BitSet, BitSetAnd, BitSetLike, BitSetNot};
+#[cfg(feature = "rayon")]
+use rayon::{
+    iter::IndexedParallelIterator,
+    slice::{ParallelSlice, ParallelSliceMut},
+};
 
 use crate::{
-    join::{Index, Join},
-    storage::{DenseStorage, RawStorage},
-    tracked::{ModifiedBitSet, TrackedStorage},
+    join::{Index, Join, JoinChecks},
+    memory::{bitset_usage, reserve_bitset, MemoryUsage},
+    storage::{DenseStorage, RawStorage, SplitStorage, VecStorage},
+    tracked::{ModifiedBitSet, ReaderId, TrackedStorage},
 };
 
+/// Identifies the set of occupied indexes of a `MaskedStorage`.
+///
+/// The version changes whenever a component is inserted at an empty index or removed, and the
+/// versions of two different storages are never equal.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MaskVersion {
+    storage: u64,
+    changes: u64,
+}
+
+impl MaskVersion {
+    fn new() -> Self {
+        static NEXT_STORAGE: AtomicU64 = AtomicU64::new(0);
+        MaskVersion {
+            storage: NEXT_STORAGE.fetch_add(1, Ordering::Relaxed),
+            changes: 0,
+        }
+    }
+
+    fn bump(&mut self) {
+        self.changes += 1;
+    }
+}
+
 /// Wraps a `RawStorage` for some component with a `BitSet` mask to provide a safe, `Join`-able
 /// interface for component storage.
 pub struct MaskedStorage<S: RawStorage> {
     mask: BitSet,
     storage: S,
+    pub(crate) mask_version: MaskVersion,
 }
 
 impl<S: RawStorage + Default> Default for MaskedStorage<S> {
@@ -20,15 +55,34 @@ impl<S: RawStorage + Default> Default for MaskedStorage<S> {
         Self {
             mask: Default::default(),
             storage: Default::default(),
+            mask_version: MaskVersion::new(),
         }
     }
 }
 
 impl<S: RawStorage> MaskedStorage<S> {
+    /// Wrap the given `RawStorage`, which must not contain any components.
+    ///
+    /// `RawStorage` does not track which indexes are occupied, so any components already in the
+    /// given storage can never be accessed and will be leaked.
+    pub fn new(storage: S) -> Self {
+        Self {
+            mask: BitSet::new(),
+            storage,
+            mask_version: MaskVersion::new(),
+        }
+    }
+
     pub fn mask(&self) -> &BitSet {
         &self.mask
     }
 
+    /// The current version of the mask of this storage, which can be compared with a previous
+    /// version to cheaply find out whether any component was inserted or removed since.
+    pub fn mask_version(&self) -> MaskVersion {
+        self.mask_version
+    }
+
     pub fn raw_storage(&self) -> &S {
         &self.storage
     }
@@ -41,6 +95,31 @@ impl<S: RawStorage> MaskedStorage<S> {
         self.mask.contains(index)
     }
 
+    /// Report the memory used by the mask of this storage.
+    pub fn mask_memory_usage(&self) -> MemoryUsage {
+        bitset_usage(&self.mask)
+    }
+
+    /// Report the memory used by both the mask and the inner `RawStorage`.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.mask_memory_usage() + self.storage.memory_usage()
+    }
+
+    /// Reserve space in the mask and the inner storage for components at every index below `len`.
+    pub fn reserve(&mut self, len: Index) {
+        reserve_bitset(&mut self.mask, len);
+        self.storage.reserve(len);
+    }
+
+    /// Release memory in the inner storage that is no longer needed, see `RawStorage::shrink`.
+    ///
+    /// This visits every present index, so it is meant for occasional maintenance, such as between
+    /// levels, rather than every frame.
+    pub fn shrink(&mut self) {
+        let len = (&self.mask).iter().last().map(|i| i + 1).unwrap_or(0);
+        self.storage.shrink(len);
+    }
+
     pub fn get(&self, index: Index) -> Option<&S::Item> {
         if self.mask.contains(index) {
             Some(unsafe { self.storage.get(index) })
@@ -80,6 +159,7 @@ impl<S: RawStorage> MaskedStorage<S> {
     ) -> &mut S::Item {
         if !self.mask.contains(index) {
             self.mask.add(index);
+            self.mask_version.bump();
             unsafe { self.storage.insert(index, f()) };
         }
         unsafe { self.storage.get_mut(index) }
@@ -91,26 +171,116 @@ impl<S: RawStorage> MaskedStorage<S> {
             Some(v)
         } else {
             self.mask.add(index);
+            self.mask_version.bump();
             unsafe { self.storage.insert(index, v) };
             None
         }
     }
 
+    /// Insert many components at strictly ascending, currently empty indexes, such as when loading
+    /// a level.
+    ///
+    /// This is faster than repeated calls to `MaskedStorage::insert` for storages which implement
+    /// `RawStorage::insert_sorted`, such as `VecStorage` and `DenseVecStorage`.
+    ///
+    /// # Panics
+    /// Panics if the indexes are not strictly ascending or if any index is already occupied, every
+    /// value before the offending one is still inserted.
+    pub fn insert_sorted_iter(&mut self, values: impl IntoIterator<Item = (Index, S::Item)>) {
+        self.mask_version.bump();
+        let mask = &mut self.mask;
+        let mut last = None;
+        let values = values.into_iter().inspect(|&(index, _)| {
+            assert!(
+                last.is_none_or(|last| index > last),
+                "indexes passed to insert_sorted_iter are not strictly ascending"
+            );
+            last = Some(index);
+            assert!(
+                !mask.add(index),
+                "index passed to insert_sorted_iter is already occupied"
+            );
+        });
+        unsafe { self.storage.insert_sorted(values) };
+    }
+
     pub fn remove(&mut self, index: Index) -> Option<S::Item> {
         if self.mask.remove(index) {
+            self.mask_version.bump();
             Some(unsafe { self.storage.remove(index) })
         } else {
             None
         }
     }
 
+    /// Remove every component whose index is in the given mask, returning the number of removed
+    /// components.
+    pub fn remove_mask(&mut self, mask: impl BitSetLike) -> usize {
+        let mut count = 0;
+        self.remove_mask_with(mask, |_, _| count += 1);
+        count
+    }
+
+    /// Remove every component whose index is in the given mask, calling the given function with
+    /// each removed component in index order.
+    ///
+    /// The removals go through `RawStorage::remove`, so a tracked storage flags each one as
+    /// modified.
+    pub fn remove_mask_with(&mut self, mask: impl BitSetLike, mut f: impl FnMut(Index, S::Item)) {
+        let indexes: Vec<Index> = BitSetAnd(&self.mask, mask).iter().collect();
+        if !indexes.is_empty() {
+            self.mask_version.bump();
+        }
+        for index in indexes {
+            self.mask.remove(index);
+            f(index, unsafe { self.storage.remove(index) });
+        }
+    }
+
     /// Returns an `IntoJoin` type whose values are `GuardedJoin` wrappers.
     ///
     /// A `GuardedJoin` wrapper does not automatically call `RawStorage::get_mut`, so it can be
     /// useful to avoid flagging modifications with a `FlaggedStorage`.
-    pub fn guard(&mut self) -> GuardedJoin<S> {
+    pub fn guard(&mut self) -> GuardedJoin<'_, S> {
         GuardedJoin(self)
     }
+
+    /// Returns an `IntoJoin` type whose values are `ReadGuardedElement` wrappers.
+    ///
+    /// Like `MaskedStorage::guard`, but only requires a shared borrow, so it only gives immutable
+    /// access to each value.  This is useful to read values and selectively mark them modified
+    /// without needing to borrow the storage mutably.
+    pub fn read_guard(&self) -> ReadGuardedJoin<'_, S> {
+        ReadGuardedJoin(self)
+    }
+
+    /// Split this storage into two partitions over disjoint sets of indexes, the first containing
+    /// every present index which is also in `mask`, and the second every present index which is
+    /// not.
+    ///
+    /// Each partition can be joined over mutably independently of the other, and if the storage
+    /// and its components are thread safe, the two partitions can be sent to different threads and
+    /// mutated concurrently.
+    pub fn split_by(
+        &mut self,
+        mask: impl BitSetLike,
+    ) -> (PartitionMut<'_, S>, PartitionMut<'_, S>) {
+        let inside = BitSetAnd(&self.mask, &mask).iter().collect();
+        let outside = BitSetAnd(&self.mask, BitSetNot(&mask)).iter().collect();
+        let storage = &*self;
+        (
+            PartitionMut {
+                storage,
+                mask: inside,
+                marker: PhantomData,
+            },
+            PartitionMut {
+                storage,
+                mask: outside,
+                marker: PhantomData,
+            },
+        )
+    }
 }
 
 impl<S: DenseStorage> MaskedStorage<S> {
@@ -121,18 +291,152 @@ impl<S: DenseStorage> MaskedStorage<S> {
     pub fn as_mut_slice(&mut self) -> &mut [S::Item] {
         self.storage.as_mut_slice()
     }
+
+    /// The index of every value in the slice returned from `MaskedStorage::as_slice`, in the same
+    /// order.
+    pub fn slice_indexes(&self) -> &[Index] {
+        self.storage.indexes()
+    }
+
+    /// Copy every value into `values` and its index into `indexes`, the same as
+    /// `IntoJoinExt::pack_into`, but by copying the whole dense slice at once.
+    ///
+    /// Both buffers are cleared first.  Values are packed in the order of `MaskedStorage::as_slice`
+    /// rather than in index order.
+    pub fn pack_copied_into(&self, values: &mut Vec<S::Item>, indexes: &mut Vec<Index>)
+    where
+        S::Item: Copy,
+    {
+        values.clear();
+        values.extend_from_slice(self.storage.as_slice());
+        indexes.clear();
+        indexes.extend_from_slice(self.storage.indexes());
+    }
+
+    /// Iterate over runs of the dense slice whose indexes are consecutive, yielding the first index
+    /// of each run along with a slice of its components.
+    ///
+    /// Runs are split so that no slice is longer than `max_len`.  Runs follow the order of
+    /// `MaskedStorage::as_slice` rather than index order, so indexes are only consecutive within a
+    /// run where the values were inserted (or arranged) in index order.
+    ///
+    /// # Panics
+    /// Panics if `max_len` is 0.
+    pub fn chunks(&self, max_len: usize) -> DenseChunks<'_, S::Item> {
+        assert!(max_len != 0, "chunk length must be non-zero");
+        DenseChunks {
+            indexes: self.storage.indexes(),
+            values: self.storage.as_slice(),
+            max_len,
+        }
+    }
+}
+
+impl<S: SplitStorage> MaskedStorage<S> {
+    /// Split the dense slice of values into disjoint mutable chunks of at most `chunk_size`
+    /// values, each paired with the matching chunk of `MaskedStorage::slice_indexes`, and process
+    /// them in parallel.
+    ///
+    /// Unlike a parallel join, this never goes through `Join::get` per element, so it is the
+    /// fastest way to update every component of a single dense storage.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0.
+    #[cfg(feature = "rayon")]
+    pub fn par_chunks_mut(
+        &mut self,
+        chunk_size: usize,
+    ) -> impl IndexedParallelIterator<Item = (&[Index], &mut [S::Item])> + '_
+    where
+        S::Item: Send,
+    {
+        let (indexes, values) = self.storage.indexes_and_mut_slice();
+        indexes
+            .par_chunks(chunk_size)
+            .zip(values.par_chunks_mut(chunk_size))
+    }
+
+    /// Without the `rayon` feature, chunks are processed sequentially.
+    #[cfg(not(feature = "rayon"))]
+    pub fn par_chunks_mut(
+        &mut self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = (&[Index], &mut [S::Item])> + '_ {
+        let (indexes, values) = self.storage.indexes_and_mut_slice();
+        indexes
+            .chunks(chunk_size)
+            .zip(values.chunks_mut(chunk_size))
+    }
+
+    /// Like `MaskedStorage::chunks`, but yields mutable slices.
+    ///
+    /// # Panics
+    /// Panics if `max_len` is 0.
+    pub fn chunks_mut(&mut self, max_len: usize) -> DenseChunksMut<'_, S::Item> {
+        assert!(max_len != 0, "chunk length must be non-zero");
+        let (indexes, values) = self.storage.indexes_and_mut_slice();
+        DenseChunksMut {
+            indexes,
+            values,
+            max_len,
+        }
+    }
+}
+
+impl<T> MaskedStorage<VecStorage<T>> {
+    /// Iterate over runs of consecutive indexes that all have a component, yielding the first
+    /// index of each run along with a slice of its components.
+    ///
+    /// Runs are split so that no slice is longer than `max_len`.  This allows processing components
+    /// in contiguous blocks, which can be much friendlier to vectorization than one at a time.
+    ///
+    /// # Panics
+    /// Panics if `max_len` is 0.
+    pub fn chunks(&self, max_len: usize) -> Chunks<'_, T> {
+        Chunks {
+            runs: Runs::new(&self.mask, max_len),
+            storage: &self.storage,
+        }
+    }
+
+    /// Copy every value into `values` and its index into `indexes`, the same as
+    /// `IntoJoinExt::pack_into`, but by copying each run of consecutive indexes at once.
+    ///
+    /// Both buffers are cleared first, and values are packed in ascending index order.
+    pub fn pack_copied_into(&self, values: &mut Vec<T>, indexes: &mut Vec<Index>)
+    where
+        T: Copy,
+    {
+        values.clear();
+        indexes.clear();
+        for (start, chunk) in self.chunks(usize::MAX) {
+            values.extend_from_slice(chunk);
+            indexes.extend(start..start + chunk.len() as Index);
+        }
+    }
+
+    /// Like `MaskedStorage::chunks`, but yields mutable slices.
+    pub fn chunks_mut(&mut self, max_len: usize) -> ChunksMut<'_, T> {
+        ChunksMut {
+            runs: Runs::new(&self.mask, max_len),
+            storage: &self.storage,
+        }
+    }
 }
 
 impl<S: TrackedStorage> MaskedStorage<S> {
     pub fn tracking_modified(&self) -> bool {
+        let () = S::ASSERT_TRACKED;
         self.storage.tracking_modified()
     }
 
     pub fn modified_indexes(&self) -> &ModifiedBitSet {
+        let () = S::ASSERT_TRACKED;
         self.storage.modified_indexes()
     }
 
     pub fn set_track_modified(&mut self, flag: bool) {
+        let () = S::ASSERT_TRACKED;
         self.storage.set_track_modified(flag);
     }
 
@@ -141,22 +445,65 @@ impl<S: TrackedStorage> MaskedStorage<S> {
     }
 
     pub fn clear_modified(&mut self) {
+        let () = S::ASSERT_TRACKED;
         self.storage.clear_modified();
     }
 
     /// Returns an `IntoJoin` type which joins over all the modified elements.
     ///
-    /// The items on the returned join are all `Option<&S::Item>`, removed elements will show up as
-    /// None.
-    pub fn modified(&self) -> ModifiedJoin<S> {
-        ModifiedJoin(self)
+    /// The items on the returned join are all `ModifiedItem<&S::Item>`, which distinguish inserted,
+    /// modified, and removed elements.
+    pub fn modified(&self) -> ModifiedJoin<'_, S> {
+        ModifiedJoin {
+            storage: self,
+            reader: None,
+        }
     }
 
     /// Returns an `IntoJoin` type which joins over all the modified elements mutably.
     ///
     /// This is similar to `MaskedStorage::modified`, but returns mutable access to each item.
-    pub fn modified_mut(&mut self) -> ModifiedJoinMut<S> {
-        ModifiedJoinMut(self)
+    pub fn modified_mut(&mut self) -> ModifiedJoinMut<'_, S> {
+        ModifiedJoinMut {
+            storage: self,
+            reader: None,
+        }
+    }
+
+    /// Register an independent consumer of modifications, see `TrackedStorage::register_reader`.
+    pub fn register_reader(&mut self) -> ReaderId {
+        let () = S::ASSERT_TRACKED;
+        self.storage.register_reader()
+    }
+
+    pub fn unregister_reader(&mut self, reader: ReaderId) {
+        self.storage.unregister_reader(reader);
+    }
+
+    pub fn reader_modified_indexes(&self, reader: ReaderId) -> &ModifiedBitSet {
+        self.storage.reader_modified_indexes(reader)
+    }
+
+    pub fn clear_reader_modified(&mut self, reader: ReaderId) {
+        self.storage.clear_reader_modified(reader);
+    }
+
+    /// Like `MaskedStorage::modified`, but joins over the elements modified since the given reader
+    /// was last cleared.
+    pub fn reader_modified(&self, reader: ReaderId) -> ModifiedJoin<'_, S> {
+        ModifiedJoin {
+            storage: self,
+            reader: Some(reader),
+        }
+    }
+
+    /// Like `MaskedStorage::modified_mut`, but joins over the elements modified since the given
+    /// reader was last cleared.
+    pub fn reader_modified_mut(&mut self, reader: ReaderId) -> ModifiedJoinMut<'_, S> {
+        ModifiedJoinMut {
+            storage: self,
+            reader: Some(reader),
+        }
     }
 }
 
@@ -176,15 +523,23 @@ impl<'a, S: RawStorage> Join for &'a MaskedStorage<S> {
 
 impl<'a, S: RawStorage> Join for &'a mut MaskedStorage<S> {
     type Item = &'a mut S::Item;
-    type Access = &'a S;
+    type Access = (&'a BitSet, &'a S, JoinChecks);
     type Mask = &'a BitSet;
 
     fn open(self) -> (Self::Mask, Self::Access) {
-        (&self.mask, &self.storage)
+        (
+            &self.mask,
+            (&self.mask, &self.storage, JoinChecks::default()),
+        )
     }
 
-    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
-        access.get_mut(index)
+    unsafe fn get((mask, storage, checks): &Self::Access, index: Index) -> Self::Item {
+        checks.check(|| mask.contains(index), index);
+        storage.get_mut(index)
+    }
+
+    fn clear_checks((_, _, checks): &mut Self::Access) {
+        checks.clear();
     }
 }
 
@@ -197,7 +552,7 @@ impl<S: RawStorage> Drop for MaskedStorage<S> {
                 if let Some(iter) = self.0.take() {
                     let mut guard: DropGuard<S> = DropGuard(Some(&mut *iter), &mut *self.1);
                     while let Some(index) = guard.0.as_mut().unwrap().next() {
-                        unsafe { S::remove(&mut guard.1, index) };
+                        unsafe { S::remove(guard.1, index) };
                     }
                     guard.0 = None;
                 }
@@ -209,22 +564,98 @@ impl<S: RawStorage> Drop for MaskedStorage<S> {
     }
 }
 
+/// A mutable view over a subset of the components in a `MaskedStorage`, returned from
+/// `MaskedStorage::split_by`.
+pub struct PartitionMut<'a, S: RawStorage> {
+    storage: &'a MaskedStorage<S>,
+    mask: BitSet,
+    // Partitions give out mutable references to components, so they must only be `Send` if the
+    // components are.
+    marker: PhantomData<&'a mut S::Item>,
+}
+
+impl<'a, S: RawStorage> PartitionMut<'a, S> {
+    /// The indexes of every component in this partition.
+    pub fn mask(&self) -> &BitSet {
+        &self.mask
+    }
+
+    pub fn contains(&self, index: Index) -> bool {
+        self.mask.contains(index)
+    }
+
+    pub fn get(&self, index: Index) -> Option<&S::Item> {
+        if self.mask.contains(index) {
+            Some(unsafe { self.storage.storage.get(index) })
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, index: Index) -> Option<&mut S::Item> {
+        // Partitions never share an index, and we hold the only mutable borrow of this
+        // partition, so there can be no other reference to this component.
+        if self.mask.contains(index) {
+            Some(unsafe { self.storage.storage.get_mut(index) })
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, 'b, S: RawStorage> Join for &'a PartitionMut<'b, S> {
+    type Item = &'a S::Item;
+    type Access = &'a S;
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (&self.mask, &self.storage.storage)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        access.get(index)
+    }
+}
+
+impl<'a, 'b, S: RawStorage> Join for &'a mut PartitionMut<'b, S> {
+    type Item = &'a mut S::Item;
+    type Access = (&'a BitSet, &'a S, JoinChecks);
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        let access = (&self.mask, &self.storage.storage, JoinChecks::default());
+        (&self.mask, access)
+    }
+
+    unsafe fn get((mask, storage, checks): &Self::Access, index: Index) -> Self::Item {
+        checks.check(|| mask.contains(index), index);
+        storage.get_mut(index)
+    }
+
+    fn clear_checks((_, _, checks): &mut Self::Access) {
+        checks.clear();
+    }
+}
+
 pub struct GuardedJoin<'a, S: RawStorage>(&'a mut MaskedStorage<S>);
 
 impl<'a, S: RawStorage> Join for GuardedJoin<'a, S> {
     type Item = GuardedElement<'a, S>;
-    type Access = &'a S;
+    type Access = (&'a BitSet, &'a S, JoinChecks);
     type Mask = &'a BitSet;
 
     fn open(self) -> (Self::Mask, Self::Access) {
-        (&self.0.mask, &self.0.storage)
+        let access = (&self.0.mask, &self.0.storage, JoinChecks::default());
+        (&self.0.mask, access)
     }
 
-    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
-        GuardedElement {
-            storage: *access,
-            index,
-        }
+    unsafe fn get((mask, storage, checks): &Self::Access, index: Index) -> Self::Item {
+        checks.check(|| mask.contains(index), index);
+        GuardedElement { storage, index }
+    }
+
+    fn clear_checks((_, _, checks): &mut Self::Access) {
+        checks.clear();
     }
 }
 
@@ -249,48 +680,267 @@ impl<'a, S: TrackedStorage> GuardedElement<'a, S> {
     }
 }
 
-pub struct ModifiedJoin<'a, S: RawStorage>(&'a MaskedStorage<S>);
+pub struct ReadGuardedJoin<'a, S: RawStorage>(&'a MaskedStorage<S>);
+
+impl<'a, S: RawStorage> Join for ReadGuardedJoin<'a, S> {
+    type Item = ReadGuardedElement<'a, S>;
+    type Access = &'a S;
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (&self.0.mask, &self.0.storage)
+    }
+
+    unsafe fn get(access: &Self::Access, index: Index) -> Self::Item {
+        ReadGuardedElement {
+            storage: *access,
+            index,
+        }
+    }
+}
+
+pub struct ReadGuardedElement<'a, S> {
+    storage: &'a S,
+    index: Index,
+}
+
+impl<'a, S: RawStorage> ReadGuardedElement<'a, S> {
+    pub fn get(&self) -> &'a S::Item {
+        unsafe { self.storage.get(self.index) }
+    }
+}
+
+impl<'a, S: TrackedStorage> ReadGuardedElement<'a, S> {
+    pub fn mark_modified(&self) {
+        self.storage.mark_modified(self.index);
+    }
+}
+
+/// An item of a `ModifiedJoin` or `ModifiedJoinMut`, describing how the component at a modified
+/// index changed.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ModifiedItem<T> {
+    /// A component was inserted at this index and has not been removed since.
+    Inserted(T),
+    /// A component which was already present at this index was modified.
+    Modified(T),
+    /// The component at this index was removed.
+    Removed,
+}
+
+impl<T> ModifiedItem<T> {
+    /// Returns the current component if there is one, for both inserted and modified components.
+    pub fn value(self) -> Option<T> {
+        match self {
+            ModifiedItem::Inserted(v) | ModifiedItem::Modified(v) => Some(v),
+            ModifiedItem::Removed => None,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> ModifiedItem<U> {
+        match self {
+            ModifiedItem::Inserted(v) => ModifiedItem::Inserted(f(v)),
+            ModifiedItem::Modified(v) => ModifiedItem::Modified(f(v)),
+            ModifiedItem::Removed => ModifiedItem::Removed,
+        }
+    }
+}
+
+pub struct ModifiedJoin<'a, S: RawStorage> {
+    storage: &'a MaskedStorage<S>,
+    reader: Option<ReaderId>,
+}
 
 impl<'a, S: TrackedStorage> Join for ModifiedJoin<'a, S> {
-    type Item = Option<&'a S::Item>;
-    type Access = (&'a BitSet, &'a S);
+    type Item = ModifiedItem<&'a S::Item>;
+    type Access = (&'a BitSet, &'a BitSet, &'a S);
     type Mask = &'a ModifiedBitSet;
 
     fn open(self) -> (Self::Mask, Self::Access) {
-        (
-            &self.0.storage.modified_indexes(),
-            (&self.0.mask, &self.0.storage),
-        )
+        open_modified(self.storage, self.reader)
     }
 
-    unsafe fn get((mask, storage): &Self::Access, index: Index) -> Self::Item {
-        if mask.contains(index) {
-            Some(storage.get(index))
+    unsafe fn get((mask, inserted, storage): &Self::Access, index: Index) -> Self::Item {
+        if !mask.contains(index) {
+            ModifiedItem::Removed
+        } else if inserted.contains(index) {
+            ModifiedItem::Inserted(storage.get(index))
         } else {
-            None
+            ModifiedItem::Modified(storage.get(index))
         }
     }
 }
 
-pub struct ModifiedJoinMut<'a, S: RawStorage>(&'a mut MaskedStorage<S>);
+pub struct ModifiedJoinMut<'a, S: RawStorage> {
+    storage: &'a mut MaskedStorage<S>,
+    reader: Option<ReaderId>,
+}
 
 impl<'a, S: TrackedStorage> Join for ModifiedJoinMut<'a, S> {
-    type Item = Option<&'a mut S::Item>;
-    type Access = (&'a BitSet, &'a S);
+    type Item = ModifiedItem<&'a mut S::Item>;
+    type Access = (
+        &'a BitSet,
+        &'a BitSet,
+        &'a S,
+        &'a ModifiedBitSet,
+        JoinChecks,
+    );
     type Mask = &'a ModifiedBitSet;
 
     fn open(self) -> (Self::Mask, Self::Access) {
+        let (modified, (mask, inserted, storage)) = open_modified(self.storage, self.reader);
+        let access = (mask, inserted, storage, modified, JoinChecks::default());
+        (modified, access)
+    }
+
+    unsafe fn get(
+        (mask, inserted, storage, modified, checks): &Self::Access,
+        index: Index,
+    ) -> Self::Item {
+        checks.check(|| modified.contains(index), index);
+        if !mask.contains(index) {
+            ModifiedItem::Removed
+        } else if inserted.contains(index) {
+            ModifiedItem::Inserted(storage.get_mut(index))
+        } else {
+            ModifiedItem::Modified(storage.get_mut(index))
+        }
+    }
+
+    fn clear_checks((_, _, _, _, checks): &mut Self::Access) {
+        checks.clear();
+    }
+}
+
+fn open_modified<S: TrackedStorage>(
+    storage: &MaskedStorage<S>,
+    reader: Option<ReaderId>,
+) -> (&ModifiedBitSet, (&BitSet, &BitSet, &S)) {
+    let (modified, inserted) = if let Some(reader) = reader {
         (
-            &self.0.storage.modified_indexes(),
-            (&self.0.mask, &self.0.storage),
+            storage.storage.reader_modified_indexes(reader),
+            storage.storage.reader_inserted_indexes(reader),
         )
+    } else {
+        (
+            storage.storage.modified_indexes(),
+            storage.storage.inserted_indexes(),
+        )
+    };
+    (modified, (&storage.mask, inserted, &storage.storage))
+}
+
+// Splits the indexes of a `BitSet` into runs of consecutive indexes no longer than `max_len`.
+struct Runs<'a> {
+    iter: BitIter<&'a BitSet>,
+    next: Option<Index>,
+    max_len: usize,
+}
+
+impl<'a> Runs<'a> {
+    fn new(mask: &'a BitSet, max_len: usize) -> Self {
+        assert!(max_len != 0, "chunk length must be non-zero");
+        Runs {
+            iter: mask.iter(),
+            next: None,
+            max_len,
+        }
     }
+}
 
-    unsafe fn get((mask, storage): &Self::Access, index: Index) -> Self::Item {
-        if mask.contains(index) {
-            Some(storage.get_mut(index))
-        } else {
-            None
+impl<'a> Iterator for Runs<'a> {
+    type Item = (Index, usize);
+
+    fn next(&mut self) -> Option<(Index, usize)> {
+        let start = self.next.take().or_else(|| self.iter.next())?;
+        let mut len = 1;
+        while len < self.max_len {
+            match self.iter.next() {
+                Some(index) if index as usize == start as usize + len => len += 1,
+                next => {
+                    self.next = next;
+                    break;
+                }
+            }
         }
+        Some((start, len))
+    }
+}
+
+pub struct Chunks<'a, T> {
+    runs: Runs<'a>,
+    storage: &'a VecStorage<T>,
+}
+
+impl<'a, T> Iterator for Chunks<'a, T> {
+    type Item = (Index, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, len) = self.runs.next()?;
+        // Every index in a run is present in the mask, so it is non-empty.
+        Some((start, unsafe { self.storage.slice(start, len) }))
+    }
+}
+
+/// The length of the run of consecutive indexes at the start of `indexes`, at most `max_len`.
+fn dense_run_len(indexes: &[Index], max_len: usize) -> usize {
+    let mut len = 1;
+    while len < max_len.min(indexes.len()) && indexes[len] as usize == indexes[0] as usize + len {
+        len += 1;
+    }
+    len
+}
+
+pub struct DenseChunks<'a, T> {
+    indexes: &'a [Index],
+    values: &'a [T],
+    max_len: usize,
+}
+
+impl<'a, T> Iterator for DenseChunks<'a, T> {
+    type Item = (Index, &'a [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = *self.indexes.first()?;
+        let len = dense_run_len(self.indexes, self.max_len);
+        self.indexes = &self.indexes[len..];
+        let (chunk, rest) = self.values.split_at(len);
+        self.values = rest;
+        Some((start, chunk))
+    }
+}
+
+pub struct DenseChunksMut<'a, T> {
+    indexes: &'a [Index],
+    values: &'a mut [T],
+    max_len: usize,
+}
+
+impl<'a, T> Iterator for DenseChunksMut<'a, T> {
+    type Item = (Index, &'a mut [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = *self.indexes.first()?;
+        let len = dense_run_len(self.indexes, self.max_len);
+        self.indexes = &self.indexes[len..];
+        let (chunk, rest) = mem::take(&mut self.values).split_at_mut(len);
+        self.values = rest;
+        Some((start, chunk))
+    }
+}
+
+pub struct ChunksMut<'a, T> {
+    runs: Runs<'a>,
+    storage: &'a VecStorage<T>,
+}
+
+impl<'a, T> Iterator for ChunksMut<'a, T> {
+    type Item = (Index, &'a mut [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, len) = self.runs.next()?;
+        // Every index in a run is present in the mask, and runs never overlap, so no index is
+        // returned mutably more than once while we hold a unique borrow of the storage.
+        Some((start, unsafe { self.storage.slice_mut(start, len) }))
     }
 }
@@ -0,0 +1,138 @@
+use std::{
+    mem,
+    ops::{Add, AddAssign},
+};
+
+use hibitset::{BitSet, BitSetLike};
+
+use crate::{
+    join::Index,
+    world_common::{ComponentId, ResourceId},
+};
+
+/// An approximate measurement of the memory held by some container, in bytes.
+///
+/// `used` is the amount of memory occupied by live data, `capacity` is the amount of memory that
+/// has been allocated, and will always be at least as large as `used`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct MemoryUsage {
+    pub used: usize,
+    pub capacity: usize,
+}
+
+impl MemoryUsage {
+    pub fn new(used: usize, capacity: usize) -> Self {
+        MemoryUsage { used, capacity }
+    }
+
+    /// The memory usage of a `Vec<T>`, based on its length and capacity.
+    #[allow(clippy::ptr_arg)]
+    pub fn of_vec<T>(v: &Vec<T>) -> Self {
+        MemoryUsage {
+            used: v.len() * mem::size_of::<T>(),
+            capacity: v.capacity() * mem::size_of::<T>(),
+        }
+    }
+}
+
+impl Add for MemoryUsage {
+    type Output = MemoryUsage;
+
+    fn add(self, other: MemoryUsage) -> MemoryUsage {
+        MemoryUsage {
+            used: self.used + other.used,
+            capacity: self.capacity + other.capacity,
+        }
+    }
+}
+
+impl AddAssign for MemoryUsage {
+    fn add_assign(&mut self, other: MemoryUsage) {
+        *self = *self + other;
+    }
+}
+
+/// Estimates the memory used by a hierarchical bitset.
+///
+/// `hibitset` does not expose the sizes of its internal layers, so this is computed from the
+/// highest index currently present in the set.  Bitsets never shrink, so a set that previously held
+/// higher indexes may be using more memory than reported.
+pub fn bitset_usage<B: BitSetLike>(set: &B) -> MemoryUsage {
+    const BITS: usize = usize::BITS as usize;
+
+    fn highest_bit(word: usize) -> usize {
+        BITS - 1 - word.leading_zeros() as usize
+    }
+
+    let layer3 = set.layer3();
+    if layer3 == 0 {
+        return MemoryUsage::default();
+    }
+
+    let i2 = highest_bit(layer3);
+    let i1 = i2 * BITS + highest_bit(set.layer2(i2));
+    let i0 = i1 * BITS + highest_bit(set.layer1(i1));
+
+    let bytes = (i0 + 1 + i1 + 1 + i2 + 1) * mem::size_of::<usize>();
+    MemoryUsage::new(bytes, bytes)
+}
+
+/// Grow the layers of a `BitSet` so that it can hold every index below `len` without allocating.
+pub(crate) fn reserve_bitset(set: &mut BitSet, len: Index) {
+    if len > 0 {
+        // `BitSet` has no method to reserve space, but it never shrinks, so adding and then
+        // removing the highest index grows every layer.
+        if !set.add(len - 1) {
+            set.remove(len - 1);
+        }
+    }
+}
+
+/// The memory used by a single registered component storage.
+#[derive(Clone, Debug)]
+pub struct ComponentMemory {
+    pub id: ComponentId,
+    pub type_name: &'static str,
+    /// Memory used by the `MaskedStorage` mask.
+    pub mask: MemoryUsage,
+    /// Memory used by the inner `RawStorage`.
+    pub storage: MemoryUsage,
+}
+
+impl ComponentMemory {
+    pub fn total(&self) -> MemoryUsage {
+        self.mask + self.storage
+    }
+}
+
+/// The memory used by a single resource.
+///
+/// Resources are arbitrary types, so only the size of the resource value itself is known, any heap
+/// memory owned by the resource is not included.
+#[derive(Clone, Debug)]
+pub struct ResourceMemory {
+    pub id: ResourceId,
+    pub type_name: &'static str,
+    pub size: usize,
+}
+
+/// Memory usage for every part of a `World`, returned from `World::memory_report`.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    pub entities: MemoryUsage,
+    pub components: Vec<ComponentMemory>,
+    pub resources: Vec<ResourceMemory>,
+}
+
+impl MemoryReport {
+    pub fn total(&self) -> MemoryUsage {
+        let mut total = self.entities;
+        for c in &self.components {
+            total += c.total();
+        }
+        for r in &self.resources {
+            total += MemoryUsage::new(r.size, r.size);
+        }
+        total
+    }
+}
@@ -0,0 +1,547 @@
+use std::{any::type_name, cmp::Ordering, convert::TryInto};
+
+use hibitset::{BitSet, BitSetLike};
+use rustc_hash::FxHashMap;
+use thiserror::Error;
+
+use crate::{
+    entity::Entity,
+    join::{Index, IntoJoinExt},
+    tracked::{ReaderId, TrackedStorage},
+    world::World,
+    world_common::Component,
+};
+
+/// A component which can be encoded to and decoded from bytes, so that it can be synchronized
+/// over the network or saved with `NetSync`.
+pub trait SyncComponent: Component + Send + 'static {
+    /// The version of the encoded format, which should be incremented whenever the encoding
+    /// changes shape.
+    ///
+    /// Packets record the version of every component they were encoded with, and values encoded
+    /// with an older version are decoded with `SyncComponent::migrate` instead.
+    const VERSION: u32 = 0;
+
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decode a value encoded with the current `SyncComponent::VERSION`.
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError>;
+
+    /// Decode a value encoded with an older version.
+    ///
+    /// The default implementation cannot migrate from any older version.
+    fn migrate(bytes: &[u8], old_version: u32) -> Result<Self, DecodeError> {
+        let _ = (bytes, old_version);
+        Err(DecodeError::new(
+            "component with an unsupported older version",
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("could not decode {what}")]
+pub struct DecodeError {
+    pub what: &'static str,
+}
+
+impl DecodeError {
+    pub fn new(what: &'static str) -> Self {
+        DecodeError { what }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum NetSyncError {
+    #[error("no component is registered with network id {0}")]
+    UnknownComponent(u16),
+    #[error("no resource is registered with id {0}")]
+    UnknownResource(u16),
+    #[error("component {type_name:?} could not be decoded: {source}")]
+    Decode {
+        type_name: &'static str,
+        source: DecodeError,
+    },
+    #[error("component {type_name:?} was encoded with newer version {version}")]
+    NewerVersion {
+        type_name: &'static str,
+        version: u32,
+    },
+}
+
+/// A single component change in a `DeltaPacket`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ComponentChange {
+    /// The index of the entity in the sending world.
+    pub index: Index,
+    /// The generation of the entity in the sending world.
+    pub generation: u32,
+    /// The network id of the component, which is its position in the `NetSync` registration
+    /// order.
+    pub component: u16,
+    /// The encoded new value of the component, or `None` if it was removed.
+    pub value: Option<Vec<u8>>,
+}
+
+/// Every change to the synchronized components of a `World` since the last delta was collected.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DeltaPacket {
+    /// The `SyncComponent::VERSION` of every registered component on the sending side, in
+    /// registration order.
+    pub versions: Vec<u32>,
+    /// The indexes of every previously sent entity which has since been deleted.
+    pub despawned: Vec<Index>,
+    pub changes: Vec<ComponentChange>,
+}
+
+impl DeltaPacket {
+    pub fn is_empty(&self) -> bool {
+        self.despawned.is_empty() && self.changes.is_empty()
+    }
+
+    /// Encode this packet in a compact little-endian binary format.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.versions.len() as u32).to_le_bytes());
+        for &version in &self.versions {
+            out.extend_from_slice(&version.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.despawned.len() as u32).to_le_bytes());
+        for &index in &self.despawned {
+            out.extend_from_slice(&index.to_le_bytes());
+        }
+
+        out.extend_from_slice(&(self.changes.len() as u32).to_le_bytes());
+        for change in &self.changes {
+            out.extend_from_slice(&change.index.to_le_bytes());
+            out.extend_from_slice(&change.generation.to_le_bytes());
+            out.extend_from_slice(&change.component.to_le_bytes());
+            match &change.value {
+                Some(value) => {
+                    out.push(1);
+                    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    out.extend_from_slice(value);
+                }
+                None => out.push(0),
+            }
+        }
+    }
+
+    pub fn decode(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        const WHAT: &str = "delta packet";
+
+        let mut packet = DeltaPacket::default();
+
+        for _ in 0..take_u32(&mut bytes, WHAT)? {
+            packet.versions.push(take_u32(&mut bytes, WHAT)?);
+        }
+
+        for _ in 0..take_u32(&mut bytes, WHAT)? {
+            packet.despawned.push(take_u32(&mut bytes, WHAT)?);
+        }
+
+        for _ in 0..take_u32(&mut bytes, WHAT)? {
+            let index = take_u32(&mut bytes, WHAT)?;
+            let generation = take_u32(&mut bytes, WHAT)?;
+            let component = u16::from_le_bytes(take(&mut bytes, 2, WHAT)?.try_into().unwrap());
+            let value = match take(&mut bytes, 1, WHAT)?[0] {
+                0 => None,
+                1 => {
+                    let len = take_u32(&mut bytes, WHAT)? as usize;
+                    Some(take(&mut bytes, len, WHAT)?.to_vec())
+                }
+                _ => return Err(DecodeError::new(WHAT)),
+            };
+            packet.changes.push(ComponentChange {
+                index,
+                generation,
+                component,
+                value,
+            });
+        }
+
+        if bytes.is_empty() {
+            Ok(packet)
+        } else {
+            Err(DecodeError::new(WHAT))
+        }
+    }
+}
+
+/// Produces `DeltaPacket`s from the changes to a set of tracked components in one `World`, and
+/// applies them to another.
+///
+/// Each synchronized component is registered with `NetSync::register`, and is identified in
+/// packets by its registration order, so the sending and receiving sides must register the same
+/// components in the same order.
+///
+/// The sending side calls `NetSync::collect_delta` once per tick, normally just after
+/// `World::merge` so that the components of deleted entities have already been removed.  The
+/// receiving side calls `NetSync::apply_delta`, which creates a local entity for every remote
+/// entity it has not seen before.
+#[derive(Default)]
+pub struct NetSync {
+    components: Vec<SyncRegistration>,
+    // The generation of every entity index a component value has been sent for.
+    sent: FxHashMap<Index, u32>,
+    // The local entity for every remote entity index, along with the remote generation.
+    received: FxHashMap<Index, (u32, Entity)>,
+}
+
+impl NetSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a component for synchronization, turning on modification tracking for it.
+    ///
+    /// Only changes made after registration are included in deltas.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted into the world.
+    pub fn register<C>(&mut self, world: &mut World)
+    where
+        C: SyncComponent,
+        C::Storage: TrackedStorage + Send,
+    {
+        assert!(
+            self.components.len() <= u16::MAX as usize,
+            "too many synchronized components"
+        );
+        let mut storage = world.get_component_mut::<C>();
+        storage.set_track_modified(true);
+        let reader = storage.register_reader();
+        self.components.push(SyncRegistration {
+            reader,
+            version: C::VERSION,
+            collect: collect::<C>,
+            snapshot: snapshot::<C>,
+            hash: hash::<C>,
+            apply: apply::<C>,
+            unregister: unregister::<C>,
+        });
+    }
+
+    /// Stop synchronizing every registered component.
+    pub fn unregister_all(&mut self, world: &mut World) {
+        for registration in self.components.drain(..) {
+            (registration.unregister)(world, registration.reader);
+        }
+    }
+
+    /// Collect every change to the registered components since the last call.
+    pub fn collect_delta(&mut self, world: &World) -> DeltaPacket {
+        let mut packet = self.empty_packet();
+
+        let mut despawned = BitSet::new();
+        let entities = world.entities();
+        self.sent
+            .retain(|&index, &mut generation| match entities.entity(index) {
+                Some(e) if e.generation() == generation => true,
+                _ => {
+                    despawned.add(index);
+                    false
+                }
+            });
+        packet.despawned.extend((&despawned).iter());
+
+        for (component, registration) in self.components.iter().enumerate() {
+            (registration.collect)(
+                world,
+                registration.reader,
+                component as u16,
+                &mut self.sent,
+                &mut packet.changes,
+            );
+        }
+
+        packet
+    }
+
+    /// Produce a packet containing every registered component currently in the world, such as for
+    /// a save file or for a newly connected client.
+    ///
+    /// This does not affect which changes are included in the next `NetSync::collect_delta`.
+    pub fn snapshot(&self, world: &World) -> DeltaPacket {
+        let mut packet = self.empty_packet();
+        for (component, registration) in self.components.iter().enumerate() {
+            (registration.snapshot)(world, component as u16, &mut packet.changes);
+        }
+        packet
+    }
+
+    pub(crate) fn hash_components(&self, world: &World, hasher: &mut StateHasher) {
+        let mut buf = Vec::new();
+        for registration in &self.components {
+            (registration.hash)(world, hasher, &mut buf);
+        }
+    }
+
+    fn empty_packet(&self) -> DeltaPacket {
+        DeltaPacket {
+            versions: self.components.iter().map(|r| r.version).collect(),
+            ..DeltaPacket::default()
+        }
+    }
+
+    /// Apply a packet produced by `NetSync::collect_delta` or `NetSync::snapshot` on another
+    /// world.
+    ///
+    /// Values encoded with an older version of a component are migrated with
+    /// `SyncComponent::migrate`.  Changes before an error are still applied.
+    pub fn apply_delta(
+        &mut self,
+        world: &mut World,
+        packet: &DeltaPacket,
+    ) -> Result<(), NetSyncError> {
+        for index in &packet.despawned {
+            if let Some((_, entity)) = self.received.remove(index) {
+                let _ = world.delete_entity(entity);
+            }
+        }
+
+        for change in &packet.changes {
+            let (apply, version) = self
+                .components
+                .get(change.component as usize)
+                .map(|r| (r.apply, r.version))
+                .ok_or(NetSyncError::UnknownComponent(change.component))?;
+
+            let entity = match self.received.get(&change.index) {
+                Some(&(generation, entity)) if generation == change.generation => entity,
+                _ if change.value.is_none() => continue,
+                _ => self.apply_spawned(world, change.index, change.generation),
+            };
+
+            let version = packet
+                .versions
+                .get(change.component as usize)
+                .copied()
+                .unwrap_or(version);
+            apply(world, entity, change.value.as_deref(), version)?;
+        }
+
+        Ok(())
+    }
+
+    // Start tracking every live entity which has not been sent yet, so that the creation of
+    // entities without any synchronized components is not lost.
+    pub(crate) fn collect_spawned(&mut self, world: &World, out: &mut Vec<(Index, u32)>) {
+        for entity in (&world.entities()).join() {
+            if self.sent.get(&entity.index()) != Some(&entity.generation()) {
+                self.sent.insert(entity.index(), entity.generation());
+                out.push((entity.index(), entity.generation()));
+            }
+        }
+    }
+
+    // Create a local entity for a remote entity produced by `NetSync::collect_spawned`, if one has
+    // not been created already.
+    pub(crate) fn apply_spawned(
+        &mut self,
+        world: &mut World,
+        index: Index,
+        generation: u32,
+    ) -> Entity {
+        match self.received.get(&index) {
+            Some(&(g, entity)) if g == generation => entity,
+            previous => {
+                if let Some(&(_, entity)) = previous {
+                    let _ = world.delete_entity(entity);
+                }
+                let entity = world.create_entity();
+                self.received.insert(index, (generation, entity));
+                entity
+            }
+        }
+    }
+
+    /// The local entity created for the given remote entity index, if any.
+    pub fn local_entity(&self, remote_index: Index) -> Option<Entity> {
+        self.received.get(&remote_index).map(|&(_, e)| e)
+    }
+}
+
+pub(crate) fn take<'a>(
+    bytes: &mut &'a [u8],
+    len: usize,
+    what: &'static str,
+) -> Result<&'a [u8], DecodeError> {
+    if bytes.len() < len {
+        return Err(DecodeError::new(what));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+pub(crate) fn take_u32(bytes: &mut &[u8], what: &'static str) -> Result<u32, DecodeError> {
+    Ok(u32::from_le_bytes(
+        take(bytes, 4, what)?.try_into().unwrap(),
+    ))
+}
+
+type CollectChanges =
+    fn(&World, ReaderId, u16, &mut FxHashMap<Index, u32>, &mut Vec<ComponentChange>);
+type ApplyChange = fn(&mut World, Entity, Option<&[u8]>, u32) -> Result<(), NetSyncError>;
+
+struct SyncRegistration {
+    reader: ReaderId,
+    version: u32,
+    collect: CollectChanges,
+    snapshot: fn(&World, u16, &mut Vec<ComponentChange>),
+    hash: fn(&World, &mut StateHasher, &mut Vec<u8>),
+    apply: ApplyChange,
+    unregister: fn(&mut World, ReaderId),
+}
+
+fn collect<C>(
+    world: &World,
+    reader: ReaderId,
+    component: u16,
+    sent: &mut FxHashMap<Index, u32>,
+    out: &mut Vec<ComponentChange>,
+) where
+    C: SyncComponent,
+    C::Storage: TrackedStorage + Send,
+{
+    let entities = world.entities();
+    let mut storage = world.write_component::<C>();
+    // An index may be modified both because the entity which was sent for it was despawned and
+    // because a new entity re-using the index was given the component in the same tick, so
+    // changes are always matched with the entity which is alive now, by index and generation.
+    for index in storage.reader_modified_indexes(reader).iter() {
+        let entity = entities.entity(index);
+        match entity.and_then(|e| Some((e, storage.get(e)?))) {
+            Some((entity, value)) => {
+                let mut bytes = Vec::new();
+                value.encode(&mut bytes);
+                sent.insert(index, entity.generation());
+                out.push(ComponentChange {
+                    index,
+                    generation: entity.generation(),
+                    component,
+                    value: Some(bytes),
+                });
+            }
+            None => {
+                // Only entities which are still alive can have their components removed, the
+                // components of despawned entities are removed along with them.
+                let generation = entity.map(|e| e.generation());
+                if let Some(generation) = generation.filter(|g| sent.get(&index) == Some(g)) {
+                    out.push(ComponentChange {
+                        index,
+                        generation,
+                        component,
+                        value: None,
+                    });
+                }
+            }
+        }
+    }
+    storage.clear_reader_modified(reader);
+}
+
+fn snapshot<C>(world: &World, component: u16, out: &mut Vec<ComponentChange>)
+where
+    C: SyncComponent,
+    C::Storage: TrackedStorage + Send,
+{
+    let storage = world.write_component::<C>();
+    for (entity, value) in (&world.entities(), &storage).join() {
+        let mut bytes = Vec::new();
+        value.encode(&mut bytes);
+        out.push(ComponentChange {
+            index: entity.index(),
+            generation: entity.generation(),
+            component,
+            value: Some(bytes),
+        });
+    }
+}
+
+fn hash<C>(world: &World, hasher: &mut StateHasher, buf: &mut Vec<u8>)
+where
+    C: SyncComponent,
+    C::Storage: TrackedStorage + Send,
+{
+    let storage = world.write_component::<C>();
+    hasher.write_u32(C::VERSION);
+    for (entity, value) in (&world.entities(), &storage).join() {
+        buf.clear();
+        value.encode(buf);
+        hasher.write_u32(entity.index());
+        hasher.write_u32(buf.len() as u32);
+        hasher.write(buf);
+    }
+}
+
+fn apply<C>(
+    world: &mut World,
+    entity: Entity,
+    value: Option<&[u8]>,
+    version: u32,
+) -> Result<(), NetSyncError>
+where
+    C: SyncComponent,
+    C::Storage: TrackedStorage + Send,
+{
+    let mut storage = world.get_component_mut::<C>();
+    match value {
+        Some(bytes) => {
+            let value = match version.cmp(&C::VERSION) {
+                Ordering::Equal => C::decode(bytes),
+                Ordering::Less => C::migrate(bytes, version),
+                Ordering::Greater => {
+                    return Err(NetSyncError::NewerVersion {
+                        type_name: type_name::<C>(),
+                        version,
+                    })
+                }
+            };
+            let value = value.map_err(|source| NetSyncError::Decode {
+                type_name: type_name::<C>(),
+                source,
+            })?;
+            storage.insert(entity, value).unwrap();
+        }
+        None => {
+            storage.remove(entity).unwrap();
+        }
+    }
+    Ok(())
+}
+
+fn unregister<C>(world: &mut World, reader: ReaderId)
+where
+    C: SyncComponent,
+    C::Storage: TrackedStorage + Send,
+{
+    if world.contains_component::<C>() {
+        world.get_component_mut::<C>().unregister_reader(reader);
+    }
+}
+
+// A 64-bit FNV-1a hasher, whose output depends only on the bytes written and not on the platform or
+// the process, unlike `std::collections::hash_map::DefaultHasher`.
+pub(crate) struct StateHasher(u64);
+
+impl StateHasher {
+    pub(crate) fn new() -> Self {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
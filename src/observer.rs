@@ -0,0 +1,84 @@
+use std::marker::PhantomData;
+
+use hibitset::BitSetLike;
+
+use crate::{
+    resources::{ResourceConflict, Resources},
+    system::System,
+    tracked::TrackedStorage,
+    world::World,
+    world_common::{Component, WorldResourceId, WorldResources},
+};
+
+/// A `System` wrapper that only runs the inner system when the tracked component `C` has been
+/// modified.
+///
+/// Before each run, checks whether the modified set of `C` is empty, and if it is, skips running
+/// the inner system entirely.  After the inner system runs successfully, the modified set of `C` is
+/// cleared.  If the inner system returns an error, the modified set is left alone.
+///
+/// Since clearing happens after the inner system runs, any modifications the inner system itself
+/// makes to `C` are also cleared.
+///
+/// Because it must clear the modified set, an `Observer` always declares a write of `C` in addition
+/// to the resources of the inner system.
+pub struct Observer<C, S> {
+    system: S,
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<C, S> Observer<C, S> {
+    pub fn new(system: S) -> Self {
+        Observer {
+            system,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.system
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.system
+    }
+
+    pub fn into_inner(self) -> S {
+        self.system
+    }
+}
+
+impl<'a, C, S> System<&'a World> for Observer<C, S>
+where
+    C: Component + 'static,
+    C::Storage: TrackedStorage + Send,
+    S: System<&'a World, Resources = WorldResources>,
+{
+    type Resources = WorldResources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<WorldResources, ResourceConflict> {
+        let mut resources = self.system.check_resources()?;
+        resources.union(
+            &WorldResources::new()
+                .read(WorldResourceId::Entities)
+                .write(WorldResourceId::component::<C>()),
+        );
+        Ok(resources)
+    }
+
+    fn run(&mut self, pool: &Self::Pool, world: &'a World) -> Result<(), Self::Error> {
+        if world.write_component::<C>().modified_indexes().is_empty() {
+            return Ok(());
+        }
+
+        self.system.run(pool, world)?;
+        world.write_component::<C>().clear_modified();
+        Ok(())
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.system.is_pinned()
+    }
+}
@@ -1,7 +1,18 @@
-use hibitset::{BitProducer, BitSetLike};
+#[cfg(feature = "rayon")]
+use std::slice;
+#[cfg(not(feature = "rayon"))]
+use std::vec;
+
+#[cfg(feature = "rayon")]
+use hibitset::BitProducer;
+use hibitset::BitSetLike;
+#[cfg(feature = "rayon")]
 use rayon::iter::{
-    plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer},
-    ParallelIterator,
+    plumbing::{
+        bridge, bridge_unindexed, Consumer, Folder, Producer, ProducerCallback, UnindexedConsumer,
+        UnindexedProducer,
+    },
+    FoldChunks, IndexedParallelIterator, MinLen, ParallelIterator,
 };
 
 pub use crate::join::{BitSetConstrained, Index, IntoJoin, Join, JoinIterUnconstrained};
@@ -9,6 +20,8 @@ pub use crate::join::{BitSetConstrained, Index, IntoJoin, Join, JoinIterUnconstr
 pub trait ParJoinExt: IntoJoin {
     /// Safely iterate over this `Join` in parallel.
     ///
+    /// Items are yielded in an unspecified order.
+    ///
     /// # Panics
     /// Panics if the result of this join is unconstrained.
     fn par_join(self) -> JoinParIter<Self::IntoJoin>
@@ -31,10 +44,34 @@ pub trait ParJoinExt: IntoJoin {
     {
         JoinParIter::new_unconstrained(self.into_join())
     }
+
+    /// Safely iterate over this `Join` in parallel with an `IndexedParallelIterator`.
+    ///
+    /// Every matching index is collected up front, which costs an allocation, but in exchange the
+    /// work is split exactly in half at every level regardless of how skewed the mask is, and the
+    /// iterator supports indexed adapters like `zip`, `enumerate` and `with_min_len`.  Items are
+    /// ordered by ascending index.
+    ///
+    /// # Panics
+    /// Panics if the result of this join is unconstrained.
+    fn par_join_indexed(self) -> JoinIndexedParIter<Self::IntoJoin>
+    where
+        Self: Sized + Send + Sync,
+        Self::Item: Send,
+        <Self::IntoJoin as Join>::Mask: BitSetConstrained + Send + Sync,
+    {
+        JoinIndexedParIter::new(self.into_join()).unwrap()
+    }
 }
 
 impl<J: IntoJoin> ParJoinExt for J {}
 
+/// A join iterated in parallel, returned from `ParJoinExt::par_join`.
+///
+/// With the `rayon` feature this is a `ParallelIterator`.  Without it, the join is iterated
+/// sequentially in the current thread by a few methods with the same signatures as their rayon
+/// counterparts, so code which compiles without the feature also compiles with it.  Code should
+/// not rely on item order either way.
 pub struct JoinParIter<J: Join>(J::Mask, J::Access);
 
 impl<J: Join> JoinParIter<J> {
@@ -54,8 +91,147 @@ impl<J: Join> JoinParIter<J> {
         let (mask, access) = j.open();
         Self(mask, access)
     }
+
+    /// Collect every matching index to turn this into a `JoinIndexedParIter`, see
+    /// `ParJoinExt::par_join_indexed`.
+    pub fn into_indexed(self) -> JoinIndexedParIter<J> {
+        let JoinParIter(mask, access) = self;
+        JoinIndexedParIter {
+            indexes: mask.iter().collect(),
+            access,
+        }
+    }
 }
 
+#[cfg(not(feature = "rayon"))]
+impl<J: Join> JoinParIter<J> {
+    /// Call `op` with every item.
+    ///
+    /// This is the same as `ParallelIterator::for_each`.
+    pub fn for_each<OP>(self, op: OP)
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        J::Mask: Send + Sync,
+        OP: Fn(J::Item) + Sync + Send,
+    {
+        self.for_each_seq(op);
+    }
+
+    /// Call `op` with every item along with a value created by `init`, which is only called once.
+    ///
+    /// This is the same as `ParallelIterator::for_each_init`.
+    pub fn for_each_init<T, INIT, OP>(self, init: INIT, op: OP)
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        J::Mask: Send + Sync,
+        INIT: Fn() -> T + Sync + Send,
+        OP: Fn(&mut T, J::Item) + Sync + Send,
+    {
+        let mut state = init();
+        self.for_each_seq(|item| op(&mut state, item));
+    }
+
+    /// Only meaningful with the `rayon` feature, this collects every matching index, see
+    /// `JoinParIter::into_indexed`.
+    pub fn with_min_len(self, min: usize) -> JoinIndexedParIter<J>
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+    {
+        self.into_indexed().with_min_len(min)
+    }
+
+    /// Fold every run of `chunk_size` consecutive items (in ascending index order) into a single
+    /// value, starting from `identity()` for each run.
+    ///
+    /// This collects every matching index, see `JoinParIter::into_indexed`.
+    pub fn fold_chunks<T, ID, F>(
+        self,
+        chunk_size: usize,
+        identity: ID,
+        fold_op: F,
+    ) -> vec::IntoIter<T>
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        T: Send,
+        ID: Fn() -> T + Send + Sync,
+        F: Fn(T, J::Item) -> T + Send + Sync,
+    {
+        self.into_indexed()
+            .fold_chunks(chunk_size, identity, fold_op)
+    }
+
+    fn for_each_seq(self, mut f: impl FnMut(J::Item)) {
+        let JoinParIter(mask, access) = self;
+        for index in (&mask).iter() {
+            // Every index comes from the mask returned by `J::open` and is fetched only once.
+            f(unsafe { J::get(&access, index) });
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<J: Join> JoinParIter<J> {
+    /// Call `op` with every item along with a value created by `init`, which is only called once
+    /// per unit of work rather than once per item.
+    ///
+    /// This is the same as `ParallelIterator::for_each_init`.
+    pub fn for_each_init<T, INIT, OP>(self, init: INIT, op: OP)
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        J::Mask: Send + Sync,
+        INIT: Fn() -> T + Sync + Send,
+        OP: Fn(&mut T, J::Item) + Sync + Send,
+    {
+        ParallelIterator::for_each_init(self, init, op)
+    }
+
+    /// Split the work into units of no fewer than `min` items, which is useful when each item is
+    /// very cheap to process.
+    ///
+    /// This collects every matching index, see `JoinParIter::into_indexed`.
+    pub fn with_min_len(self, min: usize) -> MinLen<JoinIndexedParIter<J>>
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+    {
+        self.into_indexed().with_min_len(min)
+    }
+
+    /// Fold every run of `chunk_size` consecutive items (in ascending index order) into a single
+    /// value, starting from `identity()` for each run.
+    ///
+    /// This collects every matching index, see `JoinParIter::into_indexed`.
+    pub fn fold_chunks<T, ID, F>(
+        self,
+        chunk_size: usize,
+        identity: ID,
+        fold_op: F,
+    ) -> FoldChunks<JoinIndexedParIter<J>, ID, F>
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        T: Send,
+        ID: Fn() -> T + Send + Sync,
+        F: Fn(T, J::Item) -> T + Send + Sync,
+    {
+        self.into_indexed()
+            .fold_chunks(chunk_size, identity, fold_op)
+    }
+}
+
+#[cfg(feature = "rayon")]
 impl<J> ParallelIterator for JoinParIter<J>
 where
     J: Join + Send,
@@ -85,6 +261,7 @@ where
     }
 }
 
+#[cfg(feature = "rayon")]
 struct JoinProducer<'a, J>
 where
     J: Join + Send,
@@ -96,6 +273,7 @@ where
     access: &'a J::Access,
 }
 
+#[cfg(feature = "rayon")]
 impl<'a, J> UnindexedProducer for JoinProducer<'a, J>
 where
     J: Join + Send,
@@ -125,6 +303,240 @@ where
         // know they are valid.  Each `JoinProducer` has a *distinct* subset of the valid indexes,
         // and we only fold over each index that this `JoinProducer` owns *once*, so we uphold the
         // aliasing requirements.
-        folder.consume_iter(producer.0.map(|idx| unsafe { J::get(access, idx) }))
+        let mut iter = producer.0;
+        folder.consume_iter(std::iter::from_fn(move || {
+            let idx = iter.next()?;
+            Some(unsafe { J::get(access, idx) })
+        }))
+    }
+}
+
+/// A join iterated in parallel over every matching index collected up front, returned from
+/// `ParJoinExt::par_join_indexed`.
+///
+/// With the `rayon` feature this is an `IndexedParallelIterator`.  Without it, the same as
+/// `JoinParIter`, the join is iterated sequentially in the current thread by a few methods with
+/// the same signatures as their rayon counterparts.
+pub struct JoinIndexedParIter<J: Join> {
+    indexes: Vec<Index>,
+    access: J::Access,
+}
+
+impl<J: Join> JoinIndexedParIter<J> {
+    pub fn new(j: J) -> Result<Self, JoinIterUnconstrained>
+    where
+        J::Mask: BitSetConstrained,
+    {
+        Ok(JoinParIter::new(j)?.into_indexed())
+    }
+
+    /// The number of items.
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+impl<J: Join> JoinIndexedParIter<J> {
+    /// Call `op` with every item, in ascending index order.
+    ///
+    /// This is the same as `ParallelIterator::for_each`.
+    pub fn for_each<OP>(self, op: OP)
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        OP: Fn(J::Item) + Sync + Send,
+    {
+        self.for_each_seq(op);
+    }
+
+    /// Call `op` with every item along with a value created by `init`, which is only called once.
+    ///
+    /// This is the same as `ParallelIterator::for_each_init`.
+    pub fn for_each_init<T, INIT, OP>(self, init: INIT, op: OP)
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        INIT: Fn() -> T + Sync + Send,
+        OP: Fn(&mut T, J::Item) + Sync + Send,
+    {
+        let mut state = init();
+        self.for_each_seq(|item| op(&mut state, item));
+    }
+
+    /// Does nothing without the `rayon` feature.
+    ///
+    /// This is the same as `IndexedParallelIterator::with_min_len`.
+    pub fn with_min_len(self, _min: usize) -> Self {
+        self
+    }
+
+    /// Fold every run of `chunk_size` consecutive items into a single value, starting from
+    /// `identity()` for each run, and iterate over the results.
+    ///
+    /// This is the same as `IndexedParallelIterator::fold_chunks`.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0.
+    pub fn fold_chunks<T, ID, F>(
+        self,
+        chunk_size: usize,
+        identity: ID,
+        fold_op: F,
+    ) -> vec::IntoIter<T>
+    where
+        J: Send,
+        J::Item: Send,
+        J::Access: Send + Sync,
+        T: Send,
+        ID: Fn() -> T + Send + Sync,
+        F: Fn(T, J::Item) -> T + Send + Sync,
+    {
+        assert!(chunk_size != 0, "chunk_size must not be zero");
+        self.indexes
+            .chunks(chunk_size)
+            .map(|chunk| {
+                chunk.iter().fold(identity(), |acc, &index| {
+                    // The indexes are unique, so every index is still fetched only once.
+                    fold_op(acc, unsafe { J::get(&self.access, index) })
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn for_each_seq(self, mut f: impl FnMut(J::Item)) {
+        for index in self.indexes {
+            // The indexes are collected from the mask returned by `J::open` and are unique.
+            f(unsafe { J::get(&self.access, index) });
+        }
     }
 }
+
+#[cfg(feature = "rayon")]
+impl<J> ParallelIterator for JoinIndexedParIter<J>
+where
+    J: Join + Send,
+    J::Item: Send,
+    J::Access: Send + Sync,
+{
+    type Item = J::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.indexes.len())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<J> IndexedParallelIterator for JoinIndexedParIter<J>
+where
+    J: Join + Send,
+    J::Item: Send,
+    J::Access: Send + Sync,
+{
+    fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    fn drive<C>(self, consumer: C) -> C::Result
+    where
+        C: Consumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB>(self, callback: CB) -> CB::Output
+    where
+        CB: ProducerCallback<Self::Item>,
+    {
+        callback.callback(IndexedJoinProducer::<J> {
+            indexes: &self.indexes,
+            access: &self.access,
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+struct IndexedJoinProducer<'a, J: Join> {
+    indexes: &'a [Index],
+    access: &'a J::Access,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, J> Producer for IndexedJoinProducer<'a, J>
+where
+    J: Join + Send,
+    J::Item: Send,
+    J::Access: Sync + 'a,
+{
+    type Item = J::Item;
+    type IntoIter = IndexedJoinIter<'a, J>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IndexedJoinIter {
+            indexes: self.indexes.iter(),
+            access: self.access,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (first, second) = self.indexes.split_at(index);
+        (
+            IndexedJoinProducer {
+                indexes: first,
+                access: self.access,
+            },
+            IndexedJoinProducer {
+                indexes: second,
+                access: self.access,
+            },
+        )
+    }
+}
+
+// All of the indexes here are ultimately derived from the mask returned by `J::open` and are
+// unique, and splitting only ever hands out disjoint sub-slices, so every index is fetched at most
+// once and we uphold the aliasing requirements of `Join::get`.
+#[cfg(feature = "rayon")]
+struct IndexedJoinIter<'a, J: Join> {
+    indexes: slice::Iter<'a, Index>,
+    access: &'a J::Access,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, J: Join> Iterator for IndexedJoinIter<'a, J> {
+    type Item = J::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = *self.indexes.next()?;
+        Some(unsafe { J::get(self.access, index) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indexes.size_hint()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, J: Join> DoubleEndedIterator for IndexedJoinIter<'a, J> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = *self.indexes.next_back()?;
+        Some(unsafe { J::get(self.access, index) })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, J: Join> ExactSizeIterator for IndexedJoinIter<'a, J> {}
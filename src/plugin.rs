@@ -0,0 +1,21 @@
+use crate::world::World;
+
+/// A reusable bundle of world setup, such as a physics or audio subsystem.
+///
+/// A plugin registers everything it needs (components, resources, component indexes) in the
+/// `World`, and adds its systems to a schedule. The schedule type is left generic, so a plugin can
+/// add systems to whatever structure the application uses to build its systems, such as a `Vec` of
+/// boxed systems to be passed to `parallelize`.
+pub trait Plugin<S: ?Sized> {
+    fn build(&self, world: &mut World, schedule: &mut S);
+}
+
+impl<S, F> Plugin<S> for F
+where
+    S: ?Sized,
+    F: Fn(&mut World, &mut S),
+{
+    fn build(&self, world: &mut World, schedule: &mut S) {
+        self(world, schedule)
+    }
+}
@@ -0,0 +1,103 @@
+/// Join several components of a `World` together, with a compact syntax for the common cases,
+/// such as `query!(world, (entity, &Pos, &mut Vel, without Frozen))`.
+///
+/// Each term of the query is one of:
+///
+/// * `entity`, which yields the `Entity`.
+/// * `&C` or `&mut C`, which yields a reference to the component `C`.
+/// * `maybe &C` or `maybe &mut C`, which yields an `Option` reference to the component `C` without
+///   constraining the join.
+/// * `with C` or `without C`, which only constrain the join to entities with or without the
+///   component `C` and do not yield anything.
+///
+/// Every term which yields something adds a single element to the yielded tuple, in order, so a
+/// query with a single such term yields 1-tuples.
+///
+/// The macro expands to a `JoinIter` which borrows the component storages as temporaries, so it
+/// must be consumed within the same statement, such as in a `for` loop or by calling `collect`.
+/// The `world` expression is evaluated once for every term.
+///
+/// # Panics
+/// Panics if any component is not inserted, or if the borrows of the components conflict.
+#[macro_export]
+macro_rules! query {
+    ($world:expr, ($($terms:tt)*)) => {
+        $crate::query!(@term $world; []; []; $($terms)*)
+    };
+
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*]; $(,)?) => {
+        $crate::join::IntoJoinExt::join(
+            $crate::query!(@constrain ($($join,)*); $($mask,)*)
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        entity $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)* &$world.entities(),];
+            [$($mask,)*];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        maybe &mut $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)* $crate::join::IntoJoinExt::maybe(&mut $world.write_component::<$c>()),];
+            [$($mask,)*];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        maybe & $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)* $crate::join::IntoJoinExt::maybe(&$world.read_component::<$c>()),];
+            [$($mask,)*];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        with $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)*];
+            [$($mask,)* $world.has_component::<$c>().mask(),];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        without $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)*];
+            [$($mask,)* $crate::hibitset::BitSetNot($world.has_component::<$c>().mask()),];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        &mut $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)* &mut $world.write_component::<$c>(),];
+            [$($mask,)*];
+            $($($rest)*)?
+        )
+    };
+    (@term $world:expr; [$($join:expr,)*]; [$($mask:expr,)*];
+        & $c:ty $(, $($rest:tt)*)?
+    ) => {
+        $crate::query!(@term $world;
+            [$($join,)* &$world.read_component::<$c>(),];
+            [$($mask,)*];
+            $($($rest)*)?
+        )
+    };
+
+    (@constrain $join:expr;) => {
+        $join
+    };
+    (@constrain $join:expr; $mask:expr, $($rest:expr,)*) => {
+        $crate::query!(@constrain $crate::join::IntoJoinExt::constrain($join, $mask); $($rest,)*)
+    };
+}
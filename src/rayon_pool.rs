@@ -1,6 +1,19 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use rustc_hash::FxHashMap;
+
 use crate::system::Pool;
 
 /// A system runner that runs parallel systems using `rayon::join`.
+///
+/// `Pool::limit_threads` is implemented by running the function inside a separate rayon
+/// `ThreadPool` with the given number of threads, so any `par_join` or other rayon parallel
+/// iteration inside it is limited as well. These thread pools are created on first use and shared
+/// between every `RayonPool`.
+///
+/// `Pool::join_local` runs the local function on the calling thread with `rayon::in_place_scope`
+/// while the other function is spawned onto the rayon pool.
 #[derive(Default)]
 pub struct RayonPool;
 
@@ -14,4 +27,43 @@ impl Pool for RayonPool {
     {
         rayon::join(a, b)
     }
+
+    fn limit_threads<R, F>(&self, threads: usize, f: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        if threads >= rayon::current_num_threads() {
+            return f();
+        }
+        limited_pool(threads).install(f)
+    }
+
+    fn join_local<A, B, RA, RB>(&self, local: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA,
+        B: FnOnce() -> RB + Send,
+        RB: Send,
+    {
+        let mut rb = None;
+        let ra = rayon::in_place_scope(|scope| {
+            scope.spawn(|_| rb = Some(b()));
+            local()
+        });
+        (ra, rb.unwrap())
+    }
+}
+
+fn limited_pool(threads: usize) -> Arc<ThreadPool> {
+    static POOLS: OnceLock<Mutex<FxHashMap<usize, Arc<ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS.get_or_init(Default::default).lock().unwrap();
+    Arc::clone(pools.entry(threads).or_insert_with(|| {
+        Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .thread_name(move |i| format!("goggles-limit-{}-{}", threads, i))
+                .build()
+                .expect("could not build a limited rayon thread pool"),
+        )
+    }))
 }
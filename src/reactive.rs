@@ -0,0 +1,84 @@
+use hibitset::{BitSet, BitSetAnd, BitSetLike, BitSetNot};
+
+use crate::join::{BitSetConstrained, IntoJoin, Join};
+
+/// Remembers which indexes matched a `Join` the last time it was updated, so that it is possible to
+/// find only the indexes which started or stopped matching since then.
+///
+/// Call `ReactiveQuery::update` once per frame with the join to watch, then use the `added` and
+/// `removed` bitsets (which are themselves joinable) to find the changes.  For example, joining
+/// `(&entities, &pos, &vel, query.added())` yields only entities which gained both `pos` and `vel`
+/// since the previous update.
+///
+/// This only tracks the join *mask*, so it works entirely in terms of indexes.  If an entity is
+/// deleted and its index is re-used by a new matching entity between two updates, the index will
+/// not show up as changed.
+#[derive(Debug, Default)]
+pub struct ReactiveQuery {
+    matched: BitSet,
+    added: BitSet,
+    removed: BitSet,
+}
+
+impl ReactiveQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the current mask of the given join, and compute which indexes started or stopped
+    /// matching since the last call to `update`.
+    ///
+    /// The join is only opened to obtain its mask, `Join::get` is never called.
+    ///
+    /// # Panics
+    /// Panics if the given join is unconstrained.
+    pub fn update<J>(&mut self, j: J)
+    where
+        J: IntoJoin,
+        <J::IntoJoin as Join>::Mask: BitSetConstrained,
+    {
+        let (mask, _) = j.into_join().open();
+        assert!(mask.is_constrained(), "cannot track unconstrained Join");
+
+        let mut current = BitSet::new();
+        for index in mask.iter() {
+            current.add(index);
+        }
+
+        self.added.clear();
+        for index in BitSetAnd(&current, BitSetNot(&self.matched)).iter() {
+            self.added.add(index);
+        }
+
+        self.removed.clear();
+        for index in BitSetAnd(&self.matched, BitSetNot(&current)).iter() {
+            self.removed.add(index);
+        }
+
+        self.matched = current;
+    }
+
+    /// Every index that matched at the last call to `update`.
+    pub fn matched(&self) -> &BitSet {
+        &self.matched
+    }
+
+    /// Indexes which matched at the last call to `update` but did not match at the call before it.
+    pub fn added(&self) -> &BitSet {
+        &self.added
+    }
+
+    /// Indexes which matched at the second to last call to `update` but did not match at the last
+    /// one.
+    pub fn removed(&self) -> &BitSet {
+        &self.removed
+    }
+
+    /// Forget all previously matched indexes, so that on the next update every matching index will
+    /// be considered added.
+    pub fn clear(&mut self) {
+        self.matched.clear();
+        self.added.clear();
+        self.removed.clear();
+    }
+}
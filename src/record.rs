@@ -0,0 +1,263 @@
+use std::{any::type_name, convert::TryInto};
+
+use crate::{
+    entity::Entity,
+    join::Index,
+    net_sync::{take, take_u32, DecodeError, DeltaPacket, NetSync, NetSyncError, SyncComponent},
+    tracked::TrackedStorage,
+    world::{Tick, World},
+};
+
+/// A resource which can be encoded to and decoded from bytes, so that writes to it can be
+/// recorded by a `Recorder`.
+pub trait SyncResource: Sized + Send + Sync + 'static {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+/// Every structural change recorded by `Recorder::record` for a single tick.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordedTick {
+    /// The value of the `Tick` resource when this tick was recorded.
+    pub tick: u64,
+    /// The index and generation of every entity created since the last recorded tick which does
+    /// not already appear in `delta`.
+    pub spawned: Vec<(Index, u32)>,
+    /// Despawned entities and every change to the recorded components.
+    pub delta: DeltaPacket,
+    /// The new encoded value of every recorded resource which changed, identified by its
+    /// registration order.
+    pub resources: Vec<(u16, Vec<u8>)>,
+}
+
+impl RecordedTick {
+    /// Encode this tick in a compact little-endian binary format, suitable for writing to a log
+    /// file.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.tick.to_le_bytes());
+
+        out.extend_from_slice(&(self.spawned.len() as u32).to_le_bytes());
+        for &(index, generation) in &self.spawned {
+            out.extend_from_slice(&index.to_le_bytes());
+            out.extend_from_slice(&generation.to_le_bytes());
+        }
+
+        let mut delta = Vec::new();
+        self.delta.encode(&mut delta);
+        out.extend_from_slice(&(delta.len() as u32).to_le_bytes());
+        out.extend_from_slice(&delta);
+
+        out.extend_from_slice(&(self.resources.len() as u32).to_le_bytes());
+        for (resource, value) in &self.resources {
+            out.extend_from_slice(&resource.to_le_bytes());
+            out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+    }
+
+    /// Decode a single tick produced by `RecordedTick::encode`, returning the remaining bytes.
+    pub fn decode(mut bytes: &[u8]) -> Result<(Self, &[u8]), DecodeError> {
+        const WHAT: &str = "recorded tick";
+
+        let mut recorded = RecordedTick {
+            tick: u64::from_le_bytes(take(&mut bytes, 8, WHAT)?.try_into().unwrap()),
+            ..RecordedTick::default()
+        };
+
+        for _ in 0..take_u32(&mut bytes, WHAT)? {
+            let index = take_u32(&mut bytes, WHAT)?;
+            let generation = take_u32(&mut bytes, WHAT)?;
+            recorded.spawned.push((index, generation));
+        }
+
+        let len = take_u32(&mut bytes, WHAT)? as usize;
+        recorded.delta = DeltaPacket::decode(take(&mut bytes, len, WHAT)?)?;
+
+        for _ in 0..take_u32(&mut bytes, WHAT)? {
+            let resource = u16::from_le_bytes(take(&mut bytes, 2, WHAT)?.try_into().unwrap());
+            let len = take_u32(&mut bytes, WHAT)? as usize;
+            recorded
+                .resources
+                .push((resource, take(&mut bytes, len, WHAT)?.to_vec()));
+        }
+
+        Ok((recorded, bytes))
+    }
+}
+
+/// Records the structural changes to a `World` every tick, so that they can later be re-applied
+/// to a fresh `World` with a `Replayer`, such as to reproduce a rare bug reported from the field.
+///
+/// Entity creation and deletion are always recorded, along with every change to the components
+/// registered with `Recorder::register_component` and every write to the resources registered
+/// with `Recorder::register_resource`.  Components and resources are identified by registration
+/// order, so the `Replayer` must register the same components and resources in the same order.
+///
+/// `Recorder::record` is called once per tick, after `World::merge`.  Recording checks every live
+/// entity and re-encodes every recorded resource, so it is meant as a debugging aid rather than
+/// something to leave enabled unconditionally.
+#[derive(Default)]
+pub struct Recorder {
+    sync: NetSync,
+    resources: Vec<RecordedResource>,
+    log: Vec<RecordedTick>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record every change to the given component, turning on modification tracking for it.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted into the world.
+    pub fn register_component<C>(&mut self, world: &mut World)
+    where
+        C: SyncComponent,
+        C::Storage: TrackedStorage + Send,
+    {
+        self.sync.register::<C>(world);
+    }
+
+    /// Record every write to the given resource.
+    ///
+    /// Writes are detected by comparing the encoded value against the last recorded one, the
+    /// first `Recorder::record` call always records the resource if it is present.
+    pub fn register_resource<R: SyncResource>(&mut self) {
+        assert!(
+            self.resources.len() <= u16::MAX as usize,
+            "too many recorded resources"
+        );
+        self.resources.push(RecordedResource {
+            last: None,
+            encode: encode_resource::<R>,
+        });
+    }
+
+    /// Record every change since the last call.
+    pub fn record(&mut self, world: &World) {
+        let mut recorded = RecordedTick {
+            tick: world.read_resource::<Tick>().get(),
+            delta: self.sync.collect_delta(world),
+            ..RecordedTick::default()
+        };
+        self.sync.collect_spawned(world, &mut recorded.spawned);
+
+        let mut buf = Vec::new();
+        for (i, resource) in self.resources.iter_mut().enumerate() {
+            buf.clear();
+            if (resource.encode)(world, &mut buf) && resource.last.as_ref() != Some(&buf) {
+                recorded.resources.push((i as u16, buf.clone()));
+                resource.last = Some(buf.clone());
+            }
+        }
+
+        self.log.push(recorded);
+    }
+
+    /// Every tick recorded so far.
+    pub fn log(&self) -> &[RecordedTick] {
+        &self.log
+    }
+
+    /// Take every tick recorded so far, leaving the log empty.
+    pub fn take_log(&mut self) -> Vec<RecordedTick> {
+        std::mem::take(&mut self.log)
+    }
+}
+
+/// Re-applies the ticks recorded by a `Recorder` to a fresh `World`.
+///
+/// Components and resources must be registered in the same order as they were with the
+/// `Recorder`.  Recorded entities are re-created as new local entities, which can be found with
+/// `Replayer::local_entity`.
+#[derive(Default)]
+pub struct Replayer {
+    sync: NetSync,
+    resources: Vec<ApplyResource>,
+}
+
+impl Replayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Panics
+    /// Panics if the component has not been inserted into the world.
+    pub fn register_component<C>(&mut self, world: &mut World)
+    where
+        C: SyncComponent,
+        C::Storage: TrackedStorage + Send,
+    {
+        self.sync.register::<C>(world);
+    }
+
+    pub fn register_resource<R: SyncResource>(&mut self) {
+        self.resources.push(apply_resource::<R>);
+    }
+
+    /// Apply a single recorded tick, followed by `World::merge`.
+    ///
+    /// Afterwards, the `Tick` resource is set to the recorded tick.
+    pub fn replay_tick(
+        &mut self,
+        world: &mut World,
+        recorded: &RecordedTick,
+    ) -> Result<(), NetSyncError> {
+        self.sync.apply_delta(world, &recorded.delta)?;
+        for &(index, generation) in &recorded.spawned {
+            self.sync.apply_spawned(world, index, generation);
+        }
+
+        for (resource, value) in &recorded.resources {
+            let apply = self
+                .resources
+                .get(*resource as usize)
+                .ok_or(NetSyncError::UnknownResource(*resource))?;
+            apply(world, value)?;
+        }
+
+        world.merge();
+        *world.write_resource::<Tick>() = Tick(recorded.tick);
+        Ok(())
+    }
+
+    /// Apply every tick in the given log in order.
+    pub fn replay(&mut self, world: &mut World, log: &[RecordedTick]) -> Result<(), NetSyncError> {
+        for recorded in log {
+            self.replay_tick(world, recorded)?;
+        }
+        Ok(())
+    }
+
+    /// The local entity created for the given recorded entity index, if any.
+    pub fn local_entity(&self, recorded_index: Index) -> Option<Entity> {
+        self.sync.local_entity(recorded_index)
+    }
+}
+
+struct RecordedResource {
+    last: Option<Vec<u8>>,
+    encode: fn(&World, &mut Vec<u8>) -> bool,
+}
+
+type ApplyResource = fn(&mut World, &[u8]) -> Result<(), NetSyncError>;
+
+fn encode_resource<R: SyncResource>(world: &World, out: &mut Vec<u8>) -> bool {
+    if world.contains_resource::<R>() {
+        world.read_resource::<R>().encode(out);
+        true
+    } else {
+        false
+    }
+}
+
+fn apply_resource<R: SyncResource>(world: &mut World, bytes: &[u8]) -> Result<(), NetSyncError> {
+    let value = R::decode(bytes).map_err(|source| NetSyncError::Decode {
+        type_name: type_name::<R>(),
+        source,
+    })?;
+    world.insert_resource(value);
+    Ok(())
+}
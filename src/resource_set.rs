@@ -1,72 +1,123 @@
 use std::{
-    any::{type_name, TypeId},
-    iter,
+    any::{type_name, Any, TypeId},
+    iter, mem,
     ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 
-use anymap::{any::Any, Map};
 use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+use rustc_hash::FxHashMap;
 
 use crate::{
-    fetch_resources::FetchResources,
+    fetch_resources::{FetchError, FetchResources},
     make_sync::MakeSync,
     resources::{ResourceConflict, RwResources},
 };
 
 /// Store a set of arbitrary types inside `AtomicRefCell`s, and then access them for either reading
 /// or writing.
+///
+/// A `ResourceSet` may have a shared parent set, and any resource which is not present in the child
+/// is looked up in the parent instead.  Only borrowing through `borrow` and `borrow_mut` (and
+/// `contains`) falls back to the parent, methods which take `&mut self` only ever affect resources
+/// stored directly in the child.
+///
+/// Individual resources may also opt in to modification tracking with
+/// `ResourceSet::set_track_modified`, which sets a modified flag whenever the resource is borrowed
+/// mutably.
+///
+/// Resources may also be inserted through a shared reference with `ResourceSet::borrow_or_default`
+/// and `ResourceSet::borrow_mut_or_default`.
+#[derive(Default)]
 pub struct ResourceSet {
-    resources: Map<dyn Any + Send + Sync>,
+    resources: FxHashMap<TypeId, Entry>,
+    // Resources inserted through a shared reference. Each entry is boxed so that it stays in place
+    // while borrowed, and they are all moved into `resources` by the next method which takes
+    // `&mut self`.
+    lazy: Mutex<FxHashMap<TypeId, Box<Entry>>>,
+    parent: Option<Arc<ResourceSet>>,
 }
 
-impl Default for ResourceSet {
-    fn default() -> Self {
+impl ResourceSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parent(parent: Arc<ResourceSet>) -> Self {
         ResourceSet {
-            resources: Map::new(),
+            resources: FxHashMap::default(),
+            lazy: Mutex::default(),
+            parent: Some(parent),
         }
     }
-}
 
-impl ResourceSet {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn parent(&self) -> Option<&Arc<ResourceSet>> {
+        self.parent.as_ref()
+    }
+
+    /// Set the parent set that missing resources are looked up in, returning the previous parent.
+    pub fn set_parent(&mut self, parent: Option<Arc<ResourceSet>>) -> Option<Arc<ResourceSet>> {
+        mem::replace(&mut self.parent, parent)
     }
 
     pub fn insert<T>(&mut self, r: T) -> Option<T>
     where
         T: Send + 'static,
     {
-        self.resources
-            .insert::<Resource<T>>(AtomicRefCell::new(MakeSync::new(r)))
-            .map(|r| r.into_inner().into_inner())
+        self.flush_lazy();
+        match self.resources.get_mut(&TypeId::of::<T>()) {
+            Some(entry) => {
+                entry.set_modified();
+                let value = Entry::new(r).value;
+                Some(into_inner::<T>(mem::replace(&mut entry.value, value)))
+            }
+            None => {
+                self.resources.insert(TypeId::of::<T>(), Entry::new(r));
+                None
+            }
+        }
     }
 
     pub fn remove<T>(&mut self) -> Option<T>
     where
         T: Send + 'static,
     {
+        self.flush_lazy();
         self.resources
-            .remove::<Resource<T>>()
-            .map(|r| r.into_inner().into_inner())
+            .remove(&TypeId::of::<T>())
+            .map(|entry| into_inner::<T>(entry.value))
     }
 
+    /// Returns true if the resource is present either in this set or in any parent set.
     pub fn contains<T>(&self) -> bool
     where
         T: Send + 'static,
     {
-        self.resources.contains::<Resource<T>>()
+        self.entry::<T>().is_some()
+    }
+
+    /// Returns true if the resource is stored directly in this set, ignoring any parent.
+    pub fn contains_local<T>(&self) -> bool
+    where
+        T: Send + 'static,
+    {
+        self.resources.contains_key(&TypeId::of::<T>())
+            || self.lazy_entry(TypeId::of::<T>()).is_some()
     }
 
     /// Borrow the given resource immutably.
     ///
     /// # Panics
     /// Panics if the resource has not been inserted or is already borrowed mutably.
-    pub fn borrow<T>(&self) -> AtomicRef<T>
+    pub fn borrow<T>(&self) -> AtomicRef<'_, T>
     where
         T: Send + Sync + 'static,
     {
-        if let Some(r) = self.resources.get::<Resource<T>>() {
-            AtomicRef::map(r.borrow(), |r| r.get())
+        if let Some(entry) = self.entry::<T>() {
+            AtomicRef::map(entry.cell::<T>().borrow(), |r| r.get())
         } else {
             panic!("no such resource {:?}", type_name::<T>());
         }
@@ -76,37 +127,397 @@ impl ResourceSet {
     ///
     /// # Panics
     /// Panics if the resource has not been inserted or is already borrowed.
-    pub fn borrow_mut<T>(&self) -> AtomicRefMut<T>
+    pub fn borrow_mut<T>(&self) -> AtomicRefMut<'_, T>
     where
         T: Send + 'static,
     {
-        if let Some(r) = self.resources.get::<Resource<T>>() {
-            AtomicRefMut::map(r.borrow_mut(), |r| r.get_mut())
+        if let Some(entry) = self.entry::<T>() {
+            let r = AtomicRefMut::map(entry.cell::<T>().borrow_mut(), |r| r.get_mut());
+            entry.set_modified();
+            r
         } else {
             panic!("no such resource {:?}", type_name::<T>());
         }
     }
 
+    /// Borrow the given resource exclusively without marking it as modified, for reading resources
+    /// that are not `Sync`.
+    ///
     /// # Panics
-    /// Panics if the resource has not been inserted.
+    /// Panics if the resource has not been inserted or is already borrowed.
+    pub(crate) fn borrow_exclusive<T>(&self) -> AtomicRefMut<'_, T>
+    where
+        T: Send + 'static,
+    {
+        if let Some(entry) = self.entry::<T>() {
+            AtomicRefMut::map(entry.cell::<T>().borrow_mut(), |r| r.get_mut())
+        } else {
+            panic!("no such resource {:?}", type_name::<T>());
+        }
+    }
+
+    /// Like `ResourceSet::borrow`, but returns an error instead of panicking.
+    pub fn try_borrow<T>(&self) -> Result<AtomicRef<'_, T>, FetchError>
+    where
+        T: Send + Sync + 'static,
+    {
+        let entry = self
+            .entry::<T>()
+            .ok_or(FetchError::Missing(type_name::<T>()))?;
+        let r = entry
+            .cell::<T>()
+            .try_borrow()
+            .map_err(|_| FetchError::Borrowed(type_name::<T>()))?;
+        Ok(AtomicRef::map(r, |r| r.get()))
+    }
+
+    /// Like `ResourceSet::borrow_mut`, but returns an error instead of panicking.
+    pub fn try_borrow_mut<T>(&self) -> Result<AtomicRefMut<'_, T>, FetchError>
+    where
+        T: Send + 'static,
+    {
+        let entry = self
+            .entry::<T>()
+            .ok_or(FetchError::Missing(type_name::<T>()))?;
+        let r = entry
+            .cell::<T>()
+            .try_borrow_mut()
+            .map_err(|_| FetchError::Borrowed(type_name::<T>()))?;
+        entry.set_modified();
+        Ok(AtomicRefMut::map(r, |r| r.get_mut()))
+    }
+
+    /// Borrow the given resource immutably, first inserting its default value if it is not present
+    /// in this set or any parent.
+    ///
+    /// # Panics
+    /// Panics if the resource is already borrowed mutably.
+    pub fn borrow_or_default<T>(&self) -> AtomicRef<'_, T>
+    where
+        T: Default + Send + Sync + 'static,
+    {
+        AtomicRef::map(self.entry_or_default::<T>().cell::<T>().borrow(), |r| {
+            r.get()
+        })
+    }
+
+    /// Borrow the given resource mutably, first inserting its default value if it is not present
+    /// in this set or any parent.
+    ///
+    /// # Panics
+    /// Panics if the resource is already borrowed.
+    pub fn borrow_mut_or_default<T>(&self) -> AtomicRefMut<'_, T>
+    where
+        T: Default + Send + 'static,
+    {
+        let entry = self.entry_or_default::<T>();
+        let r = AtomicRefMut::map(entry.cell::<T>().borrow_mut(), |r| r.get_mut());
+        entry.set_modified();
+        r
+    }
+
+    /// Like `ResourceSet::borrow_or_default`, but returns an error instead of panicking.
+    pub fn try_borrow_or_default<T>(&self) -> Result<AtomicRef<'_, T>, FetchError>
+    where
+        T: Default + Send + Sync + 'static,
+    {
+        let r = self
+            .entry_or_default::<T>()
+            .cell::<T>()
+            .try_borrow()
+            .map_err(|_| FetchError::Borrowed(type_name::<T>()))?;
+        Ok(AtomicRef::map(r, |r| r.get()))
+    }
+
+    /// Like `ResourceSet::borrow_mut_or_default`, but returns an error instead of panicking.
+    pub fn try_borrow_mut_or_default<T>(&self) -> Result<AtomicRefMut<'_, T>, FetchError>
+    where
+        T: Default + Send + 'static,
+    {
+        let entry = self.entry_or_default::<T>();
+        let r = entry
+            .cell::<T>()
+            .try_borrow_mut()
+            .map_err(|_| FetchError::Borrowed(type_name::<T>()))?;
+        entry.set_modified();
+        Ok(AtomicRefMut::map(r, |r| r.get_mut()))
+    }
+
+    /// Borrow a `Sync` part of the given resource immutably, without requiring the whole resource
+    /// to be `Sync`.
+    ///
+    /// The resource is borrowed immutably for as long as the returned reference lives.
+    ///
+    /// # Safety
+    /// The projection function must only access parts of `T` that are themselves `Sync`, and must
+    /// not call any method on `T` which may access non-`Sync` state.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted or is already borrowed mutably.
+    pub(crate) unsafe fn borrow_projected<T, U>(&self, f: impl FnOnce(&T) -> &U) -> AtomicRef<'_, U>
+    where
+        T: Send + 'static,
+        U: Sync,
+    {
+        if let Some(entry) = self.entry::<T>() {
+            AtomicRef::map(entry.cell::<T>().borrow(), |r| f(r.get_unchecked()))
+        } else {
+            panic!("no such resource {:?}", type_name::<T>());
+        }
+    }
+
+    /// Like `ResourceSet::borrow_projected`, but returns an error instead of panicking.
+    ///
+    /// # Safety
+    /// The same requirements as `ResourceSet::borrow_projected` apply.
+    pub(crate) unsafe fn try_borrow_projected<T, U>(
+        &self,
+        f: impl FnOnce(&T) -> &U,
+    ) -> Result<AtomicRef<'_, U>, FetchError>
+    where
+        T: Send + 'static,
+        U: Sync,
+    {
+        let entry = self
+            .entry::<T>()
+            .ok_or(FetchError::Missing(type_name::<T>()))?;
+        let r = entry
+            .cell::<T>()
+            .try_borrow()
+            .map_err(|_| FetchError::Borrowed(type_name::<T>()))?;
+        Ok(AtomicRef::map(r, |r| f(r.get_unchecked())))
+    }
+
+    /// Get a shared reference to a resource stored directly in this set, without counting the
+    /// borrow.
+    ///
+    /// Resources inserted through a shared reference are not found until the set is flushed.
+    ///
+    /// # Safety
+    /// The resource must not be borrowed mutably for as long as the returned reference lives.
+    pub(crate) unsafe fn get_unchecked<T>(&self) -> Option<&T>
+    where
+        T: Send + Sync + 'static,
+    {
+        let entry = self.resources.get(&TypeId::of::<T>())?;
+        Some((*entry.cell::<T>().as_ptr()).get())
+    }
+
+    /// Get a mutable reference to a resource stored directly in this set, without counting the
+    /// borrow.
+    ///
+    /// Resources inserted through a shared reference are not found until the set is flushed.
+    ///
+    /// # Safety
+    /// The resource must not be borrowed at all for as long as the returned reference lives.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_mut_unchecked<T>(&self) -> Option<&mut T>
+    where
+        T: Send + 'static,
+    {
+        let entry = self.resources.get(&TypeId::of::<T>())?;
+        entry.set_modified();
+        Some((*entry.cell::<T>().as_ptr()).get_mut())
+    }
+
+    /// Does not fall back to the parent set, since a shared parent cannot be borrowed mutably
+    /// without runtime checks, use `borrow_mut` instead.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted into this set.
     pub fn get_mut<T>(&mut self) -> &mut T
     where
         T: Send + 'static,
     {
-        if let Some(r) = self.resources.get_mut::<Resource<T>>() {
-            r.get_mut().get_mut()
+        self.flush_lazy();
+        if let Some(entry) = self.resources.get_mut(&TypeId::of::<T>()) {
+            entry.set_modified();
+            entry
+                .value
+                .downcast_mut::<Resource<T>>()
+                .unwrap()
+                .get_mut()
+                .get_mut()
+        } else {
+            panic!("no such resource {:?}", type_name::<T>());
+        }
+    }
+
+    /// Mutably borrow several resources stored directly in this set at once, identified by the
+    /// `TypeId` and type name of each resource type.
+    ///
+    /// Each returned value must be converted with `downcast_resource_mut` using the matching type.
+    ///
+    /// # Panics
+    /// Panics if any two of the given ids are the same, or if any of the resources has not been
+    /// inserted into this set.
+    pub(crate) fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        ids: [(TypeId, &'static str); N],
+    ) -> [&mut (dyn Any + Send + Sync); N] {
+        self.flush_lazy();
+        let entries = self
+            .resources
+            .get_disjoint_mut(ids.each_ref().map(|(id, _)| id));
+        let mut names = ids.into_iter().map(|(_, name)| name);
+        entries.map(|entry| {
+            let name = names.next().unwrap();
+            if let Some(entry) = entry {
+                entry.set_modified();
+                &mut *entry.value
+            } else {
+                panic!("no such resource {:?}", name);
+            }
+        })
+    }
+
+    /// Turn modification tracking on or off for the given resource in this set.
+    ///
+    /// While tracking is on, every mutable borrow of the resource sets its modified flag, which
+    /// stays set until it is cleared with `ResourceSet::clear_modified` or
+    /// `ResourceSet::clear_all_modified`.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted into this set.
+    pub fn set_track_modified<T>(&mut self, track: bool)
+    where
+        T: Send + 'static,
+    {
+        self.expect_local_entry::<T>().track_modified = track;
+    }
+
+    /// Returns true if the resource has been modified since its modified flag was last cleared.
+    ///
+    /// Always false for a resource which is not present, or which has never had tracking turned
+    /// on or been explicitly marked.
+    pub fn is_modified<T>(&self) -> bool
+    where
+        T: Send + 'static,
+    {
+        self.entry::<T>()
+            .is_some_and(|entry| entry.modified.load(Ordering::Relaxed))
+    }
+
+    /// Explicitly set the modified flag of a resource, whether or not tracking is turned on.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted.
+    pub fn mark_modified<T>(&self)
+    where
+        T: Send + 'static,
+    {
+        if let Some(entry) = self.entry::<T>() {
+            entry.modified.store(true, Ordering::Relaxed);
+        } else {
+            panic!("no such resource {:?}", type_name::<T>());
+        }
+    }
+
+    pub fn clear_modified<T>(&self)
+    where
+        T: Send + 'static,
+    {
+        if let Some(entry) = self.entry::<T>() {
+            entry.modified.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Clear the modified flag of every resource stored directly in this set.
+    pub fn clear_all_modified(&mut self) {
+        self.flush_lazy();
+        for entry in self.resources.values_mut() {
+            *entry.modified.get_mut() = false;
+        }
+    }
+
+    fn entry<T: Send + 'static>(&self) -> Option<&Entry> {
+        match self.resources.get(&TypeId::of::<T>()) {
+            Some(entry) => Some(entry),
+            None => match self.lazy_entry(TypeId::of::<T>()) {
+                Some(entry) => Some(entry),
+                None => self.parent.as_ref()?.entry::<T>(),
+            },
+        }
+    }
+
+    fn lazy_entry(&self, id: TypeId) -> Option<&Entry> {
+        let lazy = self.lazy.lock().unwrap();
+        // Safe because boxed entries in `lazy` are only ever moved or dropped through `&mut self`.
+        lazy.get(&id)
+            .map(|entry| unsafe { &*(&**entry as *const Entry) })
+    }
+
+    fn entry_or_default<T: Default + Send + 'static>(&self) -> &Entry {
+        if let Some(entry) = self.entry::<T>() {
+            return entry;
+        }
+
+        let mut lazy = self.lazy.lock().unwrap();
+        let entry = lazy
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Entry::new(T::default())));
+        // Safe for the same reason as in `ResourceSet::lazy_entry`.
+        unsafe { &*(&**entry as *const Entry) }
+    }
+
+    /// Insert a resource through a shared reference if it is not present in this set or any
+    /// parent, to be moved into this set by the next method which takes `&mut self`.
+    pub(crate) fn insert_shared<T: Send + 'static>(&self, f: impl FnOnce() -> T) {
+        if self.entry::<T>().is_none() {
+            self.lazy
+                .lock()
+                .unwrap()
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Box::new(Entry::new(f())));
+        }
+    }
+
+    pub(crate) fn flush_lazy(&mut self) {
+        for (id, entry) in self.lazy.get_mut().unwrap().drain() {
+            self.resources.insert(id, *entry);
+        }
+    }
+
+    fn expect_local_entry<T: Send + 'static>(&mut self) -> &mut Entry {
+        self.flush_lazy();
+        if let Some(entry) = self.resources.get_mut(&TypeId::of::<T>()) {
+            entry
         } else {
             panic!("no such resource {:?}", type_name::<T>());
         }
     }
 
     /// Fetch the given `FetchResources`.
+    ///
+    /// # Panics
+    /// Panics if any of the resources cannot be borrowed, and in debug builds, if `F` has an
+    /// internal resource conflict.
     pub fn fetch<'a, F>(&'a self) -> F
     where
         F: FetchResources<'a, Self>,
     {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
         F::fetch(self)
     }
+
+    /// Like `ResourceSet::fetch`, but returns an error if any of the resources are missing or
+    /// cannot be borrowed, see `FetchResources::try_fetch`.
+    ///
+    /// # Panics
+    /// Panics in debug builds if `F` has an internal resource conflict.
+    pub fn try_fetch<'a, F>(&'a self) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, Self>,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
+        F::try_fetch(self)
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
@@ -140,13 +551,17 @@ where
     fn fetch(set: &'a ResourceSet) -> Self {
         Read(set.borrow())
     }
+
+    fn try_fetch(set: &'a ResourceSet) -> Result<Self, FetchError> {
+        Ok(Read(set.try_borrow()?))
+    }
 }
 
 impl<'a, T> Deref for Read<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        &*self.0
+        &self.0
     }
 }
 
@@ -172,20 +587,62 @@ where
     fn fetch(set: &'a ResourceSet) -> Self {
         Write(set.borrow_mut())
     }
+
+    fn try_fetch(set: &'a ResourceSet) -> Result<Self, FetchError> {
+        Ok(Write(set.try_borrow_mut()?))
+    }
 }
 
 impl<'a, T> Deref for Write<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &T {
-        &*self.0
+        &self.0
     }
 }
 
 impl<'a, T> DerefMut for Write<'a, T> {
     fn deref_mut(&mut self) -> &mut T {
-        &mut *self.0
+        &mut self.0
     }
 }
 
 type Resource<T> = AtomicRefCell<MakeSync<T>>;
+
+struct Entry {
+    value: Box<dyn Any + Send + Sync>,
+    track_modified: bool,
+    modified: AtomicBool,
+}
+
+impl Entry {
+    fn new<T: Send + 'static>(r: T) -> Self {
+        Entry {
+            value: Box::new(AtomicRefCell::new(MakeSync::new(r))),
+            track_modified: false,
+            modified: AtomicBool::new(false),
+        }
+    }
+
+    fn cell<T: Send + 'static>(&self) -> &Resource<T> {
+        self.value.downcast_ref().unwrap()
+    }
+
+    fn set_modified(&self) {
+        if self.track_modified {
+            self.modified.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Convert one of the values returned from `ResourceSet::get_disjoint_mut` into the resource type.
+pub(crate) fn downcast_resource_mut<T: Send + 'static>(r: &mut (dyn Any + Send + Sync)) -> &mut T {
+    r.downcast_mut::<Resource<T>>().unwrap().get_mut().get_mut()
+}
+
+fn into_inner<T: Send + 'static>(r: Box<dyn Any + Send + Sync>) -> T {
+    r.downcast::<Resource<T>>()
+        .unwrap()
+        .into_inner()
+        .into_inner()
+}
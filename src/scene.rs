@@ -0,0 +1,150 @@
+use std::ops;
+
+use crate::{
+    any_components::AnyCloneComponentSet, entity::Entity, world::World, world_common::Component,
+};
+
+/// Identifies an entity within a `Scene`, independent of any `World`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SceneEntity(usize);
+
+impl SceneEntity {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+// Clones a resource of the scene into a world.
+type InsertResource = Box<dyn Fn(&mut World) + Send + Sync>;
+
+/// A declarative description of a set of entities and resources, such as level data, which can be
+/// instantiated into any number of worlds.
+///
+/// Each entity is described by an `AnyCloneComponentSet`, and each resource is cloned on
+/// instantiation, so a scene can be instantiated many times.
+#[derive(Default)]
+pub struct Scene {
+    registrations: Vec<fn(&mut World)>,
+    resources: Vec<InsertResource>,
+    entities: Vec<AnyCloneComponentSet>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make sure that the given component is inserted into any world this scene is instantiated
+    /// into, with the default configuration if it is not inserted already.
+    ///
+    /// Every component type used by the entities of this scene must either be registered here or
+    /// be inserted into the world before instantiating.
+    pub fn register_component<C>(&mut self)
+    where
+        C: Component + 'static,
+        C::Storage: Default + Send,
+    {
+        fn register<C>(world: &mut World)
+        where
+            C: Component + 'static,
+            C::Storage: Default + Send,
+        {
+            let _ = world.insert_component::<C>();
+        }
+
+        self.registrations.push(register::<C>);
+    }
+
+    /// Add a resource which is inserted into the world on instantiation, replacing any resource of
+    /// the same type.
+    pub fn add_resource<R>(&mut self, r: R)
+    where
+        R: Clone + Send + Sync + 'static,
+    {
+        self.resources.push(Box::new(move |world: &mut World| {
+            world.insert_resource(r.clone());
+        }));
+    }
+
+    pub fn add_entity(&mut self, components: AnyCloneComponentSet) -> SceneEntity {
+        self.entities.push(components);
+        SceneEntity(self.entities.len() - 1)
+    }
+
+    pub fn entity(&self, e: SceneEntity) -> &AnyCloneComponentSet {
+        &self.entities[e.0]
+    }
+
+    pub fn entity_mut(&mut self, e: SceneEntity) -> &mut AnyCloneComponentSet {
+        &mut self.entities[e.0]
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Create every entity and insert every resource in this scene into an existing world.
+    ///
+    /// Returns the mapping from each `SceneEntity` to the `Entity` created for it, which can be
+    /// used to fix up any components which refer to other entities of the scene.
+    ///
+    /// # Panics
+    /// Panics if any component type of the scene is neither registered with
+    /// `Scene::register_component` nor inserted into the world.
+    pub fn instantiate(&self, world: &mut World) -> SceneEntities {
+        for register in &self.registrations {
+            register(world);
+        }
+        for insert in &self.resources {
+            insert(world);
+        }
+
+        SceneEntities(
+            self.entities
+                .iter()
+                .map(|components| {
+                    let e = world.create_entity();
+                    components.insert_into_world(world, e).unwrap();
+                    e
+                })
+                .collect(),
+        )
+    }
+
+    /// Instantiate this scene into a new, otherwise empty `World`.
+    pub fn to_world(&self) -> (World, SceneEntities) {
+        let mut world = World::new();
+        let entities = self.instantiate(&mut world);
+        (world, entities)
+    }
+}
+
+/// Returned from `Scene::instantiate`, maps each `SceneEntity` to the `Entity` created for it.
+#[derive(Debug, Clone, Default)]
+pub struct SceneEntities(Vec<Entity>);
+
+impl SceneEntities {
+    pub fn get(&self, e: SceneEntity) -> Option<Entity> {
+        self.0.get(e.0).copied()
+    }
+
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (SceneEntity, Entity)> + '_ {
+        self.0.iter().enumerate().map(|(i, &e)| (SceneEntity(i), e))
+    }
+}
+
+impl ops::Index<SceneEntity> for SceneEntities {
+    type Output = Entity;
+
+    fn index(&self, e: SceneEntity) -> &Entity {
+        &self.0[e.0]
+    }
+}
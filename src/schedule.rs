@@ -0,0 +1,263 @@
+use std::mem;
+
+use crate::{
+    resources::{ResourceConflict, Resources},
+    system::{run_parallel, System},
+    world::World,
+    world_common::WorldResources,
+};
+
+/// A boxed system which runs against a `World`, for use as the system type of a `Schedule`.
+pub type BoxedWorldSystem<P, E> =
+    Box<dyn for<'a> System<&'a World, Resources = WorldResources, Pool = P, Error = E> + Send>;
+
+enum Item<S> {
+    System {
+        label: Option<&'static str>,
+        system: S,
+    },
+    Barrier,
+}
+
+/// An ordered list of systems of the same type which can be changed at runtime, and which is run
+/// as parallel as possible without changing the order of any conflicting systems.
+///
+/// This is a single type no matter how many systems it holds, so unlike nested `Par` and `Seq`
+/// combinators it is easy to store in a struct and to extend while the program runs.  With boxed
+/// systems, such as `BoxedWorldSystem`, it can hold any mix of systems.
+///
+/// Systems are planned into stages the same way as `parallelize_with_barriers`: every stage runs in
+/// parallel and the stages run in sequence, stopping at the first stage with an error.  The plan
+/// is recomputed on the next run after every change.
+///
+/// Systems may be given a label, which is used to find them again later.
+pub struct Schedule<S> {
+    items: Vec<Item<S>>,
+    // Indexes into `items` of the systems in each stage, or `None` if the schedule has changed
+    // since the stages were last planned.
+    stages: Option<Vec<Vec<usize>>>,
+}
+
+impl<S> Default for Schedule<S> {
+    fn default() -> Self {
+        Schedule {
+            items: Vec::new(),
+            stages: None,
+        }
+    }
+}
+
+impl<S> Schedule<S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a system to the end of the schedule.
+    pub fn add(&mut self, system: S) -> &mut Self {
+        self.push(Item::System {
+            label: None,
+            system,
+        })
+    }
+
+    /// Add a system with the given label to the end of the schedule.
+    ///
+    /// # Panics
+    /// Panics if a system with the same label is already in the schedule.
+    pub fn add_labeled(&mut self, label: &'static str, system: S) -> &mut Self {
+        assert!(
+            !self.contains(label),
+            "system {:?} is already in the schedule",
+            label
+        );
+        self.push(Item::System {
+            label: Some(label),
+            system,
+        })
+    }
+
+    /// Add a barrier to the end of the schedule, see `ScheduleItem::Barrier`.
+    pub fn add_barrier(&mut self) -> &mut Self {
+        self.push(Item::Barrier)
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.position(label).is_some()
+    }
+
+    pub fn get(&self, label: &str) -> Option<&S> {
+        match &self.items[self.position(label)?] {
+            Item::System { system, .. } => Some(system),
+            Item::Barrier => unreachable!(),
+        }
+    }
+
+    pub fn get_mut(&mut self, label: &str) -> Option<&mut S> {
+        let i = self.position(label)?;
+        match &mut self.items[i] {
+            Item::System { system, .. } => Some(system),
+            Item::Barrier => unreachable!(),
+        }
+    }
+
+    /// Replace the system with the given label, keeping its position and label, and return the
+    /// previous system.
+    pub fn replace(&mut self, label: &str, system: S) -> Option<S> {
+        let prev = mem::replace(self.get_mut(label)?, system);
+        self.stages = None;
+        Some(prev)
+    }
+
+    /// Remove the system with the given label from the schedule.
+    pub fn remove(&mut self, label: &str) -> Option<S> {
+        let i = self.position(label)?;
+        self.stages = None;
+        match self.items.remove(i) {
+            Item::System { system, .. } => Some(system),
+            Item::Barrier => unreachable!(),
+        }
+    }
+
+    /// The number of systems in the schedule, not counting barriers.
+    pub fn len(&self) -> usize {
+        self.items
+            .iter()
+            .filter(|item| matches!(item, Item::System { .. }))
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The labels of the systems in every stage, with `None` for unlabeled systems.
+    pub fn stages<A>(&mut self) -> Vec<Vec<Option<&'static str>>>
+    where
+        S: System<A>,
+    {
+        self.plan::<A>();
+        let items = &self.items;
+        self.stages
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|stage| {
+                stage
+                    .iter()
+                    .map(|&i| match &items[i] {
+                        Item::System { label, .. } => *label,
+                        Item::Barrier => unreachable!(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn push(&mut self, item: Item<S>) -> &mut Self {
+        self.items.push(item);
+        self.stages = None;
+        self
+    }
+
+    fn position(&self, label: &str) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| matches!(item, Item::System { label: Some(l), .. } if *l == label))
+    }
+
+    fn plan<A>(&mut self)
+    where
+        S: System<A>,
+    {
+        if self.stages.is_some() {
+            return;
+        }
+
+        let mut stages = Vec::new();
+        let mut stage = Vec::new();
+        let mut stage_resources = S::Resources::default();
+
+        for (i, item) in self.items.iter().enumerate() {
+            let system = match item {
+                Item::System { system, .. } => system,
+                Item::Barrier => {
+                    if !stage.is_empty() {
+                        stages.push(mem::take(&mut stage));
+                        stage_resources = S::Resources::default();
+                    }
+                    continue;
+                }
+            };
+
+            // Like `parallelize_with_barriers`, a system with an internal conflict is assumed to
+            // conflict with everything, and the conflict is reported by `check_resources`.
+            if let Ok(resources) = system.check_resources() {
+                if stage_resources.conflicts_with(&resources) {
+                    stages.push(mem::take(&mut stage));
+                    stage_resources = S::Resources::default();
+                }
+                stage_resources.union(&resources);
+                stage.push(i);
+            } else {
+                if !stage.is_empty() {
+                    stages.push(mem::take(&mut stage));
+                    stage_resources = S::Resources::default();
+                }
+                stages.push(vec![i]);
+            }
+        }
+
+        if !stage.is_empty() {
+            stages.push(stage);
+        }
+        self.stages = Some(stages);
+    }
+}
+
+impl<A, S> System<A> for Schedule<S>
+where
+    A: Copy + Send,
+    S: System<A> + Send,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    type Resources = S::Resources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<Self::Resources, ResourceConflict> {
+        let mut r = S::Resources::default();
+        for item in &self.items {
+            if let Item::System { system, .. } = item {
+                r.union(&system.check_resources()?);
+            }
+        }
+        Ok(r)
+    }
+
+    fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
+        self.plan::<A>();
+        let stages = self.stages.as_ref().unwrap();
+
+        for stage in stages {
+            let mut stage = stage.iter().copied().peekable();
+            let systems = self
+                .items
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(i, item)| match item {
+                    Item::System { system, .. } if stage.next_if_eq(&i).is_some() => Some(system),
+                    _ => None,
+                })
+                .collect();
+            run_parallel(systems, pool, args)?;
+        }
+        Ok(())
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.items
+            .iter()
+            .any(|item| matches!(item, Item::System { system, .. } if system.is_pinned()))
+    }
+}
@@ -0,0 +1,387 @@
+use hibitset::BitSet;
+
+use crate::{entity::Entity, join::Index};
+
+/// Define a struct component along with a structure-of-arrays storage for it, which keeps every
+/// field in its own contiguous column.
+///
+/// ```text
+/// soa_component! {
+///     #[derive(Clone, Debug)]
+///     pub struct Body {
+///         pub position: [f32; 3],
+///         pub velocity: [f32; 3],
+///     }
+///     pub mod body_soa;
+/// }
+/// ```
+///
+/// Along with the struct itself, this defines a module with the given name containing:
+///
+/// * `Storage`, which holds a value of the struct for any number of indexes, like a
+///   `MaskedStorage`, but stores each field in its own `Vec`.
+/// * `Ref<'a>` and `Mut<'a>`, which have a reference to every field of a single value, and are
+///   returned from `Storage::get`, `Storage::get_mut`, and from joining `&Storage` or
+///   `&mut Storage`.
+/// * `Columns<'a>` and `ColumnsMut<'a>`, which have a slice of every field of every value, in the
+///   order of `Storage::indexes`, and are returned from `Storage::columns` and
+///   `Storage::columns_mut`.
+///
+/// The module glob imports its parent, so field types are named the same way as in the struct.
+/// The struct must have at least one field and may not be generic.
+///
+/// A `Storage` is not a component storage of the `World`, it is inserted as a resource with
+/// `World::insert_soa_storage`, which removes the values of deleted entities and moves values in
+/// `World::compact`.  A `Storage` inserted any other way must be told about deleted entities with
+/// `Storage::remove_entities`.
+#[macro_export]
+macro_rules! soa_component {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident : $fty:ty),+ $(,)?
+        }
+        $mvis:vis mod $module:ident;
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $fty,)+
+        }
+
+        #[doc = concat!("Structure-of-arrays storage for `", stringify!($name), "`.")]
+        $mvis mod $module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #[derive(Default)]
+            struct Vecs {
+                $($field: ::std::vec::Vec<$fty>,)+
+            }
+
+            struct Ptrs {
+                $($field: $crate::soa::SoaColumnPtr<$fty>,)+
+            }
+
+            #[doc = concat!("Stores every field of `", stringify!($name), "` in its own column.")]
+            #[derive(Default)]
+            pub struct Storage {
+                index: $crate::soa::SoaIndex,
+                columns: Vecs,
+            }
+
+            #[doc = concat!("A reference to every field of a single `", stringify!($name), "`.")]
+            pub struct Ref<'a> {
+                $(pub $field: &'a $fty,)+
+            }
+
+            #[doc = concat!(
+                "A mutable reference to every field of a single `", stringify!($name), "`."
+            )]
+            pub struct Mut<'a> {
+                $(pub $field: &'a mut $fty,)+
+            }
+
+            /// Every column of a `Storage`, in the order of `Storage::indexes`.
+            pub struct Columns<'a> {
+                $(pub $field: &'a [$fty],)+
+            }
+
+            /// Every column of a `Storage` mutably, in the order of `Storage::indexes`.
+            pub struct ColumnsMut<'a> {
+                $(pub $field: &'a mut [$fty],)+
+            }
+
+            /// The `Join::Access` type of `&mut Storage`.
+            pub struct JoinMut<'a> {
+                index: &'a $crate::soa::SoaIndex,
+                columns: Ptrs,
+                checks: $crate::join::JoinChecks,
+                marker: ::std::marker::PhantomData<&'a mut Storage>,
+            }
+
+            impl Storage {
+                pub fn new() -> Self {
+                    Self::default()
+                }
+
+                pub fn mask(&self) -> &$crate::hibitset::BitSet {
+                    self.index.mask()
+                }
+
+                /// The index of every value, in the same order as the columns.
+                pub fn indexes(&self) -> &[$crate::join::Index] {
+                    self.index.indexes()
+                }
+
+                pub fn len(&self) -> usize {
+                    self.index.len()
+                }
+
+                pub fn is_empty(&self) -> bool {
+                    self.index.is_empty()
+                }
+
+                pub fn contains(&self, index: $crate::join::Index) -> bool {
+                    self.index.position(index).is_some()
+                }
+
+                /// Insert a value at the given index, returning the previous value if there was
+                /// one.
+                pub fn insert(
+                    &mut self,
+                    index: $crate::join::Index,
+                    value: $name,
+                ) -> ::std::option::Option<$name> {
+                    let $name { $($field,)+ } = value;
+                    match self.index.position(index) {
+                        ::std::option::Option::Some(i) => ::std::option::Option::Some($name {
+                            $($field: ::std::mem::replace(&mut self.columns.$field[i], $field),)+
+                        }),
+                        ::std::option::Option::None => {
+                            self.index.push(index);
+                            $(self.columns.$field.push($field);)+
+                            ::std::option::Option::None
+                        }
+                    }
+                }
+
+                /// Remove the value at the given index.
+                ///
+                /// The last value in the columns is moved into its place.
+                pub fn remove(
+                    &mut self,
+                    index: $crate::join::Index,
+                ) -> ::std::option::Option<$name> {
+                    let i = self.index.swap_remove(index)?;
+                    ::std::option::Option::Some($name {
+                        $($field: self.columns.$field.swap_remove(i),)+
+                    })
+                }
+
+                /// Remove the values of every given entity, such as the entities returned from
+                /// `World::merge`.
+                ///
+                /// Values are removed by index, so this must be called before the indexes of the
+                /// given entities can be re-used.
+                pub fn remove_entities(&mut self, entities: &[$crate::entity::Entity]) {
+                    for e in entities {
+                        self.remove(e.index());
+                    }
+                }
+
+                pub fn clear(&mut self) {
+                    self.index.clear();
+                    $(self.columns.$field.clear();)+
+                }
+
+                pub fn get(&self, index: $crate::join::Index) -> ::std::option::Option<Ref<'_>> {
+                    let i = self.index.position(index)?;
+                    ::std::option::Option::Some(Ref {
+                        $($field: &self.columns.$field[i],)+
+                    })
+                }
+
+                pub fn get_mut(
+                    &mut self,
+                    index: $crate::join::Index,
+                ) -> ::std::option::Option<Mut<'_>> {
+                    let i = self.index.position(index)?;
+                    ::std::option::Option::Some(Mut {
+                        $($field: &mut self.columns.$field[i],)+
+                    })
+                }
+
+                pub fn columns(&self) -> Columns<'_> {
+                    Columns {
+                        $($field: &self.columns.$field,)+
+                    }
+                }
+
+                pub fn columns_mut(&mut self) -> ColumnsMut<'_> {
+                    ColumnsMut {
+                        $($field: &mut self.columns.$field,)+
+                    }
+                }
+            }
+
+            impl $crate::soa::SoaStorage for Storage {
+                fn remove_entities(&mut self, entities: &[$crate::entity::Entity]) {
+                    Storage::remove_entities(self, entities);
+                }
+
+                fn move_entities(
+                    &mut self,
+                    moved: &[($crate::entity::Entity, $crate::entity::Entity)],
+                ) {
+                    for &(old, new) in moved {
+                        if let ::std::option::Option::Some(value) = self.remove(old.index()) {
+                            self.insert(new.index(), value);
+                        }
+                    }
+                }
+            }
+
+            impl<'a> $crate::join::Join for &'a Storage {
+                type Item = Ref<'a>;
+                type Access = &'a Storage;
+                type Mask = &'a $crate::hibitset::BitSet;
+
+                fn open(self) -> (Self::Mask, Self::Access) {
+                    (self.index.mask(), self)
+                }
+
+                unsafe fn get(access: &Self::Access, index: $crate::join::Index) -> Ref<'a> {
+                    let storage: &'a Storage = *access;
+                    let i = storage.index.position_unchecked(index);
+                    Ref {
+                        $($field: storage.columns.$field.get_unchecked(i),)+
+                    }
+                }
+            }
+
+            impl<'a> $crate::join::Join for &'a mut Storage {
+                type Item = Mut<'a>;
+                type Access = JoinMut<'a>;
+                type Mask = &'a $crate::hibitset::BitSet;
+
+                fn open(self) -> (Self::Mask, Self::Access) {
+                    let access = JoinMut {
+                        index: &self.index,
+                        columns: Ptrs {
+                            $($field: $crate::soa::SoaColumnPtr::new(&mut self.columns.$field),)+
+                        },
+                        checks: $crate::join::JoinChecks::default(),
+                        marker: ::std::marker::PhantomData,
+                    };
+                    (self.index.mask(), access)
+                }
+
+                unsafe fn get(access: &Self::Access, index: $crate::join::Index) -> Mut<'a> {
+                    access
+                        .checks
+                        .check(|| access.index.mask().contains(index), index);
+                    let i = access.index.position_unchecked(index);
+                    Mut {
+                        $($field: access.columns.$field.get_mut(i),)+
+                    }
+                }
+
+                fn clear_checks(access: &mut Self::Access) {
+                    access.checks.clear();
+                }
+            }
+        }
+    };
+}
+
+/// Implemented by every storage generated by `soa_component!`, so that a storage inserted with
+/// `World::insert_soa_storage` is kept up to date with the entities of the `World`.
+pub trait SoaStorage: Default {
+    /// Remove the values of every given entity.
+    fn remove_entities(&mut self, entities: &[Entity]);
+
+    /// Move the value of every entity in each `(old, new)` pair to the index of the new entity,
+    /// see `World::compact`.
+    fn move_entities(&mut self, moved: &[(Entity, Entity)]);
+}
+
+/// The dense bookkeeping of a storage generated by `soa_component!`.
+///
+/// Tracks which indexes are present, and the position of each of them in the columns, which are
+/// kept densely packed by removing values with `Vec::swap_remove`.
+#[derive(Default, Debug)]
+pub struct SoaIndex {
+    mask: BitSet,
+    // The position in the columns of every index in `mask`, and unspecified for other indexes.
+    positions: Vec<u32>,
+    indexes: Vec<Index>,
+}
+
+impl SoaIndex {
+    pub fn mask(&self) -> &BitSet {
+        &self.mask
+    }
+
+    /// The index of every value, in column order.
+    pub fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+
+    pub fn len(&self) -> usize {
+        self.indexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indexes.is_empty()
+    }
+
+    /// The position in the columns of the given index, if it is present.
+    pub fn position(&self, index: Index) -> Option<usize> {
+        if self.mask.contains(index) {
+            Some(self.positions[index as usize] as usize)
+        } else {
+            None
+        }
+    }
+
+    /// # Safety
+    /// The index must be present.
+    pub unsafe fn position_unchecked(&self, index: Index) -> usize {
+        *self.positions.get_unchecked(index as usize) as usize
+    }
+
+    /// Add an index at the end of the columns, returning its position.
+    ///
+    /// # Panics
+    /// Panics if the index is already present.
+    pub fn push(&mut self, index: Index) -> usize {
+        assert!(!self.mask.add(index), "index {} is already present", index);
+        let position = self.indexes.len();
+        if self.positions.len() <= index as usize {
+            self.positions.resize(index as usize + 1, 0);
+        }
+        self.positions[index as usize] = position as u32;
+        self.indexes.push(index);
+        position
+    }
+
+    /// Remove an index, returning the position which must be removed from every column with
+    /// `Vec::swap_remove`.
+    pub fn swap_remove(&mut self, index: Index) -> Option<usize> {
+        let position = self.position(index)?;
+        self.mask.remove(index);
+        self.indexes.swap_remove(position);
+        if let Some(&moved) = self.indexes.get(position) {
+            self.positions[moved as usize] = position as u32;
+        }
+        Some(position)
+    }
+
+    pub fn clear(&mut self) {
+        self.mask.clear();
+        self.indexes.clear();
+    }
+}
+
+/// A pointer to the start of a column, used by `soa_component!` to hand out mutable references to
+/// different values of the same column from a shared `Join::Access`.
+pub struct SoaColumnPtr<T>(*mut T);
+
+// Safe for the same reason as sending a `&mut [T]`, every value is only ever accessed through one
+// reference at a time.
+unsafe impl<T: Send> Send for SoaColumnPtr<T> {}
+unsafe impl<T: Send> Sync for SoaColumnPtr<T> {}
+
+impl<T> SoaColumnPtr<T> {
+    pub fn new(column: &mut [T]) -> Self {
+        SoaColumnPtr(column.as_mut_ptr())
+    }
+
+    /// # Safety
+    /// The position must be in bounds of the column this was created from, which must still be
+    /// mutably borrowed for `'a`, and no other reference to the same value may be alive.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut<'a>(&self, position: usize) -> &'a mut T {
+        &mut *self.0.add(position)
+    }
+}
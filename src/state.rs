@@ -0,0 +1,184 @@
+use std::mem;
+
+use crate::{
+    resources::{ResourceConflict, Resources},
+    system::System,
+    world::World,
+    world_common::{WorldResourceId, WorldResources},
+};
+
+/// A resource holding the current value of a simple state machine, such as the current game mode.
+///
+/// Changing the state is deferred, `State::set` only queues the next state, which is applied by a
+/// `StateSystems` after it has finished running the systems for the current state.
+#[derive(Debug, Clone)]
+pub struct State<S> {
+    current: S,
+    next: Option<S>,
+}
+
+impl<S> State<S> {
+    pub fn new(initial: S) -> Self {
+        State {
+            current: initial,
+            next: None,
+        }
+    }
+
+    pub fn current(&self) -> &S {
+        &self.current
+    }
+
+    /// The state queued by the last call to `State::set`, if it has not been applied yet.
+    pub fn pending(&self) -> Option<&S> {
+        self.next.as_ref()
+    }
+
+    /// Queue a transition to the given state, replacing any previously queued transition.
+    pub fn set(&mut self, next: S) {
+        self.next = Some(next);
+    }
+
+    /// Apply any queued transition immediately, returning the previous state.
+    ///
+    /// This does not run any `OnExit` or `OnEnter` systems, it is normally called by
+    /// `StateSystems`.
+    pub fn apply(&mut self) -> Option<S> {
+        let next = self.next.take()?;
+        Some(mem::replace(&mut self.current, next))
+    }
+}
+
+/// The group that a system in a `StateSystems` belongs to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum StateSet<S> {
+    /// Run once when the state is entered, including when the initial state is first seen.
+    OnEnter(S),
+    /// Run on every run of the `StateSystems` while in the given state.
+    OnUpdate(S),
+    /// Run once when the state is left.
+    OnExit(S),
+}
+
+/// A `System` which runs groups of systems depending on the current value of the `State<S>`
+/// resource.
+///
+/// Every run first runs the `OnEnter` systems for the current state if it has not been entered
+/// yet, then the `OnUpdate` systems for the current state. Afterwards, any transitions queued with
+/// `State::set` are applied, running the `OnExit` systems of the old state and the `OnEnter`
+/// systems of the new state, until no transition remains queued.
+///
+/// Systems within each group are run in sequence, in the order they were added. The `State<S>`
+/// resource is not borrowed while the inner systems run, so they may freely read it or queue a
+/// transition.
+pub struct StateSystems<S, Sys> {
+    systems: Vec<(StateSet<S>, Sys)>,
+    entered: Option<S>,
+}
+
+impl<S, Sys> Default for StateSystems<S, Sys> {
+    fn default() -> Self {
+        StateSystems {
+            systems: Vec::new(),
+            entered: None,
+        }
+    }
+}
+
+impl<S, Sys> StateSystems<S, Sys> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, set: StateSet<S>, system: Sys) {
+        self.systems.push((set, system));
+    }
+
+    pub fn with(mut self, set: StateSet<S>, system: Sys) -> Self {
+        self.add(set, system);
+        self
+    }
+}
+
+impl<S, Sys> StateSystems<S, Sys> {
+    fn run_set<'a>(
+        &mut self,
+        pool: &Sys::Pool,
+        world: &'a World,
+        matches: impl Fn(&StateSet<S>) -> bool,
+    ) -> Result<(), Sys::Error>
+    where
+        Sys: System<&'a World>,
+    {
+        for (set, system) in &mut self.systems {
+            if matches(set) {
+                system.run(pool, world)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, S, Sys> System<&'a World> for StateSystems<S, Sys>
+where
+    S: Clone + PartialEq + Send + Sync + 'static,
+    Sys: System<&'a World, Resources = WorldResources>,
+{
+    type Resources = WorldResources;
+    type Pool = Sys::Pool;
+    type Error = Sys::Error;
+
+    fn check_resources(&self) -> Result<WorldResources, ResourceConflict> {
+        let mut resources = WorldResources::new().write(WorldResourceId::resource::<State<S>>());
+        for (_, system) in &self.systems {
+            resources.union(&system.check_resources()?);
+        }
+        Ok(resources)
+    }
+
+    fn run(&mut self, pool: &Self::Pool, world: &'a World) -> Result<(), Self::Error> {
+        let current = world.read_resource::<State<S>>().current().clone();
+        if self.entered.as_ref() != Some(&current) {
+            self.entered = Some(current.clone());
+            self.run_set(
+                pool,
+                world,
+                |set| matches!(set, StateSet::OnEnter(s) if *s == current),
+            )?;
+        }
+
+        self.run_set(
+            pool,
+            world,
+            |set| matches!(set, StateSet::OnUpdate(s) if *s == current),
+        )?;
+
+        loop {
+            let (prev, next) = {
+                let mut state = world.write_resource::<State<S>>();
+                match state.apply() {
+                    Some(prev) => (prev, state.current().clone()),
+                    None => break,
+                }
+            };
+
+            self.run_set(
+                pool,
+                world,
+                |set| matches!(set, StateSet::OnExit(s) if *s == prev),
+            )?;
+            self.entered = Some(next.clone());
+            self.run_set(
+                pool,
+                world,
+                |set| matches!(set, StateSet::OnEnter(s) if *s == next),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.systems.iter().any(|(_, system)| system.is_pinned())
+    }
+}
@@ -0,0 +1,29 @@
+use rustc_hash::FxHashMap;
+
+use crate::world_common::ComponentId;
+
+/// The number of times a single component type was inserted into or removed from entities.
+///
+/// Only changes to the *presence* of a component are counted, the same as for `ComponentEvent`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ComponentStats {
+    pub inserted: u64,
+    pub removed: u64,
+}
+
+/// Counts of the structural operations performed on a `World` during a single frame, returned from
+/// `World::frame_stats`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FrameStats {
+    pub entities_created: u64,
+    pub entities_killed: u64,
+    /// Component types which were not inserted or removed during the frame are not present.
+    pub components: FxHashMap<ComponentId, ComponentStats>,
+}
+
+impl FrameStats {
+    /// The counts for the given component type, which are zero if it is not present.
+    pub fn component(&self, id: ComponentId) -> ComponentStats {
+        self.components.get(&id).copied().unwrap_or_default()
+    }
+}
@@ -1,12 +1,17 @@
+#[cfg(feature = "debug-assertions")]
+use std::{any::type_name, thread};
 use std::{
     cell::UnsafeCell,
+    marker::PhantomData,
     mem::{self, MaybeUninit},
-    ptr,
+    ptr, slice,
 };
 
+#[cfg(feature = "debug-assertions")]
+use hibitset::{BitSet, BitSetLike};
 use rustc_hash::FxHashMap;
 
-use crate::join::Index;
+use crate::{join::Index, memory::MemoryUsage};
 
 /// A trait for storing components in memory based on low valued indexes.
 ///
@@ -19,33 +24,98 @@ use crate::join::Index;
 pub trait RawStorage {
     type Item;
 
+    /// Whether this storage tracks modified components, must be true exactly when the storage also
+    /// implements `TrackedStorage`.
+    ///
+    /// This cannot be derived from the `TrackedStorage` impl, but a `TrackedStorage` which leaves
+    /// it false fails to compile once it is used through `MaskedStorage`.
+    const TRACKED: bool = false;
+
+    /// Which of the built-in storage layouts this is, used by `World::storage_report` to tell
+    /// whether a component already uses the recommended storage.
+    const KIND: StorageKind = StorageKind::Other;
+
     /// Return a reference to the component at the given index.
     ///
+    /// # Safety
     /// You *must* only call `get` with index values that are non-empty (have been previously had
     /// components inserted with `insert`).
     unsafe fn get(&self, index: Index) -> &Self::Item;
 
     /// Return a mutable reference to the component at the given index.
     ///
+    /// # Safety
     /// You *must* only call `get_mut` with index values that are non-empty (have been previously
     /// had components inserted with `insert`).
     ///
     /// Returns a *mutable* reference to the previously inserted component.  You must follow Rust's
     /// aliasing rules here, so you must not call this method if there is any other live reference
     /// to the same component.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn get_mut(&self, index: Index) -> &mut Self::Item;
 
     /// Insert a new component value in the given index.
     ///
+    /// # Safety
     /// You must only call `insert` on indexes that are empty.  All indexes start empty, but become
     /// non-empty once `insert` is called on them.
     unsafe fn insert(&mut self, index: Index, value: Self::Item);
 
     /// Remove a component previously inserted in the given index.
     ///
+    /// # Safety
     /// You must only call `remove` on a non-empty index (after you have inserted a value with
     /// `insert`).  After calling `remove` the index becomes empty.
     unsafe fn remove(&mut self, index: Index) -> Self::Item;
+
+    /// Insert many new component values at once.
+    ///
+    /// The default implementation calls `insert` for each value.
+    ///
+    /// # Safety
+    /// The same rules as `insert` apply to every index, and additionally the indexes must be
+    /// strictly ascending, which lets storages append without searching or re-checking capacity
+    /// for every value.
+    unsafe fn insert_sorted(&mut self, values: impl Iterator<Item = (Index, Self::Item)>)
+    where
+        Self: Sized,
+    {
+        for (index, value) in values {
+            self.insert(index, value);
+        }
+    }
+
+    /// Report the memory used by this storage.
+    ///
+    /// The default implementation reports no memory usage at all.
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::default()
+    }
+
+    /// Hint that components may soon be inserted at any index below `len`, so the storage can
+    /// allocate ahead of time.
+    ///
+    /// The default implementation does nothing.
+    fn reserve(&mut self, _len: Index) {}
+
+    /// Release memory that is no longer needed, given that every index at or above `len` is empty.
+    ///
+    /// This is a maintenance operation that is free to be slow, so storages may also reorganize
+    /// their memory here, such as restoring the order of a dense slice.
+    ///
+    /// The default implementation does nothing.
+    fn shrink(&mut self, _len: Index) {}
+}
+
+/// The built-in storage layouts.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StorageKind {
+    Vec,
+    DenseVec,
+    HashMap,
+    Stable,
+    /// Any storage not provided by this crate.
+    Other,
 }
 
 /// Trait for storages that hold their populated values densely in a contiguous slice, enabling
@@ -53,22 +123,67 @@ pub trait RawStorage {
 pub trait DenseStorage: RawStorage {
     fn as_slice(&self) -> &[Self::Item];
     fn as_mut_slice(&mut self) -> &mut [Self::Item];
+
+    /// The index of every value in the slice returned from `DenseStorage::as_slice`, in the same
+    /// order.
+    fn indexes(&self) -> &[Index];
 }
 
-pub struct VecStorage<T>(Vec<UnsafeCell<MaybeUninit<T>>>);
+/// Trait for dense storages which can hand out their indexes and their mutable slice at the same
+/// time, which is what allows `MaskedStorage::par_chunks_mut`.
+pub trait SplitStorage: DenseStorage {
+    /// Returns the same slices as `DenseStorage::indexes` and `DenseStorage::as_mut_slice` at once.
+    fn indexes_and_mut_slice(&mut self) -> (&[Index], &mut [Self::Item]);
+}
+
+/// Trait for dense storages whose slice can be put in any order, which is what allows a component
+/// group (see `World::insert_component_group`) to line up the slices of several components.
+pub trait ArrangeStorage: DenseStorage {
+    /// Reorder the dense slice so that it is sorted by the given key of each index.
+    ///
+    /// Does nothing if the slice is already sorted by the key.
+    fn arrange_by_key<K: Ord>(&mut self, key: impl FnMut(Index) -> K);
+}
+
+pub struct VecStorage<T>(Vec<UnsafeCell<MaybeUninit<T>>>, Occupancy<T>);
 
 unsafe impl<T: Send> Send for VecStorage<T> {}
 unsafe impl<T: Sync> Sync for VecStorage<T> {}
 
 impl<T> Default for VecStorage<T> {
     fn default() -> Self {
-        Self(Vec::new())
+        Self(Vec::new(), Occupancy::default())
+    }
+}
+
+impl<T> VecStorage<T> {
+    /// Returns the `len` components starting at index `start` as a slice.
+    ///
+    /// # Safety
+    /// Every index in the range *must* be non-empty.
+    pub unsafe fn slice(&self, start: Index, len: usize) -> &[T] {
+        debug_assert!(start as usize + len <= self.0.len());
+        slice::from_raw_parts(self.0.as_ptr().add(start as usize) as *const T, len)
+    }
+
+    /// Returns the `len` components starting at index `start` as a mutable slice.
+    ///
+    /// # Safety
+    /// Every index in the range *must* be non-empty, and the same aliasing rules as
+    /// `RawStorage::get_mut` apply to every index in the range.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn slice_mut(&self, start: Index, len: usize) -> &mut [T] {
+        debug_assert!(start as usize + len <= self.0.len());
+        let ptr = UnsafeCell::raw_get(self.0.as_ptr().add(start as usize));
+        slice::from_raw_parts_mut(ptr as *mut T, len)
     }
 }
 
 impl<T> RawStorage for VecStorage<T> {
     type Item = T;
 
+    const KIND: StorageKind = StorageKind::Vec;
+
     unsafe fn get(&self, index: Index) -> &T {
         &*(*self.0.get_unchecked(index as usize).get()).as_ptr()
     }
@@ -78,24 +193,59 @@ impl<T> RawStorage for VecStorage<T> {
     }
 
     unsafe fn insert(&mut self, index: Index, c: T) {
+        self.1.insert(index);
         let index = index as usize;
         if self.0.len() <= index {
             let delta = index + 1 - self.0.len();
             self.0.reserve(delta);
             self.0.set_len(index + 1);
         }
-        *self.0.get_unchecked_mut(index as usize) = UnsafeCell::new(MaybeUninit::new(c));
+        *self.0.get_unchecked_mut(index) = UnsafeCell::new(MaybeUninit::new(c));
     }
 
     unsafe fn remove(&mut self, index: Index) -> T {
+        self.1.remove(index);
         ptr::read((*self.0.get_unchecked(index as usize).get()).as_mut_ptr())
     }
+
+    unsafe fn insert_sorted(&mut self, values: impl Iterator<Item = (Index, T)>) {
+        let mut len = self.0.len();
+        for (index, c) in values {
+            self.1.insert(index);
+            let index = index as usize;
+            if index >= len {
+                // Indexes are ascending, so everything from here on is past the end.
+                self.0.reserve(index + 1 - len);
+                self.0.set_len(index + 1);
+                len = index + 1;
+            }
+            self.0
+                .as_mut_ptr()
+                .add(index)
+                .write(UnsafeCell::new(MaybeUninit::new(c)));
+        }
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::of_vec(&self.0)
+    }
+
+    fn reserve(&mut self, len: Index) {
+        self.0.reserve((len as usize).saturating_sub(self.0.len()));
+    }
+
+    fn shrink(&mut self, len: Index) {
+        // Every slot past `len` is empty, and empty slots are never dropped.
+        self.0.truncate(len as usize);
+        self.0.shrink_to_fit();
+    }
 }
 
 pub struct DenseVecStorage<T> {
     data: Vec<MaybeUninit<Index>>,
     values: Vec<UnsafeCell<T>>,
     indexes: Vec<Index>,
+    occupancy: Occupancy<T>,
 }
 
 unsafe impl<T: Send> Send for DenseVecStorage<T> {}
@@ -107,6 +257,7 @@ impl<T> Default for DenseVecStorage<T> {
             data: Vec::new(),
             values: Vec::new(),
             indexes: Vec::new(),
+            occupancy: Occupancy::default(),
         }
     }
 }
@@ -114,6 +265,8 @@ impl<T> Default for DenseVecStorage<T> {
 impl<T> RawStorage for DenseVecStorage<T> {
     type Item = T;
 
+    const KIND: StorageKind = StorageKind::DenseVec;
+
     unsafe fn get(&self, index: Index) -> &T {
         let dind = *self.data.get_unchecked(index as usize).as_ptr();
         &*self.values.get_unchecked(dind as usize).get()
@@ -125,6 +278,7 @@ impl<T> RawStorage for DenseVecStorage<T> {
     }
 
     unsafe fn insert(&mut self, index: Index, c: T) {
+        self.occupancy.insert(index);
         if self.data.len() <= index as usize {
             let delta = index as usize + 1 - self.data.len();
             self.data.reserve(delta);
@@ -142,6 +296,7 @@ impl<T> RawStorage for DenseVecStorage<T> {
     }
 
     unsafe fn remove(&mut self, index: Index) -> T {
+        self.occupancy.remove(index);
         let dind = *self.data.get_unchecked(index as usize).as_ptr();
         let last_index = *self.indexes.get_unchecked(self.indexes.len() - 1);
         self.data
@@ -151,6 +306,63 @@ impl<T> RawStorage for DenseVecStorage<T> {
         self.indexes.swap_remove(dind as usize);
         self.values.swap_remove(dind as usize).into_inner()
     }
+
+    unsafe fn insert_sorted(&mut self, values: impl Iterator<Item = (Index, T)>) {
+        let (lower, _) = values.size_hint();
+        self.indexes.reserve(lower);
+        self.values.reserve(lower);
+        for (index, c) in values {
+            self.occupancy.insert(index);
+            if self.data.len() <= index as usize {
+                let delta = index as usize + 1 - self.data.len();
+                self.data.reserve(delta);
+                self.data.set_len(index as usize + 1);
+            }
+            self.data
+                .get_unchecked_mut(index as usize)
+                .as_mut_ptr()
+                .write(self.values.len() as Index);
+            self.indexes.push(index);
+            self.values.push(UnsafeCell::new(c));
+        }
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::of_vec(&self.data)
+            + MemoryUsage::of_vec(&self.values)
+            + MemoryUsage::of_vec(&self.indexes)
+    }
+
+    fn reserve(&mut self, len: Index) {
+        self.data
+            .reserve((len as usize).saturating_sub(self.data.len()));
+    }
+
+    /// Also sorts the dense slice by index, so that iterating in index order walks memory in order.
+    fn shrink(&mut self, len: Index) {
+        self.data.truncate(len as usize);
+        self.data.shrink_to_fit();
+        self.sort_dense_by_key(|index| index);
+    }
+}
+
+impl<T> DenseVecStorage<T> {
+    // Sort the dense slice by the given key of each index, leaving it with no spare capacity.
+    fn sort_dense_by_key<K: Ord>(&mut self, mut key: impl FnMut(Index) -> K) {
+        let mut pairs = mem::take(&mut self.indexes)
+            .into_iter()
+            .zip(mem::take(&mut self.values))
+            .collect::<Vec<_>>();
+        pairs.sort_unstable_by_key(|&(index, _)| key(index));
+
+        self.indexes.reserve_exact(pairs.len());
+        self.values.reserve_exact(pairs.len());
+        for (dind, (index, value)) in pairs.into_iter().enumerate() {
+            self.data[index as usize] = MaybeUninit::new(dind as Index);
+            self.indexes.push(index);
+            self.values.push(value);
+        }
+    }
 }
 
 impl<T> DenseStorage for DenseVecStorage<T> {
@@ -161,22 +373,44 @@ impl<T> DenseStorage for DenseVecStorage<T> {
     fn as_mut_slice(&mut self) -> &mut [Self::Item] {
         unsafe { mem::transmute::<&mut [UnsafeCell<T>], &mut [T]>(&mut self.values) }
     }
+
+    fn indexes(&self) -> &[Index] {
+        &self.indexes
+    }
+}
+
+impl<T> SplitStorage for DenseVecStorage<T> {
+    fn indexes_and_mut_slice(&mut self) -> (&[Index], &mut [Self::Item]) {
+        (&self.indexes, unsafe {
+            mem::transmute::<&mut [UnsafeCell<T>], &mut [T]>(&mut self.values)
+        })
+    }
+}
+
+impl<T> ArrangeStorage for DenseVecStorage<T> {
+    fn arrange_by_key<K: Ord>(&mut self, mut key: impl FnMut(Index) -> K) {
+        if self.indexes.windows(2).any(|w| key(w[0]) > key(w[1])) {
+            self.sort_dense_by_key(key);
+        }
+    }
 }
 
-pub struct HashMapStorage<T>(FxHashMap<Index, UnsafeCell<T>>);
+pub struct HashMapStorage<T>(FxHashMap<Index, UnsafeCell<T>>, Occupancy<T>);
 
 unsafe impl<T: Send> Send for HashMapStorage<T> {}
 unsafe impl<T: Sync> Sync for HashMapStorage<T> {}
 
 impl<T> Default for HashMapStorage<T> {
     fn default() -> Self {
-        Self(FxHashMap::default())
+        Self(FxHashMap::default(), Occupancy::default())
     }
 }
 
 impl<T> RawStorage for HashMapStorage<T> {
     type Item = T;
 
+    const KIND: StorageKind = StorageKind::HashMap;
+
     unsafe fn get(&self, index: Index) -> &T {
         &*self.0.get(&index).unwrap().get()
     }
@@ -186,10 +420,189 @@ impl<T> RawStorage for HashMapStorage<T> {
     }
 
     unsafe fn insert(&mut self, index: Index, v: T) {
+        self.1.insert(index);
         self.0.insert(index, UnsafeCell::new(v));
     }
 
     unsafe fn remove(&mut self, index: Index) -> T {
+        self.1.remove(index);
         self.0.remove(&index).unwrap().into_inner()
     }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let entry_size = mem::size_of::<(Index, UnsafeCell<T>)>();
+        MemoryUsage::new(self.0.len() * entry_size, self.0.capacity() * entry_size)
+    }
+
+    fn shrink(&mut self, _len: Index) {
+        self.0.shrink_to_fit();
+    }
+}
+
+/// A storage which never moves a component in memory for as long as it is present, so raw pointers
+/// to components may be handed to external (e.g. C) libraries and stay valid across inserts and
+/// removes of *other* components.
+///
+/// Components are stored in fixed size chunks that are allocated on demand and never reallocated,
+/// and are only freed when the storage is dropped or shrunk past them.  Like `VecStorage`, the
+/// position of a component within a chunk is determined by its index, but only chunks which have
+/// had a component inserted are allocated, so memory is used in proportion to the number of
+/// chunks touched plus a pointer for every chunk below the highest index.
+///
+/// The address of a component only changes when it is removed, or when it is moved to a different
+/// index, such as by `World::compact`.
+pub struct StableStorage<T>(Vec<Option<StableChunk<T>>>, Occupancy<T>);
+
+type StableChunk<T> = Box<[UnsafeCell<MaybeUninit<T>>]>;
+
+const STABLE_CHUNK_LEN: usize = 64;
+
+unsafe impl<T: Send> Send for StableStorage<T> {}
+unsafe impl<T: Sync> Sync for StableStorage<T> {}
+
+impl<T> Default for StableStorage<T> {
+    fn default() -> Self {
+        Self(Vec::new(), Occupancy::default())
+    }
+}
+
+impl<T> StableStorage<T> {
+    unsafe fn slot(&self, index: Index) -> &UnsafeCell<MaybeUninit<T>> {
+        let index = index as usize;
+        let chunk = self.0.get_unchecked(index / STABLE_CHUNK_LEN);
+        debug_assert!(chunk.is_some());
+        chunk
+            .as_ref()
+            .unwrap_unchecked()
+            .get_unchecked(index % STABLE_CHUNK_LEN)
+    }
+}
+
+impl<T> RawStorage for StableStorage<T> {
+    type Item = T;
+
+    const KIND: StorageKind = StorageKind::Stable;
+
+    unsafe fn get(&self, index: Index) -> &T {
+        &*(*self.slot(index).get()).as_ptr()
+    }
+
+    unsafe fn get_mut(&self, index: Index) -> &mut T {
+        &mut *(*self.slot(index).get()).as_mut_ptr()
+    }
+
+    unsafe fn insert(&mut self, index: Index, c: T) {
+        self.1.insert(index);
+        let chunk = index as usize / STABLE_CHUNK_LEN;
+        if self.0.len() <= chunk {
+            self.0.resize_with(chunk + 1, || None);
+        }
+        let chunk = self.0.get_unchecked_mut(chunk).get_or_insert_with(|| {
+            (0..STABLE_CHUNK_LEN)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect()
+        });
+        *chunk.get_unchecked_mut(index as usize % STABLE_CHUNK_LEN) =
+            UnsafeCell::new(MaybeUninit::new(c));
+    }
+
+    unsafe fn remove(&mut self, index: Index) -> T {
+        self.1.remove(index);
+        ptr::read((*self.slot(index).get()).as_ptr())
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let chunk_size = STABLE_CHUNK_LEN * mem::size_of::<T>();
+        let chunks = self.0.iter().filter(|c| c.is_some()).count();
+        MemoryUsage::of_vec(&self.0) + MemoryUsage::new(chunks * chunk_size, chunks * chunk_size)
+    }
+
+    fn reserve(&mut self, len: Index) {
+        let chunks = (len as usize).div_ceil(STABLE_CHUNK_LEN);
+        self.0.reserve(chunks.saturating_sub(self.0.len()));
+    }
+
+    /// Only chunks which are entirely at or above `len` are freed, so no present component ever
+    /// moves.
+    fn shrink(&mut self, len: Index) {
+        self.0.truncate((len as usize).div_ceil(STABLE_CHUNK_LEN));
+        self.0.shrink_to_fit();
+    }
+}
+
+// Tracks which indexes of a built-in storage hold values, so that misusing the `RawStorage` API
+// panics with the component type name rather than silently causing undefined behavior.
+//
+// Without the `debug-assertions` feature this is zero sized and does nothing.
+#[cfg(feature = "debug-assertions")]
+struct Occupancy<T> {
+    indexes: BitSet,
+    marker: PhantomData<fn() -> T>,
+}
+
+#[cfg(feature = "debug-assertions")]
+impl<T> Default for Occupancy<T> {
+    fn default() -> Self {
+        Self {
+            indexes: BitSet::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "debug-assertions")]
+impl<T> Occupancy<T> {
+    fn insert(&mut self, index: Index) {
+        if self.indexes.add(index) {
+            panic!(
+                "`RawStorage::insert` of `{}` at index {} which already holds a value",
+                type_name::<T>(),
+                index
+            );
+        }
+    }
+
+    fn remove(&mut self, index: Index) {
+        if !self.indexes.remove(index) {
+            panic!(
+                "`RawStorage::remove` of `{}` at index {} which holds no value",
+                type_name::<T>(),
+                index
+            );
+        }
+    }
+}
+
+#[cfg(feature = "debug-assertions")]
+impl<T> Drop for Occupancy<T> {
+    fn drop(&mut self) {
+        if let Some(index) = (&self.indexes).iter().next() {
+            if !thread::panicking() {
+                panic!(
+                    "storage of `{}` dropped while index {} still holds a value",
+                    type_name::<T>(),
+                    index
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "debug-assertions"))]
+struct Occupancy<T>(PhantomData<fn() -> T>);
+
+#[cfg(not(feature = "debug-assertions"))]
+impl<T> Default for Occupancy<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(not(feature = "debug-assertions"))]
+impl<T> Occupancy<T> {
+    #[inline]
+    fn insert(&mut self, _index: Index) {}
+
+    #[inline]
+    fn remove(&mut self, _index: Index) {}
 }
@@ -0,0 +1,170 @@
+use std::mem;
+
+use hibitset::{BitSet, BitSetAnd, BitSetLike};
+
+use crate::{join::Index, storage::StorageKind, world_common::ComponentId};
+
+/// Measurements of how a single component is used, along with the storage layout those
+/// measurements suggest.
+#[derive(Clone, Debug)]
+pub struct ComponentStorageReport {
+    pub id: ComponentId,
+    pub type_name: &'static str,
+    /// The layout of the storage the component currently uses.
+    pub kind: StorageKind,
+    /// `mem::size_of` the component type.
+    pub size: usize,
+    /// The number of entities which have this component.
+    pub count: usize,
+    /// One past the highest index which has this component, which is the length a `VecStorage`
+    /// would need.
+    pub len: Index,
+    /// The fraction of living entities which have this component.
+    pub density: f64,
+    /// For every other component present on at least one of the same entities, the fraction of
+    /// the entities with this component that also have the other one, highest first.
+    pub overlap: Vec<(ComponentId, f64)>,
+    /// The built-in layout which is estimated to use the least memory for the current contents.
+    pub recommended: StorageKind,
+    /// Whether the component is a member of a group inserted with `World::insert_component_group`.
+    pub grouped: bool,
+}
+
+impl ComponentStorageReport {
+    /// Whether the component uses a built-in storage which is not the recommended one.
+    ///
+    /// `StableStorage` and custom storages are never considered mismatched, since they are chosen
+    /// for reasons other than memory use, and neither are members of a component group, which must
+    /// use an `ArrangeStorage`.
+    pub fn is_mismatched(&self) -> bool {
+        !self.grouped
+            && matches!(
+                self.kind,
+                StorageKind::Vec | StorageKind::DenseVec | StorageKind::HashMap
+            )
+            && self.kind != self.recommended
+    }
+
+    /// The components which are present on at least `fraction` of the entities that have this
+    /// component.
+    ///
+    /// Components which almost always appear together are the ones most likely to be joined
+    /// together.
+    pub fn overlapping(&self, fraction: f64) -> impl Iterator<Item = ComponentId> + '_ {
+        self.overlap
+            .iter()
+            .take_while(move |&&(_, f)| f >= fraction)
+            .map(|&(id, _)| id)
+    }
+}
+
+/// Component usage measurements for a whole `World`, returned from `World::storage_report`.
+#[derive(Clone, Debug, Default)]
+pub struct StorageReport {
+    /// The number of living entities at the time of the report.
+    pub alive: usize,
+    /// Every registered component, in order of their type names.
+    pub components: Vec<ComponentStorageReport>,
+}
+
+impl StorageReport {
+    pub(crate) fn new(alive: usize, mut components: Vec<(ComponentStorageReport, BitSet)>) -> Self {
+        components.sort_by_key(|(c, _)| c.type_name);
+
+        for i in 0..components.len() {
+            let mut overlap = Vec::new();
+            if components[i].0.count != 0 {
+                for j in 0..components.len() {
+                    if i != j {
+                        let shared = BitSetAnd(&components[i].1, &components[j].1).iter().count();
+                        if shared != 0 {
+                            overlap.push((
+                                components[j].0.id,
+                                shared as f64 / components[i].0.count as f64,
+                            ));
+                        }
+                    }
+                }
+            }
+            overlap.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            let c = &mut components[i].0;
+            c.overlap = overlap;
+            c.density = if alive == 0 {
+                0.0
+            } else {
+                c.count as f64 / alive as f64
+            };
+        }
+
+        StorageReport {
+            alive,
+            components: components.into_iter().map(|(c, _)| c).collect(),
+        }
+    }
+
+    pub fn component(&self, id: ComponentId) -> Option<&ComponentStorageReport> {
+        self.components.iter().find(|c| c.id == id)
+    }
+
+    /// Every component which uses a built-in storage other than the recommended one.
+    pub fn mismatched(&self) -> impl Iterator<Item = &ComponentStorageReport> + '_ {
+        self.components.iter().filter(|c| c.is_mismatched())
+    }
+
+    /// Every pair of components that are not yet grouped, where each is present on at least
+    /// `fraction` of the entities that have the other.
+    ///
+    /// Such components are nearly always found together, so they are candidates for a group
+    /// inserted with `World::insert_component_group`, which requires both to use an
+    /// `ArrangeStorage` such as `DenseVecStorage`.  Components that no entity has are never
+    /// candidates.
+    pub fn group_candidates(&self, fraction: f64) -> Vec<(ComponentId, ComponentId)> {
+        let overlap = |a: &ComponentStorageReport, b: ComponentId| {
+            a.overlap
+                .iter()
+                .find(|&&(id, _)| id == b)
+                .map_or(0.0, |&(_, f)| f)
+        };
+
+        let mut candidates = Vec::new();
+        for (i, a) in self.components.iter().enumerate() {
+            for b in &self.components[i + 1..] {
+                if !a.grouped
+                    && !b.grouped
+                    && a.count != 0
+                    && b.count != 0
+                    && overlap(a, b.id) >= fraction
+                    && overlap(b, a.id) >= fraction
+                {
+                    candidates.push((a.id, b.id));
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// Choose the built-in layout with the smallest estimated memory use.
+///
+/// `VecStorage` holds a value for every index below `len`, `DenseVecStorage` holds an index for
+/// every index below `len` plus a value and an index for every present component, and
+/// `HashMapStorage` holds a value and a key for every component with roughly double overhead.
+/// `HashMapStorage` is only chosen when it is less than half the size of `DenseVecStorage`, since
+/// it is also slower to access and to iterate.
+pub(crate) fn recommend_storage(size: usize, count: usize, len: Index) -> StorageKind {
+    let index_size = mem::size_of::<Index>();
+    let len = len as usize;
+
+    let vec = len * size;
+    let dense = len * index_size + count * (size + index_size);
+    let hash_map = count * (size + index_size) * 2;
+
+    if vec <= dense {
+        StorageKind::Vec
+    } else if hash_map * 2 < dense {
+        StorageKind::HashMap
+    } else {
+        StorageKind::DenseVec
+    }
+}
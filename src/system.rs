@@ -1,4 +1,12 @@
-use std::{convert::Infallible, mem};
+use std::{
+    convert::Infallible,
+    mem::{self, ManuallyDrop},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, ThreadId},
+};
 
 use crate::resources::{ResourceConflict, Resources};
 
@@ -11,6 +19,33 @@ pub trait Pool {
         B: FnOnce() -> RB + Send,
         RA: Send,
         RB: Send;
+
+    /// Run the given function with any parallel work it starts through this pool limited to at
+    /// most `threads` threads.
+    ///
+    /// The default implementation ignores the limit, which is correct for pools which do not run
+    /// anything in parallel.
+    fn limit_threads<R, F>(&self, threads: usize, f: F) -> R
+    where
+        F: FnOnce() -> R + Send,
+        R: Send,
+    {
+        let _ = threads;
+        f()
+    }
+
+    /// Run the two functions (potentially in parallel) like `Pool::join`, but `local` must run on
+    /// the calling thread.
+    ///
+    /// The default implementation runs the two functions in sequence, which is always correct.
+    fn join_local<A, B, RA, RB>(&self, local: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA,
+        B: FnOnce() -> RB + Send,
+        RB: Send,
+    {
+        (local(), b())
+    }
 }
 
 /// Trait for error types returned from `System::run`.
@@ -27,6 +62,21 @@ impl Error for Infallible {
     }
 }
 
+/// What a `Seq` does once one of its systems returns an error.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum FailurePolicy {
+    /// Stop immediately and return the error, the remaining systems are not run.
+    #[default]
+    Abort,
+    /// Run every remaining system anyway, and return every error combined.
+    Continue,
+    /// Run every remaining system except those whose resources conflict with a system that
+    /// failed or was skipped, and return every error combined.
+    ///
+    /// A system with an internal resource conflict is assumed to conflict with everything.
+    SkipDependents,
+}
+
 /// A system that may be run in parallel or in sequence with other such systems in a group.
 ///
 /// This trait is designed so that systems may read or write to resources inside the `args`
@@ -44,6 +94,54 @@ pub trait System<Args> {
     fn check_resources(&self) -> Result<Self::Resources, ResourceConflict>;
 
     fn run(&mut self, pool: &Self::Pool, args: Args) -> Result<(), Self::Error>;
+
+    /// Run this system as one step of a `Seq` with the given `FailurePolicy`.
+    ///
+    /// `failed` holds the union of the resources of every system in the sequence which has failed
+    /// or been skipped so far, or `None` if none have.  The default implementation skips the system
+    /// if the policy requires it, otherwise runs it and adds its resources to `failed` if it fails.
+    ///
+    /// Combinators which run their systems in sequence should override this to apply the policy to
+    /// each of them in turn.
+    fn run_with_policy(
+        &mut self,
+        pool: &Self::Pool,
+        args: Args,
+        policy: FailurePolicy,
+        failed: &mut Option<Self::Resources>,
+    ) -> Result<(), Self::Error> {
+        if let Some(failed) = failed {
+            match policy {
+                FailurePolicy::Abort => return Ok(()),
+                FailurePolicy::Continue => {}
+                FailurePolicy::SkipDependents => match self.check_resources() {
+                    Ok(resources) if !resources.conflicts_with(failed) => {}
+                    Ok(resources) => {
+                        failed.union(&resources);
+                        return Ok(());
+                    }
+                    Err(_) => return Ok(()),
+                },
+            }
+        }
+
+        let res = self.run(pool, args);
+        if res.is_err() {
+            let resources = self.check_resources().unwrap_or_default();
+            failed
+                .get_or_insert_with(Default::default)
+                .union(&resources);
+        }
+        res
+    }
+
+    /// Whether this system is or contains a `Pinned` system, which must run on the thread that
+    /// created it.
+    ///
+    /// Combinators which contain other systems should return true if any of them do.
+    fn is_pinned(&self) -> bool {
+        false
+    }
 }
 
 impl<A, S> System<A> for Box<S>
@@ -61,6 +159,326 @@ where
     fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
         (**self).run(pool, args)
     }
+
+    fn run_with_policy(
+        &mut self,
+        pool: &Self::Pool,
+        args: A,
+        policy: FailurePolicy,
+        failed: &mut Option<Self::Resources>,
+    ) -> Result<(), Self::Error> {
+        (**self).run_with_policy(pool, args, policy, failed)
+    }
+
+    fn is_pinned(&self) -> bool {
+        (**self).is_pinned()
+    }
+}
+
+/// Wraps a system so that its parallelism is limited to at most a given number of threads, with
+/// `Pool::limit_threads`.
+///
+/// This is useful for a bandwidth bound system with a large parallel join, which would otherwise
+/// take every available thread away from other systems running at the same time.
+///
+/// The limit may move the system to another thread, so the system must not contain any `Pinned`
+/// systems.
+///
+/// # Panics
+/// `ThreadLimit::check_resources` and `ThreadLimit::run` panic if the system is pinned.
+pub struct ThreadLimit<S> {
+    system: S,
+    threads: usize,
+}
+
+impl<S> ThreadLimit<S> {
+    /// # Panics
+    /// Panics if `threads` is 0.
+    pub fn new(system: S, threads: usize) -> Self {
+        assert!(threads > 0, "thread limit must be at least 1");
+        ThreadLimit { system, threads }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn into_inner(self) -> S {
+        self.system
+    }
+}
+
+impl<A, S> System<A> for ThreadLimit<S>
+where
+    A: Send,
+    S: System<A> + Send,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    type Resources = S::Resources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<Self::Resources, ResourceConflict> {
+        assert_not_pinned(&self.system);
+        self.system.check_resources()
+    }
+
+    fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
+        assert_not_pinned(&self.system);
+        let system = &mut self.system;
+        pool.limit_threads(self.threads, move || system.run(pool, args))
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.system.is_pinned()
+    }
+}
+
+/// Wraps a system so that it can be switched on and off at runtime through an `EnabledFlag`,
+/// without rebuilding the schedule it is part of.
+///
+/// A disabled system does nothing and always succeeds.  It still reports its resources from
+/// `check_resources`, so enabling it later can never introduce a resource conflict.
+pub struct Enabled<S> {
+    system: S,
+    flag: EnabledFlag,
+}
+
+impl<S> Enabled<S> {
+    /// Wrap the system, initially enabled.
+    pub fn new(system: S) -> Self {
+        Self::with_flag(system, EnabledFlag::new(true))
+    }
+
+    /// Wrap the system, controlled by an existing flag, which may be shared by many systems.
+    pub fn with_flag(system: S, flag: EnabledFlag) -> Self {
+        Enabled { system, flag }
+    }
+
+    pub fn flag(&self) -> &EnabledFlag {
+        &self.flag
+    }
+
+    pub fn into_inner(self) -> S {
+        self.system
+    }
+}
+
+impl<A, S> System<A> for Enabled<S>
+where
+    S: System<A>,
+{
+    type Resources = S::Resources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<Self::Resources, ResourceConflict> {
+        self.system.check_resources()
+    }
+
+    fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
+        if self.flag.is_enabled() {
+            self.system.run(pool, args)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn run_with_policy(
+        &mut self,
+        pool: &Self::Pool,
+        args: A,
+        policy: FailurePolicy,
+        failed: &mut Option<Self::Resources>,
+    ) -> Result<(), Self::Error> {
+        if self.flag.is_enabled() {
+            self.system.run_with_policy(pool, args, policy, failed)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.system.is_pinned()
+    }
+}
+
+/// A shared switch which turns every `Enabled` system created with it on or off.
+///
+/// Cloning the flag gives another handle to the same switch, so it can be kept outside of the
+/// schedule, such as in a debug menu or a resource.
+#[derive(Clone, Debug)]
+pub struct EnabledFlag(Arc<AtomicBool>);
+
+impl EnabledFlag {
+    pub fn new(enabled: bool) -> Self {
+        EnabledFlag(Arc::new(AtomicBool::new(enabled)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Takes effect the next time a system controlled by this flag is run.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.0.store(enabled, Ordering::Release);
+    }
+}
+
+impl Default for EnabledFlag {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+/// Run a system with every bit of its parallelism confined to a single thread, so that it executes
+/// strictly sequentially in declaration order.
+///
+/// `Par` and `ParList` run their systems through `Pool::join`, which for `SeqPool` and for a pool
+/// limited to one thread with `Pool::limit_threads` runs the first function to completion before
+/// the second.  The same limit applies to any `par_join` inside the systems.  This is meant for
+/// pinning down nondeterminism introduced by parallel execution, see `verify::verify_sequential`.
+///
+/// Like `ThreadLimit`, this may move the system to another thread, so the system must not
+/// contain any `Pinned` systems.
+///
+/// # Panics
+/// Panics if the system is pinned.
+pub fn run_sequential<A, S>(system: &mut S, pool: &S::Pool, args: A) -> Result<(), S::Error>
+where
+    A: Send,
+    S: System<A> + Send,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    assert_not_pinned(system);
+    pool.limit_threads(1, move || system.run(pool, args))
+}
+
+// For systems which are run through `Pool::limit_threads`, which may move them to another thread.
+fn assert_not_pinned<A, S: System<A>>(system: &S) {
+    assert!(
+        !system.is_pinned(),
+        "a pinned system cannot be run with a thread limit"
+    );
+}
+
+/// Wraps a system which must only be used on the thread that created it, such as a system which
+/// holds a graphics context or uses some other thread-affine API.
+///
+/// `Pinned` is always `Send`, so the wrapped system may be placed inside `Par`, `ParList` and other
+/// combinators, and `Par` and `ParList` run it on the thread which called `run` while the rest of
+/// their systems run on the pool. This means that the outermost system must be run on the thread
+/// which created the pinned system, and that there must be nothing in between which moves work to
+/// another thread, such as `ThreadLimit`.
+///
+/// # Panics
+/// Accessing the wrapped system in any way from another thread panics. If a `Pinned` is dropped on
+/// another thread, the wrapped system is leaked instead.
+pub struct Pinned<S> {
+    system: ManuallyDrop<S>,
+    thread: ThreadId,
+}
+
+// Safety: The wrapped system is only ever accessed or dropped on the thread which created it.
+unsafe impl<S> Send for Pinned<S> {}
+
+impl<S> Pinned<S> {
+    pub fn new(system: S) -> Self {
+        Pinned {
+            system: ManuallyDrop::new(system),
+            thread: thread::current().id(),
+        }
+    }
+
+    pub fn get(&self) -> &S {
+        self.check_thread();
+        &self.system
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        self.check_thread();
+        &mut self.system
+    }
+
+    pub fn into_inner(self) -> S {
+        self.check_thread();
+        let mut this = ManuallyDrop::new(self);
+        // Safety: `this` is never used or dropped again.
+        unsafe { ManuallyDrop::take(&mut this.system) }
+    }
+
+    fn check_thread(&self) {
+        assert!(
+            thread::current().id() == self.thread,
+            "pinned system accessed from a thread other than the one that created it"
+        );
+    }
+}
+
+impl<S> Drop for Pinned<S> {
+    fn drop(&mut self) {
+        if thread::current().id() == self.thread {
+            // Safety: The system is never used again.
+            unsafe { ManuallyDrop::drop(&mut self.system) }
+        }
+    }
+}
+
+impl<A, S> System<A> for Pinned<S>
+where
+    S: System<A>,
+{
+    type Resources = S::Resources;
+    type Pool = S::Pool;
+    type Error = S::Error;
+
+    fn check_resources(&self) -> Result<Self::Resources, ResourceConflict> {
+        self.get().check_resources()
+    }
+
+    fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
+        self.get_mut().run(pool, args)
+    }
+
+    fn is_pinned(&self) -> bool {
+        true
+    }
+}
+
+// Run the two functions through the pool, keeping any which is pinned on the calling thread.
+fn join_pinned<P, A, B, E>(
+    pool: &P,
+    (a, a_pinned): (A, bool),
+    (b, b_pinned): (B, bool),
+) -> Result<(), E>
+where
+    P: Pool,
+    A: FnOnce() -> Result<(), E> + Send,
+    B: FnOnce() -> Result<(), E> + Send,
+    E: Error + Send,
+{
+    let res = if a_pinned && b_pinned {
+        (a(), b())
+    } else if a_pinned {
+        pool.join_local(a, b)
+    } else if b_pinned {
+        let (rb, ra) = pool.join_local(b, a);
+        (ra, rb)
+    } else {
+        pool.join(a, b)
+    };
+    combine_results(res.0, res.1)
+}
+
+fn combine_results<E: Error>(a: Result<(), E>, b: Result<(), E>) -> Result<(), E> {
+    match (a, b) {
+        (Ok(()), Ok(())) => Ok(()),
+        (Err(a), Ok(())) => Err(a),
+        (Ok(()), Err(b)) => Err(b),
+        (Err(a), Err(b)) => Err(a.combine(b)),
+    }
 }
 
 pub struct Par<H, T> {
@@ -108,12 +526,16 @@ where
 
     fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
         let Self { head, tail, .. } = self;
-        match pool.join(move || head.run(pool, args), move || tail.run(pool, args)) {
-            (Ok(()), Ok(())) => Ok(()),
-            (Err(a), Ok(())) => Err(a),
-            (Ok(()), Err(b)) => Err(b),
-            (Err(a), Err(b)) => Err(a.combine(b)),
-        }
+        let (head_pinned, tail_pinned) = (head.is_pinned(), tail.is_pinned());
+        join_pinned(
+            pool,
+            (move || head.run(pool, args), head_pinned),
+            (move || tail.run(pool, args), tail_pinned),
+        )
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.head.is_pinned() || self.tail.is_pinned()
     }
 }
 
@@ -127,22 +549,41 @@ macro_rules! par {
     };
 }
 
+/// Runs two systems in sequence.
+///
+/// By default the first error stops the sequence, this can be changed with `Seq::with_policy`.
+/// When sequences are nested, the policy of the outermost `Seq` applies to all of them.
 pub struct Seq<H, T> {
     head: H,
     tail: T,
+    policy: FailurePolicy,
 }
 
 impl<H, T> Seq<H, T> {
     pub fn new(head: H, tail: T) -> Seq<H, T> {
-        Seq { head, tail }
+        Seq {
+            head,
+            tail,
+            policy: FailurePolicy::Abort,
+        }
     }
 
     pub fn with<S>(self, sys: S) -> Seq<H, Seq<T, S>> {
         Seq {
             head: self.head,
             tail: Seq::new(self.tail, sys),
+            policy: self.policy,
         }
     }
+
+    pub fn with_policy(mut self, policy: FailurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> FailurePolicy {
+        self.policy
+    }
 }
 
 impl<H, T, A, R, P, E> System<A> for Seq<H, T>
@@ -165,8 +606,27 @@ where
     }
 
     fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
-        self.head.run(pool, args)?;
-        self.tail.run(pool, args)
+        let policy = self.policy;
+        self.run_with_policy(pool, args, policy, &mut None)
+    }
+
+    fn run_with_policy(
+        &mut self,
+        pool: &Self::Pool,
+        args: A,
+        policy: FailurePolicy,
+        failed: &mut Option<Self::Resources>,
+    ) -> Result<(), Self::Error> {
+        let head = self.head.run_with_policy(pool, args, policy, failed);
+        if head.is_err() && policy == FailurePolicy::Abort {
+            return head;
+        }
+        let tail = self.tail.run_with_policy(pool, args, policy, failed);
+        combine_results(head, tail)
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.head.is_pinned() || self.tail.is_pinned()
     }
 }
 
@@ -182,6 +642,55 @@ macro_rules! seq {
 
 pub struct ParList<S>(pub Vec<S>);
 
+// Run every given system in parallel through the pool, with any pinned systems run in sequence on
+// the calling thread.
+pub(crate) fn run_parallel<A, S>(
+    systems: Vec<&mut S>,
+    pool: &S::Pool,
+    args: A,
+) -> Result<(), S::Error>
+where
+    A: Copy + Send,
+    S: System<A> + Send,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    fn run<A, S>(s: &mut [&mut S], pinned: bool, pool: &S::Pool, args: A) -> Result<(), S::Error>
+    where
+        A: Copy + Send,
+        S: System<A> + Send,
+        S::Pool: Sync,
+        S::Error: Send,
+    {
+        if s.is_empty() {
+            Ok(())
+        } else if s.len() == 1 {
+            s[0].run(pool, args)
+        } else {
+            let mid = s.len() / 2;
+            let (lo, hi) = s.split_at_mut(mid);
+            join_pinned(
+                pool,
+                (move || run(lo, pinned, pool, args), pinned),
+                (move || run(hi, pinned, pool, args), pinned),
+            )
+        }
+    }
+
+    // Pinned systems all run in sequence on the calling thread, alongside the rest which run in
+    // parallel on the pool.
+    let (mut pinned, mut rest): (Vec<_>, Vec<_>) = systems.into_iter().partition(|s| s.is_pinned());
+    if pinned.is_empty() {
+        return run(&mut rest, false, pool, args);
+    }
+
+    join_pinned(
+        pool,
+        (move || run(&mut pinned, true, pool, args), true),
+        (move || run(&mut rest, false, pool, args), false),
+    )
+}
+
 impl<A, S> System<A> for ParList<S>
 where
     A: Copy + Send,
@@ -206,30 +715,11 @@ where
     }
 
     fn run(&mut self, pool: &Self::Pool, args: A) -> Result<(), Self::Error> {
-        fn run<A, S>(s: &mut [S], pool: &S::Pool, args: A) -> Result<(), S::Error>
-        where
-            A: Copy + Send,
-            S: System<A> + Send,
-            S::Pool: Sync,
-            S::Error: Send,
-        {
-            if s.len() == 0 {
-                Ok(())
-            } else if s.len() == 1 {
-                s[0].run(pool, args)
-            } else {
-                let mid = s.len() / 2;
-                let (lo, hi) = s.split_at_mut(mid);
-                match pool.join(move || run(lo, pool, args), move || run(hi, pool, args)) {
-                    (Ok(()), Ok(())) => Ok(()),
-                    (Err(a), Ok(())) => Err(a),
-                    (Ok(()), Err(b)) => Err(b),
-                    (Err(a), Err(b)) => Err(a.combine(b)),
-                }
-            }
-        }
+        run_parallel(self.0.iter_mut().collect(), pool, args)
+    }
 
-        run(&mut self.0[..], pool, args)
+    fn is_pinned(&self) -> bool {
+        self.0.iter().any(|s| s.is_pinned())
     }
 }
 
@@ -258,6 +748,27 @@ where
         }
         Ok(())
     }
+
+    fn run_with_policy(
+        &mut self,
+        pool: &Self::Pool,
+        args: A,
+        policy: FailurePolicy,
+        failed: &mut Option<Self::Resources>,
+    ) -> Result<(), Self::Error> {
+        let mut res = Ok(());
+        for s in &mut self.0 {
+            res = combine_results(res, s.run_with_policy(pool, args, policy, failed));
+            if res.is_err() && policy == FailurePolicy::Abort {
+                break;
+            }
+        }
+        res
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.0.iter().any(|s| s.is_pinned())
+    }
 }
 
 /// Takes a list of systems all of the same type and makes them as parallel as possible without
@@ -268,6 +779,37 @@ where
 /// conflict in parallel with each other and in sequence with the remaining systems. The algorithm
 /// then repeats this process with the remaining systems until there are no more systems remaining.
 pub fn parallelize<A, S>(systems: impl IntoIterator<Item = S>) -> SeqList<ParList<S>>
+where
+    A: Copy + Send + 'static,
+    S: System<A> + Send + 'static,
+    S::Pool: Sync,
+    S::Error: Send,
+{
+    parallelize_with_barriers(systems.into_iter().map(ScheduleItem::System))
+}
+
+/// An entry in the list of systems given to `parallelize_with_barriers`.
+pub enum ScheduleItem<S> {
+    System(S),
+    /// Forces every system before the barrier to complete before any system after it starts,
+    /// regardless of whether their resources conflict.
+    ///
+    /// This is useful for systems which make structural changes that are not visible through
+    /// their resources.
+    Barrier,
+}
+
+impl<S> From<S> for ScheduleItem<S> {
+    fn from(system: S) -> Self {
+        ScheduleItem::System(system)
+    }
+}
+
+/// The same as `parallelize`, but the list may also contain `ScheduleItem::Barrier` entries which
+/// no system is ever parallelized across.
+pub fn parallelize_with_barriers<A, S>(
+    items: impl IntoIterator<Item = ScheduleItem<S>>,
+) -> SeqList<ParList<S>>
 where
     A: Copy + Send + 'static,
     S: System<A> + Send + 'static,
@@ -279,7 +821,18 @@ where
     let mut par = Vec::new();
     let mut par_resources = S::Resources::default();
 
-    for system in systems {
+    for item in items {
+        let system = match item {
+            ScheduleItem::System(system) => system,
+            ScheduleItem::Barrier => {
+                if !par.is_empty() {
+                    seq.push(ParList(mem::take(&mut par)));
+                    par_resources = S::Resources::default();
+                }
+                continue;
+            }
+        };
+
         if let Ok(sys_resources) = system.check_resources() {
             if par_resources.conflicts_with(&sys_resources) {
                 assert!(!par.is_empty());
@@ -1,10 +1,24 @@
-use hibitset::AtomicBitSet;
+use std::{any::type_name, sync::OnceLock};
 
-use crate::{join::Index, storage::RawStorage};
+use hibitset::{AtomicBitSet, BitSet};
+
+use crate::{
+    join::Index,
+    memory::{bitset_usage, MemoryUsage},
+    storage::{ArrangeStorage, DenseStorage, RawStorage, SplitStorage, StorageKind},
+};
 
 pub type ModifiedBitSet = AtomicBitSet;
 
+/// Implementations must also set `RawStorage::TRACKED` to true, which `World::component_info`
+/// reports.  Using a tracked storage through `MaskedStorage` fails to compile otherwise.
 pub trait TrackedStorage: RawStorage {
+    #[doc(hidden)]
+    const ASSERT_TRACKED: () = assert!(
+        Self::TRACKED,
+        "TrackedStorage implementations must set RawStorage::TRACKED to true"
+    );
+
     /// If this is true, then calls to `get_mut`, `insert`, and `remove` will automatically set
     /// modified bits.
     fn set_track_modified(&mut self, flag: bool);
@@ -15,22 +29,155 @@ pub trait TrackedStorage: RawStorage {
 
     fn modified_indexes(&self) -> &ModifiedBitSet;
 
-    /// Clear the modified bitset.
+    /// Indexes which had a component inserted since the modified bitset was last cleared, and
+    /// which still have one.  This is always a subset of `TrackedStorage::modified_indexes`.
+    ///
+    /// The default implementation does not track insertions and always returns an empty set, so
+    /// inserted components are reported as modified.
+    fn inserted_indexes(&self) -> &BitSet {
+        empty_bitset()
+    }
+
+    /// Clear the modified bitset, along with the inserted bitset.
     fn clear_modified(&mut self);
+
+    /// Register an independent consumer of modifications.
+    ///
+    /// Every reader has its own modified and inserted bitsets which record the same changes as the
+    /// main ones, but which are only cleared by `TrackedStorage::clear_reader_modified`.  This
+    /// allows several consumers to each clear their own view of the changes without affecting each
+    /// other.
+    ///
+    /// # Panics
+    /// The default implementation does not support readers and always panics.
+    fn register_reader(&mut self) -> ReaderId {
+        panic!("{} does not support readers", type_name::<Self>());
+    }
+
+    /// Stop recording modifications for the given reader.
+    ///
+    /// # Panics
+    /// Panics if the reader is not registered with this storage.
+    fn unregister_reader(&mut self, reader: ReaderId) {
+        no_such_reader(reader)
+    }
+
+    /// # Panics
+    /// Panics if the reader is not registered with this storage.
+    fn reader_modified_indexes(&self, reader: ReaderId) -> &ModifiedBitSet {
+        no_such_reader(reader)
+    }
+
+    /// # Panics
+    /// Panics if the reader is not registered with this storage.
+    fn reader_inserted_indexes(&self, reader: ReaderId) -> &BitSet {
+        no_such_reader(reader)
+    }
+
+    /// Clear the modified and inserted bitsets of the given reader.
+    ///
+    /// # Panics
+    /// Panics if the reader is not registered with this storage.
+    fn clear_reader_modified(&mut self, reader: ReaderId) {
+        no_such_reader(reader)
+    }
+}
+
+fn empty_bitset() -> &'static BitSet {
+    static EMPTY: OnceLock<BitSet> = OnceLock::new();
+    EMPTY.get_or_init(BitSet::new)
+}
+
+fn no_such_reader(reader: ReaderId) -> ! {
+    panic!("no such reader {:?}", reader)
+}
+
+/// Identifies a reader registered with `TrackedStorage::register_reader`.
+///
+/// The id of an unregistered reader is never valid again, even once its slot is re-used by
+/// another reader.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ReaderId {
+    index: usize,
+    generation: u32,
+}
+
+// The bitsets of a single reader, `bits` is `None` while the slot is free.
+#[derive(Default)]
+struct ReaderSlot {
+    generation: u32,
+    bits: Option<TrackedBits>,
+}
+
+#[derive(Default)]
+struct TrackedBits {
+    modified: ModifiedBitSet,
+    inserted: BitSet,
+}
+
+impl TrackedBits {
+    fn clear(&mut self) {
+        self.modified.clear();
+        self.inserted.clear();
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        bitset_usage(&self.modified) + bitset_usage(&self.inserted)
+    }
 }
 
 /// Storage that can optionally track the indexes of any changed components.
 ///
 /// Any call to the `get_mut`, `insert`, or `remove` methods of `RawStorage` will set modification
-/// bits for that index if tracking is turned on.
+/// bits for that index if tracking is turned on.  Calls to `insert` additionally set an inserted
+/// bit, which is unset again by `remove`.
 ///
 /// By default, tracking is *not* turned on, you must turn it on by calling
-/// `set_track_modified(true)`.
+/// `set_track_modified(true)`, or register the component with `World::insert_component_tracked`.
 #[derive(Default)]
 pub struct Flagged<S> {
     tracking: bool,
     storage: S,
-    modified: ModifiedBitSet,
+    bits: TrackedBits,
+    readers: Vec<ReaderSlot>,
+}
+
+impl<S> Flagged<S> {
+    fn reader(&self, reader: ReaderId) -> &TrackedBits {
+        match self.readers.get(reader.index) {
+            Some(ReaderSlot {
+                generation,
+                bits: Some(bits),
+            }) if *generation == reader.generation => bits,
+            _ => no_such_reader(reader),
+        }
+    }
+
+    fn reader_mut(&mut self, reader: ReaderId) -> &mut TrackedBits {
+        match self.readers.get_mut(reader.index) {
+            Some(ReaderSlot {
+                generation,
+                bits: Some(bits),
+            }) if *generation == reader.generation => bits,
+            _ => no_such_reader(reader),
+        }
+    }
+
+    fn reader_bits(&self) -> impl Iterator<Item = &TrackedBits> {
+        self.readers.iter().filter_map(|slot| slot.bits.as_ref())
+    }
+
+    fn all_bits(&self) -> impl Iterator<Item = &TrackedBits> {
+        Some(&self.bits).into_iter().chain(self.reader_bits())
+    }
+
+    fn all_bits_mut(&mut self) -> impl Iterator<Item = &mut TrackedBits> {
+        Some(&mut self.bits).into_iter().chain(
+            self.readers
+                .iter_mut()
+                .filter_map(|slot| slot.bits.as_mut()),
+        )
+    }
 }
 
 impl<S> RawStorage for Flagged<S>
@@ -39,30 +186,74 @@ where
 {
     type Item = S::Item;
 
+    const TRACKED: bool = true;
+
+    const KIND: StorageKind = S::KIND;
+
     unsafe fn get(&self, index: Index) -> &Self::Item {
         self.storage.get(index)
     }
 
     unsafe fn get_mut(&self, index: Index) -> &mut Self::Item {
         if self.tracking {
-            self.modified.add_atomic(index);
+            self.mark_modified(index);
         }
         self.storage.get_mut(index)
     }
 
     unsafe fn insert(&mut self, index: Index, value: Self::Item) {
         if self.tracking {
-            self.modified.add(index);
+            for bits in self.all_bits_mut() {
+                bits.modified.add(index);
+                bits.inserted.add(index);
+            }
         }
         self.storage.insert(index, value);
     }
 
     unsafe fn remove(&mut self, index: Index) -> Self::Item {
-        if self.tracking {
-            self.modified.add(index);
+        let tracking = self.tracking;
+        for bits in self.all_bits_mut() {
+            if tracking {
+                bits.modified.add(index);
+            }
+            bits.inserted.remove(index);
         }
         self.storage.remove(index)
     }
+
+    unsafe fn insert_sorted(&mut self, values: impl Iterator<Item = (Index, Self::Item)>) {
+        if self.tracking {
+            let (bits, readers) = (&mut self.bits, &mut self.readers);
+            self.storage.insert_sorted(values.inspect(|&(index, _)| {
+                for bits in Some(&mut *bits)
+                    .into_iter()
+                    .chain(readers.iter_mut().filter_map(|slot| slot.bits.as_mut()))
+                {
+                    bits.modified.add(index);
+                    bits.inserted.add(index);
+                }
+            }));
+        } else {
+            self.storage.insert_sorted(values);
+        }
+    }
+
+    fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = self.storage.memory_usage();
+        for bits in self.all_bits() {
+            usage += bits.memory_usage();
+        }
+        usage
+    }
+
+    fn reserve(&mut self, len: Index) {
+        self.storage.reserve(len);
+    }
+
+    fn shrink(&mut self, len: Index) {
+        self.storage.shrink(len);
+    }
 }
 
 impl<S> TrackedStorage for Flagged<S>
@@ -78,14 +269,104 @@ where
     }
 
     fn mark_modified(&self, index: Index) {
-        self.modified.add_atomic(index);
+        for bits in self.all_bits() {
+            bits.modified.add_atomic(index);
+        }
     }
 
     fn modified_indexes(&self) -> &ModifiedBitSet {
-        &self.modified
+        &self.bits.modified
+    }
+
+    fn inserted_indexes(&self) -> &BitSet {
+        &self.bits.inserted
     }
 
     fn clear_modified(&mut self) {
-        self.modified.clear();
+        self.bits.clear();
+    }
+
+    fn register_reader(&mut self) -> ReaderId {
+        let index = match self.readers.iter().position(|slot| slot.bits.is_none()) {
+            Some(index) => index,
+            None => {
+                self.readers.push(ReaderSlot::default());
+                self.readers.len() - 1
+            }
+        };
+        let slot = &mut self.readers[index];
+        slot.bits = Some(TrackedBits::default());
+        ReaderId {
+            index,
+            generation: slot.generation,
+        }
+    }
+
+    fn unregister_reader(&mut self, reader: ReaderId) {
+        self.reader(reader);
+        let slot = &mut self.readers[reader.index];
+        slot.bits = None;
+        slot.generation = slot.generation.wrapping_add(1);
+    }
+
+    fn reader_modified_indexes(&self, reader: ReaderId) -> &ModifiedBitSet {
+        &self.reader(reader).modified
+    }
+
+    fn reader_inserted_indexes(&self, reader: ReaderId) -> &BitSet {
+        &self.reader(reader).inserted
+    }
+
+    fn clear_reader_modified(&mut self, reader: ReaderId) {
+        self.reader_mut(reader).clear();
+    }
+}
+
+impl<S> DenseStorage for Flagged<S>
+where
+    S: DenseStorage,
+{
+    fn as_slice(&self) -> &[Self::Item] {
+        self.storage.as_slice()
+    }
+
+    /// If tracking is turned on, marks every present index as modified, since it is not possible to
+    /// know which values will actually be changed through the slice.
+    fn as_mut_slice(&mut self) -> &mut [Self::Item] {
+        if self.tracking {
+            for &index in self.storage.indexes() {
+                self.mark_modified(index);
+            }
+        }
+        self.storage.as_mut_slice()
+    }
+
+    fn indexes(&self) -> &[Index] {
+        self.storage.indexes()
+    }
+}
+
+impl<S> SplitStorage for Flagged<S>
+where
+    S: SplitStorage,
+{
+    /// Marks modified indexes the same way as `Flagged::as_mut_slice`.
+    fn indexes_and_mut_slice(&mut self) -> (&[Index], &mut [Self::Item]) {
+        if self.tracking {
+            for &index in self.storage.indexes() {
+                self.mark_modified(index);
+            }
+        }
+        self.storage.indexes_and_mut_slice()
+    }
+}
+
+impl<S> ArrangeStorage for Flagged<S>
+where
+    S: ArrangeStorage,
+{
+    /// Values are only moved within the slice and not changed, so nothing is marked as modified.
+    fn arrange_by_key<K: Ord>(&mut self, key: impl FnMut(Index) -> K) {
+        self.storage.arrange_by_key(key)
     }
 }
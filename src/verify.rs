@@ -0,0 +1,79 @@
+use thiserror::Error;
+
+use crate::{
+    system::{run_sequential, Pool, System},
+    world::World,
+};
+
+/// Returned from `verify_sequential` when the parallel and sequential runs do not agree.
+#[derive(Debug, Error)]
+pub enum VerifyError<E> {
+    #[error("system failed on frame {frame} of the {mode} run")]
+    System {
+        frame: usize,
+        mode: &'static str,
+        error: E,
+    },
+    #[error("parallel and sequential runs diverged on frame {frame}")]
+    Diverged {
+        frame: usize,
+        parallel: u64,
+        sequential: u64,
+    },
+}
+
+/// Run the same schedule both normally and with `run_sequential`, on two identical worlds, and
+/// compare a hash of the worlds after every frame.
+///
+/// `setup` is called twice and must produce the same world and schedule both times.  Every frame
+/// runs the schedule and then calls `World::merge`, and the first frame after which `hash` gives
+/// different results for the two worlds is reported.  `World::state_hash` is a good choice of
+/// hash.
+///
+/// # Panics
+/// Panics if the schedule is pinned, see `run_sequential`.
+pub fn verify_sequential<S, P, E>(
+    mut setup: impl FnMut() -> (World, S),
+    pool: &P,
+    frames: usize,
+    mut hash: impl FnMut(&World) -> u64,
+) -> Result<(), VerifyError<E>>
+where
+    S: for<'a> System<&'a World, Pool = P, Error = E> + Send,
+    P: Pool + Sync,
+    E: Send,
+{
+    let (mut parallel_world, mut parallel) = setup();
+    let (mut sequential_world, mut sequential) = setup();
+
+    for frame in 0..frames {
+        parallel
+            .run(pool, &parallel_world)
+            .map_err(|error| VerifyError::System {
+                frame,
+                mode: "parallel",
+                error,
+            })?;
+        parallel_world.merge();
+
+        run_sequential(&mut sequential, pool, &sequential_world).map_err(|error| {
+            VerifyError::System {
+                frame,
+                mode: "sequential",
+                error,
+            }
+        })?;
+        sequential_world.merge();
+
+        let (parallel, sequential) = (hash(&parallel_world), hash(&sequential_world));
+        if parallel != sequential {
+            return Err(VerifyError::Diverged {
+                frame,
+                parallel,
+                sequential,
+            });
+        }
+    }
+
+    Ok(())
+}
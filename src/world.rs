@@ -1,57 +1,782 @@
 use std::{
-    any::TypeId,
+    any::{type_name, Any, TypeId},
     marker::PhantomData,
+    mem,
     ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
 };
 
-use atomic_refcell::{AtomicRef, AtomicRefMut};
-use hibitset::BitSet;
+use atomic_refcell::{AtomicRef, AtomicRefCell, AtomicRefMut};
+use hibitset::{BitSet, BitSetAnd, BitSetLike, BitSetNot};
+#[cfg(feature = "rayon")]
+use rayon::iter::IndexedParallelIterator;
 use rustc_hash::FxHashMap;
+use thiserror::Error;
 
 use crate::{
-    entity::{Allocator, Entity, LiveBitSet, WrongGeneration},
-    fetch_resources::FetchResources,
-    join::{Index, IntoJoin},
-    masked::{GuardedElement, GuardedJoin, ModifiedJoin, ModifiedJoinMut},
-    resource_set::ResourceSet,
+    component_events::{ComponentEvent, ComponentEventKind, ComponentEventLog, SubscriptionId},
+    component_group::{ComponentGroup, GroupMembers},
+    component_index::ComponentIndex,
+    dyn_value::{DynAccess, DynStorage, ReadDyn, WriteDyn},
+    entity::{
+        AllocateError, Allocator, Entity, EntityError, GenerationOverflow, LiveBitSet, WeakEntity,
+    },
+    entity_map::{EntityMap, EntityMapRegistry},
+    events::Events,
+    fetch_resources::{FetchError, FetchResources},
+    frame_arena::FrameArena,
+    join::{Index, IntoJoin, Join},
+    local::Locals,
+    mask_query::MaskQuery,
+    masked::{
+        GuardedElement, GuardedJoin, MaskVersion, MaskedStorage, ModifiedItem, ModifiedJoin,
+        ModifiedJoinMut, ReadGuardedJoin,
+    },
+    memory::{ComponentMemory, MemoryReport, ResourceMemory},
+    net_sync::{NetSync, StateHasher},
+    plugin::Plugin,
+    resource_set::{downcast_resource_mut, ResourceSet},
     resources::ResourceConflict,
-    storage::DenseStorage,
-    tracked::{ModifiedBitSet, TrackedStorage},
-    world_common::{Component, ComponentStorage, WorldResourceId, WorldResources},
+    soa::SoaStorage,
+    stats::FrameStats,
+    storage::{DenseStorage, RawStorage, SplitStorage},
+    storage_report::{recommend_storage, ComponentStorageReport, StorageReport},
+    tracked::{ModifiedBitSet, ReaderId, TrackedStorage},
+    world_common::{
+        Component, ComponentId, ComponentStorage, ResourceId, WorldResourceId, WorldResources,
+    },
 };
 
-#[derive(Default)]
+/// Per-component configuration provided when a component is registered with
+/// `World::insert_component_with_config`.
+pub struct ComponentConfig<C: Component> {
+    on_remove: Option<Box<OnRemove<C>>>,
+    on_maintain: Option<Box<OnMaintain<C>>>,
+    clear_modified: Option<fn(&mut ComponentStorage<C>)>,
+    track_modified: Option<fn(&mut ComponentStorage<C>)>,
+    clone: Option<fn(&C) -> C>,
+    // Removals queued with `ComponentAccess::remove_deferred`, which are applied on `World::merge`.
+    deferred_removals: Mutex<Vec<Entity>>,
+}
+
+type OnRemove<C> = dyn Fn(Entity, &C) + Send + Sync;
+type OnMaintain<C> = dyn Fn(&mut ComponentStorage<C>) + Send + Sync;
+
+impl<C: Component> Default for ComponentConfig<C> {
+    fn default() -> Self {
+        ComponentConfig {
+            on_remove: None,
+            on_maintain: None,
+            clear_modified: None,
+            track_modified: None,
+            clone: None,
+            deferred_removals: Mutex::default(),
+        }
+    }
+}
+
+impl<C: Component> ComponentConfig<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a callback that is called with every component of this type that is removed from a
+    /// live entity.
+    ///
+    /// The callback runs when a component is removed through `ComponentAccess::remove`, when an
+    /// entity is deleted with `World::delete_entity`, and when entities deleted with
+    /// `Entities::delete` or queued with `ComponentAccess::remove_deferred` are removed during
+    /// `World::merge`.  It does *not* run for components
+    /// removed directly through the inner `MaskedStorage`, or when the whole storage is removed or
+    /// replaced.
+    pub fn on_remove(mut self, f: impl Fn(Entity, &C) + Send + Sync + 'static) -> Self {
+        self.on_remove = Some(Box::new(f));
+        self
+    }
+
+    /// Set a callback that is given exclusive access to the component storage on every call to
+    /// `World::maintain`.
+    ///
+    /// This is the place for end of frame bookkeeping that is specific to a single component type,
+    /// like clearing modified bitsets or resetting per-frame state.
+    pub fn on_maintain(
+        mut self,
+        f: impl Fn(&mut ComponentStorage<C>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_maintain = Some(Box::new(f));
+        self
+    }
+
+    fn removed(&self, e: Entity, c: &C) {
+        if let Some(on_remove) = &self.on_remove {
+            on_remove(e, c);
+        }
+    }
+}
+
+impl<C> ComponentConfig<C>
+where
+    C: Component + Clone,
+{
+    /// Record that this component can be cloned, which is reported in `ComponentInfo`.
+    pub fn cloneable(mut self) -> Self {
+        self.clone = Some(C::clone);
+        self
+    }
+}
+
+impl<C> ComponentConfig<C>
+where
+    C: Component,
+    C::Storage: TrackedStorage,
+{
+    /// Automatically clear the modified bitset of this component on every call to `World::merge`.
+    ///
+    /// This is useful when there is only a single consumer of the modified set, which then does not
+    /// need to clear it manually.  The modified set is cleared *before* the components of deleted
+    /// entities are removed, so those removals are still visible as modifications until the next
+    /// merge.
+    pub fn clear_modified_on_merge(mut self) -> Self {
+        self.clear_modified = Some(MaskedStorage::clear_modified);
+        self
+    }
+
+    /// Turn on modification tracking as soon as the storage is inserted, so that no code path
+    /// needs to remember to call `set_track_modified(true)`.
+    pub fn track_modified(mut self) -> Self {
+        self.track_modified = Some(|storage| storage.set_track_modified(true));
+        self
+    }
+}
+
+/// Static information about a component registered with a `World`, returned from
+/// `World::component_info`.
+#[derive(Copy, Clone, Debug)]
+pub struct ComponentInfo {
+    pub id: ComponentId,
+    pub type_name: &'static str,
+    pub size: usize,
+    pub align: usize,
+    /// True if the component was registered with `ComponentConfig::cloneable`.
+    pub cloneable: bool,
+    /// True if the component storage tracks modified components.
+    pub tracked: bool,
+}
+
+impl ComponentInfo {
+    fn of<C>(config: &ComponentConfig<C>) -> Self
+    where
+        C: Component + 'static,
+    {
+        ComponentInfo {
+            id: ComponentId::of::<C>(),
+            type_name: type_name::<C>(),
+            size: mem::size_of::<C>(),
+            align: mem::align_of::<C>(),
+            cloneable: config.clone.is_some(),
+            tracked: C::Storage::TRACKED,
+        }
+    }
+}
+
+// Type erased operations on a registered component storage.
+struct ComponentRegistration {
+    info: ComponentInfo,
+    config: Box<dyn Any + Send + Sync>,
+    remove: fn(&ResourceSet, &(dyn Any + Send + Sync), &ComponentEventLog, &[Entity]),
+    memory: fn(&mut ResourceSet) -> ComponentMemory,
+    maintain: fn(&mut ResourceSet, &(dyn Any + Send + Sync)),
+    merge: fn(&mut ResourceSet, &(dyn Any + Send + Sync)),
+    get: for<'a> fn(&'a ResourceSet, Index) -> Option<DynComponentRef<'a>>,
+    get_mut: fn(&mut ResourceSet, Index) -> Option<&mut dyn Any>,
+    reserve: fn(&mut ResourceSet, Index),
+    shrink: fn(&mut ResourceSet),
+    storage_report: fn(&mut ResourceSet) -> (ComponentStorageReport, BitSet),
+    compact: fn(&mut ResourceSet, &[(Entity, Entity)]),
+    take_deferred_removals: fn(&mut (dyn Any + Send + Sync)) -> Vec<Entity>,
+}
+
+impl ComponentRegistration {
+    fn new<C>(config: ComponentConfig<C>) -> Self
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        fn remove<C>(
+            resource_set: &ResourceSet,
+            config: &(dyn Any + Send + Sync),
+            events: &ComponentEventLog,
+            entities: &[Entity],
+        ) where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let config = config.downcast_ref::<ComponentConfig<C>>().unwrap();
+            let mut storage = resource_set.borrow_mut::<ComponentStorage<C>>();
+            for &e in entities {
+                if let Some(c) = storage.remove(e.index()) {
+                    events.record(e, ComponentId::of::<C>(), ComponentEventKind::Removed);
+                    config.removed(e, &c);
+                }
+            }
+        }
+
+        fn memory<C>(resource_set: &mut ResourceSet) -> ComponentMemory
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let storage = resource_set.get_mut::<ComponentStorage<C>>();
+            ComponentMemory {
+                id: ComponentId::of::<C>(),
+                type_name: type_name::<C>(),
+                mask: storage.mask_memory_usage(),
+                storage: storage.raw_storage().memory_usage(),
+            }
+        }
+
+        fn maintain<C>(resource_set: &mut ResourceSet, config: &(dyn Any + Send + Sync))
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let config = config.downcast_ref::<ComponentConfig<C>>().unwrap();
+            if let Some(on_maintain) = &config.on_maintain {
+                on_maintain(resource_set.get_mut::<ComponentStorage<C>>());
+            }
+        }
+
+        fn merge<C>(resource_set: &mut ResourceSet, config: &(dyn Any + Send + Sync))
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let config = config.downcast_ref::<ComponentConfig<C>>().unwrap();
+            if let Some(clear_modified) = config.clear_modified {
+                clear_modified(resource_set.get_mut::<ComponentStorage<C>>());
+            }
+        }
+
+        fn get<C>(resource_set: &ResourceSet, index: Index) -> Option<DynComponentRef<'_>>
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let storage = resource_set.borrow_exclusive::<ComponentStorage<C>>();
+            if !storage.contains(index) {
+                return None;
+            }
+            Some(DynComponentRef {
+                storage: AtomicRefMut::map(storage, |s| s as &mut dyn Any),
+                index,
+                get: |storage, index| {
+                    storage
+                        .downcast_ref::<ComponentStorage<C>>()?
+                        .get(index)
+                        .map(|c| c as &dyn Any)
+                },
+            })
+        }
+
+        fn get_mut<C>(resource_set: &mut ResourceSet, index: Index) -> Option<&mut dyn Any>
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let storage = resource_set.get_mut::<ComponentStorage<C>>();
+            storage.get_mut(index).map(|c| c as &mut dyn Any)
+        }
+
+        fn reserve<C>(resource_set: &mut ResourceSet, len: Index)
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            resource_set.get_mut::<ComponentStorage<C>>().reserve(len);
+        }
+
+        fn shrink<C>(resource_set: &mut ResourceSet)
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            resource_set.get_mut::<ComponentStorage<C>>().shrink();
+        }
+
+        fn storage_report<C>(resource_set: &mut ResourceSet) -> (ComponentStorageReport, BitSet)
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let mask = resource_set.get_mut::<ComponentStorage<C>>().mask().clone();
+            let count = (&mask).iter().count();
+            let len = (&mask).iter().last().map(|i| i + 1).unwrap_or(0);
+            let size = mem::size_of::<C>();
+            let report = ComponentStorageReport {
+                id: ComponentId::of::<C>(),
+                type_name: type_name::<C>(),
+                kind: <C::Storage as RawStorage>::KIND,
+                size,
+                count,
+                len,
+                density: 0.0,
+                overlap: Vec::new(),
+                recommended: recommend_storage(size, count, len),
+                grouped: false,
+            };
+            (report, mask)
+        }
+
+        fn compact<C>(resource_set: &mut ResourceSet, moved: &[(Entity, Entity)])
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let storage = resource_set.get_mut::<ComponentStorage<C>>();
+            for &(old, new) in moved {
+                if let Some(c) = storage.remove(old.index()) {
+                    storage.insert(new.index(), c);
+                }
+            }
+        }
+
+        fn take_deferred_removals<C>(config: &mut (dyn Any + Send + Sync)) -> Vec<Entity>
+        where
+            C: Component + 'static,
+            C::Storage: Send,
+        {
+            let config = config.downcast_mut::<ComponentConfig<C>>().unwrap();
+            mem::take(config.deferred_removals.get_mut().unwrap())
+        }
+
+        ComponentRegistration {
+            info: ComponentInfo::of(&config),
+            config: Box::new(config),
+            remove: remove::<C>,
+            memory: memory::<C>,
+            maintain: maintain::<C>,
+            merge: merge::<C>,
+            get: get::<C>,
+            get_mut: get_mut::<C>,
+            reserve: reserve::<C>,
+            shrink: shrink::<C>,
+            storage_report: storage_report::<C>,
+            compact: compact::<C>,
+            take_deferred_removals: take_deferred_removals::<C>,
+        }
+    }
+
+    fn remove(&self, components: &ResourceSet, events: &ComponentEventLog, entities: &[Entity]) {
+        (self.remove)(components, &*self.config, events, entities);
+    }
+
+    fn maintain(&self, components: &mut ResourceSet) {
+        (self.maintain)(components, &*self.config);
+    }
+
+    fn take_deferred_removals(&mut self) -> Vec<Entity> {
+        (self.take_deferred_removals)(&mut *self.config)
+    }
+
+    fn merge(&self, components: &mut ResourceSet) {
+        (self.merge)(components, &*self.config);
+    }
+}
+
+// Keeps a `ComponentIndex` resource up to date from a reader of its tracked component.
+struct IndexRegistration {
+    component: ComponentId,
+    resource: ResourceId,
+    reader: ReaderId,
+    update: fn(&mut ResourceSet, &mut ResourceSet, ReaderId),
+    // Called once the component storage is replaced, with the previous storage if there was one.
+    // Returns the reader registered with the new storage.
+    rebuild: fn(&mut ResourceSet, &mut ResourceSet, Option<&mut dyn Any>, ReaderId) -> ReaderId,
+    remove: fn(&mut ResourceSet),
+}
+
+impl IndexRegistration {
+    fn new<I>(reader: ReaderId) -> Self
+    where
+        I: ComponentIndex,
+        I::Component: 'static,
+        <I::Component as Component>::Storage: TrackedStorage + Send,
+    {
+        fn update<I>(resources: &mut ResourceSet, components: &mut ResourceSet, reader: ReaderId)
+        where
+            I: ComponentIndex,
+            I::Component: 'static,
+            <I::Component as Component>::Storage: TrackedStorage + Send,
+        {
+            if !resources.contains::<I>() {
+                return;
+            }
+            let index = resources.get_mut::<I>();
+            let storage = components.get_mut::<ComponentStorage<I::Component>>();
+            for i in storage.reader_modified_indexes(reader).iter() {
+                index.update(i, storage.get(i));
+            }
+            storage.clear_reader_modified(reader);
+        }
+
+        // Tells the index that every component of the previous storage was removed, then gives
+        // it every component of the new storage.
+        fn rebuild<I>(
+            resources: &mut ResourceSet,
+            components: &mut ResourceSet,
+            previous: Option<&mut dyn Any>,
+            reader: ReaderId,
+        ) -> ReaderId
+        where
+            I: ComponentIndex,
+            I::Component: 'static,
+            <I::Component as Component>::Storage: TrackedStorage + Send,
+        {
+            let storage = components.get_mut::<ComponentStorage<I::Component>>();
+            let previous =
+                previous.and_then(|p| p.downcast_mut::<ComponentStorage<I::Component>>());
+            if let Some(previous) = previous {
+                if resources.contains::<I>() {
+                    let index = resources.get_mut::<I>();
+                    for i in previous.mask().iter() {
+                        index.update(i, None);
+                    }
+                }
+                previous.unregister_reader(reader);
+            }
+            if resources.contains::<I>() {
+                fill_index(resources.get_mut::<I>(), storage)
+            } else {
+                storage.set_track_modified(true);
+                storage.register_reader()
+            }
+        }
+
+        fn remove<I: ComponentIndex>(resources: &mut ResourceSet) {
+            resources.remove::<I>();
+        }
+
+        IndexRegistration {
+            component: ComponentId::of::<I::Component>(),
+            resource: ResourceId::of::<I>(),
+            reader,
+            update: update::<I>,
+            rebuild: rebuild::<I>,
+            remove: remove::<I>,
+        }
+    }
+
+    fn update(&self, resources: &mut ResourceSet, components: &mut ResourceSet) {
+        (self.update)(resources, components, self.reader);
+    }
+}
+
+// Turns on tracking and registers a reader for the index, then gives it every existing component.
+fn fill_index<I>(index: &mut I, storage: &mut ComponentStorage<I::Component>) -> ReaderId
+where
+    I: ComponentIndex,
+    <I::Component as Component>::Storage: TrackedStorage,
+{
+    storage.set_track_modified(true);
+    let reader = storage.register_reader();
+    for i in storage.mask().iter() {
+        index.update(i, storage.get(i));
+    }
+    reader
+}
+
+struct SoaRegistration {
+    remove: fn(&mut ResourceSet, &[Entity]),
+    compact: fn(&mut ResourceSet, &[(Entity, Entity)]),
+}
+
+impl SoaRegistration {
+    fn new<S: SoaStorage + Send + 'static>() -> Self {
+        fn remove<S: SoaStorage + Send + 'static>(resources: &mut ResourceSet, killed: &[Entity]) {
+            if resources.contains_local::<S>() {
+                resources.get_mut::<S>().remove_entities(killed);
+            }
+        }
+
+        fn compact<S: SoaStorage + Send + 'static>(
+            resources: &mut ResourceSet,
+            moved: &[(Entity, Entity)],
+        ) {
+            if resources.contains_local::<S>() {
+                resources.get_mut::<S>().move_entities(moved);
+            }
+        }
+
+        SoaRegistration {
+            remove: remove::<S>,
+            compact: compact::<S>,
+        }
+    }
+}
+
+struct GroupRegistration {
+    members: Vec<ComponentId>,
+    resource: ResourceId,
+    arrange: fn(&mut ResourceSet, &mut ResourceSet, bool),
+}
+
+impl GroupRegistration {
+    fn new<G: GroupMembers>() -> Self {
+        fn arrange<G: GroupMembers>(
+            resources: &mut ResourceSet,
+            components: &mut ResourceSet,
+            force: bool,
+        ) {
+            if resources.contains::<ComponentGroup<G>>() {
+                resources
+                    .get_mut::<ComponentGroup<G>>()
+                    .arrange(components, force);
+            }
+        }
+
+        GroupRegistration {
+            members: G::ids(),
+            resource: ResourceId::of::<ComponentGroup<G>>(),
+            arrange: arrange::<G>,
+        }
+    }
+
+    fn arrange(&self, resources: &mut ResourceSet, components: &mut ResourceSet, force: bool) {
+        (self.arrange)(resources, components, force);
+    }
+}
+
+/// A frame counter resource, inserted by `World::new` and incremented by every `World::merge`.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Tick(pub u64);
+
+impl Tick {
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Returns true once every `n` ticks, useful for systems which should only run every few
+    /// frames.
+    pub fn every(self, n: u64) -> bool {
+        self.0.is_multiple_of(n)
+    }
+
+    /// The number of ticks elapsed since the given earlier tick.
+    pub fn since(self, earlier: Tick) -> u64 {
+        self.0.wrapping_sub(earlier.0)
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("component {type_name:?} is already inserted")]
+pub struct ComponentAlreadyInserted {
+    pub type_name: &'static str,
+}
+
+impl ComponentAlreadyInserted {
+    pub fn of<C>() -> Self {
+        Self {
+            type_name: type_name::<C>(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("component {type_name:?} is not inserted")]
+pub struct ComponentNotInserted {
+    pub type_name: &'static str,
+}
+
+impl ComponentNotInserted {
+    pub fn of<C>() -> Self {
+        Self {
+            type_name: type_name::<C>(),
+        }
+    }
+}
+
+type ChildrenHook = dyn Fn(&World, Entity, &mut Vec<Entity>) + Send + Sync;
+
+// The result of a `MaskQuery`, along with the versions it was computed from.
+struct CachedMask {
+    live_version: u64,
+    mask_versions: Vec<MaskVersion>,
+    mask: Arc<BitSet>,
+}
+
+// A resource insertion or removal queued through a shared reference to the `World`.
+type DeferredOp = Box<dyn FnOnce(&mut World) + Send>;
+
 pub struct World {
     allocator: Allocator,
     resources: ResourceSet,
     components: ResourceSet,
-    remove_components: FxHashMap<TypeId, Box<dyn Fn(&ResourceSet, &[Entity]) + Send + Sync>>,
+    registered_components: FxHashMap<ComponentId, ComponentRegistration>,
+    // Every registered component, in the order they were first inserted.
+    component_order: Vec<ComponentId>,
+    // Components registered automatically through a shared reference, which are moved into
+    // `registered_components` by the next method which needs them and takes `&mut self`.
+    pending_components: Mutex<Vec<(ComponentId, ComponentRegistration)>>,
+    deferred_ops: Mutex<Vec<DeferredOp>>,
+    children_hook: Option<Box<ChildrenHook>>,
+    mask_cache: Mutex<FxHashMap<TypeId, CachedMask>>,
+    component_indexes: Vec<IndexRegistration>,
+    component_groups: Vec<GroupRegistration>,
+    keyed_resources: FxHashMap<&'static str, ResourceSet>,
+    dyn_components: FxHashMap<String, AtomicRefCell<DynStorage>>,
+    singletons: ResourceSet,
+    singleton_entities: FxHashMap<ResourceId, Entity>,
+    // The index of every live singleton entity, which `Entities` hides.
+    hidden_entities: BitSet,
+    registered_resources: FxHashMap<ResourceId, ResourceMemory>,
+    // Resources which `DefaultAccess` may have inserted through a shared reference, moved into
+    // `registered_resources` by `World::memory_report`.
+    default_resources: Mutex<FxHashMap<ResourceId, ResourceMemory>>,
+    component_events: ComponentEventLog,
+    frame_stats: FrameStats,
+    // The allocator's created and killed totals at the start of the current frame.
+    frame_stats_start: (u64, u64),
+    // Updates every `Events` channel inserted with `World::insert_events`.
+    event_updates: FxHashMap<TypeId, fn(&mut ResourceSet)>,
+    // Keeps every storage inserted with `World::insert_soa_storage` up to date.
+    soa_storages: FxHashMap<TypeId, SoaRegistration>,
     killed: Vec<Entity>,
 }
 
+impl Default for World {
+    fn default() -> Self {
+        World::new()
+    }
+}
+
 impl World {
     pub fn new() -> Self {
-        World {
+        let mut world = World {
             allocator: Allocator::new(),
             resources: ResourceSet::new(),
             components: ResourceSet::new(),
-            remove_components: FxHashMap::default(),
+            registered_components: FxHashMap::default(),
+            component_order: Vec::new(),
+            pending_components: Mutex::default(),
+            deferred_ops: Mutex::default(),
+            children_hook: None,
+            mask_cache: Mutex::default(),
+            component_indexes: Vec::new(),
+            component_groups: Vec::new(),
+            keyed_resources: FxHashMap::default(),
+            dyn_components: FxHashMap::default(),
+            singletons: ResourceSet::new(),
+            singleton_entities: FxHashMap::default(),
+            hidden_entities: BitSet::new(),
+            registered_resources: FxHashMap::default(),
+            default_resources: Mutex::default(),
+            component_events: ComponentEventLog::default(),
+            frame_stats: FrameStats::default(),
+            frame_stats_start: (0, 0),
+            event_updates: FxHashMap::default(),
+            soa_storages: FxHashMap::default(),
             killed: Vec::new(),
+        };
+        world.insert_resource(Tick::default());
+        world.insert_resource(FrameArena::new());
+        world
+    }
+
+    pub fn entities(&self) -> Entities<'_> {
+        Entities {
+            allocator: &self.allocator,
+            hidden: &self.hidden_entities,
         }
     }
 
-    pub fn entities(&self) -> Entities {
-        Entities(&self.allocator)
+    /// Add everything in a `Plugin` to this `World`, and its systems to the given schedule.
+    pub fn add_plugin<S: ?Sized>(&mut self, plugin: impl Plugin<S>, schedule: &mut S) {
+        plugin.build(self, schedule);
     }
 
+    /// # Panics
+    /// Panics if allocation fails, see `Allocator::try_allocate`.
     pub fn create_entity(&mut self) -> Entity {
         self.allocator.allocate()
     }
 
-    pub fn delete_entity(&mut self, e: Entity) -> Result<(), WrongGeneration> {
+    pub fn try_create_entity(&mut self) -> Result<Entity, AllocateError> {
+        self.allocator.try_allocate()
+    }
+
+    /// Reserve space for `additional` more entities, so that creating them in bulk does not
+    /// repeatedly grow the entity allocator or the component storages.
+    ///
+    /// Every registered component storage is given the chance to reserve space for the new entity
+    /// indexes as well, see `RawStorage::reserve`.
+    pub fn reserve_entities(&mut self, additional: Index) {
+        self.flush_pending_components();
+        self.allocator.reserve(additional);
+        let len = self.allocator.max_entity_count().saturating_add(additional);
+        for registration in self.registered_components.values() {
+            (registration.reserve)(&mut self.components, len);
+        }
+    }
+
+    /// Give every registered component storage the chance to release memory it no longer needs or
+    /// otherwise reorganize itself, see `RawStorage::shrink`.
+    ///
+    /// This can be slow, and is meant to be called occasionally for memory hygiene, such as between
+    /// levels.
+    pub fn compact_storages(&mut self) {
+        self.flush_pending_components();
+        for registration in self.registered_components.values() {
+            (registration.shrink)(&mut self.components);
+        }
+        for group in &self.component_groups {
+            group.arrange(&mut self.resources, &mut self.components, true);
+        }
+    }
+
+    /// Set the policy for entity indexes that have been re-used so many times that their generation
+    /// is exhausted.
+    pub fn set_generation_overflow(&mut self, policy: GenerationOverflow) {
+        self.allocator.set_generation_overflow(policy);
+    }
+
+    pub fn delete_entity(&mut self, e: Entity) -> Result<(), EntityError> {
         self.allocator.kill(e)?;
-        for remove_component in self.remove_components.values() {
-            remove_component(&self.components, &[e]);
+        self.hidden_entities.remove(e.index());
+        self.flush_pending_components();
+        for registration in self.registered_components.values() {
+            registration.remove(&self.components, &self.component_events, &[e]);
+        }
+        for storage in self.dyn_components.values_mut() {
+            storage.get_mut().remove(e.index());
+        }
+        for registration in self.soa_storages.values() {
+            (registration.remove)(&mut self.resources, &[e]);
+        }
+        Ok(())
+    }
+
+    /// Set the hook which `World::delete_entity_recursive` uses to find the children of an entity,
+    /// by pushing them onto the given `Vec`.
+    pub fn set_children_hook(
+        &mut self,
+        f: impl Fn(&World, Entity, &mut Vec<Entity>) + Send + Sync + 'static,
+    ) {
+        self.children_hook = Some(Box::new(f));
+    }
+
+    /// Delete an entity together with all of its descendants, as found by the hook set with
+    /// `World::set_children_hook`.
+    ///
+    /// Without a children hook, only the given entity is deleted.  Descendants which are already
+    /// dead are skipped.
+    pub fn delete_entity_recursive(&mut self, e: Entity) -> Result<(), EntityError> {
+        self.allocator.check(e)?;
+        let descendants = match &self.children_hook {
+            Some(hook) => descendants(e, |e, children| hook(self, e, children)),
+            None => vec![e],
+        };
+        for e in descendants {
+            let _ = self.delete_entity(e);
         }
         Ok(())
     }
@@ -60,16 +785,108 @@ impl World {
     where
         R: Send + 'static,
     {
+        self.registered_resources
+            .insert(ResourceId::of::<R>(), resource_memory::<R>());
         self.resources.insert(r)
     }
 
+    /// Insert an empty `Events<T>` channel, which is updated by every call to `World::merge`.
+    ///
+    /// Does nothing if the channel is already inserted.  If the channel is later removed, it is no
+    /// longer updated.
+    pub fn insert_events<T>(&mut self)
+    where
+        T: Send + 'static,
+    {
+        fn update<T: Send + 'static>(resources: &mut ResourceSet) {
+            if resources.contains_local::<Events<T>>() {
+                resources.get_mut::<Events<T>>().update();
+            }
+        }
+
+        if !self.resources.contains_local::<Events<T>>() {
+            self.insert_resource(Events::<T>::new());
+            self.event_updates.insert(TypeId::of::<T>(), update::<T>);
+        }
+    }
+
+    /// Insert an empty structure-of-arrays storage generated by `soa_component!` as a resource.
+    ///
+    /// The values of deleted entities are removed from the storage by `World::delete_entity` and
+    /// `World::merge`, and values are moved to their entity's new index by `World::compact`.
+    /// Does nothing if the storage is already inserted.  If the storage is later removed, it is no
+    /// longer updated.
+    pub fn insert_soa_storage<S>(&mut self)
+    where
+        S: SoaStorage + Send + 'static,
+    {
+        if !self.resources.contains_local::<S>() {
+            self.insert_resource(S::default());
+            self.soa_storages
+                .insert(TypeId::of::<S>(), SoaRegistration::new::<S>());
+        }
+    }
+
+    /// Queue an arbitrary operation on the `World`, which is applied at the end of the next call to
+    /// `World::merge`.
+    ///
+    /// This only needs a shared reference to the `World`, and is what `DeferredResources` and
+    /// `Commands` are built on.
+    pub fn defer(&self, op: impl FnOnce(&mut World) + Send + 'static) {
+        self.deferred_ops.lock().unwrap().push(Box::new(op));
+    }
+
+    /// Queue the insertion of a resource on the next call to `World::merge`, replacing any resource
+    /// of the same type.
+    ///
+    /// This only needs a shared reference to the `World`, so systems can install resources lazily,
+    /// see `DeferredResources`.
+    pub fn insert_resource_deferred<R>(&self, r: R)
+    where
+        R: Send + 'static,
+    {
+        self.defer(move |world| {
+            world.insert_resource(r);
+        });
+    }
+
+    /// Queue the removal of a resource on the next call to `World::merge`.
+    pub fn remove_resource_deferred<R>(&self)
+    where
+        R: Send + 'static,
+    {
+        self.defer(|world| {
+            world.remove_resource::<R>();
+        });
+    }
+
     pub fn remove_resource<R>(&mut self) -> Option<R>
     where
         R: Send + 'static,
     {
+        self.registered_resources.remove(&ResourceId::of::<R>());
+        self.default_resources
+            .get_mut()
+            .unwrap()
+            .remove(&ResourceId::of::<R>());
         self.resources.remove::<R>()
     }
 
+    // Record a resource which `DefaultAccess` is about to insert if it does not exist, so that
+    // `World::memory_report` includes it.
+    fn register_default_resource<R: 'static>(&self) {
+        if !self
+            .registered_resources
+            .contains_key(&ResourceId::of::<R>())
+        {
+            self.default_resources
+                .lock()
+                .unwrap()
+                .entry(ResourceId::of::<R>())
+                .or_insert_with(resource_memory::<R>);
+        }
+    }
+
     pub fn contains_resource<T>(&self) -> bool
     where
         T: Send + 'static,
@@ -81,7 +898,7 @@ impl World {
     ///
     /// # Panics
     /// Panics if the resource has not been inserted or is already borrowed mutably.
-    pub fn read_resource<R>(&self) -> ReadResource<R>
+    pub fn read_resource<R>(&self) -> ReadResource<'_, R>
     where
         R: Send + Sync + 'static,
     {
@@ -92,168 +909,1463 @@ impl World {
     ///
     /// # Panics
     /// Panics if the resource has not been inserted or is already borrowed.
-    pub fn write_resource<R>(&self) -> WriteResource<R>
+    pub fn write_resource<R>(&self) -> WriteResource<'_, R>
     where
         R: Send + 'static,
     {
         ResourceAccess(self.resources.borrow_mut())
     }
 
+    /// Turn modification tracking on or off for the given resource, see
+    /// `ResourceSet::set_track_modified`.
+    ///
+    /// The modified flag of every resource is cleared on `World::merge`, except for resources
+    /// modified by operations queued with `World::defer`, see `World::merge`.
+    ///
     /// # Panics
-    /// Panics if the resource has not been inserted.
-    pub fn get_resource_mut<R>(&mut self) -> &mut R
+    /// Panics if the resource has not been inserted into this `World`.
+    pub fn set_track_resource_modified<R>(&mut self, track: bool)
     where
         R: Send + 'static,
     {
-        self.resources.get_mut()
+        self.resources.set_track_modified::<R>(track);
     }
 
-    /// Insert a new, fresh storage for the given component.
-    ///
-    /// If the component was already inserted, this will clear the storage for the component first.
-    pub fn insert_component<C>(&mut self) -> Option<ComponentStorage<C>>
+    /// Returns true if the given resource has been borrowed mutably (or explicitly marked) since
+    /// the last `World::merge`.
+    pub fn is_resource_modified<R>(&self) -> bool
     where
-        C: Component + 'static,
-        C::Storage: Default + Send,
+        R: Send + 'static,
     {
-        self.remove_components.insert(
-            TypeId::of::<C>(),
-            Box::new(|resource_set, entities| {
-                let mut storage = resource_set.borrow_mut::<ComponentStorage<C>>();
-                for e in entities {
-                    storage.remove(e.index());
-                }
-            }),
-        );
-        self.components.insert(ComponentStorage::<C>::default())
+        self.resources.is_modified::<R>()
     }
 
-    /// Remove storage for the given component.
-    pub fn remove_component<C>(&mut self) -> Option<ComponentStorage<C>>
+    /// # Panics
+    /// Panics if the resource has not been inserted.
+    pub fn mark_resource_modified<R>(&self)
     where
-        C: Component + 'static,
-        C::Storage: Default + Send,
+        R: Send + 'static,
     {
-        self.remove_components.remove(&TypeId::of::<C>());
-        self.components.remove::<ComponentStorage<C>>()
+        self.resources.mark_modified::<R>();
     }
 
-    pub fn contains_component<C>(&self) -> bool
+    pub fn clear_resource_modified<R>(&self)
     where
-        C: Component + 'static,
-        C::Storage: Send,
+        R: Send + 'static,
     {
-        self.components.contains::<ComponentStorage<C>>()
+        self.resources.clear_modified::<R>();
     }
 
-    /// Borrow the given component immutably.
+    /// Set a shared parent `ResourceSet` which is searched for any resource not present in this
+    /// `World`, returning the previous parent.
     ///
+    /// This allows, for example, a world per level to transparently read global resources like
+    /// configuration without copying them in.  Resources in the parent can be fetched and borrowed
+    /// like any other resource, but `World::get_resource_mut` and `World::remove_resource` only
+    /// ever affect resources inserted into this `World`.
+    pub fn set_parent_resources(
+        &mut self,
+        parent: Option<Arc<ResourceSet>>,
+    ) -> Option<Arc<ResourceSet>> {
+        self.resources.set_parent(parent)
+    }
+
     /// # Panics
-    /// Panics if the component has not been inserted or is already borrowed mutably.
-    pub fn read_component<C>(&self) -> ReadComponent<C>
+    /// Panics if the resource has not been inserted into this `World`.
+    pub fn get_resource_mut<R>(&mut self) -> &mut R
     where
-        C: Component + 'static,
-        C::Storage: Send + Sync,
+        R: Send + 'static,
     {
-        ComponentAccess {
-            storage: self.components.borrow(),
-            entities: self.entities(),
-            marker: PhantomData,
-        }
+        self.resources.get_mut()
     }
 
-    /// Borrow the given component mutably.
+    /// Insert a resource under the given key, returning the previous resource of the same type and
+    /// key if there was one.
     ///
-    /// # Panics
-    /// Panics if the component has not been inserted or is already borrowed.
-    pub fn write_component<C>(&self) -> WriteComponent<C>
+    /// Keyed resources allow several independent instances of the same type to coexist, and are
+    /// checked for conflicts under their own `WorldResourceId::Keyed` id, separately from each
+    /// other and from any un-keyed resource of the same type.
+    pub fn insert_resource_keyed<R>(&mut self, key: &'static str, r: R) -> Option<R>
     where
-        C: Component + 'static,
+        R: Send + 'static,
+    {
+        self.keyed_resources.entry(key).or_default().insert(r)
+    }
+
+    pub fn remove_resource_keyed<R>(&mut self, key: &'static str) -> Option<R>
+    where
+        R: Send + 'static,
+    {
+        self.keyed_resources.get_mut(key)?.remove::<R>()
+    }
+
+    pub fn contains_resource_keyed<R>(&self, key: &'static str) -> bool
+    where
+        R: Send + 'static,
+    {
+        self.keyed_resources
+            .get(key)
+            .is_some_and(|resources| resources.contains::<R>())
+    }
+
+    /// Borrow the resource with the given key immutably.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted or is already borrowed mutably.
+    pub fn read_resource_keyed<R>(&self, key: &'static str) -> AtomicRef<'_, R>
+    where
+        R: Send + Sync + 'static,
+    {
+        self.expect_keyed_resources(key).borrow()
+    }
+
+    /// Borrow the resource with the given key mutably.
+    ///
+    /// # Panics
+    /// Panics if the resource has not been inserted or is already borrowed.
+    pub fn write_resource_keyed<R>(&self, key: &'static str) -> AtomicRefMut<'_, R>
+    where
+        R: Send + 'static,
+    {
+        self.expect_keyed_resources(key).borrow_mut()
+    }
+
+    fn try_keyed_resources(&self, key: &'static str) -> Result<&ResourceSet, FetchError> {
+        self.keyed_resources
+            .get(key)
+            .ok_or(FetchError::Missing(key))
+    }
+
+    fn expect_keyed_resources(&self, key: &'static str) -> &ResourceSet {
+        if let Some(resources) = self.keyed_resources.get(key) {
+            resources
+        } else {
+            panic!("no resources with key {:?}", key);
+        }
+    }
+
+    /// Insert an empty dynamic component with the given name, returns false if a dynamic component
+    /// with this name was already present.
+    ///
+    /// Dynamic components hold a `DynValue` per entity, and are identified only by name at
+    /// runtime, so that scripts can attach ad-hoc data to entities.  They are removed along with
+    /// their entity like any other component.
+    pub fn insert_dyn_component(&mut self, name: impl Into<String>) -> bool {
+        let mut inserted = false;
+        self.dyn_components.entry(name.into()).or_insert_with(|| {
+            inserted = true;
+            let mut storage = DynStorage::default();
+            storage.set_track_modified(true);
+            AtomicRefCell::new(storage)
+        });
+        inserted
+    }
+
+    pub fn remove_dyn_component(&mut self, name: &str) -> Option<DynStorage> {
+        self.dyn_components
+            .remove(name)
+            .map(AtomicRefCell::into_inner)
+    }
+
+    pub fn contains_dyn_component(&self, name: &str) -> bool {
+        self.dyn_components.contains_key(name)
+    }
+
+    /// The names of every dynamic component, in no particular order.
+    pub fn dyn_component_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.dyn_components.keys().map(String::as_str)
+    }
+
+    /// # Panics
+    /// Panics if the dynamic component has not been inserted or is already borrowed mutably.
+    pub fn read_dyn_component(&self, name: &str) -> ReadDyn<'_> {
+        DynAccess {
+            entities: self.entities(),
+            storage: self.expect_dyn_component(name).borrow(),
+        }
+    }
+
+    /// # Panics
+    /// Panics if the dynamic component has not been inserted or is already borrowed.
+    pub fn write_dyn_component(&self, name: &str) -> WriteDyn<'_> {
+        DynAccess {
+            entities: self.entities(),
+            storage: self.expect_dyn_component(name).borrow_mut(),
+        }
+    }
+
+    fn expect_dyn_component(&self, name: &str) -> &AtomicRefCell<DynStorage> {
+        if let Some(storage) = self.dyn_components.get(name) {
+            storage
+        } else {
+            panic!("no such dynamic component {:?}", name);
+        }
+    }
+
+    /// Insert the single instance of a singleton type, returning the previous instance if there was
+    /// one.
+    ///
+    /// A singleton is similar to a resource, but it is tied to a hidden entity which is created
+    /// when the singleton is first inserted, so that regular components can be attached to it as
+    /// well.  The hidden entity is alive, but it is skipped by joins over `Entities` and is not
+    /// counted by `Entities::alive_count`.  Singletons are checked for conflicts under their own `WorldResourceId::Singleton`
+    /// id, separately from any resource of the same type.
+    pub fn insert_singleton<C>(&mut self, c: C) -> Option<C>
+    where
+        C: Send + 'static,
+    {
+        if !self.singleton_entities.contains_key(&ResourceId::of::<C>()) {
+            let entity = self.create_entity();
+            self.singleton_entities
+                .insert(ResourceId::of::<C>(), entity);
+            self.hidden_entities.add(entity.index());
+        }
+        self.singletons.insert(c)
+    }
+
+    /// Remove the given singleton, deleting its entity along with any components attached to it.
+    pub fn remove_singleton<C>(&mut self) -> Option<C>
+    where
+        C: Send + 'static,
+    {
+        if let Some(entity) = self.singleton_entities.remove(&ResourceId::of::<C>()) {
+            // The entity may have been deleted already, in which case there is nothing to do.
+            let _ = self.delete_entity(entity);
+        }
+        self.singletons.remove()
+    }
+
+    pub fn contains_singleton<C>(&self) -> bool
+    where
+        C: Send + 'static,
+    {
+        self.singletons.contains::<C>()
+    }
+
+    /// The entity the given singleton is tied to.
+    pub fn singleton_entity<C>(&self) -> Option<Entity>
+    where
+        C: 'static,
+    {
+        self.singleton_entities.get(&ResourceId::of::<C>()).copied()
+    }
+
+    /// Borrow the given singleton immutably.
+    ///
+    /// # Panics
+    /// Panics if the singleton has not been inserted or is already borrowed mutably.
+    pub fn read_singleton<C>(&self) -> ReadSingleton<'_, C>
+    where
+        C: Send + Sync + 'static,
+    {
+        SingletonAccess {
+            entity: self.expect_singleton_entity::<C>(),
+            value: self.singletons.borrow(),
+        }
+    }
+
+    /// Borrow the given singleton mutably.
+    ///
+    /// # Panics
+    /// Panics if the singleton has not been inserted or is already borrowed.
+    pub fn write_singleton<C>(&self) -> WriteSingleton<'_, C>
+    where
+        C: Send + 'static,
+    {
+        SingletonAccess {
+            entity: self.expect_singleton_entity::<C>(),
+            value: self.singletons.borrow_mut(),
+        }
+    }
+
+    fn try_singleton_entity<C: 'static>(&self) -> Result<Entity, FetchError> {
+        self.singleton_entity::<C>()
+            .ok_or(FetchError::Missing(type_name::<C>()))
+    }
+
+    fn expect_singleton_entity<C: 'static>(&self) -> Entity {
+        if let Some(entity) = self.singleton_entity::<C>() {
+            entity
+        } else {
+            panic!("no such singleton {:?}", type_name::<C>());
+        }
+    }
+
+    /// Insert a new, fresh storage for the given component.
+    ///
+    /// If the component was already inserted, returns an error and leaves the existing storage
+    /// untouched, use `World::replace_component_storage` to replace an existing storage.
+    pub fn insert_component<C>(&mut self) -> Result<(), ComponentAlreadyInserted>
+    where
+        C: Component + 'static,
+        C::Storage: Default + Send,
+    {
+        self.insert_component_with_config::<C>(ComponentConfig::default())
+    }
+
+    /// Insert a new, fresh storage for the given component with the provided per-component
+    /// configuration.
+    ///
+    /// If the component was already inserted, returns an error and leaves the existing storage and
+    /// configuration untouched.
+    pub fn insert_component_with_config<C>(
+        &mut self,
+        config: ComponentConfig<C>,
+    ) -> Result<(), ComponentAlreadyInserted>
+    where
+        C: Component + 'static,
+        C::Storage: Default + Send,
+    {
+        self.insert_component_with_storage_and_config(C::Storage::default(), config)
+    }
+
+    /// Insert a new, fresh storage for the given tracked component, with modification tracking
+    /// already turned on.
+    ///
+    /// Shorthand for inserting the component with `ComponentConfig::track_modified`.
+    pub fn insert_component_tracked<C>(&mut self) -> Result<(), ComponentAlreadyInserted>
+    where
+        C: Component + 'static,
+        C::Storage: TrackedStorage + Default + Send,
+    {
+        self.insert_component_with_config::<C>(ComponentConfig::new().track_modified())
+    }
+
+    /// Insert storage for the given component from an already constructed `RawStorage`.
+    ///
+    /// This allows registering storages which are not `Default` or which need construction
+    /// parameters.  The given storage must be empty, see `MaskedStorage::new`.
+    ///
+    /// If the component was already inserted, returns an error and leaves the existing storage
+    /// untouched.
+    pub fn insert_component_with<C>(
+        &mut self,
+        storage: C::Storage,
+    ) -> Result<(), ComponentAlreadyInserted>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.insert_component_with_storage_and_config::<C>(storage, ComponentConfig::default())
+    }
+
+    /// Insert storage for the given component from an already constructed `RawStorage` with the
+    /// provided per-component configuration.
+    pub fn insert_component_with_storage_and_config<C>(
+        &mut self,
+        storage: C::Storage,
+        config: ComponentConfig<C>,
+    ) -> Result<(), ComponentAlreadyInserted>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.flush_pending_components();
+        if self.contains_component::<C>() {
+            return Err(ComponentAlreadyInserted::of::<C>());
+        }
+        let mut storage = ComponentStorage::<C>::new(storage);
+        if let Some(track_modified) = config.track_modified {
+            track_modified(&mut storage);
+        }
+        self.register_component(config);
+        self.components.insert(storage);
+        Ok(())
+    }
+
+    /// Replace the storage for the given component, returning the previous storage if there was
+    /// one.
+    ///
+    /// No `ComponentConfig::on_remove` callbacks are called for the components in the previous
+    /// storage.  If the component was not already inserted, it is inserted with the default
+    /// configuration, otherwise the existing configuration is kept.
+    ///
+    /// Every `ComponentIndex` of the component is rebuilt from the new storage, as though every
+    /// component of the previous storage was removed and every component of the new storage was
+    /// inserted.
+    pub fn replace_component_storage<C>(
+        &mut self,
+        storage: ComponentStorage<C>,
+    ) -> Option<ComponentStorage<C>>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.flush_pending_components();
+        if !self.contains_component::<C>() {
+            self.register_component::<C>(ComponentConfig::default());
+        }
+        let mut previous = self.components.insert(storage);
+        for registration in &mut self.component_indexes {
+            if registration.component == ComponentId::of::<C>() {
+                registration.reader = (registration.rebuild)(
+                    &mut self.resources,
+                    &mut self.components,
+                    previous.as_mut().map(|p| p as &mut dyn Any),
+                    registration.reader,
+                );
+            }
+        }
+        previous
+    }
+
+    fn register_component<C>(&mut self, config: ComponentConfig<C>)
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        if self
+            .registered_components
+            .insert(ComponentId::of::<C>(), ComponentRegistration::new(config))
+            .is_none()
+        {
+            self.component_order.push(ComponentId::of::<C>());
+        }
+    }
+
+    /// Remove storage for the given component.
+    ///
+    /// Every `ComponentIndex` of the component is removed as well.
+    pub fn remove_component<C>(&mut self) -> Option<ComponentStorage<C>>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.flush_pending_components();
+        if self
+            .registered_components
+            .remove(&ComponentId::of::<C>())
+            .is_some()
+        {
+            self.component_order
+                .retain(|&id| id != ComponentId::of::<C>());
+        }
+        for registration in &self.component_indexes {
+            if registration.component == ComponentId::of::<C>() {
+                self.registered_resources.remove(&registration.resource);
+                (registration.remove)(&mut self.resources);
+            }
+        }
+        self.component_indexes
+            .retain(|r| r.component != ComponentId::of::<C>());
+        self.component_groups
+            .retain(|r| !r.members.contains(&ComponentId::of::<C>()));
+        self.components.remove::<ComponentStorage<C>>()
+    }
+
+    /// Insert a `ComponentIndex` as a resource, and keep it up to date with every change to its
+    /// component during `World::merge`.
+    ///
+    /// The index is immediately given every existing component.  Modification tracking is turned
+    /// on for the component, and the index uses its own reader (see
+    /// `TrackedStorage::register_reader`), so it does not interfere with other consumers of the
+    /// modified set.  If an index of the same type was already inserted, it is replaced.
+    ///
+    /// Returns an error if the component has not been inserted.
+    pub fn insert_component_index<I>(&mut self, mut index: I) -> Result<(), ComponentNotInserted>
+    where
+        I: ComponentIndex,
+        I::Component: 'static,
+        <I::Component as Component>::Storage: TrackedStorage + Send,
+    {
+        self.flush_pending_components();
+        if !self.contains_component::<I::Component>() {
+            return Err(ComponentNotInserted::of::<I::Component>());
+        }
+        let storage = self.components.get_mut::<ComponentStorage<I::Component>>();
+        for registration in &self.component_indexes {
+            if registration.resource == ResourceId::of::<I>() {
+                storage.unregister_reader(registration.reader);
+            }
+        }
+        self.component_indexes
+            .retain(|r| r.resource != ResourceId::of::<I>());
+
+        let reader = fill_index(&mut index, storage);
+        self.component_indexes
+            .push(IndexRegistration::new::<I>(reader));
+        self.insert_resource(index);
+        Ok(())
+    }
+
+    /// Insert a `ComponentGroup` as a resource, and keep the storages of its members arranged by
+    /// archetype during `World::merge`.
+    ///
+    /// Entities which have exactly the same members of the group are stored together and in the
+    /// same order in the dense slice of every member, so iterating over an archetype is a walk over
+    /// aligned slices rather than a join.  Every member stays an ordinary component with the usual
+    /// sparse API.  If the same group was already inserted, it is replaced.
+    ///
+    /// Rearranging only happens when a member has had a component inserted or removed, so this
+    /// suits components which are changed much more often than they are added or removed.
+    ///
+    /// # Panics
+    /// Panics if any of the components has not been inserted, is repeated, or is already a member
+    /// of another group.
+    pub fn insert_component_group<G: GroupMembers>(&mut self) {
+        self.flush_pending_components();
+        self.component_groups
+            .retain(|r| r.resource != ResourceId::of::<ComponentGroup<G>>());
+
+        let members = G::ids();
+        for (i, id) in members.iter().enumerate() {
+            assert!(
+                self.registered_components.contains_key(id),
+                "member {} of {} has not been inserted",
+                i,
+                type_name::<G>(),
+            );
+            assert!(
+                !members[..i].contains(id),
+                "member {} of {} is repeated",
+                i,
+                type_name::<G>(),
+            );
+            assert!(
+                !self.component_groups.iter().any(|r| r.members.contains(id)),
+                "member {} of {} is already a member of another group",
+                i,
+                type_name::<G>(),
+            );
+        }
+
+        let mut group = ComponentGroup::<G>::new();
+        group.arrange(&mut self.components, true);
+        self.component_groups.push(GroupRegistration::new::<G>());
+        self.insert_resource(group);
+    }
+
+    pub fn contains_component<C>(&self) -> bool
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.components.contains::<ComponentStorage<C>>()
+    }
+
+    /// Borrow the given component immutably.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted or is already borrowed mutably.
+    pub fn read_component<C>(&self) -> ReadComponent<'_, C>
+    where
+        C: Component + 'static,
+        C::Storage: Send + Sync,
+    {
+        ComponentAccess {
+            storage: self.components.borrow(),
+            entities: self.entities(),
+            config: self.component_config(),
+            events: &self.component_events,
+        }
+    }
+
+    /// Borrow the given component mutably.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted or is already borrowed.
+    pub fn write_component<C>(&self) -> WriteComponent<'_, C>
+    where
+        C: Component + 'static,
         C::Storage: Send,
     {
         ComponentAccess {
             storage: self.components.borrow_mut(),
             entities: self.entities(),
+            config: self.component_config(),
+            events: &self.component_events,
+        }
+    }
+
+    fn try_read_component<C>(&self) -> Result<ReadComponent<'_, C>, FetchError>
+    where
+        C: Component + 'static,
+        C::Storage: Send + Sync,
+    {
+        Ok(ComponentAccess {
+            storage: self
+                .components
+                .try_borrow()
+                .map_err(component_fetch_error::<C>)?,
+            entities: self.entities(),
+            config: self.component_config(),
+            events: &self.component_events,
+        })
+    }
+
+    /// # Panics
+    /// Panics if the component has not been inserted.
+    pub fn get_component_mut<C>(&mut self) -> ComponentAccess<'_, C, &mut ComponentStorage<C>>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        self.flush_pending_components();
+        ComponentAccess {
+            storage: self.components.get_mut(),
+            entities: Entities {
+                allocator: &self.allocator,
+                hidden: &self.hidden_entities,
+            },
+            config: Self::config_in(&self.registered_components),
+            events: &self.component_events,
+        }
+    }
+
+    /// Mutably borrow the storages of several different components at once, such as
+    /// `world.split_components::<(A, B)>()`.
+    ///
+    /// Since this requires `&mut self`, it does not touch any borrow flags at all, which is
+    /// useful in code that already has exclusive access to the `World`, like loading or editor
+    /// tooling.
+    ///
+    /// # Panics
+    /// Panics if any of the components has not been inserted, or if the same component is given
+    /// more than once.
+    pub fn split_components<'a, T>(&'a mut self) -> T::Access
+    where
+        T: SplitComponents<'a>,
+    {
+        self.flush_pending_components();
+        T::split(self)
+    }
+
+    /// Produce a hash of the state of the entity allocator and of every component registered in
+    /// the given `NetSync`.
+    ///
+    /// Components are hashed in registration order, using their `SyncComponent` encoding, in
+    /// entity index order, so the hash is stable across runs and platforms. Two simulations which
+    /// are supposed to be running in lockstep can compare their hashes to detect a desync.
+    ///
+    /// Should be called after `World::merge`, since atomically deleted entities are still live
+    /// until then.
+    pub fn state_hash(&self, registry: &NetSync) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write_u32(self.allocator.max_entity_count());
+        hasher.write_u32(self.allocator.alive_count());
+        for entity in self.allocator.iter() {
+            hasher.write_u32(entity.index());
+            hasher.write_u32(entity.generation());
+        }
+        registry.hash_components(self, &mut hasher);
+        hasher.finish()
+    }
+
+    /// Evaluate a boolean expression over component masks, such as
+    /// `World::query_mask::<(With<A>, Without<B>)>()`, returning the matching live entities.
+    ///
+    /// Only the masks of the components are borrowed, and only for the duration of this call, so
+    /// the returned `BitSet` can be used freely to constrain later joins.
+    ///
+    /// # Panics
+    /// Panics if any component in the expression is not inserted or is borrowed mutably.
+    pub fn query_mask<Q: MaskQuery>(&self) -> BitSet {
+        let mut mask = BitSet::new();
+        mask |= &self.allocator.live_bitset();
+        Q::restrict(self, &mut mask);
+        mask
+    }
+
+    /// Like `World::query_mask`, but the result is cached in the `World` and only recomputed once
+    /// a component in the expression has a component inserted or removed, or an entity is created
+    /// or deleted.
+    ///
+    /// This is useful for expressions over large storages which are evaluated many times between
+    /// structural changes.
+    ///
+    /// # Panics
+    /// Panics if any component in the expression is not inserted or is borrowed mutably.
+    pub fn query_mask_cached<Q: MaskQuery + 'static>(&self) -> Arc<BitSet> {
+        let live_version = self.allocator.live_version();
+        let mut mask_versions = Vec::new();
+        Q::mask_versions(self, &mut mask_versions);
+
+        let mut cache = self.mask_cache.lock().unwrap();
+        if let Some(cached) = cache.get(&TypeId::of::<Q>()) {
+            if cached.live_version == live_version && cached.mask_versions == mask_versions {
+                return Arc::clone(&cached.mask);
+            }
+        }
+
+        let mask = Arc::new(self.query_mask::<Q>());
+        cache.insert(
+            TypeId::of::<Q>(),
+            CachedMask {
+                live_version,
+                mask_versions,
+                mask: Arc::clone(&mask),
+            },
+        );
+        mask
+    }
+
+    /// Drop every result cached by `World::query_mask_cached`.
+    pub fn clear_mask_cache(&mut self) {
+        self.mask_cache.get_mut().unwrap().clear();
+    }
+
+    /// The current `MaskVersion` of the given component's storage.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted or is borrowed mutably.
+    pub fn component_mask_version<C>(&self) -> MaskVersion
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        // Safe because `MaskVersion` is `Copy` plain data which is `Sync`.
+        *unsafe {
+            self.components
+                .borrow_projected(|storage: &ComponentStorage<C>| &storage.mask_version)
+        }
+    }
+
+    /// Borrow only the mask of the given component, which records which entities have the
+    /// component.
+    ///
+    /// This does not provide any access to the component values themselves, so unlike
+    /// `World::read_component` it does not require the component storage to be `Sync`.
+    ///
+    /// # Panics
+    /// Panics if the component has not been inserted or is already borrowed mutably.
+    pub fn has_component<C>(&self) -> HasComponent<'_, C>
+    where
+        C: Component + 'static,
+        C::Storage: Send,
+    {
+        HasComponent {
+            // Safe because `MaskedStorage::mask` only accesses the `BitSet` mask, which is `Sync`.
+            mask: unsafe {
+                self.components
+                    .borrow_projected(|storage: &ComponentStorage<C>| storage.mask())
+            },
             marker: PhantomData,
         }
     }
 
+    fn component_config<C>(&self) -> &ComponentConfig<C>
+    where
+        C: Component + 'static,
+    {
+        if !self
+            .registered_components
+            .contains_key(&ComponentId::of::<C>())
+        {
+            let pending = self.pending_components.lock().unwrap();
+            if let Some((_, registration)) =
+                pending.iter().find(|(id, _)| *id == ComponentId::of::<C>())
+            {
+                let config = registration.config.downcast_ref().unwrap();
+                // Safe because every config is boxed, and pending registrations are only ever
+                // moved or dropped through `&mut self`.
+                return unsafe { &*(config as *const ComponentConfig<C>) };
+            }
+        }
+        Self::config_in(&self.registered_components)
+    }
+
+    /// Insert an empty storage for the given component with the default configuration if it is
+    /// not already inserted.
+    ///
+    /// Unlike `World::insert_component`, this only needs a shared reference, so it can be called
+    /// while other components are borrowed, such as from inside a system.  Components registered
+    /// this way are visible to every method immediately, but are not included in
+    /// `World::component_info` until the next call to a method which takes `&mut self`.
+    ///
+    /// This is how `AutoRegister` registers components on first access.
+    pub fn ensure_component<C>(&self)
+    where
+        C: Component + 'static,
+        C::Storage: Default + Send,
+    {
+        if self.contains_component::<C>() {
+            return;
+        }
+
+        // The storage is inserted while the lock is held, so that no other thread can see the
+        // pending registration without the storage.
+        let mut pending = self.pending_components.lock().unwrap();
+        if pending.iter().all(|(id, _)| *id != ComponentId::of::<C>()) {
+            pending.push((
+                ComponentId::of::<C>(),
+                ComponentRegistration::new(ComponentConfig::<C>::default()),
+            ));
+            self.components
+                .insert_shared(ComponentStorage::<C>::default);
+        }
+    }
+
+    fn flush_pending_components(&mut self) {
+        for (id, registration) in self.pending_components.get_mut().unwrap().drain(..) {
+            self.registered_components.insert(id, registration);
+            self.component_order.push(id);
+        }
+    }
+
+    fn config_in<C>(
+        registered_components: &FxHashMap<ComponentId, ComponentRegistration>,
+    ) -> &ComponentConfig<C>
+    where
+        C: Component + 'static,
+    {
+        if let Some(registration) = registered_components.get(&ComponentId::of::<C>()) {
+            registration.config.downcast_ref().unwrap()
+        } else {
+            panic!("no such component {:?}", type_name::<C>());
+        }
+    }
+
+    /// Fetch the given `FetchResources`.
+    ///
+    /// In debug builds, this first checks `F` for internal resource conflicts, such as
+    /// `(ReadComponent<A>, WriteComponent<A>)`.
+    ///
+    /// # Panics
+    /// Panics if any of the resources cannot be borrowed, and in debug builds, if `F` has an
+    /// internal resource conflict.
+    pub fn fetch<'a, F>(&'a self) -> F
+    where
+        F: FetchResources<'a, Self>,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
+        F::fetch(self)
+    }
+
+    /// Fetch `F` without counting any borrows, which removes the atomic operations from every
+    /// fetch.
+    ///
+    /// This is meant for hot paths which already guarantee that no two borrows conflict, such as
+    /// systems run by a schedule which checks their resources against each other.  With
+    /// `debug_assertions` on, `F::Checked` is still checked for internal conflicts, and is
+    /// briefly fetched to check that nothing else currently holds a conflicting borrow.
+    ///
+    /// Only resources and components stored directly in the world are visible.  Resources of a
+    /// parent `ResourceSet`, and components registered through a shared reference with
+    /// `World::ensure_component`, are not found until the next call to a method which takes
+    /// `&mut self`.
+    ///
+    /// # Safety
+    /// For as long as the returned value lives, no resource or component which `F` writes may be
+    /// borrowed by anything else, and no resource or component which `F` reads may be borrowed
+    /// mutably by anything else.  This includes borrows made through checked fetches, which
+    /// cannot see the borrows made here, and `F` itself must not contain conflicting borrows.
+    ///
+    /// # Panics
+    /// Panics if any of the resources or components have not been inserted.
+    pub unsafe fn fetch_unchecked<'a, F>(&'a self) -> F
+    where
+        F: FetchUnchecked<'a>,
+    {
+        #[cfg(debug_assertions)]
+        {
+            if let Err(err) = F::Checked::check_resources() {
+                panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+            }
+            if let Err(err) = F::Checked::try_fetch(self) {
+                panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+            }
+        }
+        F::fetch_unchecked(self)
+    }
+
+    /// Like `World::fetch`, but with the `Locals` of the fetching system, see
+    /// `FetchResources::fetch_local`.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `World::fetch`.
+    pub fn fetch_local<'a, F>(&'a self, locals: &Locals) -> F
+    where
+        F: FetchResources<'a, Self>,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
+        F::fetch_local(self, locals)
+    }
+
+    /// Like `World::fetch`, but returns an error if any of the resources are missing or cannot be
+    /// borrowed, see `FetchResources::try_fetch`.
+    ///
+    /// # Panics
+    /// Panics in debug builds if `F` has an internal resource conflict.
+    pub fn try_fetch<'a, F>(&'a self) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, Self>,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
+        F::try_fetch(self)
+    }
+
+    /// Like `World::try_fetch`, but with the `Locals` of the fetching system, see
+    /// `FetchResources::try_fetch_local`.
+    ///
+    /// # Panics
+    /// Panics in debug builds if `F` has an internal resource conflict.
+    pub fn try_fetch_local<'a, F>(&'a self, locals: &Locals) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, Self>,
+    {
+        #[cfg(debug_assertions)]
+        if let Err(err) = F::check_resources() {
+            panic!("cannot fetch {:?}: {}", type_name::<F>(), err);
+        }
+        F::try_fetch_local(self, locals)
+    }
+
+    /// Fetch `D`, run the given closure with it, then release every borrow.
+    ///
+    /// This is a lightweight way to run one-off logic without defining a `System`, for example
+    /// `world.run(|(a, mut b): (ReadComponent<A>, WriteComponent<B>)| ...)`.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `World::fetch`.
+    pub fn run<'a, D>(&'a self, f: impl FnOnce(D))
+    where
+        D: FetchResources<'a, Self>,
+    {
+        f(self.fetch());
+    }
+
+    /// Like `World::run`, but returns the result of the closure.
+    ///
+    /// `D` is fetched with `World::try_fetch`, so instead of panicking, a missing or already
+    /// borrowed resource is returned as an error converted from `FetchError`.
+    pub fn try_run<'a, D, R, E>(&'a self, f: impl FnOnce(D) -> Result<R, E>) -> Result<R, E>
+    where
+        D: FetchResources<'a, Self>,
+        E: From<FetchError>,
+    {
+        f(self.try_fetch()?)
+    }
+
+    /// Merge any pending atomic entity operations.
+    ///
+    /// Merges atomically allocated entities into the normal entity `BitSet` for performance, and
+    /// finalizes any entities that were requested to be deleted.
+    ///
+    /// No entity is actually removed until this method is called.
+    ///
+    /// Also clears the modified set of any component registered with
+    /// `ComponentConfig::clear_modified_on_merge`, updates every `ComponentIndex` inserted with
+    /// `World::insert_component_index`, and rearranges every `ComponentGroup` inserted with
+    /// `World::insert_component_group`.
+    ///
+    /// Finally, increments the `Tick` resource and resets the `FrameArena` resource if they have not
+    /// been removed, updates every `Events` channel inserted with `World::insert_events`, and then
+    /// clears the modified flag of every resource, so these updates never leave a resource marked
+    /// as modified.  Operations queued with `World::defer`, such as those from `DeferredResources`
+    /// and `Commands`, are applied last, in the order they were queued, and any resource they
+    /// modify stays marked until the next merge.
+    ///
+    /// Returns every entity that was killed by this merge, their components have already been
+    /// removed.
+    pub fn merge(&mut self) -> &[Entity] {
+        self.allocator.merge_atomic(&mut self.killed);
+        for e in &self.killed {
+            self.hidden_entities.remove(e.index());
+        }
+        self.flush_pending_components();
+        for registration in self.registered_components.values_mut() {
+            registration.merge(&mut self.components);
+            registration.remove(&self.components, &self.component_events, &self.killed);
+            let mut deferred = registration.take_deferred_removals();
+            deferred.retain(|&e| self.allocator.is_alive(e));
+            registration.remove(&self.components, &self.component_events, &deferred);
+        }
+        for storage in self.dyn_components.values_mut() {
+            let storage = storage.get_mut();
+            storage.clear_modified();
+            for e in &self.killed {
+                storage.remove(e.index());
+            }
+        }
+        for registration in self.soa_storages.values() {
+            (registration.remove)(&mut self.resources, &self.killed);
+        }
+        if let Some(components) = self.component_events.take_counts() {
+            let start = self.frame_stats_start;
+            let end = (
+                self.allocator.allocated_total(),
+                self.allocator.killed_total(),
+            );
+            self.frame_stats = FrameStats {
+                entities_created: end.0 - start.0,
+                entities_killed: end.1 - start.1,
+                components,
+            };
+            self.frame_stats_start = end;
+        }
+        self.component_events.drain();
+        if self.resources.contains_local::<Tick>() {
+            self.resources.get_mut::<Tick>().0 += 1;
+        }
+        if self.resources.contains_local::<FrameArena>() {
+            self.resources.get_mut::<FrameArena>().reset();
+        }
+        for update in self.event_updates.values() {
+            update(&mut self.resources);
+        }
+        self.resources.clear_all_modified();
+        for op in mem::take(self.deferred_ops.get_mut().unwrap()) {
+            op(self);
+        }
+        // Deferred operations may change indexed or grouped components, so these go last.
+        self.update_indexes_and_groups();
+        &self.killed
+    }
+
+    /// Renumber every live entity so that live entities occupy the lowest usable indexes, moving
+    /// every component to its entity's new index, see `Allocator::compact`.
+    ///
+    /// This first calls `World::merge`.  `Entity` values held inside components and resources are
+    /// only rewritten for the types in the given registry, any other `Entity` held outside of the
+    /// world must be translated with the returned map.  Moved components are marked as modified
+    /// but no component events are recorded.
+    pub fn compact(&mut self, registry: &EntityMapRegistry) -> EntityMap {
+        self.merge();
+
+        let moved = self.allocator.compact();
+        for registration in self.registered_components.values() {
+            (registration.compact)(&mut self.components, &moved);
+        }
+        for storage in self.dyn_components.values_mut() {
+            let storage = storage.get_mut();
+            for &(old, new) in &moved {
+                if let Some(v) = storage.remove(old.index()) {
+                    storage.insert(new.index(), v);
+                }
+            }
+        }
+        for registration in self.soa_storages.values() {
+            (registration.compact)(&mut self.resources, &moved);
+        }
+
+        let map: EntityMap = moved.into_iter().collect();
+        self.hidden_entities.clear();
+        for e in self.singleton_entities.values_mut() {
+            *e = map.map(*e);
+            self.hidden_entities.add(e.index());
+        }
+        registry.map_entities(&mut self.resources, &mut self.components, &map);
+        self.update_indexes_and_groups();
+        map
+    }
+
+    fn update_indexes_and_groups(&mut self) {
+        for index in &self.component_indexes {
+            index.update(&mut self.resources, &mut self.components);
+        }
+        for group in &self.component_groups {
+            group.arrange(&mut self.resources, &mut self.components, false);
+        }
+    }
+
+    /// Information about every registered component, in the order the components were first
+    /// inserted.
+    ///
+    /// Components registered through a shared reference (see `World::ensure_component`) are not
+    /// included until the next call to a method such as `World::merge` which takes `&mut self`.
+    pub fn component_info(&self) -> impl Iterator<Item = &ComponentInfo> + '_ {
+        self.component_order
+            .iter()
+            .map(move |id| &self.registered_components[id].info)
+    }
+
+    /// Type erased access to the component with the given id for the given entity, for tools like
+    /// inspectors which do not know the component types statically.
+    ///
+    /// Returns `None` if the entity is not alive, the component is not registered, or the entity
+    /// does not have the component.  The returned value can be downcast to the component type.
+    ///
+    /// Component storages are not required to be `Sync`, so the returned value holds an exclusive
+    /// borrow of the whole storage until it is dropped.
+    ///
+    /// # Panics
+    /// Panics if the component is already borrowed.
+    pub fn get_component_dyn(&self, e: Entity, id: ComponentId) -> Option<DynComponentRef<'_>> {
+        if !self.allocator.is_alive(e) {
+            return None;
+        }
+        let get = match self.registered_components.get(&id) {
+            Some(registration) => registration.get,
+            None => {
+                let pending = self.pending_components.lock().unwrap();
+                pending.iter().find(|(i, _)| *i == id)?.1.get
+            }
+        };
+        get(&self.components, e.index())
+    }
+
+    /// Mutable type erased access to the component with the given id for the given entity.
+    ///
+    /// If the component storage is tracking modifications, this marks the component as modified.
+    pub fn get_component_dyn_mut(&mut self, e: Entity, id: ComponentId) -> Option<&mut dyn Any> {
+        if !self.allocator.is_alive(e) {
+            return None;
+        }
+        self.flush_pending_components();
+        let registration = self.registered_components.get(&id)?;
+        (registration.get_mut)(&mut self.components, e.index())
+    }
+
+    /// Perform all end of frame maintenance for this world.
+    ///
+    /// First calls `World::merge`, then runs the `ComponentConfig::on_maintain` callback of every
+    /// component that has one, in the order the components were first inserted.
+    ///
+    /// Returns every entity that was killed by the merge.
+    pub fn maintain(&mut self) -> &[Entity] {
+        self.merge();
+        for id in &self.component_order {
+            self.registered_components[id].maintain(&mut self.components);
+        }
+        &self.killed
+    }
+
+    /// Subscribe to every structural change of every component storage in this world.
+    ///
+    /// Component events are only recorded while there is at least one subscriber.  Events are
+    /// collected as components are added and removed, and are delivered to every subscriber in the
+    /// order they occurred at the end of each call to `World::merge`.
+    ///
+    /// Only changes made through `ComponentAccess` or the `World` itself are recorded, changes made
+    /// directly to the inner `MaskedStorage` are not.
+    pub fn subscribe_component_events(
+        &mut self,
+        f: impl FnMut(&[ComponentEvent]) + Send + 'static,
+    ) -> SubscriptionId {
+        self.component_events.subscribe(f)
+    }
+
+    /// Remove a subscription previously added with `World::subscribe_component_events`.
+    ///
+    /// Returns false if there was no such subscription.
+    pub fn unsubscribe_component_events(&mut self, id: SubscriptionId) -> bool {
+        self.component_events.unsubscribe(id)
+    }
+
+    /// Turn on or off counting of structural operations, see `World::frame_stats`.
+    ///
+    /// Counting is off by default.  Entity counts are always kept by the allocator, but component
+    /// inserts and removes are only counted while this is turned on.
+    pub fn set_collect_stats(&mut self, collect: bool) {
+        self.component_events.set_counting(collect);
+        if collect {
+            self.frame_stats_start = (
+                self.allocator.allocated_total(),
+                self.allocator.killed_total(),
+            );
+        } else {
+            self.frame_stats = FrameStats::default();
+        }
+    }
+
+    /// Counts of the entities created and killed and the components inserted and removed during
+    /// the last frame, that is, between the two most recent calls to `World::merge`.
+    ///
+    /// Entities killed atomically are counted in the frame of the merge which actually kills them.
+    /// Always empty unless turned on with `World::set_collect_stats`.
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    /// Make this world read-only, so that analysis or export passes can read resources and
+    /// component storages through plain references without any borrow checking overhead.
+    ///
+    /// Call `FrozenWorld::thaw` to get the world back.
+    pub fn freeze(mut self) -> FrozenWorld {
+        self.flush_pending_components();
+        self.resources.flush_lazy();
+        self.components.flush_lazy();
+        FrozenWorld(self)
+    }
+
+    /// Report the memory used by the entity allocator and every registered component and resource.
+    ///
+    /// Components and resources are listed in order of their type names.  Resources inserted on
+    /// first access through `ReadDefault` or `WriteDefault` are included as well.
+    pub fn memory_report(&mut self) -> MemoryReport {
+        self.flush_pending_components();
+        let mut components = self
+            .registered_components
+            .values()
+            .map(|registration| (registration.memory)(&mut self.components))
+            .collect::<Vec<_>>();
+        components.sort_by_key(|c| c.type_name);
+
+        self.registered_resources
+            .extend(self.default_resources.get_mut().unwrap().drain());
+        let mut resources = self
+            .registered_resources
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        resources.sort_by_key(|r| r.type_name);
+
+        MemoryReport {
+            entities: self.allocator.memory_usage(),
+            components,
+            resources,
+        }
+    }
+
+    /// Measure how every registered component is used, and recommend the built-in storage which
+    /// would suit each one best.
+    ///
+    /// This visits every component mask, so like `World::memory_report` it is meant for profiling
+    /// rather than for every frame.  Recommendations only reflect the contents of the world at the
+    /// time of the report.
+    ///
+    /// Components which are already members of a group inserted with
+    /// `World::insert_component_group` are marked as `ComponentStorageReport::grouped`, see
+    /// `StorageReport::group_candidates` for components which might benefit from being grouped.
+    pub fn storage_report(&mut self) -> StorageReport {
+        self.flush_pending_components();
+        let components = self
+            .registered_components
+            .values()
+            .map(|registration| {
+                let (mut report, mask) = (registration.storage_report)(&mut self.components);
+                report.grouped = self
+                    .component_groups
+                    .iter()
+                    .any(|r| r.members.contains(&report.id));
+                (report, mask)
+            })
+            .collect();
+        StorageReport::new(self.allocator.alive_count() as usize, components)
+    }
+}
+
+/// A `World` which can only be read, returned from `World::freeze`.
+///
+/// Nothing can borrow a resource or component storage mutably while the world is frozen, so
+/// every accessor returns a plain reference without touching any borrow counts.  Only resources
+/// stored directly in the world are visible, never those of a parent `ResourceSet`, which may
+/// still be borrowed elsewhere.
+pub struct FrozenWorld(World);
+
+impl FrozenWorld {
+    /// Unfreeze the world, giving back mutable access.
+    pub fn thaw(self) -> World {
+        self.0
+    }
+
+    pub fn is_alive(&self, e: Entity) -> bool {
+        self.0.allocator.is_alive(e)
+    }
+
+    /// The number of live entities, see `Entities::alive_count`.
+    pub fn alive_count(&self) -> Index {
+        self.0.entities().alive_count()
+    }
+
+    /// Iterate over every live entity in index order, see `Entities::iter`.
+    pub fn iter_entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        let hidden = &self.0.hidden_entities;
+        self.0
+            .allocator
+            .iter()
+            .filter(move |e| !hidden.contains(e.index()))
+    }
+
+    /// # Panics
+    /// Panics if the resource has not been inserted.
+    pub fn resource<R>(&self) -> &R
+    where
+        R: Send + Sync + 'static,
+    {
+        match self.try_resource::<R>() {
+            Some(r) => r,
+            None => panic!("no such resource {:?}", type_name::<R>()),
+        }
+    }
+
+    pub fn try_resource<R>(&self) -> Option<&R>
+    where
+        R: Send + Sync + 'static,
+    {
+        // The world is owned, so nothing else can borrow its resources mutably.
+        unsafe { self.0.resources.get_unchecked() }
+    }
+
     /// # Panics
     /// Panics if the component has not been inserted.
-    pub fn get_component_mut<C>(&mut self) -> ComponentAccess<C, &mut ComponentStorage<C>>
+    pub fn component<C>(&self) -> &ComponentStorage<C>
+    where
+        C: Component + 'static,
+        C::Storage: Send + Sync,
+    {
+        match self.try_component::<C>() {
+            Some(storage) => storage,
+            None => panic!("no such component {:?}", type_name::<C>()),
+        }
+    }
+
+    pub fn try_component<C>(&self) -> Option<&ComponentStorage<C>>
     where
         C: Component + 'static,
-        C::Storage: Send,
+        C::Storage: Send + Sync,
     {
-        ComponentAccess {
-            storage: self.components.get_mut(),
-            entities: Entities(&self.allocator),
-            marker: PhantomData,
-        }
+        // The world is owned, so nothing else can borrow its storages mutably.
+        unsafe { self.0.components.get_unchecked() }
     }
 
-    pub fn fetch<'a, F>(&'a self) -> F
+    /// Returns the component of the given entity, or `None` if the entity is dead, or does not
+    /// have the component, or the component has not been inserted.
+    pub fn get_component<C>(&self, e: Entity) -> Option<&C>
     where
-        F: FetchResources<'a, Self>,
+        C: Component + 'static,
+        C::Storage: Send + Sync,
     {
-        F::fetch(self)
+        if self.is_alive(e) {
+            self.try_component::<C>()?.get(e.index())
+        } else {
+            None
+        }
     }
+}
 
-    /// Merge any pending atomic entity operations.
-    ///
-    /// Merges atomically allocated entities into the normal entity `BitSet` for performance, and
-    /// finalizes any entities that were requested to be deleted.
-    ///
-    /// No entity is actually removed until this method is called.
-    pub fn merge(&mut self) {
-        self.allocator.merge_atomic(&mut self.killed);
-        for remove_component in self.remove_components.values() {
-            remove_component(&self.components, &self.killed);
-        }
+/// A type erased component returned by `World::get_component_dyn`, which holds an exclusive borrow
+/// of the component's storage.
+pub struct DynComponentRef<'a> {
+    storage: AtomicRefMut<'a, dyn Any>,
+    index: Index,
+    get: fn(&dyn Any, Index) -> Option<&dyn Any>,
+}
+
+impl<'a> Deref for DynComponentRef<'a> {
+    type Target = dyn Any;
+
+    fn deref(&self) -> &dyn Any {
+        (self.get)(&*self.storage, self.index).unwrap()
     }
 }
 
-pub struct Entities<'a>(&'a Allocator);
+/// The live entities of a `World`.
+///
+/// The hidden entities of singletons, see `World::insert_singleton`, are alive but are skipped by
+/// joins, `Entities::iter` and `Entities::alive_count`.
+pub struct Entities<'a> {
+    allocator: &'a Allocator,
+    hidden: &'a BitSet,
+}
 
 impl<'a> Entities<'a> {
     /// Atomically request that this entity be removed on the next call to `World::merge_atomic`.
     ///
     /// An entity is not deleted until `World::merge_atomic` is called, so it will still be 'alive'
     /// and show up in queries until that time.
-    pub fn delete(&self, e: Entity) -> Result<(), WrongGeneration> {
-        self.0.kill_atomic(e)
+    pub fn delete(&self, e: Entity) -> Result<(), EntityError> {
+        self.allocator.kill_atomic(e)
+    }
+
+    /// Atomically request that this entity and all of its descendants be removed on the next call
+    /// to `World::merge`.
+    ///
+    /// The `children` function is called with every entity to be deleted and should push that
+    /// entity's children onto the given `Vec`.  Descendants which are already dead are skipped.
+    pub fn delete_recursive(
+        &self,
+        e: Entity,
+        mut children: impl FnMut(Entity, &mut Vec<Entity>),
+    ) -> Result<(), EntityError> {
+        self.allocator.check(e)?;
+        for e in descendants(e, |e, out| children(e, out)) {
+            let _ = self.allocator.kill_atomic(e);
+        }
+        Ok(())
     }
 
     pub fn is_alive(&self, e: Entity) -> bool {
-        self.0.is_alive(e)
+        self.allocator.is_alive(e)
+    }
+
+    /// Returns `Ok(())` if the given entity is alive, otherwise the reason it is not.
+    pub fn check(&self, e: Entity) -> Result<(), EntityError> {
+        self.allocator.check(e)
+    }
+
+    /// Create a `WeakEntity` which can check whether the given entity is alive without borrowing
+    /// the `World`.
+    pub fn weak(&self, e: Entity) -> WeakEntity {
+        self.allocator.weak(e)
     }
 
     pub fn entity(&self, index: Index) -> Option<Entity> {
-        self.0.entity(index)
+        self.allocator.entity(index)
     }
 
     /// Atomically allocate an entity.  An atomically allocated entity is indistinguishable from a
     /// regular live entity, but when `World::merge_atomic` is called it will be merged into a
     /// non-atomic `BitSet` for performance.
+    ///
+    /// # Panics
+    /// Panics if allocation fails, see `Allocator::try_allocate`.
     pub fn create(&self) -> Entity {
-        self.0.allocate_atomic()
+        self.allocator.allocate_atomic()
+    }
+
+    pub fn try_create(&self) -> Result<Entity, AllocateError> {
+        self.allocator.try_allocate_atomic()
+    }
+
+    /// Atomically allocate `count` entities at once, pushing them onto the given `Vec`.
+    ///
+    /// Much faster than calling `Entities::create` repeatedly when many threads are creating
+    /// entities at the same time.
+    pub fn create_batch(
+        &self,
+        count: Index,
+        entities: &mut Vec<Entity>,
+    ) -> Result<(), AllocateError> {
+        self.allocator.allocate_atomic_batch(count, entities)
+    }
+
+    pub fn live_bitset(&self) -> LiveBitSet<'_> {
+        self.allocator.live_bitset()
+    }
+
+    /// Iterate over every live entity, including atomically created entities.
+    ///
+    /// This is a plain iterator which does not need the `Join` machinery, see `Allocator::iter`.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.allocator
+            .iter()
+            .filter(|e| !self.hidden.contains(e.index()))
     }
 
-    pub fn live_bitset(&self) -> LiveBitSet {
-        self.0.live_bitset()
+    /// The number of currently live entities, see `Allocator::alive_count`.
+    pub fn alive_count(&self) -> Index {
+        self.allocator.alive_count() - self.hidden.iter().count() as Index
     }
 
     pub fn max_entity_count(&self) -> Index {
-        self.0.max_entity_count()
+        self.allocator.max_entity_count()
     }
 }
 
-impl<'a> IntoJoin for &'a Entities<'a> {
+impl<'a> Join for &'a Entities<'a> {
     type Item = Entity;
-    type IntoJoin = &'a Allocator;
+    type Access = &'a Allocator;
+    type Mask = BitSetAnd<LiveBitSet<'a>, BitSetNot<&'a BitSet>>;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (
+            BitSetAnd(self.allocator.live_bitset(), BitSetNot(self.hidden)),
+            self.allocator,
+        )
+    }
 
-    fn into_join(self) -> Self::IntoJoin {
-        &*self.0
+    unsafe fn get(access: &Self::Access, index: Index) -> Entity {
+        <&'a Allocator as Join>::get(access, index)
     }
 }
 
@@ -265,71 +2377,419 @@ impl<'a> FetchResources<'a, World> for Entities<'a> {
     }
 
     fn fetch(world: &'a World) -> Self {
-        world.entities()
+        world.entities()
+    }
+}
+
+/// Queues resource insertions and removals which are applied on the next call to `World::merge`.
+///
+/// This does not conflict with any other resource, so any system can fetch it.
+#[derive(Copy, Clone)]
+pub struct DeferredResources<'a>(&'a World);
+
+impl<'a> DeferredResources<'a> {
+    pub fn insert<R>(&self, r: R)
+    where
+        R: Send + 'static,
+    {
+        self.0.insert_resource_deferred(r);
+    }
+
+    pub fn remove<R>(&self)
+    where
+        R: Send + 'static,
+    {
+        self.0.remove_resource_deferred::<R>();
+    }
+}
+
+impl<'a> FetchResources<'a, World> for DeferredResources<'a> {
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new())
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        DeferredResources(world)
+    }
+}
+
+// Returns the given entity followed by all of its descendants, visiting each index only once
+// so that cycles terminate.
+fn resource_memory<R: 'static>() -> ResourceMemory {
+    ResourceMemory {
+        id: ResourceId::of::<R>(),
+        type_name: type_name::<R>(),
+        size: mem::size_of::<R>(),
+    }
+}
+
+fn descendants(root: Entity, mut children: impl FnMut(Entity, &mut Vec<Entity>)) -> Vec<Entity> {
+    let mut visited = BitSet::new();
+    let mut found = Vec::new();
+    let mut stack = vec![root];
+    let mut buffer = Vec::new();
+    while let Some(e) = stack.pop() {
+        if visited.add(e.index()) {
+            continue;
+        }
+        found.push(e);
+        children(e, &mut buffer);
+        stack.append(&mut buffer);
+    }
+    found
+}
+
+pub struct ResourceAccess<R>(R);
+
+impl<R> Deref for ResourceAccess<R>
+where
+    R: Deref,
+{
+    type Target = R::Target;
+
+    fn deref(&self) -> &R::Target {
+        &self.0
+    }
+}
+
+impl<R> DerefMut for ResourceAccess<R>
+where
+    R: DerefMut,
+{
+    fn deref_mut(&mut self) -> &mut R::Target {
+        &mut self.0
+    }
+}
+
+/// `SystemData` type that reads the given resource.
+///
+/// # Panics
+/// Panics if the resource does not exist or has already been borrowed for writing.
+pub type ReadResource<'a, R> = ResourceAccess<AtomicRef<'a, R>>;
+
+impl<'a, R> FetchResources<'a, World> for ReadResource<'a, R>
+where
+    R: Send + Sync + 'static,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().read(WorldResourceId::resource::<R>()))
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.read_resource()
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(ResourceAccess(world.resources.try_borrow()?))
+    }
+}
+
+/// `SystemData` type that writes the given resource.
+///
+/// # Panics
+/// Panics if the resource does not exist or has already been borrowed for writing.
+pub type WriteResource<'a, R> = ResourceAccess<AtomicRefMut<'a, R>>;
+
+impl<'a, R> FetchResources<'a, World> for WriteResource<'a, R>
+where
+    R: Send + 'static,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().write(WorldResourceId::resource::<R>()))
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.write_resource()
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(ResourceAccess(world.resources.try_borrow_mut()?))
+    }
+}
+
+/// A resource with a sensible default value, which can be fetched with `ReadDefault` and
+/// `WriteDefault` without first being inserted.
+pub trait DefaultResource: Default + Send + 'static {}
+
+/// Returned from fetching `ReadDefault` or `WriteDefault`.
+pub struct DefaultAccess<R>(R);
+
+impl<R> Deref for DefaultAccess<R>
+where
+    R: Deref,
+{
+    type Target = R::Target;
+
+    fn deref(&self) -> &R::Target {
+        &self.0
+    }
+}
+
+impl<R> DerefMut for DefaultAccess<R>
+where
+    R: DerefMut,
+{
+    fn deref_mut(&mut self) -> &mut R::Target {
+        &mut self.0
+    }
+}
+
+/// `SystemData` type that reads the given resource, inserting its default value on first access
+/// if it does not exist.
+///
+/// Declares the same resources as `ReadResource<R>`.
+///
+/// # Panics
+/// Panics if the resource has already been borrowed for writing.
+pub type ReadDefault<'a, R> = DefaultAccess<AtomicRef<'a, R>>;
+
+impl<'a, R> FetchResources<'a, World> for ReadDefault<'a, R>
+where
+    R: DefaultResource + Sync,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        ReadResource::<R>::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.register_default_resource::<R>();
+        DefaultAccess(world.resources.borrow_or_default())
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        world.register_default_resource::<R>();
+        Ok(DefaultAccess(world.resources.try_borrow_or_default()?))
+    }
+}
+
+/// `SystemData` type that writes the given resource, inserting its default value on first access
+/// if it does not exist.
+///
+/// Declares the same resources as `WriteResource<R>`.
+///
+/// # Panics
+/// Panics if the resource has already been borrowed.
+pub type WriteDefault<'a, R> = DefaultAccess<AtomicRefMut<'a, R>>;
+
+impl<'a, R> FetchResources<'a, World> for WriteDefault<'a, R>
+where
+    R: DefaultResource,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        WriteResource::<R>::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.register_default_resource::<R>();
+        DefaultAccess(world.resources.borrow_mut_or_default())
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        world.register_default_resource::<R>();
+        Ok(DefaultAccess(world.resources.try_borrow_mut_or_default()?))
+    }
+}
+
+/// A type level key for a keyed resource, used by `ReadKeyed` and `WriteKeyed`.
+pub trait ResourceKey: 'static {
+    const KEY: &'static str;
+}
+
+/// Returned from fetching `ReadKeyed` or `WriteKeyed`, gives access to the resource with the key
+/// `K::KEY`.
+pub struct KeyedAccess<R, K> {
+    value: R,
+    marker: PhantomData<fn() -> K>,
+}
+
+impl<R, K> Deref for KeyedAccess<R, K>
+where
+    R: Deref,
+{
+    type Target = R::Target;
+
+    fn deref(&self) -> &R::Target {
+        &self.value
+    }
+}
+
+impl<R, K> DerefMut for KeyedAccess<R, K>
+where
+    R: DerefMut,
+{
+    fn deref_mut(&mut self) -> &mut R::Target {
+        &mut self.value
+    }
+}
+
+/// `SystemData` type that reads the resource with the key `K::KEY`.
+///
+/// # Panics
+/// Panics if the resource does not exist or has already been borrowed for writing.
+pub type ReadKeyed<'a, R, K> = KeyedAccess<AtomicRef<'a, R>, K>;
+
+impl<'a, R, K> FetchResources<'a, World> for ReadKeyed<'a, R, K>
+where
+    R: Send + Sync + 'static,
+    K: ResourceKey,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().read(WorldResourceId::keyed::<R>(K::KEY)))
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        KeyedAccess {
+            value: world.read_resource_keyed(K::KEY),
+            marker: PhantomData,
+        }
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(KeyedAccess {
+            value: world.try_keyed_resources(K::KEY)?.try_borrow()?,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// `SystemData` type that writes the resource with the key `K::KEY`.
+///
+/// # Panics
+/// Panics if the resource does not exist or has already been borrowed.
+pub type WriteKeyed<'a, R, K> = KeyedAccess<AtomicRefMut<'a, R>, K>;
+
+impl<'a, R, K> FetchResources<'a, World> for WriteKeyed<'a, R, K>
+where
+    R: Send + 'static,
+    K: ResourceKey,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().write(WorldResourceId::keyed::<R>(K::KEY)))
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        KeyedAccess {
+            value: world.write_resource_keyed(K::KEY),
+            marker: PhantomData,
+        }
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(KeyedAccess {
+            value: world.try_keyed_resources(K::KEY)?.try_borrow_mut()?,
+            marker: PhantomData,
+        })
     }
 }
 
-pub struct ResourceAccess<R>(R);
+/// Returned from `World::read_singleton` and `World::write_singleton`, gives access to the
+/// singleton value along with the entity it is tied to.
+pub struct SingletonAccess<R> {
+    entity: Entity,
+    value: R,
+}
 
-impl<R> Deref for ResourceAccess<R>
+impl<R> SingletonAccess<R> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+impl<R> Deref for SingletonAccess<R>
 where
     R: Deref,
 {
     type Target = R::Target;
 
     fn deref(&self) -> &R::Target {
-        &*self.0
+        &self.value
     }
 }
 
-impl<R> DerefMut for ResourceAccess<R>
+impl<R> DerefMut for SingletonAccess<R>
 where
     R: DerefMut,
 {
     fn deref_mut(&mut self) -> &mut R::Target {
-        &mut *self.0
+        &mut self.value
     }
 }
 
-/// `SystemData` type that reads the given resource.
+/// `SystemData` type that reads the given singleton.
 ///
 /// # Panics
-/// Panics if the resource does not exist or has already been borrowed for writing.
-pub type ReadResource<'a, R> = ResourceAccess<AtomicRef<'a, R>>;
+/// Panics if the singleton does not exist or has already been borrowed for writing.
+pub type ReadSingleton<'a, C> = SingletonAccess<AtomicRef<'a, C>>;
 
-impl<'a, R> FetchResources<'a, World> for ReadResource<'a, R>
+impl<'a, C> FetchResources<'a, World> for ReadSingleton<'a, C>
 where
-    R: Send + Sync + 'static,
+    C: Send + Sync + 'static,
 {
     type Resources = WorldResources;
 
     fn check_resources() -> Result<WorldResources, ResourceConflict> {
-        Ok(WorldResources::new().read(WorldResourceId::resource::<R>()))
+        Ok(WorldResources::new().read(WorldResourceId::singleton::<C>()))
     }
 
     fn fetch(world: &'a World) -> Self {
-        world.read_resource()
+        world.read_singleton()
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(SingletonAccess {
+            entity: world.try_singleton_entity::<C>()?,
+            value: world.singletons.try_borrow()?,
+        })
     }
 }
 
-/// `SystemData` type that writes the given resource.
+/// `SystemData` type that writes the given singleton.
 ///
 /// # Panics
-/// Panics if the resource does not exist or has already been borrowed for writing.
-pub type WriteResource<'a, R> = ResourceAccess<AtomicRefMut<'a, R>>;
+/// Panics if the singleton does not exist or has already been borrowed for writing.
+pub type WriteSingleton<'a, C> = SingletonAccess<AtomicRefMut<'a, C>>;
 
-impl<'a, R> FetchResources<'a, World> for WriteResource<'a, R>
+impl<'a, C> FetchResources<'a, World> for WriteSingleton<'a, C>
 where
-    R: Send + 'static,
+    C: Send + 'static,
 {
     type Resources = WorldResources;
 
     fn check_resources() -> Result<WorldResources, ResourceConflict> {
-        Ok(WorldResources::new().write(WorldResourceId::resource::<R>()))
+        Ok(WorldResources::new().write(WorldResourceId::singleton::<C>()))
     }
 
     fn fetch(world: &'a World) -> Self {
-        world.write_resource()
+        world.write_singleton()
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(SingletonAccess {
+            entity: world.try_singleton_entity::<C>()?,
+            value: world.singletons.try_borrow_mut()?,
+        })
+    }
+}
+
+// Errors from borrowing a component storage name the component type rather than the storage type.
+fn component_fetch_error<C>(err: FetchError) -> FetchError {
+    match err {
+        FetchError::Missing(_) => FetchError::Missing(type_name::<C>()),
+        FetchError::Borrowed(_) => FetchError::Borrowed(type_name::<C>()),
     }
 }
 
@@ -344,7 +2804,8 @@ where
 {
     entities: Entities<'a>,
     storage: R,
-    marker: PhantomData<C>,
+    config: &'a ComponentConfig<C>,
+    events: &'a ComponentEventLog,
 }
 
 impl<'a, C, R> ComponentAccess<'a, C, R>
@@ -371,11 +2832,26 @@ where
             None
         }
     }
+
+    pub fn read_guard(&self) -> ReadGuardedJoin<'_, C::Storage> {
+        self.storage.read_guard()
+    }
+
+    /// Queue the removal of this component from the given entity on the next call to
+    /// `World::merge`, which only needs read access to the storage.
+    ///
+    /// Like `Entities::delete`, the component is still present until the merge.  The removal is
+    /// skipped if the entity is no longer alive by then.
+    pub fn remove_deferred(&self, e: Entity) -> Result<(), EntityError> {
+        self.entities.check(e)?;
+        self.config.deferred_removals.lock().unwrap().push(e);
+        Ok(())
+    }
 }
 
 impl<'a, C, R> ComponentAccess<'a, C, R>
 where
-    C: Component,
+    C: Component + 'static,
     R: DerefMut<Target = ComponentStorage<C>>,
 {
     /// Access the inner `MaskedStorage` type.
@@ -408,38 +2884,62 @@ where
         &mut self,
         e: Entity,
         f: impl FnOnce() -> C,
-    ) -> Result<&mut C, WrongGeneration> {
-        if self.entities.is_alive(e) {
-            Ok(self.storage.get_or_insert_with(e.index(), f))
-        } else {
-            Err(WrongGeneration)
+    ) -> Result<&mut C, EntityError> {
+        self.entities.check(e)?;
+        if !self.storage.contains(e.index()) {
+            self.events
+                .record(e, ComponentId::of::<C>(), ComponentEventKind::Added);
         }
+        Ok(self.storage.get_or_insert_with(e.index(), f))
     }
 
-    pub fn get_or_default(&mut self, e: Entity) -> Result<&mut C, WrongGeneration>
+    pub fn get_or_default(&mut self, e: Entity) -> Result<&mut C, EntityError>
     where
         C: Default,
     {
         self.get_or_insert_with(e, Default::default)
     }
 
-    pub fn insert(&mut self, e: Entity, c: C) -> Result<Option<C>, WrongGeneration> {
-        if self.entities.is_alive(e) {
-            Ok(self.storage.insert(e.index(), c))
-        } else {
-            Err(WrongGeneration)
+    pub fn insert(&mut self, e: Entity, c: C) -> Result<Option<C>, EntityError> {
+        self.entities.check(e)?;
+        let replaced = self.storage.insert(e.index(), c);
+        if replaced.is_none() {
+            self.events
+                .record(e, ComponentId::of::<C>(), ComponentEventKind::Added);
         }
+        Ok(replaced)
     }
 
-    pub fn remove(&mut self, e: Entity) -> Result<Option<C>, WrongGeneration> {
-        if self.entities.is_alive(e) {
-            Ok(self.storage.remove(e.index()))
-        } else {
-            Err(WrongGeneration)
+    /// Remove the component for the given entity, calling the `on_remove` callback set in the
+    /// component's `ComponentConfig` if a component was present.
+    pub fn remove(&mut self, e: Entity) -> Result<Option<C>, EntityError> {
+        self.entities.check(e)?;
+        let removed = self.storage.remove(e.index());
+        if let Some(c) = &removed {
+            self.events
+                .record(e, ComponentId::of::<C>(), ComponentEventKind::Removed);
+            self.config.removed(e, c);
         }
+        Ok(removed)
+    }
+
+    /// Remove this component from every entity whose index is in the given mask, calling the
+    /// `on_remove` callback for each, and return the number of removed components.
+    ///
+    /// This is much faster than removing components one entity at a time.
+    pub fn remove_matching(&mut self, mask: impl BitSetLike) -> usize {
+        let (entities, events, config) = (&self.entities, self.events, self.config);
+        let mut count = 0;
+        self.storage.remove_mask_with(mask, |index, c| {
+            let e = entities.entity(index).unwrap();
+            events.record(e, ComponentId::of::<C>(), ComponentEventKind::Removed);
+            config.removed(e, &c);
+            count += 1;
+        });
+        count
     }
 
-    pub fn guard(&mut self) -> GuardedJoin<C::Storage> {
+    pub fn guard(&mut self) -> GuardedJoin<'_, C::Storage> {
         self.storage.guard()
     }
 }
@@ -453,6 +2953,10 @@ where
     pub fn as_slice(&self) -> &[C] {
         self.storage.as_slice()
     }
+
+    pub fn slice_indexes(&self) -> &[Index] {
+        self.storage.slice_indexes()
+    }
 }
 
 impl<'a, C, R> ComponentAccess<'a, C, R>
@@ -469,18 +2973,23 @@ where
         self.storage.modified_indexes()
     }
 
-    pub fn mark_modified(&self, entity: Entity) -> Result<(), WrongGeneration> {
-        if self.entities.is_alive(entity) {
-            self.storage.mark_modified(entity.index());
-            Ok(())
-        } else {
-            Err(WrongGeneration)
-        }
+    pub fn mark_modified(&self, entity: Entity) -> Result<(), EntityError> {
+        self.entities.check(entity)?;
+        self.storage.mark_modified(entity.index());
+        Ok(())
     }
 
-    pub fn modified(&self) -> ModifiedJoin<C::Storage> {
+    pub fn modified(&self) -> ModifiedJoin<'_, C::Storage> {
         self.storage.modified()
     }
+
+    pub fn reader_modified_indexes(&self, reader: ReaderId) -> &ModifiedBitSet {
+        self.storage.reader_modified_indexes(reader)
+    }
+
+    pub fn reader_modified(&self, reader: ReaderId) -> ModifiedJoin<'_, C::Storage> {
+        self.storage.reader_modified(reader)
+    }
 }
 
 impl<'a, C, R> ComponentAccess<'a, C, R>
@@ -494,6 +3003,34 @@ where
     }
 }
 
+impl<'a, C, R> ComponentAccess<'a, C, R>
+where
+    C: Component,
+    C::Storage: SplitStorage,
+    R: DerefMut<Target = ComponentStorage<C>>,
+{
+    /// See `MaskedStorage::par_chunks_mut`.
+    #[cfg(feature = "rayon")]
+    pub fn par_chunks_mut(
+        &mut self,
+        chunk_size: usize,
+    ) -> impl IndexedParallelIterator<Item = (&[Index], &mut [C])> + '_
+    where
+        C: Send,
+    {
+        self.storage.par_chunks_mut(chunk_size)
+    }
+
+    /// See `MaskedStorage::par_chunks_mut`.
+    #[cfg(not(feature = "rayon"))]
+    pub fn par_chunks_mut(
+        &mut self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = (&[Index], &mut [C])> + '_ {
+        self.storage.par_chunks_mut(chunk_size)
+    }
+}
+
 impl<'a, C, R> ComponentAccess<'a, C, R>
 where
     C: Component,
@@ -508,9 +3045,25 @@ where
         self.storage.clear_modified();
     }
 
-    pub fn modified_mut(&mut self) -> ModifiedJoinMut<C::Storage> {
+    pub fn modified_mut(&mut self) -> ModifiedJoinMut<'_, C::Storage> {
         self.storage.modified_mut()
     }
+
+    pub fn register_reader(&mut self) -> ReaderId {
+        self.storage.register_reader()
+    }
+
+    pub fn unregister_reader(&mut self, reader: ReaderId) {
+        self.storage.unregister_reader(reader);
+    }
+
+    pub fn clear_reader_modified(&mut self, reader: ReaderId) {
+        self.storage.clear_reader_modified(reader);
+    }
+
+    pub fn reader_modified_mut(&mut self, reader: ReaderId) -> ModifiedJoinMut<'_, C::Storage> {
+        self.storage.reader_modified_mut(reader)
+    }
 }
 
 impl<'a, 'b, C, R> IntoJoin for &'a ComponentAccess<'b, C, R>
@@ -539,6 +3092,68 @@ where
     }
 }
 
+/// A tuple of distinct component types which can be mutably borrowed together with
+/// `World::split_components`.
+pub trait SplitComponents<'a> {
+    type Access;
+
+    fn split(world: &'a mut World) -> Self::Access;
+}
+
+macro_rules! impl_split_components {
+    ($($ty:ident),*) => {
+        impl<'a, $($ty),*> SplitComponents<'a> for ($($ty,)*)
+        where
+            $($ty: Component + 'static, $ty::Storage: Send,)*
+        {
+            type Access = ($(ComponentAccess<'a, $ty, &'a mut ComponentStorage<$ty>>,)*);
+
+            #[allow(non_snake_case)]
+            fn split(world: &'a mut World) -> Self::Access {
+                let World {
+                    allocator,
+                    components,
+                    registered_components,
+                    component_events,
+                    hidden_entities,
+                    ..
+                } = world;
+                let (allocator, registered_components, component_events, hidden) = (
+                    &*allocator,
+                    &*registered_components,
+                    &*component_events,
+                    &*hidden_entities,
+                );
+
+                let [$($ty),*] = components.get_disjoint_mut([
+                    $((TypeId::of::<ComponentStorage<$ty>>(), type_name::<$ty>())),*
+                ]);
+                ($(
+                    ComponentAccess {
+                        storage: downcast_resource_mut::<ComponentStorage<$ty>>($ty),
+                        entities: Entities { allocator, hidden },
+                        config: World::config_in::<$ty>(registered_components),
+                        events: component_events,
+                    },
+                )*)
+            }
+        }
+    };
+}
+
+impl_split_components!(A);
+impl_split_components!(A, B);
+impl_split_components!(A, B, C);
+impl_split_components!(A, B, C, D);
+impl_split_components!(A, B, C, D, E);
+impl_split_components!(A, B, C, D, E, F);
+impl_split_components!(A, B, C, D, E, F, G);
+impl_split_components!(A, B, C, D, E, F, G, H);
+impl_split_components!(A, B, C, D, E, F, G, H, I);
+impl_split_components!(A, B, C, D, E, F, G, H, I, J);
+impl_split_components!(A, B, C, D, E, F, G, H, I, J, K);
+impl_split_components!(A, B, C, D, E, F, G, H, I, J, K, L);
+
 /// `SystemData` type that reads the given component.
 ///
 /// # Panics
@@ -561,6 +3176,10 @@ where
     fn fetch(world: &'a World) -> Self {
         world.read_component()
     }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        world.try_read_component()
+    }
 }
 
 /// `SystemData` type that writes the given component.
@@ -585,4 +3204,360 @@ where
     fn fetch(world: &'a World) -> Self {
         world.write_component()
     }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(ComponentAccess {
+            storage: world
+                .components
+                .try_borrow_mut()
+                .map_err(component_fetch_error::<C>)?,
+            entities: world.entities(),
+            config: world.component_config(),
+            events: &world.component_events,
+        })
+    }
+}
+
+/// `SystemData` type that reads only the mask of the given component.
+///
+/// Joining over this yields `()` for every index which has the component, so it is useful to
+/// filter a join by the presence of a component without accessing its storage.
+///
+/// # Panics
+/// Panics if the component does not exist or has already been borrowed for writing.
+pub struct HasComponent<'a, C> {
+    mask: AtomicRef<'a, BitSet>,
+    marker: PhantomData<fn() -> C>,
+}
+
+impl<'a, C> HasComponent<'a, C> {
+    pub fn mask(&self) -> &BitSet {
+        &self.mask
+    }
+
+    pub fn contains(&self, index: Index) -> bool {
+        self.mask.contains(index)
+    }
+}
+
+impl<'a, 'b, C> Join for &'a HasComponent<'b, C> {
+    type Item = ();
+    type Access = ();
+    type Mask = &'a BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (&self.mask, ())
+    }
+
+    unsafe fn get(_: &Self::Access, _: Index) -> Self::Item {}
+}
+
+impl<'a, C> FetchResources<'a, World> for HasComponent<'a, C>
+where
+    C: Component + 'static,
+    C::Storage: Send,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new().read(WorldResourceId::component::<C>()))
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        world.has_component()
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(HasComponent {
+            // Safe for the same reason as in `World::has_component`.
+            mask: unsafe {
+                world
+                    .components
+                    .try_borrow_projected(|storage: &ComponentStorage<C>| storage.mask())
+            }
+            .map_err(component_fetch_error::<C>)?,
+            marker: PhantomData,
+        })
+    }
+}
+
+/// `SystemData` type that reads the given tracked component, and joins over only its modified
+/// entries.
+///
+/// Declares the same resources as `ReadComponent<C>`, and dereferences to it.  Joining over a
+/// `&ReadModified<C>` is the same as joining over `ReadComponent::modified`, yielding a
+/// `ModifiedItem` for every index in the modified set.
+///
+/// Since this only reads the component, it cannot clear the modified set itself.  Use
+/// `ComponentConfig::clear_modified_on_merge` so that each frame only sees the modifications made
+/// since the last `World::merge`.
+///
+/// # Panics
+/// Panics if the component does not exist or has already been borrowed for writing.
+pub struct ReadModified<'a, C: Component>(ReadComponent<'a, C>);
+
+impl<'a, C: Component> Deref for ReadModified<'a, C> {
+    type Target = ReadComponent<'a, C>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, 'b, C> IntoJoin for &'a ReadModified<'b, C>
+where
+    C: Component,
+    C::Storage: TrackedStorage,
+{
+    type Item = ModifiedItem<&'a C>;
+    type IntoJoin = ModifiedJoin<'a, C::Storage>;
+
+    fn into_join(self) -> Self::IntoJoin {
+        self.0.modified()
+    }
+}
+
+impl<'a, C> FetchResources<'a, World> for ReadModified<'a, C>
+where
+    C: Component + Send + Sync + 'static,
+    C::Storage: TrackedStorage + Send + Sync,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        ReadComponent::<C>::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        ReadModified(world.read_component())
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        Ok(ReadModified(world.try_read_component()?))
+    }
+}
+
+/// A component `SystemData` type which `AutoRegister` can register on first access.
+pub trait RegisterComponent<'a>: FetchResources<'a, World, Resources = WorldResources> {
+    /// Register the fetched component with `World::ensure_component`.
+    fn register(world: &World);
+}
+
+impl<'a, C> RegisterComponent<'a> for ReadComponent<'a, C>
+where
+    C: Component + Send + Sync + 'static,
+    C::Storage: Default + Send + Sync,
+{
+    fn register(world: &World) {
+        world.ensure_component::<C>();
+    }
+}
+
+impl<'a, C> RegisterComponent<'a> for WriteComponent<'a, C>
+where
+    C: Component + Send + 'static,
+    C::Storage: Default + Send,
+{
+    fn register(world: &World) {
+        world.ensure_component::<C>();
+    }
+}
+
+impl<'a, C> RegisterComponent<'a> for HasComponent<'a, C>
+where
+    C: Component + 'static,
+    C::Storage: Default + Send,
+{
+    fn register(world: &World) {
+        world.ensure_component::<C>();
+    }
+}
+
+impl<'a, C> RegisterComponent<'a> for ReadModified<'a, C>
+where
+    C: Component + Send + Sync + 'static,
+    C::Storage: TrackedStorage + Default + Send + Sync,
+{
+    fn register(world: &World) {
+        world.ensure_component::<C>();
+    }
+}
+
+/// `SystemData` type that fetches the given component `SystemData` type, first inserting an empty
+/// storage with the default configuration if the component has not been inserted.
+///
+/// Declares the same resources as `F`, and dereferences to it.
+///
+/// # Panics
+/// Panics if the component has already been borrowed incompatibly.
+pub struct AutoRegister<F>(F);
+
+impl<F> AutoRegister<F> {
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+}
+
+impl<F> Deref for AutoRegister<F> {
+    type Target = F;
+
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F> DerefMut for AutoRegister<F> {
+    fn deref_mut(&mut self) -> &mut F {
+        &mut self.0
+    }
+}
+
+impl<'a, F> FetchResources<'a, World> for AutoRegister<F>
+where
+    F: RegisterComponent<'a>,
+{
+    type Resources = WorldResources;
+
+    fn check_resources() -> Result<WorldResources, ResourceConflict> {
+        F::check_resources()
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        F::register(world);
+        AutoRegister(F::fetch(world))
+    }
+
+    fn try_fetch(world: &'a World) -> Result<Self, FetchError> {
+        F::register(world);
+        Ok(AutoRegister(F::try_fetch(world)?))
+    }
+
+    fn fetch_local(world: &'a World, locals: &Locals) -> Self {
+        F::register(world);
+        AutoRegister(F::fetch_local(world, locals))
+    }
+
+    fn try_fetch_local(world: &'a World, locals: &Locals) -> Result<Self, FetchError> {
+        F::register(world);
+        Ok(AutoRegister(F::try_fetch_local(world, locals)?))
+    }
+}
+
+/// A `SystemData` type which can be fetched with `World::fetch_unchecked`, without counting any
+/// borrows.
+pub trait FetchUnchecked<'a>: Sized {
+    /// The `SystemData` type which borrows the same resources with checking.
+    type Checked: FetchResources<'a, World, Resources = WorldResources>;
+
+    /// # Safety
+    /// The same contract as `World::fetch_unchecked`.
+    unsafe fn fetch_unchecked(world: &'a World) -> Self;
+}
+
+/// `SystemData` type that reads the given resource without counting the borrow, see
+/// `World::fetch_unchecked`.
+pub type ReadResourceUnchecked<'a, R> = ResourceAccess<&'a R>;
+
+impl<'a, R> FetchUnchecked<'a> for ReadResourceUnchecked<'a, R>
+where
+    R: Send + Sync + 'static,
+{
+    type Checked = ReadResource<'a, R>;
+
+    unsafe fn fetch_unchecked(world: &'a World) -> Self {
+        match world.resources.get_unchecked() {
+            Some(r) => ResourceAccess(r),
+            None => panic!("no such resource {:?}", type_name::<R>()),
+        }
+    }
+}
+
+/// `SystemData` type that writes the given resource without counting the borrow, see
+/// `World::fetch_unchecked`.
+pub type WriteResourceUnchecked<'a, R> = ResourceAccess<&'a mut R>;
+
+impl<'a, R> FetchUnchecked<'a> for WriteResourceUnchecked<'a, R>
+where
+    R: Send + 'static,
+{
+    type Checked = WriteResource<'a, R>;
+
+    unsafe fn fetch_unchecked(world: &'a World) -> Self {
+        match world.resources.get_mut_unchecked() {
+            Some(r) => ResourceAccess(r),
+            None => panic!("no such resource {:?}", type_name::<R>()),
+        }
+    }
+}
+
+/// `SystemData` type that reads the given component without counting the borrow, see
+/// `World::fetch_unchecked`.
+pub type ReadComponentUnchecked<'a, C> = ComponentAccess<'a, C, &'a ComponentStorage<C>>;
+
+impl<'a, C> FetchUnchecked<'a> for ReadComponentUnchecked<'a, C>
+where
+    C: Component + Send + Sync + 'static,
+    C::Storage: Send + Sync,
+{
+    type Checked = ReadComponent<'a, C>;
+
+    unsafe fn fetch_unchecked(world: &'a World) -> Self {
+        match world.components.get_unchecked() {
+            Some(storage) => ComponentAccess {
+                storage,
+                entities: world.entities(),
+                config: world.component_config(),
+                events: &world.component_events,
+            },
+            None => panic!("no such component {:?}", type_name::<C>()),
+        }
+    }
+}
+
+/// `SystemData` type that writes the given component without counting the borrow, see
+/// `World::fetch_unchecked`.
+pub type WriteComponentUnchecked<'a, C> = ComponentAccess<'a, C, &'a mut ComponentStorage<C>>;
+
+impl<'a, C> FetchUnchecked<'a> for WriteComponentUnchecked<'a, C>
+where
+    C: Component + Send + 'static,
+    C::Storage: Send,
+{
+    type Checked = WriteComponent<'a, C>;
+
+    unsafe fn fetch_unchecked(world: &'a World) -> Self {
+        match world.components.get_mut_unchecked() {
+            Some(storage) => ComponentAccess {
+                storage,
+                entities: world.entities(),
+                config: world.component_config(),
+                events: &world.component_events,
+            },
+            None => panic!("no such component {:?}", type_name::<C>()),
+        }
+    }
 }
+
+macro_rules! impl_fetch_unchecked {
+    ($($ty:ident),*) => {
+        impl<'a, $($ty),*> FetchUnchecked<'a> for ($($ty,)*)
+        where
+            $($ty: FetchUnchecked<'a>),*
+        {
+            type Checked = ($($ty::Checked,)*);
+
+            unsafe fn fetch_unchecked(world: &'a World) -> Self {
+                ($($ty::fetch_unchecked(world),)*)
+            }
+        }
+    };
+}
+
+impl_fetch_unchecked!(A);
+impl_fetch_unchecked!(A, B);
+impl_fetch_unchecked!(A, B, C);
+impl_fetch_unchecked!(A, B, C, D);
+impl_fetch_unchecked!(A, B, C, D, E);
+impl_fetch_unchecked!(A, B, C, D, E, F);
+impl_fetch_unchecked!(A, B, C, D, E, F, G);
+impl_fetch_unchecked!(A, B, C, D, E, F, G, H);
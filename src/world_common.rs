@@ -12,23 +12,50 @@ pub type ComponentStorage<C> = MaskedStorage<<C as Component>::Storage>;
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct ResourceId(TypeId);
 
+impl ResourceId {
+    pub fn of<R: 'static>() -> Self {
+        ResourceId(TypeId::of::<R>())
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct ComponentId(TypeId);
 
+impl ComponentId {
+    pub fn of<C: Component + 'static>() -> Self {
+        ComponentId(TypeId::of::<C>())
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum WorldResourceId {
     Entities,
     Resource(ResourceId),
     Component(ComponentId),
+    Singleton(ResourceId),
+    Keyed(ResourceId, &'static str),
+    Local(ResourceId),
 }
 
 impl WorldResourceId {
     pub fn resource<C: 'static>() -> Self {
-        Self::Resource(ResourceId(TypeId::of::<C>()))
+        Self::Resource(ResourceId::of::<C>())
     }
 
     pub fn component<C: Component + 'static>() -> Self {
-        Self::Component(ComponentId(TypeId::of::<C>()))
+        Self::Component(ComponentId::of::<C>())
+    }
+
+    pub fn singleton<C: 'static>() -> Self {
+        Self::Singleton(ResourceId::of::<C>())
+    }
+
+    pub fn keyed<C: 'static>(key: &'static str) -> Self {
+        Self::Keyed(ResourceId::of::<C>(), key)
+    }
+
+    pub fn local<C: 'static>() -> Self {
+        Self::Local(ResourceId::of::<C>())
     }
 }
 
@@ -0,0 +1,123 @@
+use std::{ops::Deref, sync::Arc, thread};
+
+use crate::{
+    fetch_resources::{FetchError, FetchResources},
+    local::Locals,
+    world::World,
+};
+
+/// A cheaply clonable, shared handle to a `World`, for reading and writing it from several threads
+/// at once, such as from jobs of an external job system rather than from systems run by
+/// `parallelize`.
+///
+/// Every resource and component storage is borrowed through a thread-safe cell, so fetches from
+/// different threads never cause undefined behavior.  Fetches which do not conflict with each
+/// other always succeed, while a fetch which conflicts with a borrow currently held by another
+/// thread either panics (`WorldHandle::fetch`), returns `FetchError::Borrowed`
+/// (`WorldHandle::try_fetch`), or waits for the borrow to be released
+/// (`WorldHandle::fetch_blocking`).
+///
+/// Operations that need `&mut World`, such as `World::merge`, are only possible once every other
+/// handle is dropped, see `WorldHandle::try_into_inner`.
+#[derive(Clone)]
+pub struct WorldHandle(Arc<World>);
+
+impl WorldHandle {
+    pub fn new(world: World) -> Self {
+        WorldHandle(Arc::new(world))
+    }
+
+    /// Fetch the given `FetchResources`, see `World::fetch`.
+    ///
+    /// # Panics
+    /// Panics if any of the resources are missing or are borrowed in a conflicting way, including
+    /// by another thread.
+    pub fn fetch<'a, F>(&'a self) -> F
+    where
+        F: FetchResources<'a, World>,
+    {
+        self.0.fetch()
+    }
+
+    /// Fetch the given `FetchResources`, returning an error rather than panicking if any of the
+    /// resources are missing or are borrowed in a conflicting way.
+    ///
+    /// If any resource cannot be borrowed, every resource which was already borrowed is released
+    /// again before returning.
+    pub fn try_fetch<'a, F>(&'a self) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, World>,
+    {
+        self.0.try_fetch()
+    }
+
+    /// Like `WorldHandle::try_fetch`, but with the given `Locals`, which may be used from any
+    /// thread, see `FetchResources::try_fetch_local`.
+    pub fn try_fetch_local<'a, F>(&'a self, locals: &Locals) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, World>,
+    {
+        self.0.try_fetch_local(locals)
+    }
+
+    /// Fetch the given `FetchResources`, yielding the current thread and retrying for as long as
+    /// any resource is borrowed in a conflicting way.
+    ///
+    /// This can deadlock if the thread holding the conflicting borrow is itself waiting on this
+    /// thread, so borrows held while calling this should be kept to a minimum.
+    ///
+    /// Returns an error only if a resource is missing.
+    pub fn fetch_blocking<'a, F>(&'a self) -> Result<F, FetchError>
+    where
+        F: FetchResources<'a, World>,
+    {
+        loop {
+            match self.0.try_fetch() {
+                Err(FetchError::Borrowed(_)) => thread::yield_now(),
+                res => return res,
+            }
+        }
+    }
+
+    /// Fetch `D`, run the given closure with it, then release every borrow, see `World::run`.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `WorldHandle::fetch`.
+    pub fn run<'a, D>(&'a self, f: impl FnOnce(D))
+    where
+        D: FetchResources<'a, World>,
+    {
+        f(self.fetch());
+    }
+
+    /// Returns the `World` if this is the only handle to it, otherwise returns this handle
+    /// unchanged.
+    pub fn try_into_inner(self) -> Result<World, Self> {
+        Arc::try_unwrap(self.0).map_err(WorldHandle)
+    }
+
+    /// Returns mutable access to the `World` if this is the only handle to it.
+    pub fn get_mut(&mut self) -> Option<&mut World> {
+        Arc::get_mut(&mut self.0)
+    }
+}
+
+impl Deref for WorldHandle {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.0
+    }
+}
+
+impl From<World> for WorldHandle {
+    fn from(world: World) -> Self {
+        WorldHandle::new(world)
+    }
+}
+
+impl From<Arc<World>> for WorldHandle {
+    fn from(world: Arc<World>) -> Self {
+        WorldHandle(world)
+    }
+}
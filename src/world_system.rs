@@ -0,0 +1,117 @@
+/// Define a struct along with a `System<&World>` implementation for it, which fetches its
+/// resources from the `World` with `FetchResources`.
+///
+/// ```text
+/// system! {
+///     pub struct Movement;
+///     type Pool = SeqPool;
+///
+///     fn run(&mut self, (mut pos, vel): (WriteComponent<Pos>, ReadComponent<Vel>)) {
+///         for (pos, vel) in (&mut pos, &vel).join() {
+///             pos.0 += vel.0;
+///         }
+///     }
+/// }
+/// ```
+///
+/// The struct may also have named fields, which are available through `self`. The fetched type
+/// is given without lifetimes, and `check_resources` is derived from its `FetchResources`
+/// implementation. The argument pattern must be a single token tree, such as a tuple pattern or
+/// a plain identifier.
+///
+/// The system also implements `LocalSystem`, so it can be wrapped in `WithLocals` to fetch
+/// `Local` values.
+///
+/// If `run` has no return type, the system's error type is `Infallible`, otherwise `run` must
+/// return `Result<(), E>` and the system's error type is `E`.
+#[macro_export]
+macro_rules! system {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident $({ $($fields:tt)* })? $(;)?
+        type Pool = $pool:ty;
+
+        fn run(&mut $self:ident, $data:tt : $fetch:ty $(,)?) -> Result<(), $error:ty> $body:block
+    ) => {
+        $crate::system!(@struct $(#[$meta])* $vis $name $({ $($fields)* })?);
+
+        impl<'a> $crate::system::System<&'a $crate::world::World> for $name {
+            type Resources = $crate::world_common::WorldResources;
+            type Pool = $pool;
+            type Error = $error;
+
+            fn check_resources(
+                &self,
+            ) -> ::std::result::Result<
+                $crate::world_common::WorldResources,
+                $crate::resources::ResourceConflict,
+            > {
+                <$fetch as $crate::fetch_resources::FetchResources<'a, $crate::world::World>>
+                    ::check_resources()
+            }
+
+            fn run(
+                &mut self,
+                _: &$pool,
+                world: &'a $crate::world::World,
+            ) -> ::std::result::Result<(), $error> {
+                self.__run_system(world, ::std::option::Option::None)
+            }
+        }
+
+        impl<'a> $crate::local::LocalSystem<'a> for $name {
+            fn run_local(
+                &mut self,
+                _: &$pool,
+                world: &'a $crate::world::World,
+                locals: &$crate::local::Locals,
+            ) -> ::std::result::Result<(), $error> {
+                self.__run_system(world, ::std::option::Option::Some(locals))
+            }
+        }
+
+        impl $name {
+            #[doc(hidden)]
+            fn __run_system(
+                &mut $self,
+                world: &$crate::world::World,
+                locals: ::std::option::Option<&$crate::local::Locals>,
+            ) -> ::std::result::Result<(), $error> {
+                #[allow(unused_mut)]
+                let $data: $fetch = match locals {
+                    ::std::option::Option::Some(locals) => world.fetch_local(locals),
+                    ::std::option::Option::None => world.fetch(),
+                };
+                $body
+            }
+        }
+    };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident $({ $($fields:tt)* })? $(;)?
+        type Pool = $pool:ty;
+
+        fn run(&mut $self:ident, $data:tt : $fetch:ty) $body:block
+    ) => {
+        $crate::system! {
+            $(#[$meta])*
+            $vis struct $name $({ $($fields)* })?
+            type Pool = $pool;
+
+            fn run(&mut $self, $data: $fetch) -> Result<(), ::std::convert::Infallible> {
+                $body;
+                ::std::result::Result::Ok(())
+            }
+        }
+    };
+
+    (@struct $(#[$meta:meta])* $vis:vis $name:ident { $($fields:tt)* }) => {
+        $(#[$meta])*
+        $vis struct $name { $($fields)* }
+    };
+    (@struct $(#[$meta:meta])* $vis:vis $name:ident) => {
+        $(#[$meta])*
+        $vis struct $name;
+    };
+}
@@ -18,8 +18,8 @@ impl Component for CB {
 fn test_any_components() {
     let mut world = World::new();
 
-    world.insert_component::<CA>();
-    world.insert_component::<CB>();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
 
     let mut components_prefab = AnyCloneComponentSet::new();
     components_prefab.insert::<CA>(CA(1));
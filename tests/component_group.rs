@@ -0,0 +1,143 @@
+use goggles::{
+    join::IntoJoinExt, Commands, Component, ComponentGroup, ComponentId, DenseVecStorage,
+    ReadComponent, ReadResource, World, WriteComponent,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Pos(i32);
+
+impl Component for Pos {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Vel(i32);
+
+impl Component for Vel {
+    type Storage = DenseVecStorage<Self>;
+}
+
+type Motion = ComponentGroup<(Pos, Vel)>;
+
+#[test]
+fn test_component_group() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+
+    let entities: Vec<_> = (0..6).map(|_| world.create_entity()).collect();
+    {
+        let mut pos = world.write_component::<Pos>();
+        let mut vel = world.write_component::<Vel>();
+        for (i, &e) in entities.iter().enumerate().rev() {
+            if i % 3 != 2 {
+                pos.insert(e, Pos(i as i32 * 10)).unwrap();
+            }
+            if i % 3 != 0 {
+                vel.insert(e, Vel(i as i32)).unwrap();
+            }
+        }
+    }
+
+    world.insert_component_group::<(Pos, Vel)>();
+
+    let report = world.storage_report();
+    assert!(report.component(ComponentId::of::<Pos>()).unwrap().grouped);
+    assert!(report.group_candidates(0.5).is_empty());
+
+    {
+        let group = world.read_resource::<Motion>();
+        let members: Vec<_> = group
+            .archetypes()
+            .iter()
+            .map(|a| (a.members(), a.len()))
+            .collect();
+        assert_eq!(members, vec![(0b01, 2), (0b10, 2), (0b11, 2)]);
+        assert_eq!(
+            group
+                .archetypes_with(&[ComponentId::of::<Pos>(), ComponentId::of::<Vel>()])
+                .count(),
+            1
+        );
+    }
+
+    world.run(
+        |(group, mut pos, vel): (
+            ReadResource<Motion>,
+            WriteComponent<Pos>,
+            ReadComponent<Vel>,
+        )| {
+            for archetype in group.archetypes_with(&[ComponentId::of::<Vel>()]) {
+                let vel = group.column::<Vel>(archetype, vel.storage());
+                let pos = group.column_mut::<Pos>(archetype, pos.storage_mut());
+                assert_eq!(vel.len(), archetype.len());
+                for (p, v) in pos.iter_mut().zip(vel) {
+                    p.0 += v.0;
+                }
+            }
+        },
+    );
+
+    let joined: Vec<_> = (
+        &world.read_component::<Pos>(),
+        &world.read_component::<Vel>(),
+    )
+        .join()
+        .map(|(p, v)| (p.0, v.0))
+        .collect();
+    assert_eq!(joined, vec![(11, 1), (44, 4)]);
+
+    let e = world.create_entity();
+    world.write_component::<Vel>().insert(e, Vel(6)).unwrap();
+    world.write_component::<Pos>().insert(e, Pos(60)).unwrap();
+    {
+        let group = world.read_resource::<Motion>();
+        assert!(!group.is_arranged::<Pos>(world.read_component::<Pos>().storage()));
+    }
+
+    world.delete_entity(entities[1]).unwrap();
+    world.merge();
+
+    let group = world.read_resource::<Motion>();
+    let pos = world.read_component::<Pos>();
+    let vel = world.read_component::<Vel>();
+    assert!(group.is_arranged::<Pos>(pos.storage()));
+    assert!(group.is_arranged::<Vel>(vel.storage()));
+    let both = group.archetypes().last().unwrap();
+    assert_eq!(both.members(), 0b11);
+    assert_eq!(
+        group.column::<Pos>(both, pos.storage()),
+        &[Pos(44), Pos(60)]
+    );
+    assert_eq!(group.column::<Vel>(both, vel.storage()), &[Vel(4), Vel(6)]);
+}
+
+#[test]
+fn test_component_group_commands() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+    world.insert_component_group::<(Pos, Vel)>();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<Pos>().insert(b, Pos(1)).unwrap();
+    world.write_component::<Vel>().insert(a, Vel(2)).unwrap();
+    world.merge();
+
+    world.run(|commands: Commands| {
+        commands.insert(a, Pos(3));
+        commands.insert(b, Vel(4));
+    });
+    world.merge();
+
+    let group = world.read_resource::<Motion>();
+    let pos = world.read_component::<Pos>();
+    let vel = world.read_component::<Vel>();
+    assert!(group.is_arranged::<Pos>(pos.storage()));
+    assert!(group.is_arranged::<Vel>(vel.storage()));
+    let both = group.archetypes().last().unwrap();
+    assert_eq!(both.members(), 0b11);
+    assert_eq!(group.column::<Pos>(both, pos.storage()), &[Pos(3), Pos(1)]);
+    assert_eq!(group.column::<Vel>(both, vel.storage()), &[Vel(2), Vel(4)]);
+}
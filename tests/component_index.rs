@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, ops::Range};
+
+use hibitset::{BitSet, BitSetLike};
+
+use goggles::{
+    join::IntoJoinExt, world_common::ComponentStorage, Component, Flagged, SpatialIndex,
+    SpatialStructure, ValueIndex, VecStorage, World,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Faction {
+    Red,
+    Blue,
+}
+
+struct Member(Faction);
+
+impl Component for Member {
+    type Storage = Flagged<VecStorage<Member>>;
+}
+
+#[test]
+fn test_value_index() {
+    let mut world = World::new();
+    world.insert_component::<Member>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let c = world.create_entity();
+    world
+        .write_component::<Member>()
+        .insert(a, Member(Faction::Red))
+        .unwrap();
+
+    world
+        .insert_component_index(ValueIndex::new(|m: &Member| m.0))
+        .unwrap();
+
+    let members = |world: &World, faction| {
+        world
+            .read_resource::<ValueIndex<Member, Faction>>()
+            .get(&faction)
+            .map(|s| s.iter().collect::<Vec<_>>())
+            .unwrap_or_default()
+    };
+    assert_eq!(members(&world, Faction::Red), vec![a.index()]);
+
+    {
+        let mut member = world.write_component::<Member>();
+        member.insert(b, Member(Faction::Blue)).unwrap();
+        member.insert(c, Member(Faction::Red)).unwrap();
+        member.get_mut(a).unwrap().0 = Faction::Blue;
+    }
+    // Changes are only applied during merge.
+    assert_eq!(members(&world, Faction::Red), vec![a.index()]);
+    world.merge();
+    assert_eq!(members(&world, Faction::Red), vec![c.index()]);
+    assert_eq!(members(&world, Faction::Blue), vec![a.index(), b.index()]);
+
+    world.entities().delete(a).unwrap();
+    world.merge();
+    assert_eq!(members(&world, Faction::Blue), vec![b.index()]);
+    assert_eq!(
+        world
+            .read_resource::<ValueIndex<Member, Faction>>()
+            .key_of(c.index()),
+        Some(&Faction::Red)
+    );
+}
+
+#[test]
+fn test_value_index_storage_changes() {
+    let mut world = World::new();
+    assert!(world
+        .insert_component_index(ValueIndex::new(|m: &Member| m.0))
+        .is_err());
+
+    world.insert_component::<Member>().unwrap();
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world
+        .write_component::<Member>()
+        .insert(a, Member(Faction::Red))
+        .unwrap();
+    world
+        .insert_component_index(ValueIndex::new(|m: &Member| m.0))
+        .unwrap();
+
+    // Replacing the storage rebuilds the index from the new storage.
+    let mut storage = ComponentStorage::<Member>::new(Flagged::default());
+    storage.insert(b.index(), Member(Faction::Blue));
+    let previous = world.replace_component_storage::<Member>(storage).unwrap();
+    assert!(previous.contains(a.index()));
+    {
+        let index = world.read_resource::<ValueIndex<Member, Faction>>();
+        assert!(index.get(&Faction::Red).is_none_or(|s| s.is_empty()));
+        assert!(index.get(&Faction::Blue).unwrap().contains(b.index()));
+    }
+
+    world.write_component::<Member>().get_mut(b).unwrap().0 = Faction::Red;
+    world.merge();
+    assert_eq!(
+        world
+            .read_resource::<ValueIndex<Member, Faction>>()
+            .key_of(b.index()),
+        Some(&Faction::Red)
+    );
+
+    world.remove_component::<Member>();
+    assert!(!world.contains_resource::<ValueIndex<Member, Faction>>());
+}
+
+struct Position(i32);
+
+impl Component for Position {
+    type Storage = Flagged<VecStorage<Position>>;
+}
+
+// A one dimensional sorted "grid", which records every operation.
+#[derive(Default)]
+struct Line {
+    positions: BTreeMap<i32, BitSet>,
+    at: BTreeMap<u32, i32>,
+    ops: Vec<(&'static str, u32)>,
+}
+
+impl Line {
+    fn set(&mut self, index: u32, position: i32) {
+        if let Some(old) = self.at.insert(index, position) {
+            self.positions.get_mut(&old).unwrap().remove(index);
+        }
+        self.positions.entry(position).or_default().add(index);
+    }
+}
+
+impl SpatialStructure for Line {
+    type Position = i32;
+    type Region = Range<i32>;
+
+    fn insert(&mut self, index: u32, position: i32) {
+        self.ops.push(("insert", index));
+        self.set(index, position);
+    }
+
+    fn update(&mut self, index: u32, position: i32) {
+        self.ops.push(("update", index));
+        self.set(index, position);
+    }
+
+    fn remove(&mut self, index: u32) {
+        self.ops.push(("remove", index));
+        let old = self.at.remove(&index).unwrap();
+        self.positions.get_mut(&old).unwrap().remove(index);
+    }
+
+    fn query(&self, region: &Range<i32>, out: &mut BitSet) {
+        for (_, set) in self.positions.range(region.clone()) {
+            for index in set.iter() {
+                out.add(index);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_spatial_index() {
+    let mut world = World::new();
+    world.insert_component::<Position>().unwrap();
+    world
+        .insert_component_index(SpatialIndex::new(Line::default(), |p: &Position| p.0))
+        .unwrap();
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    {
+        let mut position = world.write_component::<Position>();
+        for (i, &e) in evec.iter().enumerate() {
+            position.insert(e, Position(i as i32 * 10)).unwrap();
+        }
+    }
+    world.merge();
+
+    world
+        .write_component::<Position>()
+        .get_mut(evec[0])
+        .unwrap()
+        .0 = 25;
+    world.entities().delete(evec[3]).unwrap();
+    world.merge();
+
+    let index = world.read_resource::<SpatialIndex<Position, Line>>();
+    assert_eq!(
+        index.structure().ops[4..],
+        [("update", evec[0].index()), ("remove", evec[3].index())]
+    );
+
+    let region = index.query(&(15..30));
+    let position = world.read_component::<Position>();
+    assert_eq!(
+        (&world.entities(), &position, &region)
+            .join()
+            .map(|(e, p, _)| (e, p.0))
+            .collect::<Vec<_>>(),
+        vec![(evec[0], 25), (evec[2], 20)]
+    );
+}
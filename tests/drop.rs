@@ -4,7 +4,7 @@ use goggles::{Component, Entities, VecStorage, World, WriteComponent};
 
 #[test]
 fn test_component_drop() {
-    struct CA(Arc<()>);
+    struct CA(#[allow(dead_code)] Arc<()>);
 
     impl Component for CA {
         type Storage = VecStorage<CA>;
@@ -14,7 +14,7 @@ fn test_component_drop() {
 
     {
         let mut world = World::new();
-        world.insert_component::<CA>();
+        world.insert_component::<CA>().unwrap();
         let (entities, mut component_a): (Entities, WriteComponent<CA>) = world.fetch();
 
         for _ in 0..100 {
@@ -0,0 +1,56 @@
+use goggles::{hibitset::BitSetLike, DynValue, IntoJoinExt, World};
+
+#[test]
+fn test_dyn_components() {
+    let mut world = World::new();
+    assert!(world.insert_dyn_component("health"));
+    assert!(!world.insert_dyn_component("health"));
+    assert!(world.insert_dyn_component("name"));
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    {
+        let mut health = world.write_dyn_component("health");
+        health.insert(a, 10i64).unwrap();
+        health.insert(b, 2.5).unwrap();
+        world
+            .write_dyn_component("name")
+            .insert(a, "alice")
+            .unwrap();
+    }
+
+    {
+        let health = world.read_dyn_component("health");
+        let name = world.read_dyn_component("name");
+        let joined: Vec<_> = (&health, &name).join().collect();
+        assert_eq!(joined, vec![(&DynValue::Int(10), &DynValue::from("alice"))]);
+        assert_eq!(health.get(b).and_then(DynValue::as_float), Some(2.5));
+        assert_eq!(health.storage().modified().join().count(), 2);
+    }
+
+    world.merge();
+    assert_eq!(
+        world
+            .read_dyn_component("health")
+            .storage()
+            .modified()
+            .join()
+            .count(),
+        0
+    );
+
+    world.delete_entity(a).unwrap();
+    let c = world.create_entity();
+    assert!(!world.read_dyn_component("health").contains(c));
+    assert!(!world
+        .read_dyn_component("name")
+        .storage()
+        .contains(a.index()));
+
+    let removed = world.remove_dyn_component("name").unwrap();
+    assert!(removed.mask().is_empty());
+    assert_eq!(
+        world.dyn_component_names().collect::<Vec<_>>(),
+        vec!["health"]
+    );
+}
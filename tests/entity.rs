@@ -115,3 +115,192 @@ fn kill_atomic_kill_now_create_merge_atomic() {
     allocator.merge_atomic(&mut killed);
     assert_eq!(killed, vec![]);
 }
+
+#[test]
+fn allocate_atomic_batch() {
+    let mut allocator = Allocator::default();
+
+    let mut killed = Vec::new();
+    for _ in 0..10 {
+        let e = allocator.allocate();
+        allocator.kill_atomic(e).unwrap();
+    }
+    allocator.merge_atomic(&mut killed);
+    assert_eq!(killed.len(), 10);
+
+    let mut entities = Vec::new();
+    std::thread::scope(|scope| {
+        let handles = (0..4)
+            .map(|_| {
+                let allocator = &allocator;
+                scope.spawn(move || {
+                    let mut batch = Vec::new();
+                    allocator.allocate_atomic_batch(25, &mut batch).unwrap();
+                    batch
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            entities.extend(handle.join().unwrap());
+        }
+    });
+
+    assert_eq!(entities.len(), 100);
+    assert_eq!(entities.iter().copied().collect::<HashSet<_>>().len(), 100);
+    for &e in &entities {
+        assert!(allocator.is_alive(e));
+    }
+    for e in killed {
+        assert!(!allocator.is_alive(e));
+    }
+
+    allocator.merge_atomic(&mut Vec::new());
+    for &e in &entities {
+        assert!(allocator.is_alive(e));
+    }
+    assert_eq!(allocator.max_entity_count(), 100);
+}
+
+#[test]
+fn allocate_past_bitset_capacity() {
+    let allocator = Allocator::default();
+    let capacity = (hibitset::BitSet::BITS_PER_USIZE as u32).pow(4);
+
+    let mut entities = Vec::new();
+    assert!(allocator
+        .allocate_atomic_batch(capacity + 1, &mut entities)
+        .is_err());
+    assert!(entities.is_empty());
+    assert_eq!(allocator.max_entity_count(), 0);
+
+    let e = allocator.allocate_atomic();
+    assert_eq!(e.index(), 0);
+}
+
+#[test]
+fn allocate_atomic_concurrent() {
+    let mut allocator = Allocator::default();
+
+    let mut killed = Vec::new();
+    for _ in 0..300 {
+        let e = allocator.allocate();
+        allocator.kill_atomic(e).unwrap();
+    }
+    allocator.merge_atomic(&mut killed);
+
+    let mut entities = Vec::new();
+    std::thread::scope(|scope| {
+        let handles = (0..8)
+            .map(|_| {
+                let allocator = &allocator;
+                scope.spawn(move || {
+                    (0..50)
+                        .map(|_| allocator.allocate_atomic())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            entities.extend(handle.join().unwrap());
+        }
+    });
+
+    assert_eq!(entities.iter().copied().collect::<HashSet<_>>().len(), 400);
+    assert_eq!(allocator.max_entity_count(), 400);
+
+    allocator.merge_atomic(&mut Vec::new());
+    for &e in &entities {
+        assert!(allocator.is_alive(e));
+    }
+    let e = allocator.allocate();
+    assert_eq!(e.index(), 400);
+
+    for &e in &entities[..10] {
+        allocator.kill(e).unwrap();
+    }
+    let reused = (0..10)
+        .map(|_| allocator.allocate_atomic().index())
+        .collect::<HashSet<_>>();
+    assert_eq!(
+        reused,
+        entities[..10]
+            .iter()
+            .map(|e| e.index())
+            .collect::<HashSet<_>>()
+    );
+}
+
+#[test]
+fn alive_count() {
+    let mut allocator = Allocator::default();
+    assert_eq!(allocator.alive_count(), 0);
+
+    let a = allocator.allocate();
+    let b = allocator.allocate();
+    let c = allocator.allocate_atomic();
+    assert_eq!(allocator.alive_count(), 3);
+
+    allocator.kill(a).unwrap();
+    assert_eq!(allocator.alive_count(), 2);
+
+    allocator.kill_atomic(b).unwrap();
+    allocator.kill_atomic(c).unwrap();
+    assert_eq!(allocator.alive_count(), 2);
+
+    let mut killed = Vec::new();
+    allocator.merge_atomic(&mut killed);
+    assert_eq!(allocator.alive_count(), 0);
+
+    let mut batch = Vec::new();
+    allocator.allocate_atomic_batch(5, &mut batch).unwrap();
+    assert_eq!(allocator.alive_count(), 5);
+    allocator.merge_atomic(&mut killed);
+    assert_eq!(allocator.alive_count(), 5);
+}
+
+#[test]
+fn iter_live() {
+    let mut allocator = Allocator::default();
+
+    let a = allocator.allocate();
+    let b = allocator.allocate();
+    let c = allocator.allocate();
+    allocator.kill(b).unwrap();
+    let d = allocator.allocate_atomic();
+
+    assert_eq!(allocator.iter().collect::<Vec<_>>(), vec![a, d, c]);
+
+    let mut killed = Vec::new();
+    allocator.merge_atomic(&mut killed);
+    assert_eq!(allocator.iter().collect::<Vec<_>>(), vec![a, d, c]);
+}
+
+#[test]
+fn weak_entity() {
+    let mut allocator = Allocator::default();
+    let mut killed = Vec::new();
+
+    let e1 = allocator.allocate();
+    let e2 = allocator.allocate_atomic();
+    let w1 = allocator.weak(e1);
+    let w2 = allocator.weak(e2);
+    assert_eq!(w1.entity(), e1);
+
+    let w1_clone = w1.clone();
+    std::thread::spawn(move || assert!(w1_clone.is_alive()))
+        .join()
+        .unwrap();
+
+    allocator.kill(e1).unwrap();
+    assert!(!w1.is_alive());
+
+    allocator.kill_atomic(e2).unwrap();
+    assert!(w2.is_alive());
+    allocator.merge_atomic(&mut killed);
+    assert!(!w2.is_alive());
+
+    let e3 = allocator.allocate();
+    assert_eq!(e3.index(), e2.index());
+    assert!(allocator.weak(e3).is_alive());
+    assert!(!w2.is_alive());
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "ffi")]
+
+use goggles::ffi::*;
+
+#[test]
+fn test_ffi() {
+    unsafe {
+        let world = goggles_world_new();
+        let position = goggles_component_register(world, 8);
+        let flag = goggles_component_register(world, 1);
+
+        let a = goggles_entity_create(world);
+        let b = goggles_entity_create(world);
+        assert_ne!(a, 0);
+        assert!(goggles_entity_is_alive(world, a));
+
+        let value = [1u8, 2, 3, 4, 5, 6, 7, 8];
+        assert!(goggles_component_set(world, a, position, value.as_ptr(), 8));
+        assert!(!goggles_component_set(
+            world,
+            a,
+            position,
+            value.as_ptr(),
+            4
+        ));
+        assert!(!goggles_component_set(world, a, 7, value.as_ptr(), 8));
+        assert!(goggles_component_set(world, a, flag, [1u8].as_ptr(), 1));
+
+        let mut out = [0u8; 8];
+        assert!(goggles_component_get(
+            world,
+            a,
+            position,
+            out.as_mut_ptr(),
+            8
+        ));
+        assert_eq!(out, value);
+        assert!(!goggles_component_get(
+            world,
+            b,
+            position,
+            out.as_mut_ptr(),
+            8
+        ));
+
+        assert!(goggles_component_remove(world, a, flag));
+        assert!(!goggles_component_has(world, a, flag));
+        assert!(goggles_component_has(world, a, position));
+
+        assert!(goggles_entity_delete(world, a));
+        assert!(!goggles_entity_delete(world, a));
+        goggles_world_merge(world);
+        assert!(!goggles_entity_is_alive(world, a));
+        assert!(!goggles_component_has(world, a, position));
+
+        let c = goggles_entity_create(world);
+        assert!(!goggles_component_has(world, c, position));
+
+        goggles_world_free(world);
+    }
+}
+
+#[test]
+fn test_ffi_zero_sized() {
+    unsafe {
+        let world = goggles_world_new();
+        let tag = goggles_component_register(world, 0);
+        let a = goggles_entity_create(world);
+
+        assert!(goggles_component_set(world, a, tag, std::ptr::null(), 0));
+        assert!(goggles_component_has(world, a, tag));
+        assert!(goggles_component_get(
+            world,
+            a,
+            tag,
+            std::ptr::null_mut(),
+            0
+        ));
+
+        goggles_world_free(world);
+    }
+}
@@ -1,7 +1,19 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
 use hibitset::BitSetLike;
 
 use goggles::{
-    join::IntoJoinExt, Component, Entities, Flagged, ReadComponent, VecStorage, World,
+    join::{Index, IntoJoinExt},
+    tracked::ModifiedBitSet,
+    Component, ComponentConfig, DenseVecStorage, Entities, FetchResources, Flagged, MaskedStorage,
+    ModifiedItem, Observer, RawStorage, ReadComponent, ReadModified, ResourceConflict, SeqPool,
+    StorageKind, System, TrackedStorage, VecStorage, World, WorldResourceId, WorldResources,
     WriteComponent,
 };
 
@@ -23,8 +35,8 @@ impl Component for CB {
 fn test_flagged() {
     let mut world = World::new();
 
-    world.insert_component::<CA>();
-    world.insert_component::<CB>();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
 
     let mut evec = Vec::new();
     for _ in 0..100 {
@@ -82,11 +94,11 @@ fn test_flagged() {
         assert_eq!(component_b.modified_indexes().iter().count(), 50);
 
         for a in component_a.modified().join() {
-            assert!(a.unwrap().0 % 2 == 1);
+            assert!(matches!(a, ModifiedItem::Modified(a) if a.0 % 2 == 1));
         }
 
         for b in component_b.modified_mut().join() {
-            assert!(b.unwrap().0 % 2 == 1);
+            assert!(matches!(b, ModifiedItem::Modified(b) if b.0 % 2 == 1));
         }
 
         assert_eq!(component_a.modified_indexes().iter().count(), 50);
@@ -95,8 +107,8 @@ fn test_flagged() {
         component_a.clear_modified();
         component_b.clear_modified();
 
-        for i in 0..50 {
-            entities.delete(evec[i]).unwrap();
+        for &e in &evec[0..50] {
+            entities.delete(e).unwrap();
         }
 
         assert_eq!(component_a.modified_indexes().iter().count(), 0);
@@ -115,8 +127,8 @@ fn test_flagged() {
 fn test_local_flagged() {
     let mut world = World::new();
 
-    world.insert_component::<CA>();
-    world.insert_component::<CB>();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
 
     let mut evec = Vec::new();
     for _ in 0..100 {
@@ -174,11 +186,11 @@ fn test_local_flagged() {
         assert_eq!(component_b.modified_indexes().iter().count(), 50);
 
         for a in component_a.modified().join() {
-            assert!(a.unwrap().0 % 2 == 1);
+            assert!(matches!(a, ModifiedItem::Modified(a) if a.0 % 2 == 1));
         }
 
         for b in component_b.modified_mut().join() {
-            assert!(b.unwrap().0 % 2 == 1);
+            assert!(matches!(b, ModifiedItem::Modified(b) if b.0 % 2 == 1));
         }
 
         assert_eq!(component_a.modified_indexes().iter().count(), 50);
@@ -187,8 +199,8 @@ fn test_local_flagged() {
         component_a.clear_modified();
         component_b.clear_modified();
 
-        for i in 0..50 {
-            entities.delete(evec[i]).unwrap();
+        for &e in &evec[0..50] {
+            entities.delete(e).unwrap();
         }
 
         assert_eq!(component_a.modified_indexes().iter().count(), 0);
@@ -202,3 +214,361 @@ fn test_local_flagged() {
     assert_eq!(component_a.modified_indexes().iter().count(), 50);
     assert_eq!(component_b.modified_indexes().iter().count(), 50);
 }
+
+#[test]
+fn test_observer() {
+    struct CountCA(Arc<AtomicUsize>);
+
+    impl<'a> System<&'a World> for CountCA {
+        type Resources = WorldResources;
+        type Pool = SeqPool;
+        type Error = Infallible;
+
+        fn check_resources(&self) -> Result<WorldResources, ResourceConflict> {
+            ReadComponent::<CA>::check_resources()
+        }
+
+        fn run(&mut self, _: &SeqPool, world: &'a World) -> Result<(), Infallible> {
+            let component_a: ReadComponent<CA> = world.fetch();
+            self.0
+                .fetch_add(component_a.modified().join().count(), Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut observer = Observer::<CA, _>::new(CountCA(Arc::clone(&count)));
+    assert!(observer
+        .check_resources()
+        .unwrap()
+        .writes()
+        .any(|&r| r == WorldResourceId::component::<CA>()));
+
+    let e = world.create_entity();
+    {
+        let mut component_a = world.write_component::<CA>();
+        component_a.set_track_modified(true);
+        component_a.insert(e, CA(1)).unwrap();
+    }
+
+    observer.run(&SeqPool, &world).unwrap();
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+    assert!(world.read_component::<CA>().modified_indexes().is_empty());
+
+    observer.run(&SeqPool, &world).unwrap();
+    assert_eq!(count.load(Ordering::Relaxed), 1);
+
+    world.write_component::<CA>().get_mut(e).unwrap().0 = 2;
+    observer.run(&SeqPool, &world).unwrap();
+    assert_eq!(count.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn test_clear_modified_on_merge() {
+    let mut world = World::new();
+
+    world
+        .insert_component_with_config(ComponentConfig::<CA>::new().clear_modified_on_merge())
+        .unwrap();
+    world.insert_component::<CB>().unwrap();
+    world.get_component_mut::<CA>().set_track_modified(true);
+    world.get_component_mut::<CB>().set_track_modified(true);
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    world.write_component::<CA>().insert(b, CA(2)).unwrap();
+    world.write_component::<CB>().insert(a, CB(1)).unwrap();
+
+    world.merge();
+    assert!(world.read_component::<CA>().modified_indexes().is_empty());
+    assert!(!world.read_component::<CB>().modified_indexes().is_empty());
+
+    // Removals of deleted entities happen after clearing, so they are still visible.
+    world.entities().delete(b).unwrap();
+    world.merge();
+    assert_eq!(
+        world
+            .read_component::<CA>()
+            .modified_indexes()
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![b.index()]
+    );
+}
+
+#[test]
+fn test_flagged_dense() {
+    struct CD(i32);
+
+    impl Component for CD {
+        type Storage = Flagged<DenseVecStorage<CD>>;
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CD>().unwrap();
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    let mut cd = world.write_component::<CD>();
+    for &e in &evec[1..] {
+        cd.insert(e, CD(e.index() as i32)).unwrap();
+    }
+    assert_eq!(
+        cd.as_slice().iter().map(|c| c.0).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(cd.slice_indexes(), &[1, 2, 3]);
+
+    cd.set_track_modified(true);
+    for c in cd.as_mut_slice() {
+        c.0 *= 2;
+    }
+    assert_eq!(
+        cd.modified_indexes().iter().collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+}
+
+#[test]
+fn test_read_guard() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.get_component_mut::<CA>().set_track_modified(true);
+
+    for i in 0..10 {
+        let e = world.create_entity();
+        world.write_component::<CA>().insert(e, CA(i)).unwrap();
+    }
+    world.get_component_mut::<CA>().clear_modified();
+
+    let component_a = world.read_component::<CA>();
+    for a in component_a.read_guard().join() {
+        if a.get().0 % 3 == 0 {
+            a.mark_modified();
+        }
+    }
+    assert_eq!(
+        component_a.modified_indexes().iter().collect::<Vec<_>>(),
+        vec![0, 3, 6, 9]
+    );
+}
+
+#[test]
+fn test_modified_item() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.get_component_mut::<CA>().set_track_modified(true);
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    let mut component_a = world.write_component::<CA>();
+    for &e in &evec[0..3] {
+        component_a.insert(e, CA(e.index() as i32)).unwrap();
+    }
+    component_a.clear_modified();
+
+    component_a.get_mut(evec[0]).unwrap().0 = 10;
+    component_a.remove(evec[1]).unwrap();
+    component_a.insert(evec[3], CA(3)).unwrap();
+
+    let e = world.entities();
+    assert_eq!(
+        (&e, component_a.modified())
+            .join()
+            .map(|(e, m)| (e.index(), m.map(|c| c.0)))
+            .collect::<Vec<_>>(),
+        vec![
+            (0, ModifiedItem::Modified(10)),
+            (1, ModifiedItem::Removed),
+            (3, ModifiedItem::Inserted(3)),
+        ]
+    );
+}
+
+#[test]
+fn test_modified_readers() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let mut component_a = world.get_component_mut::<CA>();
+    component_a.set_track_modified(true);
+    let reader_a = component_a.register_reader();
+    let reader_b = component_a.register_reader();
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    let mut component_a = world.write_component::<CA>();
+    for &e in &evec {
+        component_a.insert(e, CA(e.index() as i32)).unwrap();
+    }
+
+    component_a.clear_modified();
+    component_a.clear_reader_modified(reader_a);
+    assert_eq!(
+        component_a
+            .reader_modified(reader_b)
+            .join()
+            .filter(|m| matches!(m, ModifiedItem::Inserted(_)))
+            .count(),
+        4
+    );
+
+    component_a.get_mut(evec[2]).unwrap().0 = 20;
+    assert_eq!(
+        component_a
+            .reader_modified_indexes(reader_a)
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        component_a.reader_modified_indexes(reader_b).iter().count(),
+        4
+    );
+    assert_eq!(
+        component_a.modified_indexes().iter().collect::<Vec<_>>(),
+        vec![2]
+    );
+
+    component_a.unregister_reader(reader_a);
+    let reader_c = component_a.register_reader();
+    assert_ne!(reader_c, reader_a);
+    assert!(component_a.reader_modified_indexes(reader_c).is_empty());
+}
+
+#[test]
+#[should_panic(expected = "no such reader")]
+fn test_stale_reader() {
+    let mut storage = MaskedStorage::<Flagged<VecStorage<i32>>>::default();
+    let stale = storage.register_reader();
+    storage.unregister_reader(stale);
+    // The new reader re-uses the slot of the unregistered one.
+    let _reader = storage.register_reader();
+    storage.reader_modified_indexes(stale);
+}
+
+#[test]
+fn test_insert_component_tracked() {
+    let mut world = World::new();
+    world.insert_component_tracked::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    assert!(world.read_component::<CA>().tracking_modified());
+    assert!(!world.read_component::<CB>().tracking_modified());
+
+    let e = world.create_entity();
+    world.write_component::<CA>().insert(e, CA(1)).unwrap();
+    assert_eq!(
+        world
+            .read_component::<CA>()
+            .modified_indexes()
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![e.index()]
+    );
+}
+
+#[test]
+fn test_read_modified() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.get_component_mut::<CA>().set_track_modified(true);
+
+    assert!(ReadModified::<CA>::check_resources()
+        .unwrap()
+        .reads()
+        .any(|&r| r == WorldResourceId::component::<CA>()));
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    {
+        let mut component_a = world.write_component::<CA>();
+        component_a.insert(a, CA(1)).unwrap();
+        component_a.insert(b, CA(2)).unwrap();
+        component_a.clear_modified();
+        component_a.get_mut(b).unwrap().0 = 3;
+    }
+
+    let modified: ReadModified<CA> = world.fetch();
+    assert!(modified.get(a) == Some(&CA(1)));
+    let items = modified.join().collect::<Vec<_>>();
+    assert_eq!(items.len(), 1);
+    assert!(matches!(items[0], ModifiedItem::Modified(&CA(3))));
+}
+
+// A tracked storage implementing only the original `TrackedStorage` methods.
+#[derive(Default)]
+struct MinimalTracked {
+    storage: VecStorage<i32>,
+    modified: ModifiedBitSet,
+}
+
+impl RawStorage for MinimalTracked {
+    type Item = i32;
+
+    const TRACKED: bool = true;
+
+    unsafe fn get(&self, index: Index) -> &i32 {
+        self.storage.get(index)
+    }
+
+    unsafe fn get_mut(&self, index: Index) -> &mut i32 {
+        self.modified.add_atomic(index);
+        self.storage.get_mut(index)
+    }
+
+    unsafe fn insert(&mut self, index: Index, value: i32) {
+        self.modified.add(index);
+        self.storage.insert(index, value);
+    }
+
+    unsafe fn remove(&mut self, index: Index) -> i32 {
+        self.modified.add(index);
+        self.storage.remove(index)
+    }
+}
+
+impl TrackedStorage for MinimalTracked {
+    fn set_track_modified(&mut self, _flag: bool) {}
+
+    fn tracking_modified(&self) -> bool {
+        true
+    }
+
+    fn mark_modified(&self, index: Index) {
+        self.modified.add_atomic(index);
+    }
+
+    fn modified_indexes(&self) -> &ModifiedBitSet {
+        &self.modified
+    }
+
+    fn clear_modified(&mut self) {
+        self.modified.clear();
+    }
+}
+
+#[test]
+fn test_minimal_tracked_storage() {
+    const { assert!(<Flagged<DenseVecStorage<i32>> as RawStorage>::TRACKED) };
+    assert_eq!(
+        <Flagged<DenseVecStorage<i32>> as RawStorage>::KIND,
+        StorageKind::DenseVec
+    );
+
+    let mut storage = MaskedStorage::<MinimalTracked>::default();
+    assert!(storage.tracking_modified());
+    storage.insert(1, 1);
+    storage.insert(2, 2);
+    assert_eq!(storage.modified_indexes().iter().count(), 2);
+    assert!(storage.raw_storage().inserted_indexes().is_empty());
+
+    let items = storage.modified().join().collect::<Vec<_>>();
+    assert!(matches!(
+        items[..],
+        [ModifiedItem::Modified(&1), ModifiedItem::Modified(&2)]
+    ));
+    storage.remove(1);
+    storage.remove(2);
+}
@@ -1,6 +1,11 @@
-use hibitset::{BitSet, BitSetAll, BitSetAnd, BitSetNot, BitSetOr, BitSetXor};
+use std::collections::{BTreeMap, HashMap};
 
-use goggles::join::BitSetConstrained;
+use hibitset::{BitSet, BitSetAll, BitSetAnd, BitSetLike, BitSetNot, BitSetOr, BitSetXor};
+
+use goggles::{
+    join::BitSetConstrained, IntoJoinExt, Join, MapJoin, MaskedStorage, ReactiveQuery, SimpleJoin,
+    SliceJoinMut, VecStorage,
+};
 
 #[test]
 fn test_bitset_constrained() {
@@ -14,3 +19,309 @@ fn test_bitset_constrained() {
     assert!(BitSetOr(BitSetNot(BitSetAll), BitSet::new()).is_constrained());
     assert!(BitSetXor(BitSetNot(BitSetAll), BitSet::new()).is_constrained());
 }
+
+#[test]
+fn test_reactive_query() {
+    let mut a_storage = MaskedStorage::<VecStorage<u32>>::default();
+    let mut b_storage = MaskedStorage::<VecStorage<u32>>::default();
+    let mut query = ReactiveQuery::new();
+
+    a_storage.insert(1, 1);
+    a_storage.insert(2, 2);
+    b_storage.insert(2, 2);
+    b_storage.insert(3, 3);
+
+    query.update((&a_storage, &b_storage));
+    assert_eq!(query.added().iter().collect::<Vec<_>>(), vec![2]);
+    assert!(query.removed().is_empty());
+
+    b_storage.insert(1, 1);
+    a_storage.remove(2);
+
+    query.update((&a_storage, &b_storage));
+    assert_eq!(
+        (&a_storage, &b_storage, query.added())
+            .join()
+            .map(|(a, b, _)| (*a, *b))
+            .collect::<Vec<_>>(),
+        vec![(1, 1)]
+    );
+    assert_eq!(query.removed().iter().collect::<Vec<_>>(), vec![2]);
+
+    query.update((&a_storage, &b_storage));
+    assert!(query.added().is_empty());
+    assert!(query.removed().is_empty());
+    assert_eq!(query.matched().iter().collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn test_join_sorted_by_key() {
+    let mut storage = MaskedStorage::<VecStorage<i32>>::default();
+    storage.insert(0, 3);
+    storage.insert(1, -1);
+    storage.insert(5, 3);
+    storage.insert(9, 0);
+
+    assert_eq!(
+        (&storage).join().copied().collect::<Vec<_>>(),
+        vec![3, -1, 3, 0]
+    );
+
+    assert_eq!(
+        (&mut storage)
+            .join_sorted_by_key(|v| **v)
+            .map(|v| {
+                *v += 1;
+                *v
+            })
+            .collect::<Vec<_>>(),
+        vec![0, 1, 4, 4]
+    );
+}
+
+#[test]
+fn test_map_join() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    storage.insert(1, 10);
+    storage.insert(2, 20);
+    storage.insert(4, 40);
+
+    let hash_map = [(2, "b"), (3, "c"), (4, "d")]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    assert_eq!(
+        (&storage, MapJoin(&hash_map))
+            .join()
+            .map(|(a, b)| (*a, *b))
+            .collect::<Vec<_>>(),
+        vec![(20, "b"), (40, "d")]
+    );
+
+    let btree_map = [(1, 'a'), (4, 'd')].into_iter().collect::<BTreeMap<_, _>>();
+    assert_eq!(
+        (&mut storage, MapJoin(&btree_map))
+            .join()
+            .map(|(a, b)| {
+                *a += 1;
+                (*a, *b)
+            })
+            .collect::<Vec<_>>(),
+        vec![(11, 'a'), (41, 'd')]
+    );
+}
+
+#[test]
+fn test_constrain() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    for i in 0..8 {
+        storage.insert(i, i * 10);
+    }
+
+    let selection = [1, 3, 5, 9].into_iter().collect::<BitSet>();
+    assert_eq!(
+        (&storage)
+            .constrain(&selection)
+            .join()
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![10, 30, 50]
+    );
+
+    for (a, i) in (&mut storage, BitSetAll).constrain(&selection).join() {
+        *a += i;
+    }
+    assert_eq!(storage.get(3), Some(&33));
+    assert_eq!(storage.get(4), Some(&40));
+
+    // Constraining an unconstrained join by a constrained mask makes it constrained.
+    assert_eq!(BitSetAll.constrain(&selection).join().count(), 4);
+}
+
+#[test]
+fn test_map() {
+    struct Transform {
+        pos: u32,
+        rot: u32,
+    }
+
+    fn transforms<'a>(
+        pos: &'a MaskedStorage<VecStorage<u32>>,
+        rot: &'a MaskedStorage<VecStorage<u32>>,
+    ) -> impl Join<Item = Transform, Mask: BitSetConstrained> + 'a {
+        (pos, rot).map(|(&pos, &rot)| Transform { pos, rot })
+    }
+
+    let mut pos = MaskedStorage::<VecStorage<u32>>::default();
+    let mut rot = MaskedStorage::<VecStorage<u32>>::default();
+    pos.insert(1, 10);
+    pos.insert(2, 20);
+    rot.insert(2, 2);
+    rot.insert(3, 3);
+
+    let mut scale = MaskedStorage::<VecStorage<u32>>::default();
+    scale.insert(2, 5);
+
+    assert_eq!(
+        (transforms(&pos, &rot), &scale)
+            .join()
+            .map(|(t, s)| (t.pos, t.rot, *s))
+            .collect::<Vec<_>>(),
+        vec![(20, 2, 5)]
+    );
+    assert_eq!(
+        (&mut pos)
+            .map(|p| {
+                *p += 1;
+                *p
+            })
+            .join()
+            .collect::<Vec<_>>(),
+        vec![11, 21]
+    );
+}
+
+#[test]
+fn test_filter() {
+    let mut pos = MaskedStorage::<VecStorage<i32>>::default();
+    let mut vel = MaskedStorage::<VecStorage<i32>>::default();
+    for i in 0..6 {
+        pos.insert(i, i as i32 - 3);
+        vel.insert(i, 0);
+    }
+
+    let filtered = (&mut vel, &pos).filter(|(_, &p)| p > 0);
+    let positive = filtered.mask().clone();
+    for (v, p) in filtered.join() {
+        *v = *p * 2;
+    }
+
+    assert_eq!((&positive).iter().collect::<Vec<_>>(), vec![4, 5]);
+    assert_eq!(
+        (&vel).join().copied().collect::<Vec<_>>(),
+        vec![0, 0, 0, 0, 2, 4]
+    );
+    assert_eq!(
+        (&pos)
+            .constrain(&positive)
+            .join()
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn test_simple_join() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    for i in [1, 3, 5, 7] {
+        storage.insert(i, i);
+    }
+
+    let external = [10, 11, 12, 13, 14, 15, 16, 17];
+    let mut mask = BitSet::new();
+    for i in 0..external.len() as u32 {
+        mask.add(i);
+    }
+    let simple = SimpleJoin::new(&mask, |i| external[i as usize]);
+    assert_eq!(
+        (&storage, simple).join().collect::<Vec<_>>(),
+        vec![(&1, 11), (&3, 13), (&5, 15), (&7, 17)]
+    );
+
+    let mut velocities = vec![0; 8];
+    for (v, p) in (SliceJoinMut::new(&mask, &mut velocities), &storage).join() {
+        *v += *p * 2;
+    }
+    assert_eq!(velocities, vec![0, 2, 0, 6, 0, 10, 0, 14]);
+}
+
+#[test]
+fn test_matched_mask() {
+    struct NoGet(BitSet);
+
+    impl Join for NoGet {
+        type Item = ();
+        type Access = ();
+        type Mask = BitSet;
+
+        fn open(self) -> (Self::Mask, Self::Access) {
+            (self.0, ())
+        }
+
+        unsafe fn get(_: &Self::Access, _: u32) -> Self::Item {
+            panic!("matched_mask fetched an item");
+        }
+    }
+
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    storage.insert(1, 1);
+    storage.insert(4, 4);
+    storage.insert(9, 9);
+
+    let mut other = BitSet::new();
+    other.add(4);
+    other.add(9);
+    other.add(12);
+
+    let mask = (&storage, NoGet(other)).matched_mask();
+    assert_eq!(mask.iter().collect::<Vec<_>>(), vec![4, 9]);
+}
+
+// An unsound join which fetches every index of the inner join twice.
+#[cfg(feature = "debug-join-checks")]
+struct Twice<J>(J);
+
+#[cfg(feature = "debug-join-checks")]
+impl<J: Join> Join for Twice<J> {
+    type Item = (J::Item, J::Item);
+    type Access = J::Access;
+    type Mask = J::Mask;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        self.0.open()
+    }
+
+    unsafe fn get(access: &Self::Access, index: u32) -> Self::Item {
+        (J::get(access, index), J::get(access, index))
+    }
+}
+
+// An unsound join which replaces the mask of the inner join.
+#[cfg(feature = "debug-join-checks")]
+struct Remask<J>(J, BitSet);
+
+#[cfg(feature = "debug-join-checks")]
+impl<J: Join> Join for Remask<J> {
+    type Item = J::Item;
+    type Access = J::Access;
+    type Mask = BitSet;
+
+    fn open(self) -> (Self::Mask, Self::Access) {
+        (self.1, self.0.open().1)
+    }
+
+    unsafe fn get(access: &Self::Access, index: u32) -> Self::Item {
+        J::get(access, index)
+    }
+}
+
+#[cfg(feature = "debug-join-checks")]
+#[test]
+#[should_panic(expected = "called twice with index 3")]
+fn test_debug_join_checks_twice() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    storage.insert(3, 3);
+    Twice(&mut storage).join().for_each(drop);
+}
+
+#[cfg(feature = "debug-join-checks")]
+#[test]
+#[should_panic(expected = "index 4 outside of the mask")]
+fn test_debug_join_checks_mask() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    storage.insert(3, 3);
+    let mut mask = BitSet::new();
+    mask.add(3);
+    mask.add(4);
+    Remask(&mut storage, mask).join().for_each(drop);
+}
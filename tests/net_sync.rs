@@ -0,0 +1,226 @@
+use std::convert::TryInto;
+
+use goggles::{
+    Component, DecodeError, DeltaPacket, Flagged, NetSync, SyncComponent, VecStorage, World,
+};
+
+#[derive(Debug, PartialEq)]
+struct Position(i32);
+
+impl Component for Position {
+    type Storage = Flagged<VecStorage<Position>>;
+}
+
+impl SyncComponent for Position {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(Position(i32::from_le_bytes(
+            bytes.try_into().map_err(|_| DecodeError::new("position"))?,
+        )))
+    }
+}
+
+// A newer shape of `Position`, which used to be encoded as a single `i32`.
+#[derive(Debug, PartialEq)]
+struct Position2(i32, i32);
+
+impl Component for Position2 {
+    type Storage = Flagged<VecStorage<Position2>>;
+}
+
+impl SyncComponent for Position2 {
+    const VERSION: u32 = 1;
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_le_bytes());
+        out.extend_from_slice(&self.1.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() != 8 {
+            return Err(DecodeError::new("position"));
+        }
+        Ok(Position2(
+            i32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            i32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+        ))
+    }
+
+    fn migrate(bytes: &[u8], old_version: u32) -> Result<Self, DecodeError> {
+        assert_eq!(old_version, 0);
+        Ok(Position2(Position::decode(bytes)?.0, 0))
+    }
+}
+
+fn world() -> (World, NetSync) {
+    let mut world = World::new();
+    world.insert_component::<Position>().unwrap();
+    let mut sync = NetSync::new();
+    sync.register::<Position>(&mut world);
+    (world, sync)
+}
+
+fn transfer(packet: &DeltaPacket) -> DeltaPacket {
+    let mut bytes = Vec::new();
+    packet.encode(&mut bytes);
+    DeltaPacket::decode(&bytes).unwrap()
+}
+
+#[test]
+fn test_net_sync() {
+    let (mut server, mut server_sync) = world();
+    let (mut client, mut client_sync) = world();
+
+    let a = server.create_entity();
+    let b = server.create_entity();
+    server
+        .write_component::<Position>()
+        .insert(a, Position(1))
+        .unwrap();
+    server
+        .write_component::<Position>()
+        .insert(b, Position(2))
+        .unwrap();
+
+    let packet = transfer(&server_sync.collect_delta(&server));
+    assert_eq!(packet.changes.len(), 2);
+    client_sync.apply_delta(&mut client, &packet).unwrap();
+
+    let client_a = client_sync.local_entity(a.index()).unwrap();
+    let client_b = client_sync.local_entity(b.index()).unwrap();
+    assert_eq!(
+        client.read_component::<Position>().get(client_a),
+        Some(&Position(1))
+    );
+
+    assert!(server_sync.collect_delta(&server).is_empty());
+
+    server.write_component::<Position>().get_mut(a).unwrap().0 = 10;
+    server.delete_entity(b).unwrap();
+    server.merge();
+
+    let packet = transfer(&server_sync.collect_delta(&server));
+    assert_eq!(packet.despawned, vec![b.index()]);
+    assert_eq!(packet.changes.len(), 1);
+    client_sync.apply_delta(&mut client, &packet).unwrap();
+    client.merge();
+
+    assert_eq!(
+        client.read_component::<Position>().get(client_a),
+        Some(&Position(10))
+    );
+    assert!(!client.entities().is_alive(client_b));
+
+    server.write_component::<Position>().remove(a).unwrap();
+    let packet = transfer(&server_sync.collect_delta(&server));
+    client_sync.apply_delta(&mut client, &packet).unwrap();
+    assert!(!client.read_component::<Position>().contains(client_a));
+}
+
+#[test]
+fn test_delta_packet_decode_error() {
+    assert!(DeltaPacket::decode(&[1, 0, 0]).is_err());
+    assert!(DeltaPacket::decode(&[0; 13]).is_err());
+}
+
+#[test]
+fn test_snapshot_migration() {
+    let (mut old, old_sync) = world();
+    let a = old.create_entity();
+    old.write_component::<Position>()
+        .insert(a, Position(3))
+        .unwrap();
+
+    let packet = transfer(&old_sync.snapshot(&old));
+    assert_eq!(packet.versions, vec![0]);
+    assert_eq!(packet.changes.len(), 1);
+
+    let mut new = World::new();
+    new.insert_component::<Position2>().unwrap();
+    let mut new_sync = NetSync::new();
+    new_sync.register::<Position2>(&mut new);
+    new_sync.apply_delta(&mut new, &packet).unwrap();
+
+    let new_a = new_sync.local_entity(a.index()).unwrap();
+    assert_eq!(
+        new.read_component::<Position2>().get(new_a),
+        Some(&Position2(3, 0))
+    );
+
+    let newer = transfer(&new_sync.snapshot(&new));
+    assert_eq!(newer.versions, vec![1]);
+    let (mut old, mut old_sync) = world();
+    assert!(old_sync.apply_delta(&mut old, &newer).is_err());
+}
+
+#[test]
+fn test_state_hash() {
+    let (mut a, a_sync) = world();
+    let (mut b, b_sync) = world();
+
+    for world in [&mut a, &mut b] {
+        let e = world.create_entity();
+        world
+            .write_component::<Position>()
+            .insert(e, Position(1))
+            .unwrap();
+        world.create_entity();
+    }
+    assert_eq!(a.state_hash(&a_sync), b.state_hash(&b_sync));
+
+    let e = a.entities().entity(0).unwrap();
+    a.write_component::<Position>().get_mut(e).unwrap().0 = 2;
+    assert_ne!(a.state_hash(&a_sync), b.state_hash(&b_sync));
+
+    let e = b.entities().entity(0).unwrap();
+    b.write_component::<Position>().get_mut(e).unwrap().0 = 2;
+    assert_eq!(a.state_hash(&a_sync), b.state_hash(&b_sync));
+
+    let e = a.entities().entity(1).unwrap();
+    a.delete_entity(e).unwrap();
+    a.merge();
+    assert_ne!(a.state_hash(&a_sync), b.state_hash(&b_sync));
+}
+
+#[test]
+fn test_net_sync_reused_index() {
+    let (mut server, mut server_sync) = world();
+    let (mut client, mut client_sync) = world();
+
+    let a = server.create_entity();
+    server
+        .write_component::<Position>()
+        .insert(a, Position(1))
+        .unwrap();
+    client_sync
+        .apply_delta(&mut client, &server_sync.collect_delta(&server))
+        .unwrap();
+    let client_a = client_sync.local_entity(a.index()).unwrap();
+
+    server.delete_entity(a).unwrap();
+    server.merge();
+    let b = server.create_entity();
+    assert_eq!(b.index(), a.index());
+    server
+        .write_component::<Position>()
+        .insert(b, Position(2))
+        .unwrap();
+
+    let packet = transfer(&server_sync.collect_delta(&server));
+    assert_eq!(packet.despawned, vec![a.index()]);
+    assert_eq!(packet.changes.len(), 1);
+    assert_eq!(packet.changes[0].generation, b.generation());
+    client_sync.apply_delta(&mut client, &packet).unwrap();
+    client.merge();
+
+    let client_b = client_sync.local_entity(b.index()).unwrap();
+    assert!(!client.entities().is_alive(client_a));
+    assert_eq!(
+        client.read_component::<Position>().get(client_b),
+        Some(&Position(2))
+    );
+    assert!(server_sync.collect_delta(&server).is_empty());
+}
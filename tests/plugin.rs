@@ -0,0 +1,69 @@
+use goggles::{join::IntoJoinExt, Component, Plugin, VecStorage, World};
+
+struct Position(f32);
+struct Velocity(f32);
+
+impl Component for Position {
+    type Storage = VecStorage<Position>;
+}
+
+impl Component for Velocity {
+    type Storage = VecStorage<Velocity>;
+}
+
+type Step = fn(&World);
+
+struct Physics {
+    gravity: f32,
+}
+
+struct Gravity(f32);
+
+impl Plugin<Vec<Step>> for Physics {
+    fn build(&self, world: &mut World, schedule: &mut Vec<Step>) {
+        world.insert_component::<Position>().unwrap();
+        world.insert_component::<Velocity>().unwrap();
+        world.insert_resource(Gravity(self.gravity));
+        schedule.push(|world| {
+            let gravity = world.read_resource::<Gravity>().0;
+            let mut velocity = world.write_component::<Velocity>();
+            let mut position = world.write_component::<Position>();
+            for (p, v) in (&mut position, &mut velocity).join() {
+                v.0 -= gravity;
+                p.0 += v.0;
+            }
+        });
+    }
+}
+
+#[test]
+fn test_add_plugin() {
+    let mut world = World::new();
+    let mut schedule: Vec<Step> = Vec::new();
+    world.add_plugin(Physics { gravity: 2.0 }, &mut schedule);
+    world.add_plugin(
+        |world: &mut World, _: &mut Vec<Step>| {
+            world.insert_resource(0u32);
+        },
+        &mut schedule,
+    );
+
+    assert!(world.contains_resource::<u32>());
+    assert_eq!(schedule.len(), 1);
+
+    let e = world.create_entity();
+    world
+        .write_component::<Position>()
+        .insert(e, Position(0.0))
+        .unwrap();
+    world
+        .write_component::<Velocity>()
+        .insert(e, Velocity(0.0))
+        .unwrap();
+
+    for step in &schedule {
+        step(&world);
+    }
+    assert_eq!(world.read_component::<Velocity>().get(e).unwrap().0, -2.0);
+    assert_eq!(world.read_component::<Position>().get(e).unwrap().0, -2.0);
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use hibitset::{BitSet, BitSetLike};
+
+use goggles::{query, Component, Entity, Not, Or, VecStorage, With, Without, World};
+
+#[derive(Debug, PartialEq)]
+struct Pos(i32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Debug, PartialEq)]
+struct Vel(i32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+struct Frozen;
+
+impl Component for Frozen {
+    type Storage = VecStorage<Self>;
+}
+
+#[test]
+fn test_query() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+    world.insert_component::<Frozen>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    let c = world.create_entity();
+    for (i, &e) in [a, b, c].iter().enumerate() {
+        world
+            .write_component::<Pos>()
+            .insert(e, Pos(i as i32))
+            .unwrap();
+    }
+    world.write_component::<Vel>().insert(a, Vel(1)).unwrap();
+    world.write_component::<Vel>().insert(b, Vel(2)).unwrap();
+    world.write_component::<Frozen>().insert(b, Frozen).unwrap();
+
+    for (pos, vel) in query!(world, (&mut Pos, &Vel, without Frozen)) {
+        pos.0 += vel.0;
+    }
+    assert_eq!(world.read_component::<Pos>().get(a), Some(&Pos(1)));
+    assert_eq!(world.read_component::<Pos>().get(b), Some(&Pos(1)));
+
+    let found: Vec<_> = query!(world, (entity, maybe &Vel, with Pos, without Frozen,))
+        .map(|(e, vel)| (e, vel.map(|v| v.0)))
+        .collect();
+    assert_eq!(found, vec![(a, Some(1)), (c, None)]);
+
+    let frozen: Vec<_> = query!(world, (entity, with Frozen)).collect();
+    assert_eq!(frozen, vec![(b,)]);
+}
+
+#[test]
+fn test_query_mask() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+    world.insert_component::<Frozen>().unwrap();
+
+    let evec: Vec<Entity> = (0..5).map(|_| world.create_entity()).collect();
+    for &e in &evec[..4] {
+        world.write_component::<Pos>().insert(e, Pos(0)).unwrap();
+    }
+    world
+        .write_component::<Vel>()
+        .insert(evec[1], Vel(0))
+        .unwrap();
+    world
+        .write_component::<Vel>()
+        .insert(evec[2], Vel(0))
+        .unwrap();
+    world
+        .write_component::<Frozen>()
+        .insert(evec[2], Frozen)
+        .unwrap();
+    world
+        .write_component::<Frozen>()
+        .insert(evec[4], Frozen)
+        .unwrap();
+
+    let indexes = |mask: BitSet| mask.iter().collect::<Vec<_>>();
+    assert_eq!(
+        indexes(world.query_mask::<(With<Pos>, Without<Vel>)>()),
+        vec![0, 3]
+    );
+    assert_eq!(
+        indexes(world.query_mask::<Or<With<Vel>, With<Frozen>>>()),
+        vec![1, 2, 4]
+    );
+    assert_eq!(indexes(world.query_mask::<Not<With<Pos>>>()), vec![4]);
+
+    world.delete_entity(evec[3]).unwrap();
+    let mask = world.query_mask::<(With<Pos>, Not<Or<With<Vel>, With<Frozen>>>)>();
+    assert_eq!(indexes(mask), vec![0]);
+}
+
+#[test]
+fn test_query_mask_cached() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<Pos>().insert(a, Pos(0)).unwrap();
+    world.write_component::<Pos>().insert(b, Pos(0)).unwrap();
+
+    let first = world.query_mask_cached::<(With<Pos>, Without<Vel>)>();
+    assert_eq!((&*first).iter().collect::<Vec<_>>(), vec![0, 1]);
+    let second = world.query_mask_cached::<(With<Pos>, Without<Vel>)>();
+    assert!(Arc::ptr_eq(&first, &second));
+
+    // Replacing an existing component is not a structural change.
+    world.write_component::<Pos>().insert(a, Pos(1)).unwrap();
+    let third = world.query_mask_cached::<(With<Pos>, Without<Vel>)>();
+    assert!(Arc::ptr_eq(&first, &third));
+
+    world.write_component::<Vel>().insert(a, Vel(0)).unwrap();
+    let mask = world.query_mask_cached::<(With<Pos>, Without<Vel>)>();
+    assert_eq!((&*mask).iter().collect::<Vec<_>>(), vec![1]);
+
+    let c = world.create_entity();
+    let mask = world.query_mask_cached::<Without<Vel>>();
+    assert_eq!((&*mask).iter().collect::<Vec<_>>(), vec![1, c.index()]);
+    world.delete_entity(c).unwrap();
+    let mask = world.query_mask_cached::<Without<Vel>>();
+    assert_eq!((&*mask).iter().collect::<Vec<_>>(), vec![1]);
+}
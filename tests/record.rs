@@ -0,0 +1,149 @@
+use std::convert::TryInto;
+
+use goggles::{
+    Component, DecodeError, Flagged, IntoJoinExt, RecordedTick, Recorder, Replayer, SyncComponent,
+    SyncResource, Tick, VecStorage, World,
+};
+
+#[derive(Debug, PartialEq)]
+struct Health(u32);
+
+impl Component for Health {
+    type Storage = Flagged<VecStorage<Health>>;
+}
+
+impl SyncComponent for Health {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(Health(u32::from_le_bytes(
+            bytes.try_into().map_err(|_| DecodeError::new("health"))?,
+        )))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Seed(u32);
+
+impl SyncResource for Seed {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        Ok(Seed(u32::from_le_bytes(
+            bytes.try_into().map_err(|_| DecodeError::new("seed"))?,
+        )))
+    }
+}
+
+#[test]
+fn test_record_replay() {
+    let mut world = World::new();
+    world.insert_component::<Health>().unwrap();
+    world.insert_resource(Seed(7));
+    let mut recorder = Recorder::new();
+    recorder.register_component::<Health>(&mut world);
+    recorder.register_resource::<Seed>();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world
+        .write_component::<Health>()
+        .insert(a, Health(10))
+        .unwrap();
+    world.merge();
+    recorder.record(&world);
+
+    world.write_component::<Health>().get_mut(a).unwrap().0 = 5;
+    world.delete_entity(b).unwrap();
+    world.merge();
+    recorder.record(&world);
+
+    world.write_resource::<Seed>().0 = 8;
+    let c = world.create_entity();
+    world.merge();
+    recorder.record(&world);
+
+    let log = recorder.take_log();
+    assert_eq!(log.len(), 3);
+    assert_eq!(log[0].spawned, vec![(b.index(), b.generation())]);
+    assert_eq!(log[0].resources.len(), 1);
+    assert_eq!(log[1].delta.despawned, vec![b.index()]);
+    assert!(log[1].resources.is_empty());
+    assert_eq!(log[2].spawned, vec![(c.index(), c.generation())]);
+
+    let mut bytes = Vec::new();
+    for recorded in &log {
+        recorded.encode(&mut bytes);
+    }
+    let mut decoded = Vec::new();
+    let mut rest = &bytes[..];
+    while !rest.is_empty() {
+        let (recorded, r) = RecordedTick::decode(rest).unwrap();
+        decoded.push(recorded);
+        rest = r;
+    }
+    assert_eq!(decoded, log);
+
+    let mut replay_world = World::new();
+    replay_world.insert_component::<Health>().unwrap();
+    let mut replayer = Replayer::new();
+    replayer.register_component::<Health>(&mut replay_world);
+    replayer.register_resource::<Seed>();
+    replayer.replay(&mut replay_world, &decoded).unwrap();
+
+    let replay_a = replayer.local_entity(a.index()).unwrap();
+    let replay_c = replayer.local_entity(c.index()).unwrap();
+    assert_eq!(
+        replay_world.read_component::<Health>().get(replay_a),
+        Some(&Health(5))
+    );
+    assert_eq!((&replay_world.entities()).join().count(), 2);
+    assert!(replay_world.entities().is_alive(replay_c));
+    assert_eq!(*replay_world.read_resource::<Seed>(), Seed(8));
+    assert_eq!(
+        replay_world.read_resource::<Tick>().get(),
+        world.read_resource::<Tick>().get()
+    );
+}
+
+#[test]
+fn test_record_reused_index() {
+    let mut world = World::new();
+    world.insert_component::<Health>().unwrap();
+    let mut recorder = Recorder::new();
+    recorder.register_component::<Health>(&mut world);
+
+    let a = world.create_entity();
+    world
+        .write_component::<Health>()
+        .insert(a, Health(1))
+        .unwrap();
+    recorder.record(&world);
+
+    world.delete_entity(a).unwrap();
+    let b = world.create_entity();
+    assert_eq!(b.index(), a.index());
+    world
+        .write_component::<Health>()
+        .insert(b, Health(2))
+        .unwrap();
+    world.merge();
+    recorder.record(&world);
+
+    let mut replay_world = World::new();
+    replay_world.insert_component::<Health>().unwrap();
+    let mut replayer = Replayer::new();
+    replayer.register_component::<Health>(&mut replay_world);
+    replayer.replay(&mut replay_world, recorder.log()).unwrap();
+
+    let replay_b = replayer.local_entity(b.index()).unwrap();
+    assert_eq!((&replay_world.entities()).join().count(), 1);
+    assert_eq!(
+        replay_world.read_component::<Health>().get(replay_b),
+        Some(&Health(2))
+    );
+}
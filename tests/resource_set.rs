@@ -1,6 +1,8 @@
+use std::sync::Arc;
+
 use goggles::{
-    fetch_resources::FetchResources,
-    resource_set::{Read, ResourceSet, Write},
+    fetch_resources::{CachedFetch, FetchResources},
+    resource_set::{Read, ResourceId, ResourceSet, Write},
 };
 
 #[test]
@@ -24,3 +26,92 @@ fn test_conflicts() {
 
     assert!(<(Read<A>, Read<B>, Write<A>)>::check_resources().is_err());
 }
+
+#[test]
+fn test_parent_fallback() {
+    struct Config(u32);
+    struct Level(u32);
+
+    let mut global = ResourceSet::new();
+    global.insert(Config(1));
+    let global = Arc::new(global);
+
+    let mut level = ResourceSet::with_parent(Arc::clone(&global));
+    level.insert(Level(2));
+
+    assert!(level.contains::<Config>());
+    assert!(!level.contains_local::<Config>());
+    assert!(!global.contains::<Level>());
+
+    {
+        let (config, level_res) = level.fetch::<(Read<Config>, Read<Level>)>();
+        assert_eq!(config.0, 1);
+        assert_eq!(level_res.0, 2);
+    }
+
+    level.borrow_mut::<Config>().0 = 3;
+    assert_eq!(global.borrow::<Config>().0, 3);
+
+    level.insert(Config(4));
+    assert_eq!(level.borrow::<Config>().0, 4);
+    assert_eq!(global.borrow::<Config>().0, 3);
+
+    level.remove::<Config>();
+    level.set_parent(None);
+    assert!(!level.contains::<Config>());
+}
+
+#[test]
+fn test_cached_fetch() {
+    struct A(u32);
+    struct B(u32);
+
+    let mut res = ResourceSet::new();
+    res.insert(A(0));
+    res.insert(B(0));
+
+    let mut other = ResourceSet::new();
+    other.insert(A(10));
+
+    let mut cached = CachedFetch::new();
+    assert!(!cached.is_cached());
+    for _ in 0..3 {
+        let (mut a, b) = cached.fetch::<_, (Write<A>, Read<B>)>(&res);
+        a.0 += 1 + b.0;
+    }
+    assert!(cached.is_cached());
+    assert!(cached
+        .resources()
+        .unwrap()
+        .writes()
+        .eq([&ResourceId::of::<A>()]));
+
+    // Nothing stays borrowed between fetches.
+    assert_eq!(res.borrow::<A>().0, 3);
+    let _b = res.borrow_mut::<B>();
+    assert!(cached.try_fetch::<_, (Write<A>, Read<B>)>(&res).is_err());
+
+    cached.fetch::<_, Write<A>>(&other).0 += 1;
+    assert_eq!(other.borrow::<A>().0, 11);
+    assert!(cached.resources().unwrap().reads().next().is_none());
+
+    cached.clear();
+    assert!(!cached.is_cached());
+}
+
+#[test]
+fn test_borrow_or_default() {
+    let mut res = ResourceSet::new();
+    res.insert(1u8);
+
+    *res.borrow_mut_or_default::<u32>() += 3;
+    assert_eq!(*res.borrow_or_default::<u8>(), 1);
+    assert_eq!(*res.borrow_or_default::<u32>(), 3);
+    assert!(res.contains_local::<u32>());
+
+    res.set_track_modified::<u32>(true);
+    *res.get_mut::<u32>() += 1;
+    assert!(res.is_modified::<u32>());
+    assert_eq!(res.remove::<u32>(), Some(4));
+    assert!(!res.contains::<u32>());
+}
@@ -0,0 +1,60 @@
+use goggles::{AnyCloneComponentSet, Component, Scene, VecStorage, World};
+
+#[derive(Debug, Clone, PartialEq)]
+struct Name(&'static str);
+
+impl Component for Name {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Health(u32);
+
+impl Component for Health {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone)]
+struct Level(u32);
+
+#[test]
+fn test_scene() {
+    let mut scene = Scene::new();
+    scene.register_component::<Name>();
+    scene.register_component::<Health>();
+    scene.add_resource(Level(3));
+
+    let mut player = AnyCloneComponentSet::new();
+    player.insert(Name("player"));
+    player.insert(Health(10));
+    let player = scene.add_entity(player);
+
+    let mut door = AnyCloneComponentSet::new();
+    door.insert(Name("door"));
+    let door = scene.add_entity(door);
+
+    let (world, entities) = scene.to_world();
+    assert_eq!(world.read_resource::<Level>().0, 3);
+    assert_eq!(
+        world.read_component::<Name>().get(entities[player]),
+        Some(&Name("player"))
+    );
+    assert_eq!(
+        world.read_component::<Health>().get(entities[player]),
+        Some(&Health(10))
+    );
+    assert_eq!(
+        world.read_component::<Name>().get(entities[door]),
+        Some(&Name("door"))
+    );
+    assert!(!world.read_component::<Health>().contains(entities[door]));
+
+    // Instantiating into an existing world creates a fresh copy of every entity.
+    let mut world = World::new();
+    world.insert_component::<Name>().unwrap();
+    let first = scene.instantiate(&mut world);
+    let second = scene.instantiate(&mut world);
+    assert_ne!(first[player], second[player]);
+    assert_eq!(world.entities().alive_count(), 4);
+    assert_eq!(second.iter().count(), 2);
+}
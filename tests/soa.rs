@@ -0,0 +1,146 @@
+use goggles::{
+    soa_component, Component, EntityMapRegistry, IntoJoinExt, ReadComponent, VecStorage, World,
+    WriteResource,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Vec2(f32, f32);
+
+soa_component! {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Body {
+        position: Vec2,
+        mass: f32,
+    }
+    mod body_soa;
+}
+
+struct Tag(u32);
+
+impl Component for Tag {
+    type Storage = VecStorage<Self>;
+}
+
+#[test]
+fn test_soa_storage() {
+    let mut storage = body_soa::Storage::new();
+    let body = |x, mass| Body {
+        position: Vec2(x, 0.0),
+        mass,
+    };
+
+    assert_eq!(storage.insert(3, body(3.0, 1.0)), None);
+    assert_eq!(storage.insert(1, body(1.0, 2.0)), None);
+    assert_eq!(storage.insert(7, body(7.0, 3.0)), None);
+    assert_eq!(storage.insert(1, body(1.5, 2.5)), Some(body(1.0, 2.0)));
+    assert_eq!(storage.len(), 3);
+    assert_eq!(storage.indexes(), &[3, 1, 7]);
+    assert_eq!(*storage.get(1).unwrap().mass, 2.5);
+    assert!(storage.get(2).is_none());
+
+    *storage.get_mut(7).unwrap().mass = 4.0;
+
+    let columns = storage.columns();
+    assert_eq!(columns.mass, &[1.0, 2.5, 4.0]);
+    assert_eq!(columns.position[2], Vec2(7.0, 0.0));
+
+    for mass in storage.columns_mut().mass {
+        *mass *= 2.0;
+    }
+
+    assert_eq!(storage.remove(3), Some(body(3.0, 2.0)));
+    assert_eq!(storage.remove(3), None);
+    assert_eq!(storage.indexes(), &[7, 1]);
+    assert_eq!(storage.columns().mass, &[8.0, 5.0]);
+    assert!(!storage.contains(3));
+    assert!(storage.contains(7));
+
+    let joined: Vec<_> = (&storage).join().map(|b| (b.position.0, *b.mass)).collect();
+    assert_eq!(joined, vec![(1.5, 5.0), (7.0, 8.0)]);
+
+    storage.clear();
+    assert!(storage.is_empty());
+    assert!(storage.get(1).is_none());
+}
+
+#[test]
+fn test_soa_join_with_components() {
+    let mut world = World::new();
+    world.insert_component::<Tag>().unwrap();
+    world.insert_soa_storage::<body_soa::Storage>();
+
+    let entities: Vec<_> = (0..4).map(|_| world.create_entity()).collect();
+    {
+        let mut bodies = world.write_resource::<body_soa::Storage>();
+        let mut tags = world.write_component::<Tag>();
+        for (i, &e) in entities.iter().enumerate() {
+            bodies.insert(
+                e.index(),
+                Body {
+                    position: Vec2(0.0, 0.0),
+                    mass: i as f32,
+                },
+            );
+            if i % 2 == 0 {
+                tags.insert(e, Tag(i as u32 + 1)).unwrap();
+            }
+        }
+    }
+
+    world.run(
+        |(mut bodies, tags): (WriteResource<body_soa::Storage>, ReadComponent<Tag>)| {
+            for (body, tag) in (&mut *bodies, &tags).join() {
+                body.position.0 = tag.0 as f32;
+                *body.mass += 10.0;
+            }
+        },
+    );
+
+    {
+        let bodies = world.read_resource::<body_soa::Storage>();
+        assert_eq!(bodies.columns().mass, &[10.0, 1.0, 12.0, 3.0]);
+        assert_eq!(bodies.get(entities[2].index()).unwrap().position.0, 3.0);
+    }
+
+    world.delete_entity(entities[0]).unwrap();
+    world.entities().delete(entities[1]).unwrap();
+    world.merge();
+    {
+        let bodies = world.read_resource::<body_soa::Storage>();
+        assert_eq!(bodies.len(), 2);
+        assert!(!bodies.contains(entities[0].index()));
+        assert!(!bodies.contains(entities[1].index()));
+    }
+
+    let map = world.compact(&EntityMapRegistry::new());
+    let bodies = world.read_resource::<body_soa::Storage>();
+    assert_eq!(bodies.len(), 2);
+    for (e, mass) in [(entities[2], 12.0), (entities[3], 3.0)] {
+        assert_eq!(*bodies.get(map.map(e).index()).unwrap().mass, mass);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_soa_par_join() {
+    use goggles::ParJoinExt;
+    use rayon::iter::ParallelIterator;
+
+    let mut storage = body_soa::Storage::new();
+    for i in 0..1000 {
+        storage.insert(
+            i,
+            Body {
+                position: Vec2(i as f32, 0.0),
+                mass: 1.0,
+            },
+        );
+    }
+
+    (&mut storage).par_join().for_each(|body| {
+        body.position.1 = body.position.0 * *body.mass;
+    });
+
+    let columns = storage.columns();
+    assert!(columns.position.iter().all(|p| p.0 == p.1));
+}
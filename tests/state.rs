@@ -0,0 +1,78 @@
+use std::convert::Infallible;
+
+use goggles::{
+    ResourceConflict, SeqPool, State, StateSet, StateSystems, System, World, WorldResources,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Mode {
+    Menu,
+    Game,
+}
+
+struct Log(Vec<&'static str>);
+
+struct Record {
+    name: &'static str,
+    transition: Option<Mode>,
+}
+
+impl<'a> System<&'a World> for Record {
+    type Resources = WorldResources;
+    type Pool = SeqPool;
+    type Error = Infallible;
+
+    fn check_resources(&self) -> Result<WorldResources, ResourceConflict> {
+        Ok(WorldResources::new())
+    }
+
+    fn run(&mut self, _: &SeqPool, world: &'a World) -> Result<(), Infallible> {
+        world.write_resource::<Log>().0.push(self.name);
+        if let Some(mode) = self.transition.take() {
+            world.write_resource::<State<Mode>>().set(mode);
+        }
+        Ok(())
+    }
+}
+
+fn record(name: &'static str) -> Record {
+    Record {
+        name,
+        transition: None,
+    }
+}
+
+#[test]
+fn test_state_systems() {
+    let mut world = World::new();
+    world.insert_resource(State::new(Mode::Menu));
+    world.insert_resource(Log(Vec::new()));
+
+    let mut systems = StateSystems::new()
+        .with(StateSet::OnEnter(Mode::Menu), record("enter menu"))
+        .with(
+            StateSet::OnUpdate(Mode::Menu),
+            Record {
+                name: "update menu",
+                transition: Some(Mode::Game),
+            },
+        )
+        .with(StateSet::OnExit(Mode::Menu), record("exit menu"))
+        .with(StateSet::OnEnter(Mode::Game), record("enter game"))
+        .with(StateSet::OnUpdate(Mode::Game), record("update game"));
+
+    systems.run(&SeqPool, &world).unwrap();
+    assert_eq!(world.read_resource::<State<Mode>>().current(), &Mode::Game);
+    assert_eq!(
+        world.read_resource::<Log>().0,
+        ["enter menu", "update menu", "exit menu", "enter game"]
+    );
+
+    world.write_resource::<Log>().0.clear();
+    systems.run(&SeqPool, &world).unwrap();
+    systems.run(&SeqPool, &world).unwrap();
+    assert_eq!(
+        world.read_resource::<Log>().0,
+        ["update game", "update game"]
+    );
+}
@@ -1,4 +1,6 @@
-use goggles::{DenseVecStorage, IntoJoinExt, MaskedStorage, VecStorage};
+use hibitset::{BitSet, BitSetLike};
+
+use goggles::{DenseVecStorage, IntoJoinExt, MaskedStorage, StableStorage, VecStorage};
 
 pub struct CompA(i32);
 pub struct CompB(i32);
@@ -53,3 +55,384 @@ fn test_masked_storage_par_join() {
         (100..1000).collect::<Vec<i32>>(),
     );
 }
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_masked_storage_par_join_indexed() {
+    use goggles::ParJoinExt;
+    use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+    let mut a_storage = MaskedStorage::<VecStorage<CompA>>::default();
+    let mut b_storage = MaskedStorage::<DenseVecStorage<CompB>>::default();
+
+    // A heavily skewed mask, almost every component is in the last few words.
+    for i in (0..1000).step_by(100).chain(5000..6000) {
+        a_storage.insert(i, CompA(i as i32));
+        b_storage.insert(i, CompB(-(i as i32)));
+    }
+
+    let a = (&a_storage).par_join_indexed();
+    assert_eq!(a.len(), 1010);
+
+    let sums = a
+        .zip((&mut b_storage).par_join_indexed())
+        .with_min_len(16)
+        .map(|(a, b)| {
+            b.0 += a.0;
+            b.0
+        })
+        .collect::<Vec<i32>>();
+    assert_eq!(sums, vec![0; 1010]);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_join_par_iter_ergonomics() {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use goggles::ParJoinExt;
+    use rayon::iter::ParallelIterator;
+
+    let mut storage = MaskedStorage::<VecStorage<CompA>>::default();
+    for i in 0..100 {
+        storage.insert(i, CompA(i as i32));
+    }
+
+    let total = AtomicI32::new(0);
+    (&storage).par_join().for_each_init(Vec::new, |buffer, a| {
+        buffer.push(a.0);
+        total.fetch_add(a.0, Ordering::Relaxed);
+    });
+    assert_eq!(total.into_inner(), (0..100).sum::<i32>());
+
+    let sums = (&storage)
+        .par_join()
+        .fold_chunks(10, || 0, |sum, a| sum + a.0)
+        .collect::<Vec<i32>>();
+    assert_eq!(sums.len(), 10);
+    assert_eq!(sums[1], (10..20).sum::<i32>());
+
+    assert_eq!(
+        (&mut storage)
+            .par_join()
+            .with_min_len(32)
+            .map(|a| {
+                a.0 *= 2;
+                a.0
+            })
+            .sum::<i32>(),
+        (0..100).map(|i| i * 2).sum::<i32>()
+    );
+}
+
+#[cfg(not(feature = "rayon"))]
+#[test]
+fn test_join_par_iter_ergonomics_sequential() {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use goggles::ParJoinExt;
+
+    let mut storage = MaskedStorage::<VecStorage<CompA>>::default();
+    for i in 0..100 {
+        storage.insert(i, CompA(i as i32));
+    }
+
+    let total = AtomicI32::new(0);
+    (&storage).par_join().for_each_init(Vec::new, |buffer, a| {
+        buffer.push(a.0);
+        total.fetch_add(a.0, Ordering::Relaxed);
+    });
+    assert_eq!(total.into_inner(), (0..100).sum::<i32>());
+
+    let sums = (&storage)
+        .par_join()
+        .fold_chunks(10, || 0, |sum, a| sum + a.0)
+        .collect::<Vec<i32>>();
+    assert_eq!(sums.len(), 10);
+    assert_eq!(sums[1], (10..20).sum::<i32>());
+
+    let total = AtomicI32::new(0);
+    (&mut storage).par_join().with_min_len(32).for_each(|a| {
+        a.0 *= 2;
+        total.fetch_add(a.0, Ordering::Relaxed);
+    });
+    assert_eq!(total.into_inner(), (0..100).map(|i| i * 2).sum::<i32>());
+}
+
+#[cfg(not(feature = "rayon"))]
+#[test]
+fn test_masked_storage_par_join_sequential() {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use goggles::ParJoinExt;
+
+    let mut a_storage = MaskedStorage::<VecStorage<CompA>>::default();
+    for i in 0..100 {
+        a_storage.insert(i, CompA(i as i32));
+    }
+
+    let sum = AtomicI32::new(0);
+    (&mut a_storage).par_join().for_each(|a| {
+        a.0 *= 2;
+        sum.fetch_add(a.0, Ordering::Relaxed);
+    });
+    assert_eq!(sum.into_inner(), 9900);
+}
+
+#[cfg(not(feature = "rayon"))]
+#[test]
+fn test_masked_storage_par_join_indexed_sequential() {
+    use std::sync::Mutex;
+
+    use goggles::ParJoinExt;
+
+    let mut a_storage = MaskedStorage::<VecStorage<CompA>>::default();
+    for i in (0..1000).step_by(100).chain(5000..5010) {
+        a_storage.insert(i, CompA(i as i32));
+    }
+
+    let indexed = (&a_storage).par_join_indexed();
+    assert_eq!(indexed.len(), 20);
+    let seen = Mutex::new(Vec::new());
+    indexed.for_each(|a| seen.lock().unwrap().push(a.0));
+    assert_eq!(
+        seen.into_inner().unwrap(),
+        (0..1000)
+            .step_by(100)
+            .chain(5000..5010)
+            .collect::<Vec<i32>>()
+    );
+}
+
+#[test]
+fn test_chunks() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    for i in [0, 1, 2, 3, 4, 7, 8, 10] {
+        storage.insert(i, i);
+    }
+
+    assert_eq!(
+        storage.chunks(3).collect::<Vec<_>>(),
+        vec![
+            (0, &[0, 1, 2][..]),
+            (3, &[3, 4][..]),
+            (7, &[7, 8][..]),
+            (10, &[10][..]),
+        ]
+    );
+
+    for (_, chunk) in storage.chunks_mut(4) {
+        for v in chunk {
+            *v *= 2;
+        }
+    }
+    assert_eq!(
+        (&storage).join().copied().collect::<Vec<_>>(),
+        vec![0, 2, 4, 6, 8, 14, 16, 20]
+    );
+}
+
+#[test]
+fn test_dense_chunks() {
+    let mut storage = MaskedStorage::<DenseVecStorage<u32>>::default();
+    for i in [0, 1, 2, 3, 4, 7, 8, 10, 6] {
+        storage.insert(i, i);
+    }
+
+    assert_eq!(
+        storage.chunks(3).collect::<Vec<_>>(),
+        vec![
+            (0, &[0, 1, 2][..]),
+            (3, &[3, 4][..]),
+            (7, &[7, 8][..]),
+            (10, &[10][..]),
+            (6, &[6][..]),
+        ]
+    );
+
+    for (_, chunk) in storage.chunks_mut(4) {
+        for v in chunk {
+            *v *= 2;
+        }
+    }
+    assert_eq!(
+        (&storage).join().copied().collect::<Vec<_>>(),
+        vec![0, 2, 4, 6, 8, 12, 14, 16, 20]
+    );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_chunks_mut() {
+    use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+    let mut storage = MaskedStorage::<DenseVecStorage<u32>>::default();
+    for i in (0..1000).step_by(3) {
+        storage.insert(i, 0);
+    }
+
+    let chunks = storage.par_chunks_mut(64);
+    assert_eq!(chunks.len(), 6);
+    chunks.for_each(|(indexes, values)| {
+        assert_eq!(indexes.len(), values.len());
+        for (&i, v) in indexes.iter().zip(values) {
+            *v = i * 2;
+        }
+    });
+
+    for i in (0..1000).step_by(3) {
+        assert_eq!(storage.get(i), Some(&(i * 2)));
+    }
+}
+
+#[test]
+fn test_pack_into() {
+    let mut a_storage = MaskedStorage::<VecStorage<u32>>::default();
+    let mut b_storage = MaskedStorage::<DenseVecStorage<u32>>::default();
+    for i in [0, 1, 2, 5, 6, 9] {
+        a_storage.insert(i, i * 10);
+    }
+    for i in [9, 2, 5] {
+        b_storage.insert(i, i * 100);
+    }
+
+    let mut values = vec![[4, 2]];
+    let mut indexes = Vec::new();
+    (&a_storage, &b_storage).pack_into(&mut values, &mut indexes, |(&a, &b)| [a, b]);
+    assert_eq!(values, vec![[20, 200], [50, 500], [90, 900]]);
+    assert_eq!(indexes, vec![2, 5, 9]);
+
+    let mut values = Vec::new();
+    a_storage.pack_copied_into(&mut values, &mut indexes);
+    assert_eq!(values, vec![0, 10, 20, 50, 60, 90]);
+    assert_eq!(indexes, vec![0, 1, 2, 5, 6, 9]);
+
+    b_storage.pack_copied_into(&mut values, &mut indexes);
+    assert_eq!(values, vec![900, 200, 500]);
+    assert_eq!(indexes, vec![9, 2, 5]);
+}
+
+#[test]
+fn test_stable_storage() {
+    let mut storage = MaskedStorage::<StableStorage<u64>>::default();
+    storage.insert(3, 3);
+    let ptr = storage.get(3).unwrap() as *const u64;
+
+    for i in (0..1000).filter(|&i| i != 3) {
+        storage.insert(i, i as u64);
+    }
+    for i in (500..1000).step_by(2) {
+        storage.remove(i);
+    }
+
+    assert_eq!(storage.get(3).unwrap() as *const u64, ptr);
+    assert_eq!(unsafe { *ptr }, 3);
+    assert_eq!((&storage).join().count(), 750);
+    assert_eq!(storage.get(999), Some(&999));
+    assert_eq!(storage.get(998), None);
+}
+
+#[test]
+fn test_split_by() {
+    let mut storage = MaskedStorage::<VecStorage<u32>>::default();
+    for i in 0..10 {
+        storage.insert(i, i);
+    }
+
+    let selected = [1, 3, 5, 20].into_iter().collect::<BitSet>();
+    {
+        let (mut inside, mut outside) = storage.split_by(&selected);
+        assert_eq!(inside.mask().iter().collect::<Vec<_>>(), vec![1, 3, 5]);
+        assert!(!outside.contains(3));
+        assert_eq!(outside.get(5), None);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for c in (&mut inside).join() {
+                    *c += 100;
+                }
+            });
+            scope.spawn(|| {
+                for c in (&mut outside).join() {
+                    *c += 1000;
+                }
+            });
+        });
+
+        *outside.get_mut(0).unwrap() += 1;
+    }
+
+    assert_eq!(
+        (&storage).join().copied().collect::<Vec<_>>(),
+        vec![1001, 101, 1002, 103, 1004, 105, 1006, 1007, 1008, 1009]
+    );
+}
+
+#[test]
+fn test_remove_mask() {
+    let mut storage = MaskedStorage::<DenseVecStorage<CompA>>::default();
+    for i in 0..6 {
+        storage.insert(i, CompA(i as i32));
+    }
+
+    let mut mask = BitSet::new();
+    mask.add(1);
+    mask.add(3);
+    mask.add(10);
+    assert_eq!(storage.remove_mask(&mask), 2);
+    assert_eq!(storage.mask().iter().collect::<Vec<_>>(), vec![0, 2, 4, 5]);
+    assert_eq!(storage.get(4).unwrap().0, 4);
+    assert_eq!(storage.remove_mask(&mask), 0);
+}
+
+#[test]
+fn test_insert_sorted_iter() {
+    let mut a_storage = MaskedStorage::<VecStorage<CompA>>::default();
+    let mut b_storage = MaskedStorage::<DenseVecStorage<CompB>>::default();
+    a_storage.insert(1, CompA(1));
+
+    a_storage.insert_sorted_iter((3..6).map(|i| (i, CompA(i as i32))));
+    b_storage.insert_sorted_iter([0, 2, 70].into_iter().map(|i| (i, CompB(i as i32))));
+
+    assert_eq!(
+        a_storage.mask().iter().collect::<Vec<_>>(),
+        vec![1, 3, 4, 5]
+    );
+    assert_eq!(a_storage.get(4).unwrap().0, 4);
+    assert_eq!(b_storage.mask().iter().collect::<Vec<_>>(), vec![0, 2, 70]);
+    assert_eq!(b_storage.get(70).unwrap().0, 70);
+}
+
+#[test]
+#[should_panic]
+fn test_insert_sorted_iter_unsorted() {
+    let mut storage = MaskedStorage::<VecStorage<CompA>>::default();
+    storage.insert_sorted_iter([(3, CompA(3)), (2, CompA(2))]);
+}
+
+#[cfg(feature = "debug-assertions")]
+#[test]
+#[should_panic(expected = "already holds a value")]
+fn test_raw_storage_double_insert() {
+    use goggles::RawStorage;
+
+    let mut storage = DenseVecStorage::<CompA>::default();
+    unsafe {
+        storage.insert(3, CompA(1));
+        storage.insert(3, CompA(2));
+    }
+}
+
+#[cfg(feature = "debug-assertions")]
+#[test]
+#[should_panic(expected = "dropped while index 3 still holds a value")]
+fn test_raw_storage_drop_with_values() {
+    use goggles::RawStorage;
+
+    let mut storage = VecStorage::<CompA>::default();
+    unsafe {
+        storage.insert(3, CompA(1));
+        storage.insert(4, CompA(2));
+        storage.remove(4);
+    }
+}
@@ -1,7 +1,11 @@
-use std::{collections::HashSet, sync::mpsc};
+use std::{
+    collections::HashSet,
+    sync::{mpsc, Arc, Mutex},
+};
 
 use goggles::{
-    par, parallelize, seq, ResourceConflict, Resources, RwResources, SeqPool, System, SystemError,
+    par, parallelize, parallelize_with_barriers, seq, Dag, Enabled, EnabledFlag, FailurePolicy,
+    Pool, ResourceConflict, Resources, RwResources, ScheduleItem, SeqPool, System, SystemError,
 };
 
 #[derive(Default)]
@@ -71,6 +75,95 @@ fn test_par_seq_conflict() {
     assert!(sys.check_resources().is_err());
 }
 
+#[test]
+fn test_seq_failure_policy() {
+    struct Step {
+        resources: &'static [&'static str],
+        fail: bool,
+        ran: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl System<()> for Step {
+        type Resources = TestResources;
+        type Pool = SeqPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources(self.resources.iter().copied().collect()))
+        }
+
+        fn run(&mut self, _: &SeqPool, _: ()) -> Result<(), TestError> {
+            self.ran.lock().unwrap().push(self.resources[0]);
+            if self.fail {
+                Err(TestError)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    let run = |policy| {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let step = |resources, fail| Step {
+            resources,
+            fail,
+            ran: ran.clone(),
+        };
+        let mut sys = seq![
+            step(&["a"], true),
+            step(&["b"], false),
+            step(&["c", "a"], false),
+            step(&["d", "c"], false),
+            step(&["e"], false),
+        ]
+        .with_policy(policy);
+        assert!(sys.run(&SeqPool, ()).is_err());
+        let ran = ran.lock().unwrap().clone();
+        ran
+    };
+
+    assert_eq!(run(FailurePolicy::Abort), vec!["a"]);
+    assert_eq!(run(FailurePolicy::Continue), vec!["a", "b", "c", "d", "e"]);
+    assert_eq!(run(FailurePolicy::SkipDependents), vec!["a", "b", "e"]);
+}
+
+#[test]
+fn test_enabled() {
+    struct Count(Arc<Mutex<usize>>);
+
+    impl System<()> for Count {
+        type Resources = TestResources;
+        type Pool = SeqPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources(["resource_a"].into_iter().collect()))
+        }
+
+        fn run(&mut self, _: &SeqPool, _: ()) -> Result<(), TestError> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    let count = Arc::new(Mutex::new(0));
+    let flag = EnabledFlag::new(false);
+    let mut sys = seq![
+        Enabled::with_flag(Count(count.clone()), flag.clone()),
+        Enabled::with_flag(Count(count.clone()), flag.clone()),
+    ];
+
+    // Disabled systems still report their resources.
+    assert!(sys.check_resources().unwrap().0.contains("resource_a"));
+
+    sys.run(&SeqPool, ()).unwrap();
+    assert_eq!(*count.lock().unwrap(), 0);
+
+    flag.set_enabled(true);
+    sys.run(&SeqPool, ()).unwrap();
+    assert_eq!(*count.lock().unwrap(), 2);
+}
+
 #[test]
 fn test_read_write_resources() {
     let rw1 = RwResources::new()
@@ -133,3 +226,237 @@ fn test_parallelize() {
     assert_eq!(a_receiver.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
     assert_eq!(b_receiver.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
 }
+
+#[test]
+fn test_parallelize_barriers() {
+    struct TestSystem(&'static str);
+
+    impl System<()> for TestSystem {
+        type Resources = TestResources;
+        type Pool = SeqPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources([self.0].into_iter().collect()))
+        }
+
+        fn run(&mut self, _: &Self::Pool, _: ()) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    let systems = parallelize_with_barriers([
+        TestSystem("A").into(),
+        TestSystem("B").into(),
+        ScheduleItem::Barrier,
+        ScheduleItem::Barrier,
+        TestSystem("C").into(),
+        TestSystem("A").into(),
+        TestSystem("D").into(),
+    ]);
+    assert_eq!(
+        systems.0.iter().map(|p| p.0.len()).collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+}
+
+#[test]
+fn test_dag() {
+    struct LogSystem<P> {
+        name: &'static str,
+        resource: &'static str,
+        log: Arc<Mutex<Vec<&'static str>>>,
+        fail: bool,
+        marker: std::marker::PhantomData<P>,
+    }
+
+    impl<P: Pool> System<()> for LogSystem<P> {
+        type Resources = TestResources;
+        type Pool = P;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources([self.resource].into_iter().collect()))
+        }
+
+        fn run(&mut self, _: &P, _: ()) -> Result<(), TestError> {
+            self.log.lock().unwrap().push(self.name);
+            if self.fail {
+                Err(TestError)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn run_dag<P: Pool + Sync + Send>(pool: &P) {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let system = |name, resource| LogSystem {
+            name,
+            resource,
+            log: Arc::clone(&log),
+            fail: false,
+            marker: std::marker::PhantomData::<P>,
+        };
+
+        // a -> b -> c, with d unordered relative to all of them.
+        let mut dag = Dag::new();
+        let a = dag.add(system("a", "X"));
+        let b = dag.add_after(system("b", "X"), [a]);
+        dag.add_after(system("c", "X"), [b]);
+        dag.add(system("d", "Y"));
+        dag.check_resources().unwrap();
+        dag.run(pool, ()).unwrap();
+
+        let order = log.lock().unwrap().clone();
+        assert_eq!(order.len(), 4);
+        let pos = |n| order.iter().position(|&o| o == n).unwrap();
+        assert!(pos("a") < pos("b") && pos("b") < pos("c"));
+
+        dag.add_after(system("e", "Y"), [b]);
+        assert!(dag.check_resources().is_err());
+
+        let mut failing = Dag::new();
+        let a = failing.add(LogSystem {
+            fail: true,
+            ..system("fail", "X")
+        });
+        failing.add_after(system("never", "X"), [a]);
+        log.lock().unwrap().clear();
+        assert!(failing.run(pool, ()).is_err());
+        assert_eq!(*log.lock().unwrap(), vec!["fail"]);
+    }
+
+    run_dag(&SeqPool);
+    #[cfg(feature = "rayon")]
+    run_dag(&goggles::RayonPool);
+}
+
+#[test]
+#[should_panic]
+fn test_dag_cycle() {
+    let mut dag = Dag::new();
+    let a = dag.add(SystemA);
+    let b = dag.add_after(SystemA, [a]);
+    dag.add_dependency(b, a);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_thread_limit() {
+    use goggles::{rayon, RayonPool, ThreadLimit};
+
+    struct Threads(Arc<Mutex<usize>>);
+
+    impl System<()> for Threads {
+        type Resources = TestResources;
+        type Pool = RayonPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources::default())
+        }
+
+        fn run(&mut self, _: &RayonPool, _: ()) -> Result<(), TestError> {
+            *self.0.lock().unwrap() = rayon::current_num_threads();
+            Ok(())
+        }
+    }
+
+    let threads = Arc::new(Mutex::new(0));
+    let mut limited = ThreadLimit::new(Threads(Arc::clone(&threads)), 1);
+    limited.run(&RayonPool, ()).unwrap();
+    assert_eq!(*threads.lock().unwrap(), 1);
+}
+
+#[test]
+#[should_panic(expected = "pinned system cannot be run with a thread limit")]
+fn test_thread_limit_pinned() {
+    use goggles::{Pinned, ThreadLimit};
+
+    let mut limited = ThreadLimit::new(Pinned::new(SystemA), 1);
+    let _ = limited.run(&SeqPool, ());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_pinned() {
+    use std::{cell::RefCell, rc::Rc, thread};
+
+    use goggles::{system::ParList, Pinned, RayonPool};
+
+    type BoxSystem =
+        Box<dyn System<(), Resources = TestResources, Pool = RayonPool, Error = TestError> + Send>;
+
+    // Not `Send`, so it can only be run in parallel with other systems by being pinned.
+    struct ThreadAffine(Rc<RefCell<Vec<thread::ThreadId>>>);
+
+    impl System<()> for ThreadAffine {
+        type Resources = TestResources;
+        type Pool = RayonPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources::default())
+        }
+
+        fn run(&mut self, _: &RayonPool, _: ()) -> Result<(), TestError> {
+            self.0.borrow_mut().push(thread::current().id());
+            Ok(())
+        }
+    }
+
+    struct Other(Arc<Mutex<usize>>);
+
+    impl System<()> for Other {
+        type Resources = TestResources;
+        type Pool = RayonPool;
+        type Error = TestError;
+
+        fn check_resources(&self) -> Result<TestResources, ResourceConflict> {
+            Ok(TestResources::default())
+        }
+
+        fn run(&mut self, _: &RayonPool, _: ()) -> Result<(), TestError> {
+            *self.0.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    let threads = Rc::new(RefCell::new(Vec::new()));
+    let count = Arc::new(Mutex::new(0));
+    let mut systems: Vec<BoxSystem> = (0..4)
+        .map(|_| Box::new(Other(Arc::clone(&count))) as BoxSystem)
+        .collect();
+    systems.insert(2, Box::new(Pinned::new(ThreadAffine(Rc::clone(&threads)))));
+    systems.push(Box::new(Pinned::new(ThreadAffine(Rc::clone(&threads)))));
+
+    let mut par = ParList(systems);
+    assert!(par.is_pinned());
+    for _ in 0..4 {
+        par.run(&RayonPool, ()).unwrap();
+    }
+
+    assert_eq!(*count.lock().unwrap(), 16);
+    assert_eq!(threads.borrow().len(), 8);
+
+    let mut dag: Dag<BoxSystem> = Dag::new();
+    let first = dag.add(Box::new(Other(Arc::clone(&count))));
+    let pinned = dag.add_after(
+        Box::new(Pinned::new(ThreadAffine(Rc::clone(&threads)))),
+        [first],
+    );
+    dag.add_after(Box::new(Other(Arc::clone(&count))), [pinned]);
+    dag.add(Box::new(Pinned::new(ThreadAffine(Rc::clone(&threads)))));
+    assert!(dag.is_pinned());
+    for _ in 0..4 {
+        dag.run(&RayonPool, ()).unwrap();
+    }
+
+    assert_eq!(*count.lock().unwrap(), 24);
+    assert_eq!(threads.borrow().len(), 16);
+    assert!(threads
+        .borrow()
+        .iter()
+        .all(|&t| t == thread::current().id()));
+}
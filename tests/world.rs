@@ -1,6 +1,19 @@
+use std::{
+    cell::Cell,
+    sync::{Arc, Mutex},
+};
+
+use hibitset::BitSetLike;
+
 use goggles::{
-    join::IntoJoinExt, Component, Entities, ReadComponent, ReadResource, VecStorage, World,
-    WriteComponent, WriteResource,
+    join::{Index, IntoJoinExt},
+    AutoRegister, Component, ComponentConfig, ComponentEvent, ComponentEventKind, ComponentId,
+    DefaultResource, DeferredResources, DenseVecStorage, Entities, Entity, EntityMap,
+    EntityMapRegistry, FetchError, FetchResources, Flagged, FrameArena, FrameStats, HasComponent,
+    Local, Locals, MapEntities, RawStorage, ReadComponent, ReadComponentUnchecked, ReadDefault,
+    ReadKeyed, ReadResource, ReadResourceUnchecked, ReadSingleton, ResourceKey, StorageKind, Tick,
+    VecStorage, World, WorldHandle, WriteComponent, WriteComponentUnchecked, WriteDefault,
+    WriteKeyed, WriteResource, WriteResourceUnchecked, WriteSingleton,
 };
 
 struct RA(i32);
@@ -18,6 +31,12 @@ impl Component for CB {
     type Storage = VecStorage<CB>;
 }
 
+struct CD(u32);
+
+impl Component for CD {
+    type Storage = DenseVecStorage<CD>;
+}
+
 #[test]
 fn test_world() {
     let mut world = World::new();
@@ -25,8 +44,8 @@ fn test_world() {
     world.insert_resource(RA(1));
     world.insert_resource(RB(2));
 
-    world.insert_component::<CA>();
-    world.insert_component::<CB>();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
 
     let mut evec = Vec::new();
     {
@@ -74,3 +93,1255 @@ fn test_world() {
         assert!(world.entities().is_alive(e));
     }
 }
+
+#[test]
+fn test_memory_report() {
+    let mut world = World::new();
+
+    world.insert_resource(RA(1));
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    let empty = world.memory_report();
+    assert_eq!(empty.components.len(), 2);
+    let ra = empty
+        .resources
+        .iter()
+        .find(|r| r.type_name.ends_with("RA"))
+        .unwrap();
+    assert_eq!(ra.size, std::mem::size_of::<RA>());
+    assert!(empty
+        .resources
+        .iter()
+        .any(|r| r.type_name.ends_with("Tick")));
+
+    let default = World::default().memory_report();
+    for name in ["Tick", "FrameArena"] {
+        assert!(default
+            .resources
+            .iter()
+            .any(|r| r.type_name.ends_with(name)));
+    }
+
+    {
+        let mut component_a = world.write_component::<CA>();
+        for _ in 0..100 {
+            let e = world.entities().create();
+            component_a.insert(e, CA(e.index())).unwrap();
+        }
+    }
+    world.merge();
+
+    let report = world.memory_report();
+    let ca = report
+        .components
+        .iter()
+        .find(|c| c.type_name.ends_with("CA"))
+        .unwrap();
+    assert!(ca.storage.used >= 100 * std::mem::size_of::<CA>());
+    assert!(ca.mask.used > 0);
+    assert!(report.entities.used > empty.entities.used);
+    assert!(report.total().capacity >= report.total().used);
+}
+
+#[test]
+fn test_on_remove() {
+    let removed = Arc::new(Mutex::new(Vec::new()));
+
+    let mut world = World::new();
+    world
+        .insert_component_with_config::<CA>(ComponentConfig::new().on_remove({
+            let removed = Arc::clone(&removed);
+            move |e, c: &CA| {
+                assert_eq!(e.index(), c.0);
+                removed.lock().unwrap().push(e);
+            }
+        }))
+        .unwrap();
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    {
+        let mut component_a = world.get_component_mut::<CA>();
+        for &e in &evec {
+            component_a.insert(e, CA(e.index())).unwrap();
+        }
+        component_a.remove(evec[0]).unwrap();
+        assert!(component_a.remove(evec[0]).unwrap().is_none());
+    }
+    assert_eq!(*removed.lock().unwrap(), vec![evec[0]]);
+
+    world.delete_entity(evec[1]).unwrap();
+    assert_eq!(*removed.lock().unwrap(), vec![evec[0], evec[1]]);
+
+    world.entities().delete(evec[2]).unwrap();
+    assert_eq!(removed.lock().unwrap().len(), 2);
+    world.merge();
+    assert_eq!(*removed.lock().unwrap(), vec![evec[0], evec[1], evec[2]]);
+}
+
+#[test]
+fn test_remove_matching() {
+    let removed = Arc::new(Mutex::new(Vec::new()));
+
+    let mut world = World::new();
+    world
+        .insert_component_with_config::<CA>(ComponentConfig::new().on_remove({
+            let removed = Arc::clone(&removed);
+            move |e, _: &CA| removed.lock().unwrap().push(e)
+        }))
+        .unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    let evec = (0..4).map(|_| world.create_entity()).collect::<Vec<_>>();
+    for &e in &evec {
+        world
+            .write_component::<CA>()
+            .insert(e, CA(e.index()))
+            .unwrap();
+    }
+    world
+        .write_component::<CB>()
+        .insert(evec[1], CB(0))
+        .unwrap();
+    world
+        .write_component::<CB>()
+        .insert(evec[2], CB(0))
+        .unwrap();
+
+    world.run(|(mut a, b): (WriteComponent<CA>, ReadComponent<CB>)| {
+        assert_eq!(a.remove_matching(b.mask()), 2);
+    });
+    assert_eq!(*removed.lock().unwrap(), vec![evec[1], evec[2]]);
+    let a = world.read_component::<CA>();
+    assert!(a.contains(evec[0]) && a.contains(evec[3]));
+    assert!(!a.contains(evec[1]) && !a.contains(evec[2]));
+}
+
+#[test]
+fn test_remove_deferred() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    world.write_component::<CA>().insert(b, CA(2)).unwrap();
+
+    world.run(|component_a: ReadComponent<CA>| {
+        component_a.remove_deferred(a).unwrap();
+        component_a.remove_deferred(b).unwrap();
+        assert!(component_a.contains(a));
+    });
+    world.entities().delete(b).unwrap();
+    world.merge();
+
+    let component_a = world.read_component::<CA>();
+    assert!(!component_a.contains(a));
+    assert!(component_a.mask().is_empty());
+}
+
+#[test]
+fn test_component_events() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    let subscription = world.subscribe_component_events({
+        let events = Arc::clone(&events);
+        move |e: &[ComponentEvent]| events.lock().unwrap().extend_from_slice(e)
+    });
+
+    let e1 = world.create_entity();
+    let e2 = world.create_entity();
+    {
+        let (mut component_a, mut component_b): (WriteComponent<CA>, WriteComponent<CB>) =
+            world.fetch();
+        component_a.insert(e1, CA(1)).unwrap();
+        component_a.insert(e1, CA(2)).unwrap();
+        component_b.insert(e2, CB(3)).unwrap();
+        component_a.remove(e1).unwrap();
+    }
+    world.entities().delete(e2).unwrap();
+    assert!(events.lock().unwrap().is_empty());
+
+    world.merge();
+
+    let event = |entity, component, kind| ComponentEvent {
+        entity,
+        component,
+        kind,
+    };
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![
+            event(e1, ComponentId::of::<CA>(), ComponentEventKind::Added),
+            event(e2, ComponentId::of::<CB>(), ComponentEventKind::Added),
+            event(e1, ComponentId::of::<CA>(), ComponentEventKind::Removed),
+            event(e2, ComponentId::of::<CB>(), ComponentEventKind::Removed),
+        ]
+    );
+
+    assert!(world.unsubscribe_component_events(subscription));
+    assert!(!world.unsubscribe_component_events(subscription));
+}
+
+#[test]
+fn test_has_component() {
+    struct NotSync(Cell<u32>);
+
+    impl Component for NotSync {
+        type Storage = VecStorage<NotSync>;
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<NotSync>().unwrap();
+
+    let mut evec = Vec::new();
+    for i in 0..10 {
+        let e = world.create_entity();
+        world.get_component_mut::<CA>().insert(e, CA(i)).unwrap();
+        if i % 2 == 0 {
+            world
+                .get_component_mut::<NotSync>()
+                .insert(e, NotSync(Cell::new(i)))
+                .unwrap();
+        }
+        evec.push(e);
+    }
+
+    assert_eq!(
+        world
+            .get_component_mut::<NotSync>()
+            .get(evec[2])
+            .unwrap()
+            .0
+            .get(),
+        2
+    );
+
+    let (component_a, has_not_sync): (ReadComponent<CA>, HasComponent<NotSync>) = world.fetch();
+    assert!(has_not_sync.contains(evec[0].index()));
+    assert!(!has_not_sync.contains(evec[1].index()));
+    assert_eq!(
+        (&component_a, &has_not_sync)
+            .join()
+            .map(|(a, ())| a.0)
+            .collect::<Vec<_>>(),
+        vec![0, 2, 4, 6, 8]
+    );
+}
+
+#[test]
+fn test_merge_killed() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    assert!(world.merge().is_empty());
+
+    world.entities().delete(a).unwrap();
+    world.entities().delete(b).unwrap();
+    assert_eq!(world.merge(), &[a, b]);
+    assert!(world.read_component::<CA>().get(a).is_none());
+
+    assert!(world.merge().is_empty());
+}
+
+#[test]
+fn test_maintain() {
+    let mut world = World::new();
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    let order_b = order.clone();
+    world
+        .insert_component_with_config(ComponentConfig::<CB>::new().on_maintain(move |storage| {
+            order_b
+                .lock()
+                .unwrap()
+                .push(("b", storage.mask().iter().count()));
+        }))
+        .unwrap();
+    let order_a = order.clone();
+    world
+        .insert_component_with_config(ComponentConfig::<CA>::new().on_maintain(move |storage| {
+            order_a
+                .lock()
+                .unwrap()
+                .push(("a", storage.mask().iter().count()));
+        }))
+        .unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    world.write_component::<CA>().insert(b, CA(2)).unwrap();
+    world.write_component::<CB>().insert(b, CB(2)).unwrap();
+
+    world.entities().delete(b).unwrap();
+    assert_eq!(world.maintain(), &[b]);
+
+    // Maintenance runs after the merge, in component insertion order.
+    assert_eq!(*order.lock().unwrap(), vec![("b", 0), ("a", 1)]);
+}
+
+#[test]
+fn test_component_info() {
+    #[derive(Clone)]
+    struct CC(#[allow(dead_code)] u64);
+
+    impl Component for CC {
+        type Storage = Flagged<VecStorage<CC>>;
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CB>().unwrap();
+    world
+        .insert_component_with_config(ComponentConfig::<CC>::new().cloneable())
+        .unwrap();
+    world.insert_component::<CA>().unwrap();
+    world.remove_component::<CB>();
+
+    let info = world.component_info().collect::<Vec<_>>();
+    assert_eq!(info.len(), 2);
+
+    assert_eq!(info[0].id, ComponentId::of::<CC>());
+    assert!(info[0].type_name.ends_with("CC"));
+    assert_eq!(info[0].size, 8);
+    assert_eq!(info[0].align, std::mem::align_of::<u64>());
+    assert!(info[0].cloneable);
+    assert!(info[0].tracked);
+
+    assert_eq!(info[1].id, ComponentId::of::<CA>());
+    assert!(!info[1].cloneable);
+    assert!(!info[1].tracked);
+}
+
+#[test]
+fn test_get_component_dyn() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    let a = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+
+    let ca = world.get_component_dyn(a, ComponentId::of::<CA>()).unwrap();
+    assert_eq!(ca.downcast_ref::<CA>().unwrap().0, 1);
+    assert!(world
+        .get_component_dyn(a, ComponentId::of::<CB>())
+        .is_none());
+    drop(ca);
+
+    // Components registered through a shared reference are visible before the next flush.
+    let shared: &World = &world;
+    shared.ensure_component::<CD>();
+    shared.write_component::<CD>().insert(a, CD(3)).unwrap();
+    let cd = shared
+        .get_component_dyn(a, ComponentId::of::<CD>())
+        .unwrap();
+    assert_eq!(cd.downcast_ref::<CD>().unwrap().0, 3);
+    drop(cd);
+
+    world
+        .get_component_dyn_mut(a, ComponentId::of::<CA>())
+        .unwrap()
+        .downcast_mut::<CA>()
+        .unwrap()
+        .0 = 2;
+    assert_eq!(world.read_component::<CA>().get(a).unwrap().0, 2);
+
+    world.delete_entity(a).unwrap();
+    assert!(world
+        .get_component_dyn(a, ComponentId::of::<CA>())
+        .is_none());
+}
+
+#[test]
+fn test_insert_component_twice() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let a = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+
+    assert!(world.insert_component::<CA>().is_err());
+    assert_eq!(world.read_component::<CA>().get(a).unwrap().0, 1);
+
+    let old = world
+        .replace_component_storage::<CA>(Default::default())
+        .unwrap();
+    assert!(old.contains(a.index()));
+    assert!(world.read_component::<CA>().get(a).is_none());
+
+    assert!(world
+        .replace_component_storage::<CB>(Default::default())
+        .is_none());
+    assert!(world.contains_component::<CB>());
+}
+
+#[test]
+fn test_insert_component_with() {
+    let mut world = World::new();
+    world
+        .insert_component_with::<CA>(VecStorage::default())
+        .unwrap();
+    assert!(world
+        .insert_component_with::<CA>(VecStorage::default())
+        .is_err());
+
+    let a = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    assert_eq!(world.read_component::<CA>().get(a).unwrap().0, 1);
+}
+
+#[test]
+fn test_insert_component_with_non_default() {
+    // A storage which can only be constructed with a parameter.
+    struct ScaledStorage {
+        inner: VecStorage<Scaled>,
+        scale: u32,
+    }
+
+    impl ScaledStorage {
+        fn new(scale: u32) -> Self {
+            ScaledStorage {
+                inner: VecStorage::default(),
+                scale,
+            }
+        }
+    }
+
+    impl RawStorage for ScaledStorage {
+        type Item = Scaled;
+
+        unsafe fn get(&self, index: Index) -> &Scaled {
+            self.inner.get(index)
+        }
+
+        unsafe fn get_mut(&self, index: Index) -> &mut Scaled {
+            self.inner.get_mut(index)
+        }
+
+        unsafe fn insert(&mut self, index: Index, value: Scaled) {
+            self.inner.insert(index, Scaled(value.0 * self.scale));
+        }
+
+        unsafe fn remove(&mut self, index: Index) -> Scaled {
+            self.inner.remove(index)
+        }
+    }
+
+    struct Scaled(u32);
+
+    impl Component for Scaled {
+        type Storage = ScaledStorage;
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world
+        .insert_component_with::<Scaled>(ScaledStorage::new(10))
+        .unwrap();
+
+    let a = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(2)).unwrap();
+
+    world.run(
+        |(ca, mut scaled): (ReadComponent<CA>, WriteComponent<Scaled>)| {
+            scaled.insert(a, Scaled(ca.get(a).unwrap().0)).unwrap();
+        },
+    );
+
+    world.run(
+        |(mut ca, scaled): (WriteComponent<CA>, ReadComponent<Scaled>)| {
+            for (ca, scaled) in (&mut ca, &scaled).join() {
+                ca.0 = scaled.0;
+            }
+        },
+    );
+
+    assert_eq!(world.read_component::<CA>().get(a).unwrap().0, 20);
+    assert!(matches!(
+        world.try_fetch::<ReadComponent<Scaled>>(),
+        Ok(scaled) if scaled.get(a).unwrap().0 == 20
+    ));
+}
+
+#[test]
+fn test_reserve_entities() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    world.reserve_entities(1000);
+
+    let report = world.memory_report();
+    assert!(report.entities.capacity >= 1000 * std::mem::size_of::<u32>());
+    assert!(report.components[0].storage.capacity >= 1000 * std::mem::size_of::<CA>());
+
+    for i in 0..1000 {
+        let e = world.create_entity();
+        world.write_component::<CA>().insert(e, CA(i)).unwrap();
+    }
+    assert_eq!(world.entities().alive_count(), 1000);
+}
+
+#[test]
+fn test_compact_storages() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CD>().unwrap();
+
+    let entities = (0..1000).map(|_| world.create_entity()).collect::<Vec<_>>();
+    for &e in entities.iter().rev() {
+        world
+            .write_component::<CA>()
+            .insert(e, CA(e.index()))
+            .unwrap();
+        world
+            .write_component::<CD>()
+            .insert(e, CD(e.index()))
+            .unwrap();
+    }
+    for &e in &entities[10..] {
+        world.delete_entity(e).unwrap();
+    }
+    world.merge();
+
+    let before = world.memory_report().total();
+    world.compact_storages();
+    let after = world.memory_report().total();
+    assert!(after.capacity < before.capacity);
+
+    let component_d = world.read_component::<CD>();
+    assert_eq!(component_d.slice_indexes(), (0..10).collect::<Vec<_>>());
+    for &e in &entities[..10] {
+        assert_eq!(world.read_component::<CA>().get(e).unwrap().0, e.index());
+        assert_eq!(component_d.get(e).unwrap().0, e.index());
+    }
+}
+
+#[test]
+fn test_storage_report() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+    world.insert_component::<CD>().unwrap();
+
+    let entities = (0..1000).map(|_| world.create_entity()).collect::<Vec<_>>();
+    for &e in &entities {
+        world.write_component::<CA>().insert(e, CA(0)).unwrap();
+        world.write_component::<CD>().insert(e, CD(0)).unwrap();
+    }
+    world
+        .write_component::<CB>()
+        .insert(entities[999], CB(0))
+        .unwrap();
+
+    let report = world.storage_report();
+    assert_eq!(report.alive, 1000);
+
+    let a = report.component(ComponentId::of::<CA>()).unwrap();
+    assert_eq!(a.kind, StorageKind::Vec);
+    assert_eq!(a.count, 1000);
+    assert_eq!(a.len, 1000);
+    assert_eq!(a.density, 1.0);
+    assert_eq!(a.overlap[0], (ComponentId::of::<CD>(), 1.0));
+    assert_eq!(a.overlap[1], (ComponentId::of::<CB>(), 0.001));
+    assert!(!a.is_mismatched());
+
+    let b = report.component(ComponentId::of::<CB>()).unwrap();
+    assert_eq!(b.count, 1);
+    assert_eq!(b.overlapping(1.0).count(), 2);
+
+    let d = report.component(ComponentId::of::<CD>()).unwrap();
+    assert_eq!(d.kind, StorageKind::DenseVec);
+    assert_eq!(d.recommended, StorageKind::Vec);
+    assert_eq!(
+        report.mismatched().map(|c| c.id).collect::<Vec<_>>(),
+        vec![ComponentId::of::<CD>()]
+    );
+    assert_eq!(
+        report.group_candidates(0.9),
+        vec![(ComponentId::of::<CA>(), ComponentId::of::<CD>())]
+    );
+}
+
+#[test]
+fn test_frame_stats() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    world.create_entity();
+    world.merge();
+    assert_eq!(world.frame_stats().entities_created, 0);
+
+    world.set_collect_stats(true);
+    let entities = (0..10)
+        .map(|_| world.entities().create())
+        .collect::<Vec<_>>();
+    for &e in &entities {
+        world.write_component::<CA>().insert(e, CA(0)).unwrap();
+    }
+    world
+        .write_component::<CB>()
+        .insert(entities[0], CB(0))
+        .unwrap();
+    for &e in &entities[..3] {
+        world.entities().delete(e).unwrap();
+    }
+    world.merge();
+
+    let stats = world.frame_stats();
+    assert_eq!(stats.entities_created, 10);
+    assert_eq!(stats.entities_killed, 3);
+    assert_eq!(stats.component(ComponentId::of::<CA>()).inserted, 10);
+    assert_eq!(stats.component(ComponentId::of::<CA>()).removed, 3);
+    assert_eq!(stats.component(ComponentId::of::<CB>()).inserted, 1);
+    assert_eq!(stats.component(ComponentId::of::<CB>()).removed, 1);
+
+    world.merge();
+    assert_eq!(world.frame_stats(), &FrameStats::default());
+}
+
+#[test]
+fn test_fetch_unchecked() {
+    let mut world = World::new();
+    world.insert_resource(RA(3));
+    world.insert_component::<CA>().unwrap();
+
+    let e = world.create_entity();
+    {
+        // Nothing else is borrowed while the unchecked borrows are alive.
+        let (ra, mut ca) = unsafe {
+            world.fetch_unchecked::<(ReadResourceUnchecked<RA>, WriteComponentUnchecked<CA>)>()
+        };
+        ca.insert(e, CA(ra.0 as u32)).unwrap();
+        for ca in (&mut ca).join() {
+            ca.0 += 1;
+        }
+    }
+    assert_eq!(world.read_component::<CA>().get(e).unwrap().0, 4);
+
+    {
+        let mut ra = unsafe { world.fetch_unchecked::<WriteResourceUnchecked<RA>>() };
+        ra.0 = 5;
+    }
+    let ca = unsafe { world.fetch_unchecked::<ReadComponentUnchecked<CA>>() };
+    assert_eq!(ca.get(e).unwrap().0, 4);
+    assert_eq!(world.read_resource::<RA>().0, 5);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic]
+fn test_fetch_unchecked_conflict() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+
+    let _ca = world.write_component::<CA>();
+    let _ = unsafe { world.fetch_unchecked::<ReadComponentUnchecked<CA>>() };
+}
+
+#[test]
+fn test_freeze() {
+    let mut world = World::new();
+    world.insert_resource(RA(5));
+    world.insert_component::<CA>().unwrap();
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    world.delete_entity(b).unwrap();
+
+    let frozen = world.freeze();
+    let component_a = frozen.component::<CA>();
+    assert_eq!(frozen.resource::<RA>().0, 5);
+    assert!(frozen.try_resource::<RB>().is_none());
+    assert_eq!(component_a.get(a.index()).unwrap().0, 1);
+    assert_eq!(frozen.get_component::<CA>(a).unwrap().0, 1);
+    assert!(frozen.get_component::<CA>(b).is_none());
+    assert!(frozen.try_component::<CB>().is_none());
+    assert_eq!(frozen.iter_entities().collect::<Vec<_>>(), vec![a]);
+
+    let mut world = frozen.thaw();
+    world.delete_entity(a).unwrap();
+    world.write_resource::<RA>().0 = 6;
+    assert_eq!(world.read_resource::<RA>().0, 6);
+}
+
+#[test]
+fn test_world_handle() {
+    let mut world = World::new();
+    world.insert_resource(RA(1));
+    world.insert_resource(RB(2));
+    let handle = WorldHandle::new(world);
+
+    let write_a = handle.fetch::<WriteResource<RA>>();
+    let locals = Locals::default();
+    std::thread::scope(|scope| {
+        let handle = handle.clone();
+        let locals = locals.clone();
+        scope
+            .spawn(move || {
+                assert_eq!(handle.fetch::<ReadResource<RB>>().0, 2);
+                assert!(matches!(
+                    handle.try_fetch::<(ReadResource<RB>, ReadResource<RA>)>(),
+                    Err(FetchError::Borrowed(_))
+                ));
+                assert!(handle.try_fetch::<WriteResource<RB>>().is_ok());
+                assert!(handle.try_fetch::<Local<u32>>().is_err());
+                *handle.try_fetch_local::<Local<u32>>(&locals).unwrap() = 4;
+            })
+            .join()
+            .unwrap();
+    });
+
+    std::thread::scope(|scope| {
+        let waiter = scope.spawn(|| handle.fetch_blocking::<ReadResource<RA>>().unwrap().0);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let mut write_a = write_a;
+        write_a.0 = 3;
+        drop(write_a);
+        assert_eq!(waiter.join().unwrap(), 3);
+    });
+    assert_eq!(*handle.try_fetch_local::<Local<u32>>(&locals).unwrap(), 4);
+
+    let mut world = handle.try_into_inner().ok().unwrap();
+    world.merge();
+}
+
+#[test]
+fn test_singleton() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_resource(RA(1));
+
+    assert!(world.insert_singleton(RA(2)).is_none());
+    let entity = world.singleton_entity::<RA>().unwrap();
+    world.write_component::<CA>().insert(entity, CA(3)).unwrap();
+
+    // Singletons are separate from resources of the same type.
+    assert!(<(ReadSingleton<RA>, WriteResource<RA>)>::check_resources().is_ok());
+    assert!(<(ReadSingleton<RA>, WriteSingleton<RA>)>::check_resources().is_err());
+
+    {
+        let (mut singleton, resource) = world.fetch::<(WriteSingleton<RA>, ReadResource<RA>)>();
+        assert_eq!(singleton.entity(), entity);
+        assert_eq!(resource.0, 1);
+        singleton.0 = 4;
+    }
+    assert_eq!(world.read_singleton::<RA>().0, 4);
+
+    assert_eq!(world.insert_singleton(RA(5)).unwrap().0, 4);
+    assert_eq!(world.singleton_entity::<RA>(), Some(entity));
+
+    // The singleton entity is alive but hidden from `Entities`.
+    let other = world.create_entity();
+    world.write_component::<CA>().insert(other, CA(6)).unwrap();
+    {
+        let entities = world.entities();
+        assert!(entities.is_alive(entity));
+        assert_eq!(entities.alive_count(), 1);
+        assert_eq!(entities.iter().collect::<Vec<_>>(), vec![other]);
+        let components = world.read_component::<CA>();
+        let joined = (&entities, &components).join().map(|(e, _)| e);
+        assert_eq!(joined.collect::<Vec<_>>(), vec![other]);
+    }
+    world.delete_entity(other).unwrap();
+
+    assert_eq!(world.remove_singleton::<RA>().unwrap().0, 5);
+    assert!(!world.entities().is_alive(entity));
+    assert!(world.read_component::<CA>().get(entity).is_none());
+    assert!(!world.contains_singleton::<RA>());
+}
+
+#[test]
+fn test_tick() {
+    let mut world = World::new();
+    assert_eq!(*world.read_resource::<Tick>(), Tick(0));
+
+    world.merge();
+    world.maintain();
+    let tick = *world.read_resource::<Tick>();
+    assert_eq!(tick.get(), 2);
+    assert!(tick.every(2));
+    assert!(!tick.every(3));
+    assert_eq!(tick.since(Tick(1)), 1);
+
+    world.remove_resource::<Tick>();
+    world.merge();
+    assert!(!world.contains_resource::<Tick>());
+    assert!(!world
+        .memory_report()
+        .resources
+        .iter()
+        .any(|r| r.type_name.ends_with("Tick")));
+}
+
+#[test]
+fn test_frame_arena() {
+    let mut world = World::new();
+
+    let dropped = Arc::new(Mutex::new(0));
+    struct Guard(Arc<Mutex<i32>>);
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+
+    {
+        let arena = world.fetch::<ReadResource<FrameArena>>();
+        let a = arena.alloc(1u8);
+        let b = arena.alloc([7u64; 1000]);
+        let c = arena.alloc_slice_copy(&[1u32, 2, 3]);
+        arena.alloc(Guard(dropped.clone()));
+        *a += 1;
+        c[1] = 5;
+        assert_eq!(*a, 2);
+        assert_eq!(b[999], 7);
+        assert_eq!(c, &[1, 5, 3]);
+        assert_eq!(b.as_ptr() as usize % std::mem::align_of::<u64>(), 0);
+        assert!(arena.allocated_bytes() > 8000 + 12);
+    }
+    assert_eq!(*dropped.lock().unwrap(), 0);
+
+    world.merge();
+    assert_eq!(*dropped.lock().unwrap(), 1);
+    assert_eq!(world.read_resource::<FrameArena>().allocated_bytes(), 0);
+    assert!(world
+        .memory_report()
+        .resources
+        .iter()
+        .any(|r| r.type_name.ends_with("FrameArena")));
+
+    world
+        .read_resource::<FrameArena>()
+        .alloc(Guard(dropped.clone()));
+    drop(world);
+    assert_eq!(*dropped.lock().unwrap(), 2);
+}
+
+#[test]
+fn test_keyed_resources() {
+    struct Physics;
+    impl ResourceKey for Physics {
+        const KEY: &'static str = "physics";
+    }
+
+    struct Audio;
+    impl ResourceKey for Audio {
+        const KEY: &'static str = "audio";
+    }
+
+    let mut world = World::new();
+    world.insert_resource(RA(0));
+    world.insert_resource_keyed("physics", RA(1));
+    world.insert_resource_keyed("audio", RA(2));
+    assert!(world.contains_resource_keyed::<RA>("physics"));
+    assert!(!world.contains_resource_keyed::<RB>("physics"));
+    assert!(!world.contains_resource_keyed::<RA>("render"));
+
+    assert!(<(
+        WriteKeyed<RA, Physics>,
+        WriteKeyed<RA, Audio>,
+        WriteResource<RA>
+    )>::check_resources()
+    .is_ok());
+    assert!(<(WriteKeyed<RA, Physics>, ReadKeyed<RA, Physics>)>::check_resources().is_err());
+
+    {
+        let (mut physics, audio, plain): (
+            WriteKeyed<RA, Physics>,
+            ReadKeyed<RA, Audio>,
+            ReadResource<RA>,
+        ) = world.fetch();
+        physics.0 += 10;
+        assert_eq!(audio.0, 2);
+        assert_eq!(plain.0, 0);
+    }
+
+    assert_eq!(world.read_resource_keyed::<RA>("physics").0, 11);
+    assert_eq!(
+        world.remove_resource_keyed::<RA>("audio").map(|r| r.0),
+        Some(2)
+    );
+    assert!(!world.contains_resource_keyed::<RA>("audio"));
+}
+
+#[test]
+fn test_resource_modified() {
+    let mut world = World::new();
+    world.insert_resource(RA(1));
+    world.insert_resource(RB(2));
+    world.set_track_resource_modified::<RA>(true);
+
+    assert!(!world.is_resource_modified::<RA>());
+    let _ = world.read_resource::<RA>();
+    assert!(!world.is_resource_modified::<RA>());
+
+    world.write_resource::<RA>().0 += 1;
+    world.write_resource::<RB>().0 += 1;
+    assert!(world.is_resource_modified::<RA>());
+    assert!(!world.is_resource_modified::<RB>());
+
+    world.merge();
+    assert!(!world.is_resource_modified::<RA>());
+
+    world.set_track_resource_modified::<Tick>(true);
+    world.insert_events::<u32>();
+    world.set_track_resource_modified::<goggles::Events<u32>>(true);
+    world.merge();
+    assert!(!world.is_resource_modified::<Tick>());
+    assert!(!world.is_resource_modified::<goggles::Events<u32>>());
+
+    world.defer(|world| world.write_resource::<RA>().0 += 1);
+    world.merge();
+    assert!(world.is_resource_modified::<RA>());
+
+    world.mark_resource_modified::<RB>();
+    assert!(world.is_resource_modified::<RB>());
+    world.clear_resource_modified::<RB>();
+    assert!(!world.is_resource_modified::<RB>());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "resource conflict")]
+fn test_fetch_conflict() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    let _ = world.fetch::<(ReadComponent<CA>, WriteComponent<CA>)>();
+}
+
+#[test]
+fn test_split_components() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<CB>().unwrap();
+
+    let e = world.create_entity();
+    {
+        let (mut a, mut b) = world.split_components::<(CA, CB)>();
+        a.insert(e, CA(1)).unwrap();
+        b.insert(e, CB(2)).unwrap();
+        for (a, b) in (&mut a, &mut b).join() {
+            a.0 += b.0;
+            b.0 += a.0;
+        }
+    }
+
+    assert_eq!(world.read_component::<CA>().get(e).unwrap().0, 3);
+    assert_eq!(world.read_component::<CB>().get(e).unwrap().0, 5);
+}
+
+#[test]
+#[should_panic]
+fn test_split_components_duplicate() {
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    let _ = world.split_components::<(CA, CA)>();
+}
+
+#[test]
+fn test_run() {
+    let mut world = World::new();
+    world.insert_resource(RA(1));
+    world.insert_component::<CA>().unwrap();
+
+    let e = world.create_entity();
+    world.run(|(ra, mut ca): (ReadResource<RA>, WriteComponent<CA>)| {
+        ca.insert(e, CA(ra.0 as u32)).unwrap();
+    });
+
+    #[derive(Debug)]
+    enum RunError {
+        Fetch(FetchError),
+        NoComponent,
+    }
+
+    impl From<FetchError> for RunError {
+        fn from(err: FetchError) -> Self {
+            RunError::Fetch(err)
+        }
+    }
+
+    let get_ca = |ca: ReadComponent<CA>| ca.get(e).map(|c| c.0).ok_or(RunError::NoComponent);
+
+    assert!(matches!(world.try_run(get_ca), Ok(1)));
+
+    world.run(|mut ca: WriteComponent<CA>| {
+        ca.remove(e).unwrap();
+    });
+    assert!(matches!(world.try_run(get_ca), Err(RunError::NoComponent)));
+
+    assert!(matches!(
+        world.try_run(|_: ReadResource<RB>| Ok::<_, RunError>(())),
+        Err(RunError::Fetch(FetchError::Missing(_)))
+    ));
+
+    let _ca = world.write_component::<CA>();
+    assert!(matches!(
+        world.try_run(get_ca),
+        Err(RunError::Fetch(FetchError::Borrowed(_)))
+    ));
+}
+
+#[test]
+fn test_try_fetch() {
+    let mut world = World::new();
+    world.insert_resource(RA(1));
+    world.insert_component::<CA>().unwrap();
+
+    assert!(matches!(
+        world.try_fetch::<(ReadResource<RA>, ReadResource<RB>)>(),
+        Err(FetchError::Missing(_))
+    ));
+    assert!(matches!(
+        world.try_fetch::<(ReadComponent<CA>, HasComponent<CB>)>(),
+        Err(FetchError::Missing(_))
+    ));
+    assert!(matches!(
+        world.try_fetch::<ReadSingleton<RB>>(),
+        Err(FetchError::Missing(_))
+    ));
+
+    {
+        let _ca = world.write_component::<CA>();
+        assert!(matches!(
+            world.try_fetch::<ReadComponent<CA>>(),
+            Err(FetchError::Borrowed(_))
+        ));
+    }
+
+    let (ra, ca) = world
+        .try_fetch::<(WriteResource<RA>, ReadComponent<CA>)>()
+        .unwrap();
+    assert_eq!(ra.0, 1);
+    assert!(ca.mask().is_empty());
+}
+
+#[test]
+fn test_default_resource() {
+    #[derive(Default)]
+    struct Score(u32);
+
+    impl DefaultResource for Score {}
+
+    let mut world = World::new();
+    world.run(|mut score: WriteDefault<Score>| score.0 += 2);
+    world.run(|mut score: WriteDefault<Score>| score.0 += 3);
+    assert_eq!(world.fetch::<ReadDefault<Score>>().0, 5);
+    assert_eq!(world.read_resource::<Score>().0, 5);
+
+    let is_score = |r: &goggles::memory::ResourceMemory| r.type_name.ends_with("Score");
+    assert!(world.memory_report().resources.iter().any(is_score));
+    world.remove_resource::<Score>();
+    assert!(!world.memory_report().resources.iter().any(is_score));
+}
+
+#[test]
+fn test_auto_register_components() {
+    let mut world = World::new();
+
+    assert!(matches!(
+        world.try_fetch::<ReadComponent<CA>>(),
+        Err(FetchError::Missing(_))
+    ));
+
+    let e = world.create_entity();
+    world.run(
+        |(mut a, b): (
+            AutoRegister<WriteComponent<CA>>,
+            AutoRegister<HasComponent<CB>>,
+        )| {
+            a.insert(e, CA(1)).unwrap();
+            assert!(b.mask().is_empty());
+        },
+    );
+    assert!(world.contains_component::<CA>());
+    assert!(world.contains_component::<CB>());
+    assert_eq!(world.read_component::<CA>().get(e).unwrap().0, 1);
+
+    world.delete_entity(e).unwrap();
+    assert_eq!(world.component_info().count(), 2);
+    assert!(world.read_component::<CA>().mask().is_empty());
+}
+
+#[test]
+fn test_compact() {
+    struct Parent(Entity);
+
+    impl Component for Parent {
+        type Storage = VecStorage<Parent>;
+    }
+
+    impl MapEntities for Parent {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.0 = map.map(self.0);
+        }
+    }
+
+    let mut world = World::new();
+    world.insert_component::<CA>().unwrap();
+    world.insert_component::<Parent>().unwrap();
+
+    let entities: Vec<Entity> = (0..8).map(|_| world.create_entity()).collect();
+    for &e in &entities[..6] {
+        world.delete_entity(e).unwrap();
+    }
+    let (a, b) = (entities[6], entities[7]);
+    world.write_component::<CA>().insert(a, CA(1)).unwrap();
+    world.write_component::<CA>().insert(b, CA(2)).unwrap();
+    world
+        .write_component::<Parent>()
+        .insert(b, Parent(a))
+        .unwrap();
+
+    let mut registry = EntityMapRegistry::new();
+    registry.register_component::<Parent>();
+    let map = world.compact(&registry);
+    assert_eq!(map.len(), 2);
+
+    let (new_a, new_b) = (map.map(a), map.map(b));
+    assert_eq!((new_a.index(), new_b.index()), (0, 1));
+    assert!(!world.entities().is_alive(a));
+    assert!(!world.entities().is_alive(b));
+    assert!(world.entities().is_alive(new_a));
+
+    let ca = world.read_component::<CA>();
+    assert_eq!(ca.get(new_a).unwrap().0, 1);
+    assert_eq!(ca.get(new_b).unwrap().0, 2);
+    assert_eq!(ca.mask().iter().collect::<Vec<_>>(), vec![0, 1]);
+    assert_eq!(
+        world.read_component::<Parent>().get(new_b).unwrap().0,
+        new_a
+    );
+    drop(ca);
+
+    assert_eq!(world.create_entity().index(), 2);
+}
+
+#[cfg(feature = "world-id")]
+#[test]
+fn test_world_id() {
+    use goggles::EntityError;
+
+    let mut world_a = World::new();
+    let mut world_b = World::new();
+    world_a.insert_component::<CA>().unwrap();
+    world_b.insert_component::<CA>().unwrap();
+
+    let a = world_a.create_entity();
+    let b = world_b.create_entity();
+    assert_eq!(a.index(), b.index());
+    assert_eq!(a.generation(), b.generation());
+    assert_ne!(a.world_id(), b.world_id());
+
+    assert!(!world_a.entities().is_alive(b));
+    assert!(matches!(
+        world_a.write_component::<CA>().insert(b, CA(1)),
+        Err(EntityError::WrongWorld(_))
+    ));
+    assert!(matches!(
+        world_a.delete_entity(b),
+        Err(EntityError::WrongWorld(_))
+    ));
+    world_a.delete_entity(a).unwrap();
+    assert!(matches!(
+        world_a.delete_entity(a),
+        Err(EntityError::WrongGeneration(_))
+    ));
+}
+
+#[cfg(not(feature = "world-id"))]
+#[test]
+fn test_wrong_generation() {
+    use goggles::WrongGeneration;
+
+    let mut world = World::new();
+    let e = world.create_entity();
+    world.delete_entity(e).unwrap();
+    let _: Result<(), WrongGeneration> = world.delete_entity(e);
+    assert!(world.delete_entity(e).is_err());
+}
+
+#[test]
+fn test_deferred_resources() {
+    let mut world = World::new();
+    world.insert_resource(RB(1));
+
+    world.run(|deferred: DeferredResources| {
+        deferred.insert(RA(3));
+        deferred.remove::<RB>();
+    });
+    assert!(!world.contains_resource::<RA>());
+    assert!(world.contains_resource::<RB>());
+
+    world.merge();
+    assert_eq!(world.read_resource::<RA>().0, 3);
+    assert!(!world.contains_resource::<RB>());
+}
+
+#[test]
+fn test_delete_recursive() {
+    struct Children(Vec<Entity>);
+
+    impl Component for Children {
+        type Storage = VecStorage<Children>;
+    }
+
+    let mut world = World::new();
+    world.insert_component::<Children>().unwrap();
+    world.set_children_hook(|world, e, children| {
+        if let Some(c) = world.read_component::<Children>().get(e) {
+            children.extend_from_slice(&c.0);
+        }
+    });
+
+    let entities: Vec<Entity> = (0..5).map(|_| world.create_entity()).collect();
+    {
+        let mut children = world.write_component::<Children>();
+        children
+            .insert(entities[0], Children(vec![entities[4], entities[2]]))
+            .unwrap();
+        children
+            .insert(entities[2], Children(vec![entities[3], entities[0]]))
+            .unwrap();
+    }
+
+    world.delete_entity_recursive(entities[2]).unwrap();
+    assert!(world.entities().is_alive(entities[1]));
+    for &e in &[entities[0], entities[2], entities[3], entities[4]] {
+        assert!(!world.entities().is_alive(e));
+    }
+
+    let a = world.create_entity();
+    let b = world.create_entity();
+    world
+        .write_component::<Children>()
+        .insert(a, Children(vec![b]))
+        .unwrap();
+    world.run(
+        |(entities, children): (Entities, ReadComponent<Children>)| {
+            entities
+                .delete_recursive(a, |e, out| {
+                    if let Some(c) = children.get(e) {
+                        out.extend_from_slice(&c.0);
+                    }
+                })
+                .unwrap();
+        },
+    );
+    assert!(world.entities().is_alive(b));
+    world.merge();
+    assert!(!world.entities().is_alive(a));
+    assert!(!world.entities().is_alive(b));
+}
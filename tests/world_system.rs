@@ -0,0 +1,422 @@
+use std::convert::Infallible;
+
+use goggles::{
+    system, verify_sequential, BoxedWorldSystem, Commands, Component, Entities, EventReader,
+    EventWriter, Events, FetchError, FetchResources, IntoJoinExt, Local, Locals, ReadComponent,
+    ReadResource, Schedule, SeqPool, System, SystemError, VecStorage, VerifyError, WithLocals,
+    World, WorldResourceId, WriteComponent,
+};
+
+#[derive(Debug, PartialEq)]
+struct Pos(i32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+struct Vel(i32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+struct Limit(i32);
+
+#[derive(Debug, PartialEq)]
+struct OverLimit(usize);
+
+impl SystemError for OverLimit {
+    fn combine(self, other: Self) -> Self {
+        OverLimit(self.0.max(other.0))
+    }
+}
+
+system! {
+    struct Movement;
+    type Pool = SeqPool;
+
+    fn run(&mut self, (mut pos, vel): (WriteComponent<Pos>, ReadComponent<Vel>)) {
+        for (pos, vel) in (&mut pos, &vel).join() {
+            pos.0 += vel.0;
+        }
+    }
+}
+
+system! {
+    /// Counts how many positions exceed the limit.
+    struct CheckLimit {
+        over: usize,
+    }
+    type Pool = SeqPool;
+
+    fn run(
+        &mut self,
+        (pos, limit): (ReadComponent<Pos>, ReadResource<Limit>),
+    ) -> Result<(), OverLimit> {
+        self.over = (&pos).join().filter(|p| p.0 > limit.0).count();
+        if self.over > 1 {
+            Err(OverLimit(self.over))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_system_macro() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+    world.insert_resource(Limit(2));
+
+    for i in 0..2 {
+        let e = world.create_entity();
+        world.write_component::<Pos>().insert(e, Pos(i)).unwrap();
+        world.write_component::<Vel>().insert(e, Vel(2)).unwrap();
+    }
+
+    let mut movement = Movement;
+    let resources = movement.check_resources().unwrap();
+    assert!(resources
+        .writes()
+        .any(|&r| r == WorldResourceId::component::<Pos>()));
+    assert!(resources
+        .reads()
+        .any(|&r| r == WorldResourceId::component::<Vel>()));
+
+    let mut check = CheckLimit { over: 0 };
+    movement.run(&SeqPool, &world).unwrap();
+    check.run(&SeqPool, &world).unwrap();
+    assert_eq!(check.over, 1);
+
+    movement.run(&SeqPool, &world).unwrap();
+    assert_eq!(check.run(&SeqPool, &world).unwrap_err(), OverLimit(2));
+}
+
+system! {
+    /// Records every position it has ever seen in a `Local`.
+    struct History {
+        seen: Vec<i32>,
+    }
+    type Pool = SeqPool;
+
+    fn run(&mut self, (mut seen, pos): (Local<Vec<i32>>, ReadComponent<Pos>)) {
+        seen.extend((&pos).join().map(|p| p.0));
+        self.seen = seen.clone();
+    }
+}
+
+#[test]
+fn test_local() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    let e = world.create_entity();
+    world.write_component::<Pos>().insert(e, Pos(1)).unwrap();
+
+    let mut a = WithLocals::new(History { seen: Vec::new() });
+    let mut b = WithLocals::new(History { seen: Vec::new() });
+    let resources = a.check_resources().unwrap();
+    assert_eq!(resources.writes().count(), 0);
+    assert!(resources
+        .reads()
+        .all(|&r| r != WorldResourceId::resource::<Vec<i32>>()));
+
+    a.run(&SeqPool, &world).unwrap();
+    world.write_component::<Pos>().get_mut(e).unwrap().0 = 2;
+    a.run(&SeqPool, &world).unwrap();
+    b.run(&SeqPool, &world).unwrap();
+
+    assert_eq!(a.inner().seen, vec![1, 2]);
+    assert_eq!(b.inner().seen, vec![2]);
+}
+
+system! {
+    struct TwoLocals;
+    type Pool = SeqPool;
+
+    fn run(&mut self, _locals: (Local<u32>, Local<u32>)) {}
+}
+
+#[test]
+fn test_local_conflicts() {
+    let world = World::new();
+    let locals = Locals::default();
+
+    assert!(WithLocals::new(TwoLocals).check_resources().is_err());
+    assert!(matches!(
+        world.try_fetch::<Local<u32>>(),
+        Err(FetchError::Missing(_))
+    ));
+
+    let mut first = world.fetch_local::<Local<u32>>(&locals);
+    *first = 3;
+    assert!(matches!(
+        Local::<u32>::try_fetch_local(&world, &locals),
+        Err(FetchError::Borrowed(_))
+    ));
+    drop(first);
+    assert_eq!(*world.fetch_local::<Local<u32>>(&locals), 3);
+}
+
+#[test]
+fn test_verify_sequential() {
+    fn setup(vel: i32) -> (World, Movement) {
+        let mut world = World::new();
+        world.insert_component::<Pos>().unwrap();
+        world.insert_component::<Vel>().unwrap();
+        for i in 0..10 {
+            let e = world.create_entity();
+            world.write_component::<Pos>().insert(e, Pos(i)).unwrap();
+            world.write_component::<Vel>().insert(e, Vel(vel)).unwrap();
+        }
+        (world, Movement)
+    }
+
+    fn hash(world: &World) -> u64 {
+        (&world.read_component::<Pos>())
+            .join()
+            .map(|p| p.0 as u64)
+            .sum()
+    }
+
+    verify_sequential(|| setup(1), &SeqPool, 5, hash).unwrap();
+
+    // The second world moves faster, so the runs diverge immediately.
+    let mut vel = 0;
+    match verify_sequential(
+        || {
+            vel += 1;
+            setup(vel)
+        },
+        &SeqPool,
+        5,
+        hash,
+    ) {
+        Err(VerifyError::Diverged {
+            frame: 0,
+            parallel,
+            sequential,
+        }) => {
+            assert_eq!(parallel, 55);
+            assert_eq!(sequential, 65);
+        }
+        _ => panic!("expected the runs to diverge"),
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_verify_sequential_rayon() {
+    use goggles::{rayon, RayonPool, WriteResource};
+
+    struct Threads(usize);
+
+    system! {
+        struct CountThreads;
+        type Pool = RayonPool;
+
+        fn run(&mut self, (mut threads,): (WriteResource<Threads>,)) {
+            threads.0 = rayon::current_num_threads();
+        }
+    }
+
+    let setup = || {
+        let mut world = World::new();
+        world.insert_resource(Threads(0));
+        (world, CountThreads)
+    };
+    let hash = |world: &World| world.read_resource::<Threads>().0 as u64;
+
+    // The parallel run uses every thread of the pool it is called from, the sequential run only
+    // one.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(4)
+        .build()
+        .unwrap();
+    match pool.install(|| verify_sequential(setup, &RayonPool, 1, hash)) {
+        Err(VerifyError::Diverged {
+            frame: 0,
+            parallel: 4,
+            sequential: 1,
+        }) => {}
+        _ => panic!("expected only the sequential run to be limited to one thread"),
+    }
+}
+
+system! {
+    struct SendEvents {
+        next: u32,
+    }
+    type Pool = SeqPool;
+
+    fn run(&mut self, (mut writer,): (EventWriter<u32>,)) {
+        writer.send(self.next);
+        writer.send_batch([self.next + 1]);
+        self.next += 2;
+    }
+}
+
+system! {
+    struct ReceiveEvents {
+        received: Vec<u32>,
+    }
+    type Pool = SeqPool;
+
+    fn run(&mut self, (mut reader,): (EventReader<u32>,)) {
+        self.received = reader.read().copied().collect();
+        assert!(reader.is_empty());
+    }
+}
+
+#[test]
+fn test_events() {
+    let mut world = World::new();
+    world.insert_events::<u32>();
+
+    let mut send = SendEvents { next: 0 };
+    let mut early = WithLocals::new(ReceiveEvents {
+        received: Vec::new(),
+    });
+    let mut late = WithLocals::new(ReceiveEvents {
+        received: Vec::new(),
+    });
+
+    let resources = early.check_resources().unwrap();
+    assert_eq!(resources.writes().count(), 0);
+    assert!(resources
+        .reads()
+        .any(|&r| r == WorldResourceId::resource::<Events<u32>>()));
+    assert!(send
+        .check_resources()
+        .unwrap()
+        .writes()
+        .any(|&r| r == WorldResourceId::resource::<Events<u32>>()));
+
+    // Readers running before and after the writer each see every event exactly once.
+    for frame in 0..3 {
+        early.run(&SeqPool, &world).unwrap();
+        send.run(&SeqPool, &world).unwrap();
+        late.run(&SeqPool, &world).unwrap();
+        world.merge();
+
+        let sent_last_frame = if frame == 0 {
+            vec![]
+        } else {
+            vec![frame * 2 - 2, frame * 2 - 1]
+        };
+        assert_eq!(early.inner().received, sent_last_frame);
+        assert_eq!(late.inner().received, vec![frame * 2, frame * 2 + 1]);
+    }
+
+    // Events are dropped after two merges.
+    assert_eq!(world.read_resource::<Events<u32>>().len(), 2);
+    world.merge();
+    world.merge();
+    assert!(world.read_resource::<Events<u32>>().is_empty());
+    early.run(&SeqPool, &world).unwrap();
+    assert!(early.inner().received.is_empty());
+
+    assert!(world.try_fetch::<EventReader<u32>>().is_err());
+}
+
+system! {
+    /// Replaces every entity that has a position with a new one further along.
+    struct Respawn;
+    type Pool = SeqPool;
+
+    fn run(&mut self, (commands, entities, pos): (Commands, Entities, ReadComponent<Pos>)) {
+        for (e, pos) in (&entities, &pos).join() {
+            commands.despawn(e).unwrap();
+            let new = commands.spawn();
+            commands.insert(new, Pos(pos.0 + 1));
+            commands.insert(new, Vel(1));
+            commands.remove::<Vel>(new);
+        }
+    }
+}
+
+#[test]
+fn test_commands() {
+    let mut world = World::new();
+    world.insert_component::<Pos>().unwrap();
+    world.insert_component::<Vel>().unwrap();
+    let e = world.create_entity();
+    world.write_component::<Pos>().insert(e, Pos(1)).unwrap();
+
+    let mut respawn = Respawn;
+    let resources = respawn.check_resources().unwrap();
+    assert_eq!(resources.writes().count(), 0);
+
+    respawn.run(&SeqPool, &world).unwrap();
+    // Nothing is applied until the merge.
+    assert_eq!((&world.read_component::<Pos>()).join().count(), 1);
+    assert!(world.entities().is_alive(e));
+
+    world.merge();
+    assert!(!world.entities().is_alive(e));
+    let pos = world.read_component::<Pos>();
+    let positions = (&world.entities(), &pos).join().collect::<Vec<_>>();
+    assert_eq!(positions.len(), 1);
+    assert_eq!(*positions[0].1, Pos(2));
+    assert!((&world.read_component::<Vel>()).join().next().is_none());
+}
+
+#[test]
+fn test_schedule() {
+    struct Game {
+        world: World,
+        schedule: Schedule<BoxedWorldSystem<SeqPool, Infallible>>,
+    }
+
+    let mut game = Game {
+        world: World::new(),
+        schedule: Schedule::new(),
+    };
+    game.world.insert_component::<Pos>().unwrap();
+    game.world.insert_component::<Vel>().unwrap();
+    let e = game.world.create_entity();
+    game.world
+        .write_component::<Pos>()
+        .insert(e, Pos(0))
+        .unwrap();
+    game.world
+        .write_component::<Vel>()
+        .insert(e, Vel(1))
+        .unwrap();
+
+    game.schedule
+        .add_labeled("movement", Box::new(Movement))
+        .add_labeled(
+            "history",
+            Box::new(WithLocals::new(History { seen: Vec::new() })),
+        )
+        .add(Box::new(Respawn));
+    assert_eq!(
+        game.schedule.stages::<&World>(),
+        vec![vec![Some("movement")], vec![Some("history"), None]]
+    );
+    game.schedule.check_resources().unwrap();
+
+    game.schedule.run(&SeqPool, &game.world).unwrap();
+    game.world.merge();
+    assert_eq!(
+        (&game.world.read_component::<Pos>())
+            .join()
+            .collect::<Vec<_>>(),
+        vec![&Pos(2)]
+    );
+
+    let movement = game.schedule.remove("movement").unwrap();
+    game.schedule
+        .add_barrier()
+        .add_labeled("movement", movement);
+    assert_eq!(
+        game.schedule.stages::<&World>(),
+        vec![vec![Some("history"), None], vec![Some("movement")]]
+    );
+    assert!(game
+        .schedule
+        .replace("history", Box::new(Movement))
+        .is_some());
+    assert_eq!(game.schedule.len(), 3);
+}